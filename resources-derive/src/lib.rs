@@ -0,0 +1,107 @@
+//! Derive macros for the [`resources`](https://docs.rs/resources) crate;
+//! see its documentation for details.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields};
+
+/// Implements `Resource` for the type, if the `explicit` feature of `resources` is enabled;
+/// otherwise, it's already implemented for all applicable types.
+#[proc_macro_derive(Resource)]
+pub fn derive_resource(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    TokenStream::from(quote! {
+        ::resources::__impl_resource! {
+            impl #impl_generics ::resources::Resource for #name #type_generics #where_clause {}
+        }
+    })
+}
+
+/// Implements `TryFrom<&Resources<M>>` for a struct whose fields are `Clone` resources,
+/// cloning each of them into an owned snapshot.
+///
+/// All fields are borrowed at once, in the order they're declared in, before any is cloned,
+/// so that the snapshot is consistent.
+#[proc_macro_derive(TryFromResources)]
+pub fn derive_try_from_resources(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Error::new(
+                Span::call_site(),
+                "only structs can be derived from resources",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    let borrows = (0..types.len())
+        .map(|index| format_ident!("__borrow_{}", index))
+        .collect::<Vec<_>>();
+    let constructor = match fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote! {
+                Self { #(#names: ::std::clone::Clone::clone(&*#borrows),)* }
+            }
+        }
+        Fields::Unnamed(_) => quote! {
+            Self(#(::std::clone::Clone::clone(&*#borrows),)*)
+        },
+        Fields::Unit => quote! { Self },
+    };
+    let mut generics = input.generics.clone();
+    generics.params.insert(0, parse_quote!('__resources));
+    generics.params.push(parse_quote!(__Marker));
+    let (impl_generics, _, _) = generics.split_for_impl();
+    let (_, type_generics, where_clause) = input.generics.split_for_impl();
+    TokenStream::from(quote! {
+        impl #impl_generics ::std::convert::TryFrom<&'__resources ::resources::Resources<__Marker>>
+            for #name #type_generics #where_clause
+        {
+            type Error = ::resources::CantGetResource;
+
+            fn try_from(
+                resources: &'__resources ::resources::Resources<__Marker>,
+            ) -> ::std::result::Result<Self, Self::Error> {
+                #(let #borrows = resources.get::<#types>()?;)*
+                ::std::result::Result::Ok(#constructor)
+            }
+        }
+    })
+}
+
+/// Implements `RequiredResources` for a struct, requiring the types of all of its fields
+/// to be present in a container validated with `Resources::validate()`.
+#[proc_macro_derive(RequiredResources)]
+pub fn derive_required_resources(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Error::new(Span::call_site(), "only structs can require resources")
+                .to_compile_error()
+                .into()
+        }
+    };
+    let types = fields.iter().map(|field| &field.ty);
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    TokenStream::from(quote! {
+        impl #impl_generics ::resources::RequiredResources for #name #type_generics #where_clause {
+            fn required(
+                types: &mut ::std::vec::Vec<(::std::any::TypeId, &'static ::std::primitive::str)>,
+            ) {
+                #(types.push(::resources::__required::<#types>());)*
+            }
+        }
+    })
+}
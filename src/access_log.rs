@@ -0,0 +1,165 @@
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    panic::Location,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use crate::instrument::Access;
+
+/// Recorder of accesses to resources of a container, while enabled.
+#[derive(Default)]
+pub(crate) struct AccessRecorder {
+    recording: AtomicBool,
+    accesses: Mutex<Vec<AccessRecord>>,
+}
+
+impl AccessRecorder {
+    pub(crate) fn start(&self) {
+        let mut accesses = self
+            .accesses
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        accesses.clear();
+        self.recording.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn finish(&self) -> AccessLog {
+        let mut accesses = self
+            .accesses
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        self.recording.store(false, Ordering::Relaxed);
+        AccessLog {
+            accesses: std::mem::take(&mut *accesses),
+        }
+    }
+
+    /// Records an access to the resource by the caller, if recording.
+    #[track_caller]
+    pub(crate) fn record(&self, type_name: &'static str, access: Access) {
+        if self.recording.load(Ordering::Relaxed) {
+            let location = Location::caller();
+            let mut accesses = self
+                .accesses
+                .lock()
+                .unwrap_or_else(|error| error.into_inner());
+            accesses.push(AccessRecord {
+                type_name,
+                access,
+                location,
+            });
+        }
+    }
+}
+
+/// Access to a resource, as recorded in an [`AccessLog`].
+///
+/// [`AccessLog`]: struct.AccessLog.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccessRecord {
+    type_name: &'static str,
+    access: Access,
+    location: &'static Location<'static>,
+}
+
+impl AccessRecord {
+    /// Returns the compiler-provided name of the accessed resource's type.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns `true` if the resource was accessed mutably.
+    pub fn is_mutable(&self) -> bool {
+        self.access == Access::Exclusive
+    }
+
+    /// Returns the location of the code that accessed the resource.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+}
+
+impl Display for AccessRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let kind = if self.is_mutable() {
+            "mutably"
+        } else {
+            "immutably"
+        };
+        write!(
+            f,
+            "{} borrowed {} at {}",
+            self.type_name, kind, self.location
+        )
+    }
+}
+
+/// Sequence of accesses to resources of a [`Resources`] container, in the order they
+/// were made in, as returned by [`finish_access_log`].
+///
+/// [`Resources`]: struct.Resources.html
+/// [`finish_access_log`]: struct.Resources.html#method.finish_access_log
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccessLog {
+    accesses: Vec<AccessRecord>,
+}
+
+impl AccessLog {
+    /// Returns the recorded accesses, in the order they were made in.
+    pub fn accesses(&self) -> &[AccessRecord] {
+        &self.accesses
+    }
+
+    /// Compares the log with the baseline one, returning the first access that differs
+    /// in type, kind or location, or `None` if the logs are identical.
+    pub fn first_divergence(&self, baseline: &AccessLog) -> Option<Divergence> {
+        let index = self
+            .accesses
+            .iter()
+            .zip(&baseline.accesses)
+            .position(|(actual, expected)| actual != expected)
+            .unwrap_or_else(|| self.accesses.len().min(baseline.accesses.len()));
+        let divergence = Divergence {
+            index,
+            expected: baseline.accesses.get(index).copied(),
+            actual: self.accesses.get(index).copied(),
+        };
+        if divergence.expected.is_none() && divergence.actual.is_none() {
+            None
+        } else {
+            Some(divergence)
+        }
+    }
+}
+
+/// First difference between two [`AccessLog`]s, as returned by [`first_divergence`].
+///
+/// [`AccessLog`]: struct.AccessLog.html
+/// [`first_divergence`]: struct.AccessLog.html#method.first_divergence
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Position of the differing access in both logs.
+    pub index: usize,
+    /// Access in the baseline log, or `None` if it ended before.
+    pub expected: Option<AccessRecord>,
+    /// Access in the compared log, or `None` if it ended before.
+    pub actual: Option<AccessRecord>,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "access #{} diverged: expected ", self.index)?;
+        match &self.expected {
+            Some(access) => write!(f, "{}", access)?,
+            None => write!(f, "no access")?,
+        }
+        write!(f, ", found ")?;
+        match &self.actual {
+            Some(access) => write!(f, "{}", access),
+            None => write!(f, "no access"),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::any::{type_name, TypeId};
+
+use crate::{
+    error::{BorrowApi, InvalidBorrow, NoSuchResource},
+    lock::ResourceCell,
+    map::Resource,
+    storage::TypeIdMap,
+};
+
+fn get_cell<T: Resource>(
+    resources: &TypeIdMap<ResourceCell>,
+) -> Result<&ResourceCell, NoSuchResource> {
+    resources.get(&TypeId::of::<T>()).ok_or(NoSuchResource {
+        type_name: type_name::<T>(),
+    })
+}
+
+/// Shared borrow of every resource in a [`Resources`] container at once,
+/// as returned by [`read_all`].
+///
+/// While this exists, none of the resources can be accessed mutably, so all of them
+/// can be read through it without any further locking, and without failing
+/// due to borrow conflicts.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`read_all`]: struct.Resources.html#method.read_all
+pub struct AllRef<'a> {
+    resources: &'a TypeIdMap<ResourceCell>,
+}
+
+impl<'a> AllRef<'a> {
+    pub(crate) fn from_cells(
+        resources: &'a TypeIdMap<ResourceCell>,
+    ) -> Result<Self, InvalidBorrow> {
+        for (index, cell) in resources.values().enumerate() {
+            if !cell.lock.try_lock_shared() {
+                let conflict = cell.conflict();
+                // The map can't change while borrowed, so it's iterated in the same order.
+                for cell in resources.values().take(index) {
+                    unsafe { cell.lock.unlock_shared() };
+                }
+                return Err(InvalidBorrow::Immutable {
+                    type_name: cell.type_name(),
+                    conflict,
+                });
+            }
+        }
+        for cell in resources.values() {
+            cell.lock.acquired_via(BorrowApi::All);
+        }
+        Ok(Self { resources })
+    }
+
+    /// Returns `true` if a resource of type `T` exists in the container.
+    pub fn contains<T: Resource>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns a reference to the stored resource of type `T`,
+    /// or an error if it's not present in the container.
+    pub fn get<T: Resource>(&self) -> Result<&T, NoSuchResource> {
+        let cell = get_cell::<T>(self.resources)?;
+        Ok(unsafe { &*cell.resource_ptr() }
+            .downcast_ref::<T>()
+            .unwrap_or_else(|| panic!("downcasting resources should always succeed")))
+    }
+
+    /// Returns a reference to the stored resource with the given `TypeId`, if any.
+    #[cfg(feature = "autosave")]
+    pub(crate) fn get_dyn(&self, type_id: TypeId) -> Option<&dyn Resource> {
+        self.resources
+            .get(&type_id)
+            .map(|cell| unsafe { &*cell.resource_ptr() })
+    }
+}
+
+impl<'a> Drop for AllRef<'a> {
+    fn drop(&mut self) {
+        for cell in self.resources.values() {
+            unsafe { cell.lock.unlock_shared() };
+        }
+    }
+}
+
+/// Exclusive borrow of every resource in a [`Resources`] container at once,
+/// as returned by [`lock_all`].
+///
+/// While this exists, no other access to any of the resources is possible, so all of them
+/// can be accessed through it without any further locking, and without failing
+/// due to borrow conflicts.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`lock_all`]: struct.Resources.html#method.lock_all
+pub struct AllMut<'a> {
+    resources: &'a TypeIdMap<ResourceCell>,
+}
+
+impl<'a> AllMut<'a> {
+    pub(crate) fn from_cells(
+        resources: &'a TypeIdMap<ResourceCell>,
+    ) -> Result<Self, InvalidBorrow> {
+        for (index, cell) in resources.values().enumerate() {
+            if !cell.lock.try_lock_exclusive() {
+                let conflict = cell.conflict();
+                // The map can't change while borrowed, so it's iterated in the same order;
+                // nothing was accessed through these locks, so releasing them isn't a change.
+                for cell in resources.values().take(index) {
+                    unsafe { cell.lock.unlock_exclusive_untracked() };
+                }
+                return Err(InvalidBorrow::Mutable {
+                    type_name: cell.type_name(),
+                    conflict,
+                });
+            }
+        }
+        for cell in resources.values() {
+            cell.lock.acquired_via(BorrowApi::All);
+        }
+        Ok(Self { resources })
+    }
+
+    /// Returns `true` if a resource of type `T` exists in the container.
+    pub fn contains<T: Resource>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns a reference to the stored resource of type `T`,
+    /// or an error if it's not present in the container.
+    pub fn get<T: Resource>(&self) -> Result<&T, NoSuchResource> {
+        let cell = get_cell::<T>(self.resources)?;
+        Ok(unsafe { &*cell.resource_ptr() }
+            .downcast_ref::<T>()
+            .unwrap_or_else(|| panic!("downcasting resources should always succeed")))
+    }
+
+    /// Returns a mutable reference to the stored resource of type `T`,
+    /// or an error if it's not present in the container.
+    pub fn get_mut<T: Resource>(&mut self) -> Result<&mut T, NoSuchResource> {
+        let cell = get_cell::<T>(self.resources)?;
+        Ok(unsafe { &mut *cell.resource_ptr() }
+            .downcast_mut::<T>()
+            .unwrap_or_else(|| panic!("downcasting resources should always succeed")))
+    }
+}
+
+impl<'a> Drop for AllMut<'a> {
+    fn drop(&mut self) {
+        for cell in self.resources.values() {
+            unsafe { cell.lock.unlock_exclusive() };
+        }
+    }
+}
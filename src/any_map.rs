@@ -0,0 +1,63 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+use crate::map::Resource;
+
+/// Moves the value of an `Any` box into a resource box, if it's of a specific type,
+/// returning the box as is otherwise.
+type FromAnyFn =
+    fn(Box<dyn Any + Send + Sync>) -> Result<Box<dyn Resource>, Box<dyn Any + Send + Sync>>;
+
+/// Converters of values of maps of `TypeId`s to `Any` boxes, like the ones used by
+/// `anymap`-style containers, into resources, registered per type,
+/// for [`Resources::insert_any_map()`].
+///
+/// There is no `From` conversion of such maps into [`Resources`]: a resource can only
+/// be stored if its concrete type is known, which a boxed `Any` can't provide,
+/// so each type has to be registered here, where it's known.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`Resources::insert_any_map()`]: struct.Resources.html#method.insert_any_map
+#[derive(Default)]
+pub struct AnyMapConverter {
+    converters: HashMap<TypeId, FromAnyFn>,
+}
+
+impl AnyMapConverter {
+    /// Creates a converter with no types registered. Functionally identical to [`::default()`].
+    ///
+    /// [`::default()`]: #impl-Default
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the resource type `T`, so that values keyed by its `TypeId` are converted.
+    pub fn register<T: Resource>(&mut self) -> &mut Self {
+        self.converters.insert(TypeId::of::<T>(), |boxed| {
+            boxed
+                .downcast::<T>()
+                .map(|resource| resource as Box<dyn Resource>)
+        });
+        self
+    }
+
+    /// Returns `true` if the type with the `TypeId` is registered.
+    pub fn is_registered(&self, type_id: TypeId) -> bool {
+        self.converters.contains_key(&type_id)
+    }
+
+    /// Converts the value keyed by the `TypeId` into a boxed resource, returning it as is
+    /// if the type isn't registered, or if the value is of a different type.
+    pub fn convert(
+        &self,
+        type_id: TypeId,
+        boxed: Box<dyn Any + Send + Sync>,
+    ) -> Result<Box<dyn Resource>, Box<dyn Any + Send + Sync>> {
+        match self.converters.get(&type_id) {
+            Some(convert) => convert(boxed),
+            None => Err(boxed),
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    any::TypeId,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+        Arc, Weak,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use serde::{ser::SerializeMap, Serialize, Serializer};
+
+use crate::{
+    all::AllRef,
+    map::{Resource, Resources},
+};
+
+/// Erases a resource of a specific type for serialization.
+type SerializeFn = fn(&dyn Resource) -> Option<&dyn erased_serde::Serialize>;
+
+/// How long the autosave thread waits before retrying to borrow all resources.
+const RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+/// When an autosave thread saves resources, besides when requested explicitly.
+#[derive(Debug, Clone, Copy)]
+enum Schedule {
+    Every(Duration),
+    Ticks(u64),
+}
+
+enum Message {
+    Save,
+    Stop,
+}
+
+struct Entry {
+    key: String,
+    type_id: TypeId,
+    serialize: SerializeFn,
+}
+
+/// Periodic saving of selected resources of a [`Resources`] container on a background thread,
+/// started via [`spawn`].
+///
+/// Each save borrows every resource of the container immutably at once, as [`read_all`] does,
+/// so that the saved resources are consistent with each other. If any resource is accessed
+/// mutably at the time, the save is retried until it's released.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(not(feature = "explicit"))] {
+/// # use resources::{Autosave, Resources};
+/// # use std::{sync::Arc, time::Duration};
+/// let mut resources = Resources::new();
+/// resources.insert(0u32);
+/// let resources = Arc::new(resources);
+/// let autosave = Autosave::every(Duration::from_secs(60))
+///     .with::<u32>("score")
+///     .spawn(&resources, |saved| {
+///         let json = serde_json::to_string(saved).unwrap();
+///         // Write `json` to a file...
+///         # let _ = json;
+///     });
+/// autosave.save_now();
+/// # }
+/// ```
+///
+/// [`Resources`]: struct.Resources.html
+/// [`spawn`]: #method.spawn
+/// [`read_all`]: struct.Resources.html#method.read_all
+pub struct Autosave {
+    schedule: Schedule,
+    entries: Vec<Entry>,
+}
+
+impl Autosave {
+    /// Creates an autosave that saves each time the interval elapses.
+    pub fn every(interval: Duration) -> Self {
+        Self {
+            schedule: Schedule::Every(interval),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Creates an autosave that saves every given number of calls to [`AutosaveHandle::tick()`],
+    /// such as once per frame.
+    ///
+    /// [`AutosaveHandle::tick()`]: struct.AutosaveHandle.html#method.tick
+    pub fn every_ticks(ticks: u64) -> Self {
+        Self {
+            schedule: Schedule::Ticks(ticks.max(1)),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds the resource type `T` to saved ones, under the given key.
+    pub fn with<T: Resource + Serialize>(mut self, key: impl Into<String>) -> Self {
+        self.entries.push(Entry {
+            key: key.into(),
+            type_id: TypeId::of::<T>(),
+            serialize: |resource| {
+                resource
+                    .downcast_ref::<T>()
+                    .map(|resource| resource as &dyn erased_serde::Serialize)
+            },
+        });
+        self
+    }
+
+    /// Starts the background thread, which passes selected resources of the container
+    /// to the closure, to be serialized, on each save.
+    ///
+    /// The thread only holds a weak reference to the container, and stops once it's dropped,
+    /// or once the returned handle is.
+    pub fn spawn<M: 'static>(
+        self,
+        resources: &Arc<Resources<M>>,
+        save: impl FnMut(&SavedResources<'_>) + Send + 'static,
+    ) -> AutosaveHandle
+    where
+        Resources<M>: Send + Sync,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let resources = Arc::downgrade(resources);
+        let schedule = self.schedule;
+        let thread = thread::spawn(move || self.run(resources, receiver, save));
+        AutosaveHandle {
+            sender,
+            schedule,
+            ticks: AtomicU64::new(0),
+            thread: Some(thread),
+        }
+    }
+
+    fn run<M>(
+        self,
+        resources: Weak<Resources<M>>,
+        receiver: Receiver<Message>,
+        mut save: impl FnMut(&SavedResources<'_>),
+    ) {
+        loop {
+            let message = match self.schedule {
+                Schedule::Every(interval) => match receiver.recv_timeout(interval) {
+                    Ok(message) => message,
+                    Err(RecvTimeoutError::Timeout) => Message::Save,
+                    Err(RecvTimeoutError::Disconnected) => Message::Stop,
+                },
+                Schedule::Ticks(_) => receiver.recv().unwrap_or(Message::Stop),
+            };
+            if let Message::Stop = message {
+                return;
+            }
+            let resources = match resources.upgrade() {
+                Some(resources) => resources,
+                None => return,
+            };
+            let all = loop {
+                match resources.read_all() {
+                    Ok(all) => break all,
+                    Err(_) => match receiver.try_recv() {
+                        Ok(Message::Stop) | Err(TryRecvError::Disconnected) => return,
+                        _ => thread::sleep(RETRY_INTERVAL),
+                    },
+                }
+            };
+            save(&SavedResources {
+                all,
+                entries: &self.entries,
+            });
+        }
+    }
+}
+
+/// Selected resources of a container, borrowed for an [`Autosave`].
+///
+/// This serializes as a map of keys the resources were selected under to their values;
+/// resources that aren't present in the container are skipped.
+///
+/// [`Autosave`]: struct.Autosave.html
+pub struct SavedResources<'a> {
+    all: AllRef<'a>,
+    entries: &'a [Entry],
+}
+
+impl<'a> SavedResources<'a> {
+    /// Returns the underlying borrow of all resources of the container.
+    pub fn all(&self) -> &AllRef<'a> {
+        &self.all
+    }
+}
+
+impl<'a> Serialize for SavedResources<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for entry in self.entries {
+            if let Some(resource) = self
+                .all
+                .get_dyn(entry.type_id)
+                .and_then(|resource| (entry.serialize)(resource))
+            {
+                map.serialize_entry(&entry.key, resource)?;
+            }
+        }
+        map.end()
+    }
+}
+
+/// Handle of a running [`Autosave`] thread, which stops it when dropped.
+///
+/// [`Autosave`]: struct.Autosave.html
+pub struct AutosaveHandle {
+    sender: Sender<Message>,
+    schedule: Schedule,
+    ticks: AtomicU64,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AutosaveHandle {
+    /// Counts a tick, requesting a save if the autosave was created via
+    /// [`Autosave::every_ticks()`] and enough ticks passed since the previous one.
+    ///
+    /// [`Autosave::every_ticks()`]: struct.Autosave.html#method.every_ticks
+    pub fn tick(&self) {
+        if let Schedule::Ticks(ticks) = self.schedule {
+            if (self.ticks.fetch_add(1, Ordering::Relaxed) + 1).is_multiple_of(ticks) {
+                self.save_now();
+            }
+        }
+    }
+
+    /// Requests a save regardless of the schedule, such as before quitting.
+    pub fn save_now(&self) {
+        let _ = self.sender.send(Message::Save);
+    }
+
+    /// Stops the thread, waiting for the save in progress, if any, to finish.
+    pub fn stop(self) {}
+}
+
+impl Drop for AutosaveHandle {
+    fn drop(&mut self) {
+        let _ = self.sender.send(Message::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+#[cfg(loom)]
+use loom::{hint::spin_loop, thread::yield_now};
+#[cfg(not(loom))]
+use std::{hint::spin_loop, thread::yield_now};
+
+/// How blocking accessors of a [`Resources`] container wait for a resource,
+/// set via [`Resources::set_backoff()`].
+///
+/// A waiting access first spins `spins` times, then yields its time slice `yields` times
+/// (or indefinitely, if that's `None`), then repeatedly parks its thread for `park`,
+/// retrying after each step. If `park` is `None` as well, the access gives up instead,
+/// failing with an [`InvalidBorrow`] error.
+///
+/// Accesses that may give up are not queued with the others: they don't delay anything
+/// while waiting, but may be overtaken by accesses that started waiting after them.
+///
+/// The default is 64 spins followed by yielding indefinitely.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`Resources::set_backoff()`]: struct.Resources.html#method.set_backoff
+/// [`InvalidBorrow`]: enum.InvalidBorrow.html
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Backoff {
+    /// How many times to spin before yielding.
+    pub spins: u32,
+    /// How many times to yield before parking, or `None` to keep yielding.
+    pub yields: Option<u32>,
+    /// How long to park for each time after yielding, or `None` to give up.
+    pub park: Option<Duration>,
+}
+
+impl Backoff {
+    /// Spins the given number of times, then gives up;
+    /// suitable for threads that must never be descheduled, such as audio threads.
+    pub fn spin_then_fail(spins: u32) -> Self {
+        Self {
+            spins,
+            yields: Some(0),
+            park: None,
+        }
+    }
+
+    /// Spins and yields the given numbers of times, then parks for the duration at a time;
+    /// suitable for worker pools with long waits.
+    pub fn parking(spins: u32, yields: u32, park: Duration) -> Self {
+        Self {
+            spins,
+            yields: Some(yields),
+            park: Some(park),
+        }
+    }
+
+    /// Returns `true` if waiting with this policy may give up.
+    pub(crate) fn may_fail(&self) -> bool {
+        self.yields.is_some() && self.park.is_none()
+    }
+
+    /// Retries the condition until it's met, returning `false` if the policy gave up first.
+    pub(crate) fn wait(&self, mut condition: impl FnMut() -> bool) -> bool {
+        let (mut spins, mut yields) = (0, 0);
+        while !condition() {
+            if spins < self.spins {
+                spins += 1;
+                spin_loop();
+            } else if !matches!(self.yields, Some(limit) if yields >= limit) {
+                yields += 1;
+                yield_now();
+            } else if let Some(duration) = self.park {
+                park(duration);
+            } else {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            spins: 64,
+            yields: None,
+            park: None,
+        }
+    }
+}
+
+#[cfg(not(loom))]
+fn park(duration: Duration) {
+    std::thread::park_timeout(duration);
+}
+
+// `loom` doesn't model timeouts; a yield is equivalent for its purposes.
+#[cfg(loom)]
+fn park(_: Duration) {
+    yield_now();
+}
@@ -0,0 +1,198 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+#[cfg(feature = "track-borrows")]
+use std::{
+    panic::Location,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+#[cfg(feature = "watchdog")]
+use std::time::Instant;
+
+use crate::{lock::ResourceCell, storage::TypeIdMap};
+
+/// Source of unique identifiers of recorded borrows.
+#[cfg(feature = "track-borrows")]
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "track-borrows")]
+struct Site {
+    id: usize,
+    location: &'static Location<'static>,
+    label: Option<&'static str>,
+    #[cfg(feature = "watchdog")]
+    acquired: Instant,
+}
+
+/// Locations where guards currently borrowing a resource were acquired.
+///
+/// Without the `track-borrows` feature, this is zero-sized and records nothing.
+#[derive(Default)]
+pub(crate) struct BorrowSites {
+    #[cfg(feature = "track-borrows")]
+    sites: Mutex<Vec<Site>>,
+}
+
+impl BorrowSites {
+    /// Returns the label of the latest recorded site that has one.
+    ///
+    /// Without the `track-borrows` feature, this always returns `None`.
+    pub(crate) fn latest_label(&self) -> Option<&'static str> {
+        #[cfg(feature = "track-borrows")]
+        {
+            let sites = self.sites.lock().unwrap_or_else(|error| error.into_inner());
+            sites
+                .iter()
+                .filter(|site| site.label.is_some())
+                .max_by_key(|site| site.id)
+                .and_then(|site| site.label)
+        }
+        #[cfg(not(feature = "track-borrows"))]
+        None
+    }
+
+    /// Calls the closure with the ID, location, label, and acquisition time
+    /// of each recorded site.
+    #[cfg(feature = "watchdog")]
+    pub(crate) fn for_each(
+        &self,
+        mut f: impl FnMut(usize, &'static Location<'static>, Option<&'static str>, Instant),
+    ) {
+        let sites = self.sites.lock().unwrap_or_else(|error| error.into_inner());
+        for site in sites.iter() {
+            f(site.id, site.location, site.label, site.acquired);
+        }
+    }
+}
+
+/// Record of the location where a guard was acquired, removed when dropped.
+///
+/// Without the `track-borrows` feature, this is zero-sized and does nothing.
+pub(crate) struct BorrowSite<'a> {
+    #[cfg(feature = "track-borrows")]
+    record: Option<(usize, &'a BorrowSites)>,
+    #[cfg(not(feature = "track-borrows"))]
+    phantom_data: std::marker::PhantomData<&'a BorrowSites>,
+}
+
+impl<'a> BorrowSite<'a> {
+    /// Returns a record that isn't tracked anywhere.
+    pub(crate) fn untracked() -> Self {
+        Self {
+            #[cfg(feature = "track-borrows")]
+            record: None,
+            #[cfg(not(feature = "track-borrows"))]
+            phantom_data: std::marker::PhantomData,
+        }
+    }
+
+    /// Records the location of the caller as a site of a borrow of the cell's resource.
+    #[track_caller]
+    pub(crate) fn record(cell: &'a ResourceCell) -> Self {
+        Self::record_labeled(cell, None)
+    }
+
+    /// Records the location of the caller, along with the label given to the borrow,
+    /// as a site of a borrow of the cell's resource.
+    #[track_caller]
+    pub(crate) fn record_labeled(cell: &'a ResourceCell, label: Option<&'static str>) -> Self {
+        #[cfg(feature = "track-borrows")]
+        {
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            cell.borrow_sites()
+                .sites
+                .lock()
+                .unwrap_or_else(|error| error.into_inner())
+                .push(Site {
+                    id,
+                    location: Location::caller(),
+                    label,
+                    #[cfg(feature = "watchdog")]
+                    acquired: Instant::now(),
+                });
+            Self {
+                record: Some((id, cell.borrow_sites())),
+            }
+        }
+        #[cfg(not(feature = "track-borrows"))]
+        {
+            let _ = (cell, label);
+            Self::untracked()
+        }
+    }
+}
+
+#[cfg(feature = "track-borrows")]
+impl<'a> Drop for BorrowSite<'a> {
+    fn drop(&mut self) {
+        if let Some((id, sites)) = self.record {
+            let mut sites = sites
+                .sites
+                .lock()
+                .unwrap_or_else(|error| error.into_inner());
+            if let Some(position) = sites.iter().position(|site| site.id == id) {
+                sites.swap_remove(position);
+            }
+        }
+    }
+}
+
+/// Description of resources of a [`Resources`] container that are currently borrowed,
+/// as returned by [`borrow_state`].
+///
+/// It's formatted via `Display`, one resource per line, sorted by type name:
+/// each line names the resource's type and how it's borrowed and, with the `track-borrows`
+/// feature, where the borrowing guards were acquired, and with what labels.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`borrow_state`]: struct.Resources.html#method.borrow_state
+pub struct BorrowState<'a> {
+    resources: &'a TypeIdMap<ResourceCell>,
+}
+
+impl<'a> BorrowState<'a> {
+    pub(crate) fn new(resources: &'a TypeIdMap<ResourceCell>) -> Self {
+        Self { resources }
+    }
+}
+
+impl<'a> Display for BorrowState<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut cells = self.resources.values().collect::<Vec<_>>();
+        cells.sort_unstable_by_key(|cell| cell.type_name());
+        for cell in cells {
+            if cell.lock.is_locked_exclusive() {
+                write!(f, "{}: borrowed mutably", cell.type_name())?;
+            } else {
+                match cell.lock.readers() {
+                    0 => continue,
+                    1 => write!(f, "{}: borrowed immutably", cell.type_name())?,
+                    readers => write!(
+                        f,
+                        "{}: borrowed immutably {} times",
+                        cell.type_name(),
+                        readers
+                    )?,
+                }
+            }
+            #[cfg(feature = "track-borrows")]
+            {
+                // The panic hook may run while the list is locked, so this doesn't wait for it.
+                if let Ok(sites) = cell.borrow_sites().sites.try_lock() {
+                    for (index, site) in sites.iter().enumerate() {
+                        let separator = if index == 0 { ", at" } else { "," };
+                        write!(f, "{} {}", separator, site.location)?;
+                        if let Some(label) = site.label {
+                            write!(f, " (`{}`)", label)?;
+                        }
+                    }
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    any::{type_name, TypeId},
+    marker::PhantomData,
+};
+
+use crate::{
+    error::{CantGetResource, Conflict, InvalidBorrow, NoSuchResource},
+    lock::{LockKind, ResourceCell},
+    map::Resource,
+    storage::TypeIdMap,
+};
+
+/// Invariant lifetime, making each brand distinct from every other.
+type Brand<'brand> = PhantomData<fn(&'brand ()) -> &'brand ()>;
+
+/// Token that controls access to resources of a [`Branded`] view with the same brand,
+/// as passed to the closure given to [`branded`].
+///
+/// A shared reference to the token allows accessing any number of resources immutably,
+/// and a mutable reference allows accessing one of them mutably, so that borrow rules
+/// are upheld by the compiler instead of the resources' locks.
+///
+/// [`Branded`]: struct.Branded.html
+/// [`branded`]: struct.Resources.html#method.branded
+pub struct AccessToken<'brand> {
+    brand: Brand<'brand>,
+}
+
+impl<'brand> AccessToken<'brand> {
+    pub(crate) fn new() -> Self {
+        Self { brand: PhantomData }
+    }
+}
+
+/// View of resources of a [`Resources`] container that are accessed via an [`AccessToken`]
+/// with the same brand, without touching their locks, as passed to the closure given
+/// to [`branded`].
+///
+/// It can be copied and shared between threads freely; only the token controls access.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`AccessToken`]: struct.AccessToken.html
+/// [`branded`]: struct.Resources.html#method.branded
+#[derive(Clone, Copy)]
+pub struct Branded<'a, 'brand> {
+    resources: &'a TypeIdMap<ResourceCell>,
+    brand: Brand<'brand>,
+}
+
+impl<'a, 'brand> Branded<'a, 'brand> {
+    /// Creates a view of resources that are known to not be borrowed,
+    /// and can't be borrowed via other means while it exists.
+    pub(crate) fn new(resources: &'a TypeIdMap<ResourceCell>) -> Self {
+        Self {
+            resources,
+            brand: PhantomData,
+        }
+    }
+
+    fn cell<T: Resource>(&self) -> Result<&'a ResourceCell, NoSuchResource> {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .ok_or(NoSuchResource {
+                type_name: type_name::<T>(),
+            })
+    }
+
+    /// Returns `true` if a resource of type `T` exists in the container.
+    pub fn contains<T: Resource>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns a reference to the stored resource of type `T`,
+    /// or an error if it's not present in the container.
+    pub fn get<'t, T: Resource>(
+        &self,
+        token: &'t AccessToken<'brand>,
+    ) -> Result<&'t T, NoSuchResource>
+    where
+        'a: 't,
+    {
+        let _ = token;
+        let cell = self.cell::<T>()?;
+        Ok(unsafe { &*cell.resource_ptr() }
+            .downcast_ref::<T>()
+            .unwrap_or_else(|| panic!("downcasting resources should always succeed")))
+    }
+
+    /// Returns a mutable reference to the stored resource of type `T`,
+    /// or an error if it's not present in the container, or is [read-only].
+    ///
+    /// [read-only]: struct.Resources.html#method.insert_readonly
+    pub fn get_mut<'t, T: Resource>(
+        &self,
+        token: &'t mut AccessToken<'brand>,
+    ) -> Result<&'t mut T, CantGetResource>
+    where
+        'a: 't,
+    {
+        let _ = token;
+        let cell = self.cell::<T>()?;
+        if cell.lock.kind() == LockKind::ReadOnly {
+            return Err(InvalidBorrow::Mutable {
+                type_name: cell.type_name(),
+                conflict: Conflict::ReadOnly,
+            }
+            .into());
+        }
+        // Nothing releases a lock afterwards, so snapshots are invalidated beforehand.
+        #[cfg(feature = "local-cache")]
+        cell.lock.invalidate_local();
+        Ok(unsafe { &mut *cell.resource_ptr() }
+            .downcast_mut::<T>()
+            .unwrap_or_else(|| panic!("downcasting resources should always succeed")))
+    }
+}
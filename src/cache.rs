@@ -0,0 +1,96 @@
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::hash::TypeIdBuildHasher;
+
+/// Resource type marked as an evictable cache, see [`Resources::set_cache_cost()`].
+struct Cache {
+    cost: usize,
+    /// Tick of the container's clock at the last access of the resource.
+    last_access: AtomicU64,
+}
+
+/// Resource types marked as evictable caches, and the budget of their total cost.
+#[derive(Default)]
+pub(crate) struct Caches {
+    caches: HashMap<TypeId, Cache, TypeIdBuildHasher>,
+    budget: Option<usize>,
+    /// Logical clock, advanced by each access of a cache.
+    clock: AtomicU64,
+}
+
+impl Caches {
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Marks the type as a cache, as if it was just accessed.
+    pub(crate) fn set_cost(&mut self, type_id: TypeId, cost: usize) {
+        let last_access = AtomicU64::new(self.tick());
+        self.caches.insert(type_id, Cache { cost, last_access });
+    }
+
+    pub(crate) fn unset(&mut self, type_id: TypeId) {
+        self.caches.remove(&type_id);
+    }
+
+    pub(crate) fn cost(&self, type_id: TypeId) -> Option<usize> {
+        self.caches.get(&type_id).map(|cache| cache.cost)
+    }
+
+    pub(crate) fn budget(&self) -> Option<usize> {
+        self.budget
+    }
+
+    pub(crate) fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+    }
+
+    /// Records an access of the resource of the type, if it's a cache.
+    pub(crate) fn touch(&self, type_id: TypeId) {
+        if self.caches.is_empty() {
+            return;
+        }
+        if let Some(cache) = self.caches.get(&type_id) {
+            cache.last_access.store(self.tick(), Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the total cost of caches of present types.
+    pub(crate) fn usage(&self, is_present: impl Fn(TypeId) -> bool) -> usize {
+        self.caches
+            .iter()
+            .filter(|(type_id, _)| is_present(**type_id))
+            .map(|(_, cache)| cache.cost)
+            .sum()
+    }
+
+    /// Returns types of present caches to evict to fit in the budget,
+    /// least recently used first.
+    pub(crate) fn evictions(&self, is_present: impl Fn(TypeId) -> bool) -> Vec<TypeId> {
+        let budget = match self.budget {
+            Some(budget) => budget,
+            None => return Vec::new(),
+        };
+        let mut present = self
+            .caches
+            .iter()
+            .filter(|(type_id, _)| is_present(**type_id))
+            .map(|(type_id, cache)| (*type_id, cache))
+            .collect::<Vec<_>>();
+        let mut usage = present.iter().map(|(_, cache)| cache.cost).sum::<usize>();
+        present.sort_unstable_by_key(|(_, cache)| cache.last_access.load(Ordering::Relaxed));
+        let mut evictions = Vec::new();
+        for (type_id, cache) in present {
+            if usage <= budget {
+                break;
+            }
+            usage -= cache.cost;
+            evictions.push(type_id);
+        }
+        evictions
+    }
+}
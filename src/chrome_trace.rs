@@ -0,0 +1,204 @@
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::instrument::Access;
+
+/// Whether a trace is being recorded, checked before locking the recording.
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+/// The trace being recorded, and when its recording started.
+static TRACE: Mutex<Option<(Instant, Vec<Event>)>> = Mutex::new(None);
+
+enum Event {
+    Span {
+        type_name: &'static str,
+        access: Access,
+        start: Duration,
+        duration: Duration,
+    },
+    Conflict {
+        type_name: &'static str,
+        access: Access,
+        at: Duration,
+    },
+}
+
+impl Event {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Event::Span { type_name, .. } | Event::Conflict { type_name, .. } => type_name,
+        }
+    }
+}
+
+fn record(event: impl FnOnce(Instant) -> Event) {
+    if RECORDING.load(Ordering::Relaxed) {
+        let mut trace = TRACE.lock().unwrap_or_else(|error| error.into_inner());
+        if let Some((started, events)) = &mut *trace {
+            events.push(event(*started));
+        }
+    }
+}
+
+/// Records a span during which a guard held its resource.
+pub(crate) fn span(type_name: &'static str, access: Access, acquired: Instant) {
+    let released = Instant::now();
+    record(|started| Event::Span {
+        type_name,
+        access,
+        start: acquired.saturating_duration_since(started),
+        duration: released.saturating_duration_since(acquired.max(started)),
+    });
+}
+
+/// Records an acquisition that failed due to a borrow conflict.
+pub(crate) fn conflict(type_name: &'static str, access: Access) {
+    let at = Instant::now();
+    record(|started| Event::Conflict {
+        type_name,
+        access,
+        at: at.saturating_duration_since(started),
+    });
+}
+
+/// Recording of borrows of resources of all [`Resources`] containers, exported as trace events
+/// viewable in `chrome://tracing` or Perfetto.
+///
+/// Recording starts with [`start`], and ends with [`finish`]. The trace has one track per
+/// resource type, showing spans during which guards held resources, and instant events
+/// for acquisitions that failed due to borrow conflicts. Guards acquired before the recording
+/// started, but released during it, are included as spans starting at its beginning.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(not(feature = "explicit"))] {
+/// # use resources::{ChromeTrace, Resources};
+/// let mut resources = Resources::new();
+/// resources.insert(0u32);
+/// ChromeTrace::start();
+/// *resources.get_mut::<u32>().unwrap() += 1;
+/// let trace = ChromeTrace::finish();
+/// let mut json = Vec::new();
+/// trace.write_json(&mut json).unwrap();
+/// # }
+/// ```
+///
+/// [`Resources`]: struct.Resources.html
+/// [`start`]: #method.start
+/// [`finish`]: #method.finish
+pub struct ChromeTrace {
+    events: Vec<Event>,
+}
+
+impl ChromeTrace {
+    /// Starts recording, discarding the recording in progress, if any.
+    pub fn start() {
+        let mut trace = TRACE.lock().unwrap_or_else(|error| error.into_inner());
+        *trace = Some((Instant::now(), Vec::new()));
+        RECORDING.store(true, Ordering::Relaxed);
+    }
+
+    /// Stops recording, returning the recorded trace, which is empty if recording
+    /// wasn't started.
+    pub fn finish() -> Self {
+        let mut trace = TRACE.lock().unwrap_or_else(|error| error.into_inner());
+        RECORDING.store(false, Ordering::Relaxed);
+        Self {
+            events: trace.take().map(|(_, events)| events).unwrap_or_default(),
+        }
+    }
+
+    /// Returns the number of recorded spans and conflicts.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns `true` if nothing was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Writes the trace as JSON in the trace event format.
+    pub fn write_json(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut tracks = Vec::<&'static str>::new();
+        for event in &self.events {
+            if !tracks.contains(&event.type_name()) {
+                tracks.push(event.type_name());
+            }
+        }
+        let track = |type_name: &str| tracks.iter().position(|name| *name == type_name).unwrap();
+        writer.write_all(br#"{"displayTimeUnit":"ms","traceEvents":["#)?;
+        for (index, type_name) in tracks.iter().enumerate() {
+            if index > 0 {
+                writer.write_all(b",")?;
+            }
+            write!(
+                writer,
+                r#"{{"ph":"M","name":"thread_name","pid":0,"tid":{},"args":{{"name":"#,
+                index
+            )?;
+            write_string(&mut writer, type_name)?;
+            writer.write_all(b"}}")?;
+        }
+        for event in &self.events {
+            writer.write_all(b",")?;
+            match event {
+                Event::Span {
+                    type_name,
+                    access,
+                    start,
+                    duration,
+                } => write!(
+                    writer,
+                    r#"{{"ph":"X","name":"{}","cat":"borrow","pid":0,"tid":{},"ts":{},"dur":{}}}"#,
+                    access_name(*access),
+                    track(type_name),
+                    micros(*start),
+                    micros(*duration),
+                )?,
+                Event::Conflict {
+                    type_name,
+                    access,
+                    at,
+                } => write!(
+                    writer,
+                    r#"{{"ph":"i","s":"t","name":"conflict ({})","cat":"conflict","pid":0,"tid":{},"ts":{}}}"#,
+                    access_name(*access),
+                    track(type_name),
+                    micros(*at),
+                )?,
+            }
+        }
+        writer.write_all(b"]}")
+    }
+}
+
+fn access_name(access: Access) -> &'static str {
+    match access {
+        Access::Shared => "shared",
+        Access::Exclusive => "exclusive",
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.0
+}
+
+fn write_string(writer: &mut impl Write, string: &str) -> io::Result<()> {
+    writer.write_all(b"\"")?;
+    for character in string.chars() {
+        match character {
+            '"' => writer.write_all(b"\\\"")?,
+            '\\' => writer.write_all(b"\\\\")?,
+            character if character.is_control() => write!(writer, "\\u{:04x}", character as u32)?,
+            character => write!(writer, "{}", character)?,
+        }
+    }
+    writer.write_all(b"\"")
+}
@@ -1,9 +1,17 @@
-use parking_lot::RwLock;
-use std::{any::TypeId, collections::hash_map, marker::PhantomData, ops::DerefMut};
+use hashbrown::hash_map;
+use std::{
+    any::{type_name, TypeId},
+    marker::PhantomData,
+    ops::DerefMut,
+};
 
 use crate::{
+    error::MissingResources,
+    hash::TypeIdBuildHasher,
+    lock::ResourceCell,
     map::Resource,
     refs::{Ref, RefMut},
+    storage::{Storage, StorageAllocator},
 };
 
 /// A view into an entry in a [`Resources`] container, which may either be vacant or occupied.
@@ -23,7 +31,8 @@ pub enum Entry<'a, T: Resource> {
 /// [`Resources`]: struct.Resources.html
 /// [`Entry`]: enum.Entry.html
 pub struct OccupiedEntry<'a, T: Resource> {
-    base: hash_map::OccupiedEntry<'a, TypeId, RwLock<Box<dyn Resource>>>,
+    base: hash_map::OccupiedEntry<'a, TypeId, ResourceCell, TypeIdBuildHasher, StorageAllocator>,
+    storage: &'a mut Storage,
     phantom_data: PhantomData<T>,
 }
 
@@ -32,21 +41,29 @@ pub struct OccupiedEntry<'a, T: Resource> {
 /// [`Resources`]: struct.Resources.html
 /// [`Entry`]: enum.Entry.html
 pub struct VacantEntry<'a, T: Resource> {
-    base: hash_map::VacantEntry<'a, TypeId, RwLock<Box<dyn Resource>>>,
+    base: hash_map::VacantEntry<'a, TypeId, ResourceCell, TypeIdBuildHasher, StorageAllocator>,
+    storage: &'a mut Storage,
+    // Only checked in debug builds.
+    missing: Option<MissingResources>,
     phantom_data: PhantomData<T>,
 }
 
 impl<'a, T: Resource> Entry<'a, T> {
     pub(crate) fn from_hash_map_entry(
-        entry: hash_map::Entry<'a, TypeId, RwLock<Box<dyn Resource>>>,
+        entry: hash_map::Entry<'a, TypeId, ResourceCell, TypeIdBuildHasher, StorageAllocator>,
+        storage: &'a mut Storage,
+        missing: Option<MissingResources>,
     ) -> Self {
         match entry {
             hash_map::Entry::Occupied(base) => Entry::Occupied(OccupiedEntry {
                 base,
+                storage,
                 phantom_data: PhantomData,
             }),
             hash_map::Entry::Vacant(base) => Entry::Vacant(VacantEntry {
                 base,
+                storage,
+                missing,
                 phantom_data: PhantomData,
             }),
         }
@@ -54,12 +71,34 @@ impl<'a, T: Resource> Entry<'a, T> {
 
     /// Ensures a resource is in the entry by inserting the given value if empty,
     /// and returns a mutable reference to the contained resource.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry is occupied by a [read-only] resource, or if it's vacant
+    /// and the container is [sealed], or, in debug builds, resources of types that `T`
+    /// [depends on] are missing.
+    ///
+    /// [read-only]: struct.Resources.html#method.insert_readonly
+    /// [sealed]: struct.Resources.html#method.seal
+    /// [depends on]: struct.Resources.html#method.add_dependency
+    #[track_caller]
     pub fn or_insert(self, default: T) -> RefMut<'a, T> {
         self.or_insert_with(|| default)
     }
 
     /// Ensures a resource is in the entry by inserting the result of given function if empty,
     /// and returns a mutable reference to the contained resource.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry is occupied by a [read-only] resource, or if it's vacant
+    /// and the container is [sealed], or, in debug builds, resources of types that `T`
+    /// [depends on] are missing.
+    ///
+    /// [read-only]: struct.Resources.html#method.insert_readonly
+    /// [sealed]: struct.Resources.html#method.seal
+    /// [depends on]: struct.Resources.html#method.add_dependency
+    #[track_caller]
     pub fn or_insert_with(self, default: impl FnOnce() -> T) -> RefMut<'a, T> {
         use Entry::*;
         match self {
@@ -69,6 +108,13 @@ impl<'a, T: Resource> Entry<'a, T> {
     }
 
     /// Provides in-place mutable access to an occupied entry before any potential inserts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource is [read-only].
+    ///
+    /// [read-only]: struct.Resources.html#method.insert_readonly
+    #[track_caller]
     pub fn and_modify(mut self, f: impl FnOnce(&mut T)) -> Self {
         if let Entry::Occupied(occupied) = &mut self {
             f(occupied.get_mut().deref_mut());
@@ -80,6 +126,17 @@ impl<'a, T: Resource> Entry<'a, T> {
 impl<'a, T: Resource + Default> Entry<'a, T> {
     /// Ensures a resource is in the entry by inserting it's default value if empty,
     /// and returns a mutable reference to the contained resource.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry is occupied by a [read-only] resource, or if it's vacant
+    /// and the container is [sealed], or, in debug builds, resources of types that `T`
+    /// [depends on] are missing.
+    ///
+    /// [read-only]: struct.Resources.html#method.insert_readonly
+    /// [sealed]: struct.Resources.html#method.seal
+    /// [depends on]: struct.Resources.html#method.add_dependency
+    #[track_caller]
     pub fn or_default(self) -> RefMut<'a, T> {
         self.or_insert_with(T::default)
     }
@@ -87,48 +144,78 @@ impl<'a, T: Resource + Default> Entry<'a, T> {
 
 impl<'a, T: Resource> OccupiedEntry<'a, T> {
     /// Gets a reference to the value in the entry.
-    pub fn get(&self) -> Ref<T> {
-        Ref::from_lock(self.base.get()).expect("entry API assumes unique access")
+    #[track_caller]
+    pub fn get(&self) -> Ref<'_, T> {
+        Ref::from_cell(self.base.get()).expect("entry API assumes unique access")
     }
 
     /// Gets a mutable reference to the value in the entry.
-    pub fn get_mut(&mut self) -> RefMut<T> {
-        RefMut::from_lock(self.base.get_mut()).expect("entry API assumes unique access")
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource is [read-only].
+    ///
+    /// [read-only]: struct.Resources.html#method.insert_readonly
+    #[track_caller]
+    pub fn get_mut(&mut self) -> RefMut<'_, T> {
+        RefMut::from_cell(self.base.get_mut()).unwrap_or_else(|error| panic!("{}", error))
     }
 
     /// Converts the `OccupiedEntry` into a mutable reference to the value in the entry
     /// with a lifetime bound to the [`Resources`] struct itself.
     ///
+    /// # Panics
+    ///
+    /// Panics if the resource is [read-only].
+    ///
     /// [`Resources`]: struct.Resources.html
+    /// [read-only]: struct.Resources.html#method.insert_readonly
+    #[track_caller]
     pub fn into_mut(self) -> RefMut<'a, T> {
-        RefMut::from_lock(self.base.into_mut()).expect("entry API assumes unique access")
+        RefMut::from_cell(self.base.into_mut()).unwrap_or_else(|error| panic!("{}", error))
     }
 
     /// Sets the value of the entry, and returns the entry's old value.
     pub fn insert(&mut self, value: T) -> T {
-        *self
-            .base
-            .insert(RwLock::new(Box::new(value)))
-            .into_inner()
-            .downcast()
-            .unwrap_or_else(|_| panic!("downcasting resources should always succeed"))
+        self.storage.resource_replaced(TypeId::of::<T>());
+        self.base.get_mut().replace(value)
     }
 
     /// Takes the value out of the entry, and returns it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the container is [sealed].
+    ///
+    /// [sealed]: struct.Resources.html#method.seal
+    #[track_caller]
     pub fn remove(self) -> T {
-        *self
-            .base
-            .remove()
-            .into_inner()
-            .downcast()
-            .unwrap_or_else(|_| panic!("downcasting resources should always succeed"))
+        self.storage.assert_unsealed(type_name::<T>());
+        self.storage
+            .resource_removed(TypeId::of::<T>(), type_name::<T>());
+        self.base.remove().into_resource(self.storage)
     }
 }
 
 impl<'a, T: Resource> VacantEntry<'a, T> {
     /// Sets the value of the entry, and returns a mutable reference to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the container is [sealed], or, in debug builds, if resources of types
+    /// that `T` [depends on] are missing.
+    ///
+    /// [sealed]: struct.Resources.html#method.seal
+    /// [depends on]: struct.Resources.html#method.add_dependency
+    #[track_caller]
     pub fn insert(self, value: T) -> RefMut<'a, T> {
-        RefMut::from_lock(self.base.insert(RwLock::new(Box::new(value))))
+        self.storage.assert_unsealed(type_name::<T>());
+        if let Some(missing) = &self.missing {
+            panic!("cannot insert `{}`: {}", type_name::<T>(), missing);
+        }
+        self.storage
+            .resource_inserted(TypeId::of::<T>(), type_name::<T>());
+        RefMut::from_cell(self.base.insert(ResourceCell::new(value, self.storage)))
             .expect("entry API assumes unique access")
     }
 }
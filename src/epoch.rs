@@ -0,0 +1,126 @@
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned};
+use std::{
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    marker::PhantomData,
+    mem,
+    ops::Deref,
+    sync::atomic::Ordering,
+};
+
+use crate::map::Resource;
+
+/// Resource read without locking, stored in a [`Resources`] container
+/// via [`insert_rcu`] and accessed via [`rcu`].
+///
+/// Readers get [`Snapshot`]s of the current version of the value, without modifying
+/// any memory shared with other threads, while writers publish new versions;
+/// old versions are dropped once no snapshot refers to them, as tracked by `crossbeam-epoch`.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`insert_rcu`]: struct.Resources.html#method.insert_rcu
+/// [`rcu`]: struct.Resources.html#method.rcu
+/// [`Snapshot`]: struct.Snapshot.html
+pub struct Rcu<T: Resource> {
+    current: Atomic<T>,
+}
+
+impl<T: Resource> Rcu<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Self {
+            current: Atomic::new(value),
+        }
+    }
+
+    pub(crate) fn into_inner(mut self) -> T {
+        let current = mem::replace(&mut self.current, Atomic::null());
+        // Owning the value means no snapshots of it exist.
+        *unsafe { current.into_owned() }.into_box()
+    }
+
+    /// Returns a snapshot of the current version of the value.
+    pub fn load(&self) -> Snapshot<'_, T> {
+        let guard = epoch::pin();
+        let current = self.current.load(Ordering::Acquire, &guard).as_raw();
+        Snapshot {
+            _guard: guard,
+            current,
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// Publishes a new version of the value; existing snapshots keep referring to the old one.
+    pub fn publish(&self, value: T) {
+        let guard = epoch::pin();
+        let old = self
+            .current
+            .swap(Owned::new(value), Ordering::AcqRel, &guard);
+        unsafe { guard.defer_destroy(old) };
+    }
+
+    /// Publishes a new version of the value computed from the current one,
+    /// retrying if another version is published in the meantime.
+    pub fn update(&self, mut f: impl FnMut(&T) -> T) {
+        let guard = epoch::pin();
+        let mut current = self.current.load(Ordering::Acquire, &guard);
+        loop {
+            let new = Owned::new(f(unsafe { current.deref() }));
+            match self.current.compare_exchange(
+                current,
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                &guard,
+            ) {
+                Ok(_) => {
+                    unsafe { guard.defer_destroy(current) };
+                    return;
+                }
+                Err(error) => current = error.current,
+            }
+        }
+    }
+}
+
+impl<T: Resource> Drop for Rcu<T> {
+    fn drop(&mut self) {
+        let guard = unsafe { epoch::unprotected() };
+        let current = self.current.load(Ordering::Relaxed, guard);
+        if !current.is_null() {
+            drop(unsafe { current.into_owned() });
+        }
+    }
+}
+
+/// Snapshot of a version of an [`Rcu`] resource, as returned by [`Rcu::load`].
+///
+/// The thread is pinned to the current epoch while this exists, postponing destruction
+/// of any versions unpublished in the meantime, so it shouldn't be held for long.
+///
+/// [`Rcu`]: struct.Rcu.html
+/// [`Rcu::load`]: struct.Rcu.html#method.load
+pub struct Snapshot<'a, T: Resource> {
+    // Keeps the version from being destroyed while the snapshot exists.
+    _guard: Guard,
+    current: *const T,
+    phantom_data: PhantomData<&'a T>,
+}
+
+impl<'a, T: Resource> Deref for Snapshot<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.current }
+    }
+}
+
+impl<'a, T: Resource + Debug> Debug for Snapshot<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: Resource + Display> Display for Snapshot<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Display::fmt(&**self, f)
+    }
+}
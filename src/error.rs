@@ -1,23 +1,46 @@
 use std::{
+    any::TypeId,
+    convert::TryFrom,
     error::Error,
-    fmt::{Display, Formatter, Result as FmtResult},
+    fmt::{self, Display, Formatter, Result as FmtResult},
 };
 
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+
 /// Error indicating that no [`Resource`] of requested type is present in a [`Resources`] container.
 ///
 /// [`Resource`]: trait.Resource.html
 /// [`Resources`]: struct.Resources.html
 #[derive(Debug, Clone, Eq, PartialEq)]
-pub struct NoSuchResource;
+pub struct NoSuchResource {
+    /// Compiler-provided name of the requested type.
+    pub type_name: &'static str,
+}
 
 impl Display for NoSuchResource {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        f.pad("no such resource")
+        write!(f, "no such resource: {}", self.type_name)
     }
 }
 
 impl Error for NoSuchResource {}
 
+#[cfg(feature = "miette")]
+impl Diagnostic for NoSuchResource {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new("resources::no_such_resource"))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(format!(
+            "resource `{}` was never inserted, or was removed; \
+             if it's optional, consider `get_opt()` or `get_mut_opt()`",
+            self.type_name
+        )))
+    }
+}
+
 /// Error indicating that accessing the requested [`Resource`] in a [`Resources`] container
 /// via [`get`] or [`get_mut`] methods would violate borrow rules.
 ///
@@ -28,22 +51,218 @@ impl Error for NoSuchResource {}
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum InvalidBorrow {
     /// Can't access mutably because the resource is accessed either immutably or mutably elsewhere.
-    Mutable,
+    Mutable {
+        /// Compiler-provided name of the resource's type.
+        type_name: &'static str,
+        /// What the access conflicted with.
+        conflict: Conflict,
+    },
     /// Can't access immutably because the resource is accessed mutably elsewhere.
-    Immutable,
+    Immutable {
+        /// Compiler-provided name of the resource's type.
+        type_name: &'static str,
+        /// What the access conflicted with.
+        conflict: Conflict,
+    },
+}
+
+impl InvalidBorrow {
+    /// Returns the compiler-provided name of the resource's type.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            InvalidBorrow::Mutable { type_name, .. }
+            | InvalidBorrow::Immutable { type_name, .. } => type_name,
+        }
+    }
+
+    /// Returns what the access conflicted with.
+    pub fn conflict(&self) -> Conflict {
+        match self {
+            InvalidBorrow::Mutable { conflict, .. } | InvalidBorrow::Immutable { conflict, .. } => {
+                *conflict
+            }
+        }
+    }
 }
 
 impl Display for InvalidBorrow {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        f.pad(match self {
-            InvalidBorrow::Mutable => "cannot borrow mutably",
-            InvalidBorrow::Immutable => "cannot borrow immutably",
-        })
+        match self {
+            InvalidBorrow::Mutable {
+                type_name,
+                conflict,
+            } => {
+                write!(f, "cannot borrow mutably: {}: {}", type_name, conflict)
+            }
+            InvalidBorrow::Immutable {
+                type_name,
+                conflict,
+            } => {
+                write!(f, "cannot borrow immutably: {}: {}", type_name, conflict)
+            }
+        }
     }
 }
 
 impl Error for InvalidBorrow {}
 
+#[cfg(feature = "miette")]
+impl Diagnostic for InvalidBorrow {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(match self {
+            InvalidBorrow::Mutable { .. } => "resources::invalid_borrow::mutable",
+            InvalidBorrow::Immutable { .. } => "resources::invalid_borrow::immutable",
+        }))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        let type_name = self.type_name();
+        Some(Box::new(match self.conflict() {
+            Conflict::Readers { count, via, .. } => format!(
+                "resource `{}` is borrowed by {} `Ref`(s), the latest via {}; \
+                 did you forget to drop one?",
+                type_name, count, via
+            ),
+            Conflict::Writer { via, .. } => format!(
+                "resource `{}` is exclusively borrowed via {}; did you forget to drop a `RefMut`?",
+                type_name, via
+            ),
+            Conflict::Queued => format!(
+                "resource `{}` is awaited by blocking accesses, which non-blocking ones \
+                 can't overtake; consider a blocking or recursive method",
+                type_name
+            ),
+            Conflict::Released => format!(
+                "resource `{}` was released right after the access failed; \
+                 it's contended, consider retrying or a blocking method",
+                type_name
+            ),
+            Conflict::Injected => format!(
+                "the failure to access resource `{}` was injected for testing",
+                type_name
+            ),
+            Conflict::ReadOnly => format!(
+                "resource `{}` is read-only; replace it via `insert()` instead",
+                type_name
+            ),
+        }))
+    }
+}
+
+/// API through which a guard of a resource was acquired, as reported by [`Conflict`].
+///
+/// [`Conflict`]: enum.Conflict.html
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum BorrowApi {
+    /// `get()`, `get_mut()`, or another non-blocking method, such as `fetch()`.
+    Get,
+    /// `get_recursive()` or `get_blocking_recursive()`.
+    Recursive,
+    /// A blocking method, such as `get_blocking()`.
+    Blocking,
+    /// An asynchronous method, such as `get_async()` or `read()`.
+    Async,
+    /// `read_all()` or `lock_all()`.
+    All,
+    /// Any other API, such as `visit()`, or `StaticResources` methods.
+    Other,
+}
+
+impl BorrowApi {
+    pub(crate) fn from_u8(value: u8) -> Self {
+        use BorrowApi::*;
+        match value {
+            0 => Get,
+            1 => Recursive,
+            2 => Blocking,
+            3 => Async,
+            4 => All,
+            _ => Other,
+        }
+    }
+}
+
+impl Display for BorrowApi {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        use BorrowApi::*;
+        f.write_str(match self {
+            Get => "`get()` or similar",
+            Recursive => "a recursive method",
+            Blocking => "a blocking method",
+            Async => "an asynchronous method",
+            All => "`read_all()` or `lock_all()`",
+            Other => "another API",
+        })
+    }
+}
+
+/// What a failed access to a resource conflicted with, as carried by [`InvalidBorrow`].
+///
+/// This describes the resource's lock right after the access failed;
+/// it may be out of date by the time it's inspected.
+///
+/// [`InvalidBorrow`]: enum.InvalidBorrow.html
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Conflict {
+    /// The resource is borrowed immutably.
+    Readers {
+        /// How many guards borrow the resource.
+        count: usize,
+        /// API through which the latest of the guards was acquired.
+        via: BorrowApi,
+        /// Label of the latest of the guards acquired via a labeled method, such as
+        /// `get_labeled()`; always `None` without the `track-borrows` feature.
+        label: Option<&'static str>,
+    },
+    /// The resource is borrowed mutably.
+    Writer {
+        /// API through which the guard was acquired.
+        via: BorrowApi,
+        /// Label of the guard, if it was acquired via a labeled method, such as
+        /// `get_mut_labeled()`; always `None` without the `track-borrows` feature.
+        label: Option<&'static str>,
+    },
+    /// Blocking accesses are waiting for the resource, and non-blocking ones can't overtake them.
+    Queued,
+    /// The conflicting borrow was released before it could be inspected.
+    Released,
+    /// The failure was injected via `Resources::inject_failure()` or
+    /// `Resources::inject_random_failures()`, with the `test-util` feature.
+    Injected,
+    /// The resource is read-only, having been inserted via `Resources::insert_readonly()`.
+    ReadOnly,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Conflict::Readers { count, via, label } => {
+                write!(
+                    f,
+                    "borrowed immutably by {} guard(s), the latest via {}",
+                    count, via
+                )?;
+                write_label(f, *label)
+            }
+            Conflict::Writer { via, label } => {
+                write!(f, "borrowed mutably via {}", via)?;
+                write_label(f, *label)
+            }
+            Conflict::Queued => f.write_str("awaited by blocking accesses"),
+            Conflict::Released => f.write_str("released while failing"),
+            Conflict::Injected => f.write_str("failure injected for testing"),
+            Conflict::ReadOnly => f.write_str("read-only"),
+        }
+    }
+}
+
+fn write_label(f: &mut Formatter<'_>, label: Option<&'static str>) -> FmtResult {
+    match label {
+        Some(label) => write!(f, " by `{}`", label),
+        None => Ok(()),
+    }
+}
+
 /// Errors that may occur when accessing a [`Resource`] in a [`Resources`] container
 /// via [`get`] or [`get_mut`] methods.
 ///
@@ -59,6 +278,56 @@ pub enum CantGetResource {
     NoSuchResource(NoSuchResource),
 }
 
+impl CantGetResource {
+    /// Returns the compiler-provided name of the requested type.
+    pub fn type_name(&self) -> &'static str {
+        use CantGetResource::*;
+        match self {
+            InvalidBorrow(error) => error.type_name(),
+            NoSuchResource(error) => error.type_name,
+        }
+    }
+
+    /// Returns `true` if the error is caused by the resource not being present in the container.
+    pub fn is_missing(&self) -> bool {
+        matches!(self, CantGetResource::NoSuchResource(_))
+    }
+
+    /// Returns `true` if the error is caused by a conflicting borrow of the resource.
+    pub fn is_borrow_conflict(&self) -> bool {
+        matches!(self, CantGetResource::InvalidBorrow(_))
+    }
+
+    /// Returns `true` if the error is caused by a mutable access of a read-only resource.
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            CantGetResource::InvalidBorrow(error) if error.conflict() == Conflict::ReadOnly
+        )
+    }
+
+    /// Returns the underlying [`InvalidBorrow`] error, or `None` if the resource is missing.
+    ///
+    /// [`InvalidBorrow`]: enum.InvalidBorrow.html
+    pub fn into_invalid_borrow(self) -> Option<InvalidBorrow> {
+        match self {
+            CantGetResource::InvalidBorrow(error) => Some(error),
+            CantGetResource::NoSuchResource(_) => None,
+        }
+    }
+
+    /// Returns the underlying [`NoSuchResource`] error, or `None` if the resource is present,
+    /// but borrowed in a conflicting way.
+    ///
+    /// [`NoSuchResource`]: struct.NoSuchResource.html
+    pub fn into_no_such_resource(self) -> Option<NoSuchResource> {
+        match self {
+            CantGetResource::NoSuchResource(error) => Some(error),
+            CantGetResource::InvalidBorrow(_) => None,
+        }
+    }
+}
+
 impl Display for CantGetResource {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         use CantGetResource::*;
@@ -79,6 +348,26 @@ impl Error for CantGetResource {
     }
 }
 
+// Forwards to the underlying error, which is also its `source()`.
+#[cfg(feature = "miette")]
+impl Diagnostic for CantGetResource {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        use CantGetResource::*;
+        match self {
+            InvalidBorrow(error) => error.code(),
+            NoSuchResource(error) => error.code(),
+        }
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        use CantGetResource::*;
+        match self {
+            InvalidBorrow(error) => error.help(),
+            NoSuchResource(error) => error.help(),
+        }
+    }
+}
+
 impl From<NoSuchResource> for CantGetResource {
     fn from(error: NoSuchResource) -> Self {
         CantGetResource::NoSuchResource(error)
@@ -90,3 +379,216 @@ impl From<InvalidBorrow> for CantGetResource {
         CantGetResource::InvalidBorrow(error)
     }
 }
+
+impl TryFrom<CantGetResource> for NoSuchResource {
+    type Error = CantGetResource;
+
+    fn try_from(error: CantGetResource) -> Result<Self, CantGetResource> {
+        match error {
+            CantGetResource::NoSuchResource(error) => Ok(error),
+            error => Err(error),
+        }
+    }
+}
+
+impl TryFrom<CantGetResource> for InvalidBorrow {
+    type Error = CantGetResource;
+
+    fn try_from(error: CantGetResource) -> Result<Self, CantGetResource> {
+        match error {
+            CantGetResource::InvalidBorrow(error) => Ok(error),
+            error => Err(error),
+        }
+    }
+}
+
+/// Error indicating that inserting a resource into a [`Resources`] container would exceed
+/// its [memory budget]. Contains the resource that wasn't inserted, if any.
+///
+/// [`Resources`]: struct.Resources.html
+/// [memory budget]: struct.Resources.html#method.set_memory_budget
+#[derive(Clone, Eq, PartialEq)]
+pub struct BudgetExceeded<T = ()> {
+    /// The resource that wasn't inserted.
+    pub resource: T,
+    /// Compiler-provided name of the resource's type.
+    pub type_name: &'static str,
+    /// Size of the resource, in bytes.
+    pub size: usize,
+    /// Total size of resources already in the container, in bytes.
+    pub memory_usage: usize,
+    /// The container's memory budget, in bytes.
+    pub memory_budget: usize,
+}
+
+impl<T> fmt::Debug for BudgetExceeded<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("BudgetExceeded")
+            .field("type_name", &self.type_name)
+            .field("size", &self.size)
+            .field("memory_usage", &self.memory_usage)
+            .field("memory_budget", &self.memory_budget)
+            .finish()
+    }
+}
+
+impl<T> Display for BudgetExceeded<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "memory budget exceeded: {} needs {} bytes, with {} of {} bytes used",
+            self.type_name, self.size, self.memory_usage, self.memory_budget
+        )
+    }
+}
+
+impl<T> Error for BudgetExceeded<T> {}
+
+/// Error indicating that inserting or removing a resource would change the structure of
+/// a [`Resources`] container after it was [sealed]. Contains the resource that wasn't inserted,
+/// if any.
+///
+/// [`Resources`]: struct.Resources.html
+/// [sealed]: struct.Resources.html#method.seal
+#[derive(Clone, Eq, PartialEq)]
+pub struct Sealed<T = ()> {
+    /// The resource that wasn't inserted.
+    pub resource: T,
+    /// Compiler-provided name of the resource's type.
+    pub type_name: &'static str,
+}
+
+impl<T> fmt::Debug for Sealed<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Sealed")
+            .field("type_name", &self.type_name)
+            .finish()
+    }
+}
+
+impl<T> Display for Sealed<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "container is sealed: cannot insert or remove {}",
+            self.type_name
+        )
+    }
+}
+
+impl<T> Error for Sealed<T> {}
+
+/// Error indicating that a resource can't be inserted into a [`Resources`] container, because
+/// resources of some types it [depends on] are not present. Contains the resource that wasn't
+/// inserted, if any.
+///
+/// [`Resources`]: struct.Resources.html
+/// [depends on]: struct.Resources.html#method.add_dependency
+#[derive(Clone, Eq, PartialEq)]
+pub struct MissingDependencies<T = ()> {
+    /// The resource that wasn't inserted.
+    pub resource: T,
+    /// Compiler-provided name of the resource's type.
+    pub type_name: &'static str,
+    /// Names of the missing types.
+    pub missing: MissingResources,
+}
+
+impl<T> fmt::Debug for MissingDependencies<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("MissingDependencies")
+            .field("type_name", &self.type_name)
+            .field("missing", &self.missing)
+            .finish()
+    }
+}
+
+impl<T> Display for MissingDependencies<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "cannot insert `{}`: {}", self.type_name, self.missing)
+    }
+}
+
+impl<T> Error for MissingDependencies<T> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.missing)
+    }
+}
+
+/// Error returned by [`Resources::get_many_dyn()`] and [`Resources::get_many_dyn_mut()`].
+///
+/// [`Resources::get_many_dyn()`]: struct.Resources.html#method.get_many_dyn
+/// [`Resources::get_many_dyn_mut()`]: struct.Resources.html#method.get_many_dyn_mut
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CantGetDyn {
+    /// Accessing one of the resources would violate borrow rules.
+    InvalidBorrow(InvalidBorrow),
+    /// No resource of the type with this `TypeId` is present in the container.
+    NoSuchResource(TypeId),
+    /// The type was requested more than once, which can't be borrowed mutably.
+    Duplicate {
+        /// Compiler-provided name of the requested type.
+        type_name: &'static str,
+    },
+}
+
+impl Display for CantGetDyn {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            CantGetDyn::InvalidBorrow(error) => error.fmt(f),
+            CantGetDyn::NoSuchResource(type_id) => {
+                write!(f, "no such resource: {:?}", type_id)
+            }
+            CantGetDyn::Duplicate { type_name } => {
+                write!(f, "cannot borrow mutably more than once: {}", type_name)
+            }
+        }
+    }
+}
+
+impl Error for CantGetDyn {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CantGetDyn::InvalidBorrow(error) => Some(error),
+            CantGetDyn::NoSuchResource(_) | CantGetDyn::Duplicate { .. } => None,
+        }
+    }
+}
+
+impl From<InvalidBorrow> for CantGetDyn {
+    fn from(error: InvalidBorrow) -> Self {
+        CantGetDyn::InvalidBorrow(error)
+    }
+}
+
+/// Error indicating that resources of some types required via [`Resources::validate()`]
+/// are not present in a [`Resources`] container.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`Resources::validate()`]: struct.Resources.html#method.validate
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MissingResources {
+    /// Compiler-provided names of all missing types, in the order they were required in.
+    pub type_names: Vec<&'static str>,
+}
+
+impl Display for MissingResources {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "missing resources: {}", self.type_names.join(", "))
+    }
+}
+
+impl Error for MissingResources {}
+
+#[cfg(feature = "miette")]
+impl Diagnostic for MissingResources {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new("resources::missing_resources"))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(
+            "insert the missing resources before validating, for example during startup",
+        ))
+    }
+}
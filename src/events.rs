@@ -0,0 +1,187 @@
+use std::{
+    fmt::{Debug, Formatter, Result as FmtResult},
+    marker::PhantomData,
+    mem,
+};
+
+use crate::{map::Resource, refs::RefMut};
+
+/// Double-buffered queue of events of type `T`, stored as a resource.
+///
+/// Events sent since the last call to [`Resources::update_events()`] are kept
+/// in the current buffer; each call discards the previous buffer and makes the current one
+/// previous. This gives every reader one full update to see each event,
+/// via its own [`EventReader`] cursor.
+///
+/// [`Resources::update_events()`]: struct.Resources.html#method.update_events
+/// [`EventReader`]: struct.EventReader.html
+pub struct Events<T> {
+    previous: Vec<T>,
+    current: Vec<T>,
+    /// Total number of events ever sent, used as the ID of the next one.
+    sent: usize,
+}
+
+#[cfg(feature = "explicit")]
+impl<T: Send + Sync + 'static> Resource for Events<T> {}
+
+impl<T> Events<T> {
+    /// Appends the event to the current buffer.
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+        self.sent += 1;
+    }
+
+    /// Discards the previous buffer, and makes the current buffer previous.
+    pub fn update(&mut self) {
+        // Swapping instead of taking reuses the discarded buffer's capacity.
+        mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+    }
+
+    /// Discards all events.
+    pub fn clear(&mut self) {
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    /// Returns the number of events in both buffers.
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    /// Returns `true` if both buffers are empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over events in both buffers, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.previous.iter().chain(&self.current)
+    }
+
+    /// Returns the ID of the oldest event still in the buffers.
+    fn oldest(&self) -> usize {
+        self.sent - self.len()
+    }
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            sent: 0,
+        }
+    }
+}
+
+impl<T> Extend<T> for Events<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, events: I) {
+        for event in events {
+            self.send(event);
+        }
+    }
+}
+
+impl<T: Debug> Debug for Events<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Cursor into [`Events`], for reading each event once.
+///
+/// Each reader, typically owned by a system, tracks which events it has already read.
+/// Events are missed if the reader isn't used for two updates after they were sent.
+///
+/// [`Events`]: struct.Events.html
+pub struct EventReader<T> {
+    next: usize,
+    phantom_data: PhantomData<fn() -> T>,
+}
+
+impl<T> EventReader<T> {
+    /// Creates a reader that will read all events currently in the buffers.
+    pub fn new() -> Self {
+        Self {
+            next: 0,
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over events not yet read by this reader, oldest first,
+    /// and marks them as read.
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> {
+        let skip = self.next.saturating_sub(events.oldest());
+        self.next = events.sent;
+        events.iter().skip(skip)
+    }
+
+    /// Returns `true` if there are events not yet read by this reader.
+    pub fn has_unread(&self, events: &Events<T>) -> bool {
+        self.next < events.sent && !events.is_empty()
+    }
+}
+
+impl<T> Default for EventReader<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for EventReader<T> {
+    fn clone(&self) -> Self {
+        Self {
+            next: self.next,
+            phantom_data: PhantomData,
+        }
+    }
+}
+
+impl<T> Debug for EventReader<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("EventReader")
+            .field("next", &self.next)
+            .finish()
+    }
+}
+
+/// Mutable borrow of [`Events`] stored in a [`Resources`] container, for sending events;
+/// returned by [`Resources::event_writer()`].
+///
+/// [`Events`]: struct.Events.html
+/// [`Resources`]: struct.Resources.html
+/// [`Resources::event_writer()`]: struct.Resources.html#method.event_writer
+pub struct EventWriter<'a, T> {
+    events: RefMut<'a, Events<T>>,
+}
+
+impl<'a, T> EventWriter<'a, T> {
+    pub(crate) fn new(events: RefMut<'a, Events<T>>) -> Self {
+        Self { events }
+    }
+
+    /// Appends the event to the current buffer.
+    pub fn send(&mut self, event: T) {
+        self.events.send(event);
+    }
+
+    /// Appends the events to the current buffer.
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        self.events.extend(events);
+    }
+}
+
+impl<'a, T: Debug> Debug for EventWriter<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        (*self.events).fmt(f)
+    }
+}
+
+/// Makes the current buffer of the resource, which must be `Events<T>`, previous.
+pub(crate) fn update<T: Send + Sync + 'static>(events: &mut dyn Resource) {
+    if let Some(events) = events.downcast_mut::<Events<T>>() {
+        events.update();
+    }
+}
@@ -0,0 +1,251 @@
+use std::{any::TypeId, thread};
+
+#[cfg(feature = "hecs")]
+use crate::world::ComponentTypes;
+use crate::{map::Resources, subset::ResourceTypes};
+
+/// Closure run by a system.
+type Run<'a, M> = Box<dyn FnOnce(&Resources<M>) + Send + 'a>;
+
+/// Declarations of which resources something reads and writes, such as those of a [`System`].
+///
+/// With the `hecs` feature, accesses to components of a `hecs::World` can be declared as well.
+///
+/// [`System`]: struct.System.html
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct AccessSet {
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+    #[cfg(feature = "hecs")]
+    component_reads: Vec<TypeId>,
+    #[cfg(feature = "hecs")]
+    component_writes: Vec<TypeId>,
+}
+
+impl AccessSet {
+    /// Creates a set with no declared accesses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a set declaring reading and writing resources of the given types.
+    #[cfg(feature = "shred-compat")]
+    pub(crate) fn from_type_ids(reads: Vec<TypeId>, writes: Vec<TypeId>) -> Self {
+        let mut set = Self::new();
+        set.reads = reads;
+        set.writes = writes;
+        set
+    }
+
+    /// Declares reading resources of the types in the tuple `S`, such as `(A, B, C)`.
+    pub fn reads<S: ResourceTypes>(mut self) -> Self {
+        self.reads.extend(S::type_ids());
+        self
+    }
+
+    /// Declares writing resources of the types in the tuple `S`, such as `(A, B, C)`.
+    pub fn writes<S: ResourceTypes>(mut self) -> Self {
+        self.writes.extend(S::type_ids());
+        self
+    }
+
+    /// Declares reading components of the types in the tuple `S`, such as `(A, B, C)`.
+    #[cfg(feature = "hecs")]
+    pub fn reads_components<S: ComponentTypes>(mut self) -> Self {
+        self.component_reads.extend(S::type_ids());
+        self
+    }
+
+    /// Declares writing components of the types in the tuple `S`, such as `(A, B, C)`.
+    #[cfg(feature = "hecs")]
+    pub fn writes_components<S: ComponentTypes>(mut self) -> Self {
+        self.component_writes.extend(S::type_ids());
+        self
+    }
+
+    /// Returns the set with each declared resource type mapped through the closure.
+    fn resolved(&self, resolve: impl Fn(TypeId) -> TypeId) -> Self {
+        let mut set = self.clone();
+        for type_id in set.reads.iter_mut().chain(&mut set.writes) {
+            *type_id = resolve(*type_id);
+        }
+        set
+    }
+
+    /// Returns `true` if either set writes something the other reads or writes,
+    /// so that whatever they belong to can't run at the same time.
+    pub fn conflicts_with(&self, other: &Self) -> bool {
+        let conflicting = conflicts(&self.reads, &self.writes, &other.reads, &other.writes);
+        #[cfg(feature = "hecs")]
+        let conflicting = conflicting
+            || conflicts(
+                &self.component_reads,
+                &self.component_writes,
+                &other.component_reads,
+                &other.component_writes,
+            );
+        conflicting
+    }
+
+    /// Groups the sets into stages that can run one after another, with everything
+    /// within a stage running in parallel, returning indices of the sets in each stage.
+    ///
+    /// Each set is placed greedily into the earliest stage after those of all earlier sets
+    /// it conflicts with, so that conflicting accesses keep their relative order, and
+    /// no stage could take a set from a later one. This is the layout [`par_exec`] runs.
+    ///
+    /// Types are compared as declared: [lenses] and [aliases] aren't resolved here,
+    /// so a set declaring the type of a lens doesn't conflict with one declaring its parent.
+    /// [`par_exec`] resolves them through the container before scheduling.
+    ///
+    /// [`par_exec`]: struct.Resources.html#method.par_exec
+    /// [lenses]: struct.Resources.html#method.register_lens
+    /// [aliases]: struct.Resources.html#method.alias
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
+    /// use resources::AccessSet;
+    ///
+    /// struct Speed(f32);
+    /// struct Position(f32);
+    ///
+    /// let stages = AccessSet::schedule(&[
+    ///     AccessSet::new().reads::<(Speed,)>().writes::<(Position,)>(),
+    ///     AccessSet::new().reads::<(Speed,)>(),
+    ///     AccessSet::new().writes::<(Speed,)>(),
+    /// ]);
+    /// assert_eq!(stages, vec![vec![0, 1], vec![2]]);
+    /// # }
+    /// ```
+    pub fn schedule(sets: &[AccessSet]) -> Vec<Vec<usize>> {
+        let mut stages: Vec<Vec<usize>> = Vec::new();
+        let mut stage_of: Vec<usize> = Vec::with_capacity(sets.len());
+        for (index, set) in sets.iter().enumerate() {
+            let stage = sets[..index]
+                .iter()
+                .zip(&stage_of)
+                .filter(|(other, _)| set.conflicts_with(other))
+                .map(|(_, stage)| stage + 1)
+                .max()
+                .unwrap_or(0);
+            if stage == stages.len() {
+                stages.push(Vec::new());
+            }
+            stages[stage].push(index);
+            stage_of.push(stage);
+        }
+        stages
+    }
+}
+
+/// Closure accessing resources of a [`Resources`] container, along with declarations
+/// of which resources it reads and writes, to be run by [`par_exec`].
+///
+/// Accessing a resource without declaring it may fail due to borrow conflicts
+/// with other systems running at the same time. With the `hecs` feature, accesses
+/// to components of a `hecs::World` can be declared as well, for systems that share one.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`par_exec`]: struct.Resources.html#method.par_exec
+pub struct System<'a, M = ()> {
+    access: AccessSet,
+    run: Run<'a, M>,
+}
+
+impl<'a, M> System<'a, M> {
+    /// Creates a system running the closure, with no declared accesses.
+    pub fn new(run: impl FnOnce(&Resources<M>) + Send + 'a) -> Self {
+        Self::with_access(AccessSet::new(), run)
+    }
+
+    /// Creates a system running the closure, with the given declared accesses.
+    pub fn with_access(access: AccessSet, run: impl FnOnce(&Resources<M>) + Send + 'a) -> Self {
+        Self {
+            access,
+            run: Box::new(run),
+        }
+    }
+
+    /// Returns the accesses declared by the system.
+    pub fn access(&self) -> &AccessSet {
+        &self.access
+    }
+
+    /// Declares that the system reads resources of the types in the tuple `S`,
+    /// such as `(A, B, C)`.
+    pub fn reads<S: ResourceTypes>(mut self) -> Self {
+        self.access = self.access.reads::<S>();
+        self
+    }
+
+    /// Declares that the system writes resources of the types in the tuple `S`,
+    /// such as `(A, B, C)`.
+    pub fn writes<S: ResourceTypes>(mut self) -> Self {
+        self.access = self.access.writes::<S>();
+        self
+    }
+
+    /// Declares that the system reads components of the types in the tuple `S`,
+    /// such as `(A, B, C)`.
+    #[cfg(feature = "hecs")]
+    pub fn reads_components<S: ComponentTypes>(mut self) -> Self {
+        self.access = self.access.reads_components::<S>();
+        self
+    }
+
+    /// Declares that the system writes components of the types in the tuple `S`,
+    /// such as `(A, B, C)`.
+    #[cfg(feature = "hecs")]
+    pub fn writes_components<S: ComponentTypes>(mut self) -> Self {
+        self.access = self.access.writes_components::<S>();
+        self
+    }
+}
+
+/// Returns `true` if either set of accesses writes something the other accesses.
+fn conflicts(
+    reads: &[TypeId],
+    writes: &[TypeId],
+    other_reads: &[TypeId],
+    other_writes: &[TypeId],
+) -> bool {
+    let writes_any = |writes: &[TypeId], reads: &[TypeId], other_writes: &[TypeId]| {
+        writes
+            .iter()
+            .any(|type_id| reads.contains(type_id) || other_writes.contains(type_id))
+    };
+    writes_any(writes, other_reads, other_writes) || writes_any(other_writes, reads, writes)
+}
+
+/// Runs the systems in stages laid out by `AccessSet::schedule()`: each stage consists
+/// of systems that conflict neither with each other, nor with any system before them
+/// that hasn't run yet.
+///
+/// Declared types of lenses and aliases are replaced by those of the resources they project,
+/// since borrowing either borrows the same cell.
+pub(crate) fn par_exec<'a, M>(resources: &Resources<M>, systems: Vec<System<'a, M>>) {
+    let stages = AccessSet::schedule(
+        &systems
+            .iter()
+            .map(|system| {
+                system
+                    .access
+                    .resolved(|type_id| resources.resolve_lens(type_id))
+            })
+            .collect::<Vec<_>>(),
+    );
+    let mut systems: Vec<Option<System<'a, M>>> = systems.into_iter().map(Some).collect();
+    for stage in stages {
+        let mut stage = stage.into_iter().filter_map(|index| systems[index].take());
+        let first = stage.next();
+        thread::scope(|scope| {
+            for system in stage {
+                scope.spawn(move || (system.run)(resources));
+            }
+            if let Some(system) = first {
+                (system.run)(resources);
+            }
+        });
+    }
+}
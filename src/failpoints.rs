@@ -0,0 +1,130 @@
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+use crate::{
+    error::{Conflict, InvalidBorrow},
+    hash::TypeIdBuildHasher,
+    instrument::Access,
+};
+
+/// Kind of failure injected into accesses of resources, see [`Resources::inject_failure()`].
+///
+/// [`Resources::inject_failure()`]: struct.Resources.html#method.inject_failure
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum InjectedFailure {
+    /// Accesses fail as if the resource wasn't present, with `NoSuchResource`.
+    Missing,
+    /// Accesses fail as if the resource was borrowed in a conflicting way, with `InvalidBorrow`
+    /// carrying [`Conflict::Injected`].
+    ///
+    /// [`Conflict::Injected`]: enum.Conflict.html#variant.Injected
+    Conflict,
+}
+
+impl InjectedFailure {
+    /// Returns the error an access fails with, or `None` if it should act as if the resource
+    /// wasn't present.
+    pub(crate) fn into_invalid_borrow(
+        self,
+        type_name: &'static str,
+        access: Access,
+    ) -> Option<InvalidBorrow> {
+        match (self, access) {
+            (InjectedFailure::Missing, _) => None,
+            (InjectedFailure::Conflict, Access::Shared) => Some(InvalidBorrow::Immutable {
+                type_name,
+                conflict: Conflict::Injected,
+            }),
+            (InjectedFailure::Conflict, Access::Exclusive) => Some(InvalidBorrow::Mutable {
+                type_name,
+                conflict: Conflict::Injected,
+            }),
+        }
+    }
+}
+
+/// Failures injected into accesses of resources of a container.
+#[derive(Default)]
+pub(crate) struct Failpoints {
+    /// Whether any failures are injected, checked before locking the configuration.
+    armed: AtomicBool,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    by_type: HashMap<TypeId, InjectedFailure, TypeIdBuildHasher>,
+    random: Option<RandomFailures>,
+}
+
+struct RandomFailures {
+    failure: InjectedFailure,
+    probability: f64,
+    state: u64,
+}
+
+impl RandomFailures {
+    /// Advances the xorshift generator, returning whether the next access fails.
+    fn roll(&mut self) -> bool {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        let sample = (self.state >> 11) as f64 / (1u64 << 53) as f64;
+        sample < self.probability
+    }
+}
+
+impl Failpoints {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    pub(crate) fn inject(&self, type_id: TypeId, failure: InjectedFailure) {
+        self.state().by_type.insert(type_id, failure);
+        self.armed.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn inject_random(&self, failure: InjectedFailure, probability: f64, seed: u64) {
+        self.state().random = Some(RandomFailures {
+            failure,
+            probability,
+            // The generator never leaves zero, so that seed is remapped.
+            state: if seed == 0 {
+                0x9e37_79b9_7f4a_7c15
+            } else {
+                seed
+            },
+        });
+        self.armed.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn clear(&self) {
+        let mut state = self.state();
+        state.by_type.clear();
+        state.random = None;
+        self.armed.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns the failure the access of the resource of the type should fail with, if any.
+    pub(crate) fn check(&self, type_id: TypeId) -> Option<InjectedFailure> {
+        if !self.armed.load(Ordering::Relaxed) {
+            return None;
+        }
+        let mut state = self.state();
+        if let Some(failure) = state.by_type.get(&type_id) {
+            return Some(*failure);
+        }
+        let random = state.random.as_mut()?;
+        if random.roll() {
+            Some(random.failure)
+        } else {
+            None
+        }
+    }
+}
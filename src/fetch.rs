@@ -1,13 +1,17 @@
 use std::{
     any::type_name,
     error::Error,
-    fmt::{Display, Formatter, Result as FmtResult},
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    marker::PhantomData,
 };
 
 use crate::{
     error::CantGetResource,
+    instrument::Access,
+    lens::Found,
     map::{Resource, Resources},
     refs::{Ref, RefMut},
+    scoped::ScopedCell,
 };
 
 /// Error that may occur when retrieving one or several of [`Resource`]
@@ -38,8 +42,96 @@ impl Error for CantFetch {
 
 pub trait Fetch<'a> {
     type Refs;
+    type Cells: Copy;
 
-    fn fetch(resources: &'a Resources) -> Result<Self::Refs, CantFetch>;
+    fn fetch<Marker>(resources: &'a Resources<Marker>) -> Result<Self::Refs, CantFetch>;
+
+    fn prepare<Marker>(resources: &'a Resources<Marker>) -> Result<Self::Cells, CantFetch>;
+
+    fn fetch_prepared<Marker>(
+        resources: &'a Resources<Marker>,
+        cells: Self::Cells,
+    ) -> Result<Self::Refs, CantFetch>;
+}
+
+/// Cell of a resource looked up by [`Prepared`], with the [lens] that projects it, if any.
+///
+/// [lens]: struct.Resources.html#method.register_lens
+pub struct PreparedCell<'a, R>(Found<'a, R>);
+
+// Derived implementations would require `R: Copy`.
+impl<R> Clone for PreparedCell<'_, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for PreparedCell<'_, R> {}
+
+impl<'a, R: Resource> PreparedCell<'a, R> {
+    fn prepare<Marker>(resources: &'a Resources<Marker>) -> Result<Self, CantFetch> {
+        resources
+            .prepare_found::<R>()
+            .map(PreparedCell)
+            .map_err(|error| CantFetch {
+                type_name: type_name::<R>(),
+                cause: error.into(),
+            })
+    }
+
+    /// Records an access to the cell, checking injected failures, and returns it.
+    #[track_caller]
+    fn access<Marker>(
+        self,
+        resources: &'a Resources<Marker>,
+        access: Access,
+    ) -> Result<Found<'a, R>, CantFetch> {
+        resources
+            .access_prepared(&self.0, access)
+            .map(|()| self.0)
+            .map_err(|error| CantFetch {
+                type_name: type_name::<R>(),
+                cause: error,
+            })
+    }
+}
+
+/// Set of resources of a [`Resources`] container looked up once, to be borrowed repeatedly
+/// without looking them up again; created by [`Resources::prepare()`].
+///
+/// Since the plan borrows the container, resources can't be inserted or removed
+/// while it exists, so it has to be prepared again after that.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`Resources::prepare()`]: struct.Resources.html#method.prepare
+pub struct Prepared<'a, R: Fetch<'a>, M = ()> {
+    resources: &'a Resources<M>,
+    cells: R::Cells,
+}
+
+impl<'a, R: Fetch<'a>, M> Prepared<'a, R, M> {
+    pub(crate) fn new(resources: &'a Resources<M>) -> Result<Self, CantFetch> {
+        Ok(Self {
+            resources,
+            cells: R::prepare(resources)?,
+        })
+    }
+
+    /// Borrows the prepared set of resources, same as [`Resources::fetch()`] would.
+    ///
+    /// [`Resources::fetch()`]: struct.Resources.html#method.fetch
+    #[track_caller]
+    pub fn fetch(&self) -> Result<R::Refs, CantFetch> {
+        R::fetch_prepared(self.resources, self.cells)
+    }
+}
+
+impl<'a, R: Fetch<'a>, M> Debug for Prepared<'a, R, M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Prepared")
+            .field("resources", &type_name::<R>())
+            .finish()
+    }
 }
 
 impl<'a, R> Fetch<'a> for &'_ R
@@ -47,13 +139,32 @@ where
     R: Resource,
 {
     type Refs = Ref<'a, R>;
+    type Cells = PreparedCell<'a, R>;
 
-    fn fetch(resources: &'a Resources) -> Result<Self::Refs, CantFetch> {
+    fn fetch<Marker>(resources: &'a Resources<Marker>) -> Result<Self::Refs, CantFetch> {
         resources.get().map_err(|error| CantFetch {
             type_name: type_name::<R>(),
             cause: error,
         })
     }
+
+    fn prepare<Marker>(resources: &'a Resources<Marker>) -> Result<Self::Cells, CantFetch> {
+        PreparedCell::<R>::prepare(resources)
+    }
+
+    #[track_caller]
+    fn fetch_prepared<Marker>(
+        resources: &'a Resources<Marker>,
+        cells: Self::Cells,
+    ) -> Result<Self::Refs, CantFetch> {
+        let found = cells.access(resources, Access::Shared)?;
+        Ref::from_cell(found.cell())
+            .map(|resource| found.project(resource))
+            .map_err(|error| CantFetch {
+                type_name: type_name::<R>(),
+                cause: error.into(),
+            })
+    }
 }
 
 impl<'a, R> Fetch<'a> for &'_ mut R
@@ -61,13 +172,120 @@ where
     R: Resource,
 {
     type Refs = RefMut<'a, R>;
+    type Cells = PreparedCell<'a, R>;
 
-    fn fetch(resources: &'a Resources) -> Result<Self::Refs, CantFetch> {
+    fn fetch<Marker>(resources: &'a Resources<Marker>) -> Result<Self::Refs, CantFetch> {
         resources.get_mut().map_err(|error| CantFetch {
             type_name: type_name::<R>(),
             cause: error,
         })
     }
+
+    fn prepare<Marker>(resources: &'a Resources<Marker>) -> Result<Self::Cells, CantFetch> {
+        PreparedCell::<R>::prepare(resources)
+    }
+
+    #[track_caller]
+    fn fetch_prepared<Marker>(
+        resources: &'a Resources<Marker>,
+        cells: Self::Cells,
+    ) -> Result<Self::Refs, CantFetch> {
+        let found = cells.access(resources, Access::Exclusive)?;
+        RefMut::from_cell(found.cell())
+            .map(|resource| found.project_mut(resource))
+            .map_err(|error| CantFetch {
+                type_name: type_name::<R>(),
+                cause: error.into(),
+            })
+    }
+}
+
+/// Marker for fetching a value exposed by [`Resources::scope_with()`] via [`Resources::fetch()`]:
+/// `Scoped<&E>` fetches it immutably, and `Scoped<&mut E>` mutably.
+///
+/// ```rust
+/// # use resources::{Resources, Scoped};
+/// struct Engine {
+///     frame: u64,
+/// }
+///
+/// let mut engine = Engine { frame: 0 };
+/// let mut resources = Resources::new();
+/// resources.scope_with(&mut engine, |resources| {
+///     let mut engine = resources.fetch::<Scoped<&mut Engine>>().unwrap();
+///     engine.frame += 1;
+/// });
+/// assert_eq!(engine.frame, 1);
+/// ```
+///
+/// [`Resources::scope_with()`]: struct.Resources.html#method.scope_with
+/// [`Resources::fetch()`]: struct.Resources.html#method.fetch
+pub struct Scoped<T> {
+    phantom_data: PhantomData<T>,
+}
+
+impl<'a, E> Fetch<'a> for Scoped<&'_ E>
+where
+    E: Send + Sync + 'static,
+{
+    type Refs = Ref<'a, E>;
+    type Cells = &'a ScopedCell;
+
+    fn fetch<Marker>(resources: &'a Resources<Marker>) -> Result<Self::Refs, CantFetch> {
+        resources.get_scoped().map_err(|error| CantFetch {
+            type_name: type_name::<E>(),
+            cause: error,
+        })
+    }
+
+    fn prepare<Marker>(resources: &'a Resources<Marker>) -> Result<Self::Cells, CantFetch> {
+        resources.scoped_cell::<E>().map_err(|error| CantFetch {
+            type_name: type_name::<E>(),
+            cause: error.into(),
+        })
+    }
+
+    fn fetch_prepared<Marker>(
+        _: &'a Resources<Marker>,
+        cell: Self::Cells,
+    ) -> Result<Self::Refs, CantFetch> {
+        unsafe { Ref::from_lock(&cell.lock, cell.value()) }.map_err(|error| CantFetch {
+            type_name: type_name::<E>(),
+            cause: error.into(),
+        })
+    }
+}
+
+impl<'a, E> Fetch<'a> for Scoped<&'_ mut E>
+where
+    E: Send + Sync + 'static,
+{
+    type Refs = RefMut<'a, E>;
+    type Cells = &'a ScopedCell;
+
+    fn fetch<Marker>(resources: &'a Resources<Marker>) -> Result<Self::Refs, CantFetch> {
+        resources.get_scoped_mut().map_err(|error| CantFetch {
+            type_name: type_name::<E>(),
+            cause: error,
+        })
+    }
+
+    fn prepare<Marker>(resources: &'a Resources<Marker>) -> Result<Self::Cells, CantFetch> {
+        resources.scoped_cell::<E>().map_err(|error| CantFetch {
+            type_name: type_name::<E>(),
+            cause: error.into(),
+        })
+    }
+
+    fn fetch_prepared<Marker>(
+        _: &'a Resources<Marker>,
+        cell: Self::Cells,
+    ) -> Result<Self::Refs, CantFetch> {
+        unsafe { RefMut::from_lock(&cell.lock, cell.value()) }.map_err(|error| CantFetch {
+            type_name: type_name::<E>(),
+            cause: error.into(),
+        })
+    }
 }
 
 macro_rules! expand {
@@ -93,10 +311,25 @@ macro_rules! impl_fetch {
             $($letter: Fetch<'a>,)*
         {
             type Refs = ($($letter::Refs,)*);
+            type Cells = ($($letter::Cells,)*);
 
-            fn fetch(resources: &'a Resources) -> Result<Self::Refs, CantFetch> {
+            fn fetch<Marker>(resources: &'a Resources<Marker>) -> Result<Self::Refs, CantFetch> {
                 Ok(($($letter::fetch(resources)?,)*))
             }
+
+            fn prepare<Marker>(resources: &'a Resources<Marker>) -> Result<Self::Cells, CantFetch> {
+                Ok(($($letter::prepare(resources)?,)*))
+            }
+
+            #[allow(non_snake_case)]
+            #[track_caller]
+            fn fetch_prepared<Marker>(
+                resources: &'a Resources<Marker>,
+                cells: Self::Cells,
+            ) -> Result<Self::Refs, CantFetch> {
+                let ($($letter,)*) = cells;
+                Ok(($($letter::fetch_prepared(resources, $letter)?,)*))
+            }
         }
     }
 }
@@ -0,0 +1,277 @@
+use abi_stable::{
+    abi_stability::check_layout_compatibility, std_types::RStr, type_layout::TypeLayout, StableAbi,
+};
+use std::{
+    error::Error,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+use crate::{
+    error::CantGetResource,
+    map::{Resource, Resources},
+    refs::{Ref, RefMut},
+};
+
+/// Borrows the resource of a specific type, see [`Resources::register_ffi_resource()`].
+type Acquire<M> = for<'a> fn(&'a Resources<M>, bool) -> Result<Guard<'a>, CantGetResource>;
+
+/// Layout and accessor of a resource type registered for access across the FFI boundary.
+pub(crate) struct FfiType<M> {
+    layout: &'static TypeLayout,
+    acquire: Acquire<M>,
+}
+
+impl<M> FfiType<M> {
+    pub(crate) fn new<T: Resource + StableAbi>() -> Self {
+        Self {
+            layout: T::LAYOUT,
+            acquire: acquire_guard::<T, M>,
+        }
+    }
+}
+
+/// Guard of a borrowed resource, owned by the host while a module holds a reference.
+enum Guard<'a> {
+    Shared(Ref<'a, dyn Resource>),
+    Exclusive(RefMut<'a, dyn Resource>),
+}
+
+fn acquire_guard<T: Resource, M>(
+    resources: &Resources<M>,
+    mutable: bool,
+) -> Result<Guard<'_>, CantGetResource> {
+    Ok(if mutable {
+        Guard::Exclusive(RefMut::map(resources.get_mut::<T>()?, |resource| {
+            resource as &mut dyn Resource
+        }))
+    } else {
+        Guard::Shared(Ref::map(resources.get::<T>()?, |resource| {
+            resource as &dyn Resource
+        }))
+    })
+}
+
+/// Outcome of borrowing a resource across the FFI boundary.
+#[repr(u8)]
+#[derive(StableAbi)]
+enum Acquired {
+    Granted {
+        resource: *mut (),
+        guard: *mut (),
+        layout: &'static TypeLayout,
+    },
+    NotRegistered,
+    NoSuchResource,
+    InvalidBorrow,
+}
+
+extern "C" fn acquire<M>(resources: *const (), name: RStr<'_>, mutable: bool) -> Acquired {
+    let resources = unsafe { &*(resources as *const Resources<M>) };
+    let ffi_type = match resources.ffi_type(name.as_str()) {
+        Some(ffi_type) => ffi_type,
+        None => return Acquired::NotRegistered,
+    };
+    let mut guard = match (ffi_type.acquire)(resources, mutable) {
+        Ok(guard) => guard,
+        Err(CantGetResource::NoSuchResource(_)) => return Acquired::NoSuchResource,
+        Err(CantGetResource::InvalidBorrow(_)) => return Acquired::InvalidBorrow,
+    };
+    let resource = match &mut guard {
+        Guard::Shared(resource) => &**resource as *const dyn Resource as *mut (),
+        Guard::Exclusive(resource) => &mut **resource as *mut dyn Resource as *mut (),
+    };
+    Acquired::Granted {
+        resource,
+        guard: Box::into_raw(Box::new(guard)) as *mut (),
+        layout: ffi_type.layout,
+    }
+}
+
+extern "C" fn release(guard: *mut ()) {
+    drop(unsafe { Box::from_raw(guard as *mut Guard<'static>) });
+}
+
+/// FFI-safe handle to a [`Resources`] container, for sharing it with dynamically loaded
+/// modules that may be built by a different compiler; created by [`Resources::as_ffi()`].
+///
+/// Modules access resources by names they were registered under with
+/// [`Resources::register_ffi_resource()`]; layouts of their types are checked
+/// to be compatible on each access.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`Resources::as_ffi()`]: struct.Resources.html#method.as_ffi
+/// [`Resources::register_ffi_resource()`]: struct.Resources.html#method.register_ffi_resource
+#[repr(C)]
+#[derive(StableAbi, Clone, Copy)]
+pub struct FfiResources<'a> {
+    resources: *const (),
+    acquire: extern "C" fn(*const (), RStr<'_>, bool) -> Acquired,
+    release: extern "C" fn(*mut ()),
+    phantom_data: PhantomData<&'a ()>,
+}
+
+unsafe impl<'a> Send for FfiResources<'a> {}
+unsafe impl<'a> Sync for FfiResources<'a> {}
+
+impl<'a> FfiResources<'a> {
+    pub(crate) fn new<M>(resources: &'a Resources<M>) -> Self {
+        Self {
+            resources: resources as *const Resources<M> as *const (),
+            acquire: acquire::<M>,
+            release,
+            phantom_data: PhantomData,
+        }
+    }
+
+    fn acquire<T: StableAbi>(
+        &self,
+        name: &str,
+        mutable: bool,
+    ) -> Result<(NonNull<T>, *mut ()), FfiError> {
+        match (self.acquire)(self.resources, RStr::from_str(name), mutable) {
+            Acquired::Granted {
+                resource,
+                guard,
+                layout,
+            } => {
+                if let Err(error) = check_layout_compatibility(T::LAYOUT, layout).into_result() {
+                    (self.release)(guard);
+                    return Err(FfiError::IncompatibleLayout(error.to_string()));
+                }
+                Ok((NonNull::new(resource as *mut T).unwrap(), guard))
+            }
+            Acquired::NotRegistered => Err(FfiError::NotRegistered),
+            Acquired::NoSuchResource => Err(FfiError::NoSuchResource),
+            Acquired::InvalidBorrow => Err(FfiError::InvalidBorrow),
+        }
+    }
+
+    /// Returns a reference to the resource registered under the name,
+    /// if its type's layout is compatible with `T`.
+    pub fn get<T: StableAbi>(&self, name: &str) -> Result<FfiRef<'a, T>, FfiError> {
+        let (resource, guard) = self.acquire(name, false)?;
+        Ok(FfiRef {
+            resource,
+            guard,
+            release: self.release,
+            phantom_data: PhantomData,
+        })
+    }
+
+    /// Returns a mutable reference to the resource registered under the name,
+    /// if its type's layout is compatible with `T`.
+    pub fn get_mut<T: StableAbi>(&self, name: &str) -> Result<FfiRefMut<'a, T>, FfiError> {
+        let (resource, guard) = self.acquire(name, true)?;
+        Ok(FfiRefMut {
+            resource,
+            guard,
+            release: self.release,
+            phantom_data: PhantomData,
+        })
+    }
+}
+
+impl<'a> Debug for FfiResources<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("FfiResources").finish_non_exhaustive()
+    }
+}
+
+/// Immutable borrow of a resource accessed via [`FfiResources`].
+///
+/// [`FfiResources`]: struct.FfiResources.html
+pub struct FfiRef<'a, T> {
+    resource: NonNull<T>,
+    guard: *mut (),
+    release: extern "C" fn(*mut ()),
+    phantom_data: PhantomData<&'a T>,
+}
+
+impl<'a, T> Deref for FfiRef<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.resource.as_ref() }
+    }
+}
+
+impl<'a, T> Drop for FfiRef<'a, T> {
+    fn drop(&mut self) {
+        (self.release)(self.guard);
+    }
+}
+
+impl<'a, T: Debug> Debug for FfiRef<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        (**self).fmt(f)
+    }
+}
+
+/// Mutable borrow of a resource accessed via [`FfiResources`].
+///
+/// [`FfiResources`]: struct.FfiResources.html
+pub struct FfiRefMut<'a, T> {
+    resource: NonNull<T>,
+    guard: *mut (),
+    release: extern "C" fn(*mut ()),
+    phantom_data: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Deref for FfiRefMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.resource.as_ref() }
+    }
+}
+
+impl<'a, T> DerefMut for FfiRefMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.resource.as_mut() }
+    }
+}
+
+impl<'a, T> Drop for FfiRefMut<'a, T> {
+    fn drop(&mut self) {
+        (self.release)(self.guard);
+    }
+}
+
+impl<'a, T: Debug> Debug for FfiRefMut<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        (**self).fmt(f)
+    }
+}
+
+/// Error that may occur when accessing resources via [`FfiResources`].
+///
+/// [`FfiResources`]: struct.FfiResources.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FfiError {
+    /// No resource type was registered under the name.
+    NotRegistered,
+    /// No resource of the registered type is present in the container.
+    NoSuchResource,
+    /// Accessing the resource would violate borrow rules.
+    InvalidBorrow,
+    /// Layout of the registered type is incompatible with the requested one.
+    IncompatibleLayout(String),
+}
+
+impl Display for FfiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            FfiError::NotRegistered => write!(f, "no resource type is registered under the name"),
+            FfiError::NoSuchResource => write!(f, "no resource of the registered type is present"),
+            FfiError::InvalidBorrow => write!(f, "resource is already borrowed incompatibly"),
+            FfiError::IncompatibleLayout(error) => {
+                write!(f, "incompatible resource layout: {}", error)
+            }
+        }
+    }
+}
+
+impl Error for FfiError {}
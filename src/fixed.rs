@@ -0,0 +1,193 @@
+use std::{
+    any::{type_name, TypeId},
+    cell::UnsafeCell,
+    mem::{align_of, size_of, MaybeUninit},
+    ptr::{self, NonNull},
+};
+
+use crate::{
+    error::{CantGetResource, NoSuchResource},
+    lock::ResourcesRwLock,
+    map::Resource,
+    refs::{Ref, RefMut},
+};
+
+/// A [`Resource`] container with room for at most `N` resources, each stored inline
+/// in `SIZE` bytes, that never allocates.
+///
+/// Resources are looked up by a linear scan over their [`TypeId`]s, which is as fast as hashing
+/// for the handful of resources embedded targets tend to have. A resource that doesn't fit
+/// in its slot, or is aligned to more than 16 bytes, can't be inserted.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(not(feature = "explicit"))] {
+/// # use resources::FixedResources;
+/// let mut resources = FixedResources::<2>::new();
+/// assert!(resources.insert(0u32).is_ok());
+/// assert!(resources.insert(1.0f32).is_ok());
+/// assert_eq!(resources.insert(2u8), Err(2)); // No room left.
+/// *resources.get_mut::<u32>().unwrap() += 1;
+/// assert_eq!(*resources.get::<u32>().unwrap(), 1);
+/// # }
+/// ```
+///
+/// [`Resource`]: trait.Resource.html
+/// [`TypeId`]: https://doc.rust-lang.org/std/any/struct.TypeId.html
+pub struct FixedResources<const N: usize, const SIZE: usize = 64> {
+    slots: [Option<FixedCell<SIZE>>; N],
+}
+
+// Resources are `Send + Sync`, and access to them is guarded by their locks.
+unsafe impl<const N: usize, const SIZE: usize> Sync for FixedResources<N, SIZE> {}
+
+impl<const N: usize, const SIZE: usize> FixedResources<N, SIZE> {
+    /// Creates an empty container.
+    pub fn new() -> Self {
+        Self {
+            slots: std::array::from_fn(|_| None),
+        }
+    }
+
+    /// Returns the number of resources the container can hold.
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of resources in the container.
+    pub fn len(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    /// Returns `true` if the container holds no resources.
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(Option::is_none)
+    }
+
+    /// Returns `true` if a resource of type `T` exists in the container.
+    pub fn contains<T: Resource>(&self) -> bool {
+        self.position::<T>().is_some()
+    }
+
+    /// Inserts the given resource of type `T` into the container, returning the previous one,
+    /// if any.
+    ///
+    /// If the container is full, or the resource doesn't fit in a slot, returns it back
+    /// as an error instead.
+    pub fn insert<T: Resource>(&mut self, resource: T) -> Result<Option<T>, T> {
+        if !FixedCell::<SIZE>::fits::<T>() {
+            return Err(resource);
+        }
+        if let Some(index) = self.position::<T>() {
+            let cell = self.slots[index].as_mut().unwrap();
+            // The type matches, and the container is borrowed mutably.
+            return Ok(Some(unsafe {
+                ptr::replace(cell.value_ptr::<T>().as_ptr(), resource)
+            }));
+        }
+        match self.slots.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(FixedCell::new(resource));
+                Ok(None)
+            }
+            None => Err(resource),
+        }
+    }
+
+    /// Removes the resource of type `T` from the container, returning it,
+    /// or `None` if it's not present.
+    pub fn remove<T: Resource>(&mut self) -> Option<T> {
+        let cell = self.slots[self.position::<T>()?].take()?;
+        // The type matches, and the cell won't drop the resource once it's moved out.
+        Some(unsafe { cell.value_ptr::<T>().as_ptr().read() })
+    }
+
+    /// Returns a reference to the stored resource of type `T`.
+    ///
+    /// If such a resource is currently accessed mutably elsewhere,
+    /// or is not present in the container, returns an error.
+    pub fn get<T: Resource>(&self) -> Result<Ref<'_, T>, CantGetResource> {
+        let cell = self.cell::<T>()?;
+        Ok(unsafe { Ref::from_lock(&cell.lock, cell.value_ptr())? })
+    }
+
+    /// Returns a mutable reference to the stored resource of type `T`.
+    ///
+    /// If such a resource is currently accessed immutably or mutably elsewhere,
+    /// or is not present in the container, returns an error.
+    pub fn get_mut<T: Resource>(&self) -> Result<RefMut<'_, T>, CantGetResource> {
+        let cell = self.cell::<T>()?;
+        Ok(unsafe { RefMut::from_lock(&cell.lock, cell.value_ptr())? })
+    }
+
+    fn position<T: Resource>(&self) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|slot| matches!(slot, Some(cell) if cell.type_id == TypeId::of::<T>()))
+    }
+
+    fn cell<T: Resource>(&self) -> Result<&FixedCell<SIZE>, NoSuchResource> {
+        self.position::<T>()
+            .and_then(|index| self.slots[index].as_ref())
+            .ok_or(NoSuchResource {
+                type_name: type_name::<T>(),
+            })
+    }
+}
+
+impl<const N: usize, const SIZE: usize> Drop for FixedResources<N, SIZE> {
+    fn drop(&mut self) {
+        for cell in self.slots.iter_mut().flatten() {
+            unsafe { (cell.drop)(cell.value.get().cast()) }
+        }
+    }
+}
+
+impl<const N: usize, const SIZE: usize> Default for FixedResources<N, SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Inline storage of a resource, aligned for any type that a slot accepts.
+#[repr(C, align(16))]
+struct Inline<const SIZE: usize>([MaybeUninit<u8>; SIZE]);
+
+/// Storage of a single resource in a [`FixedResources`] container,
+/// which is responsible for dropping it.
+///
+/// [`FixedResources`]: struct.FixedResources.html
+struct FixedCell<const SIZE: usize> {
+    type_id: TypeId,
+    drop: unsafe fn(*mut u8),
+    lock: ResourcesRwLock,
+    value: UnsafeCell<Inline<SIZE>>,
+}
+
+impl<const SIZE: usize> FixedCell<SIZE> {
+    fn fits<T>() -> bool {
+        size_of::<T>() <= SIZE && align_of::<T>() <= align_of::<Inline<SIZE>>()
+    }
+
+    fn new<T: Resource>(resource: T) -> Self {
+        debug_assert!(Self::fits::<T>());
+        let cell = Self {
+            type_id: TypeId::of::<T>(),
+            drop: drop_value::<T>,
+            lock: ResourcesRwLock::new(),
+            value: UnsafeCell::new(Inline([MaybeUninit::uninit(); SIZE])),
+        };
+        unsafe { cell.value_ptr::<T>().as_ptr().write(resource) };
+        cell
+    }
+
+    /// # Safety
+    /// The stored resource must be of type `T`.
+    unsafe fn value_ptr<T>(&self) -> NonNull<T> {
+        NonNull::new_unchecked(self.value.get()).cast()
+    }
+}
+
+unsafe fn drop_value<T>(value: *mut u8) {
+    ptr::drop_in_place(value.cast::<T>())
+}
@@ -0,0 +1,156 @@
+use std::{
+    any::TypeId,
+    fmt::{Debug, Formatter, Result as FmtResult},
+};
+
+use crate::{
+    map::Resource,
+    refs::{Ref, RefMut},
+};
+
+/// Type-erased guard held by a [`GuardSet`].
+enum Guard<'a> {
+    Shared(Ref<'a, dyn Resource>),
+    Exclusive(RefMut<'a, dyn Resource>),
+}
+
+impl<'a> Guard<'a> {
+    fn resource(&self) -> &dyn Resource {
+        match self {
+            Guard::Shared(guard) => &**guard,
+            Guard::Exclusive(guard) => &**guard,
+        }
+    }
+}
+
+/// Collection of [`Ref`]s and [`RefMut`]s borrowing resources of different types,
+/// which can be queried back out by type, and are released all at once when the set
+/// is cleared or dropped.
+///
+/// This is meant for systems that acquire resources conditionally, instead of keeping
+/// an `Option` of a guard for each of them. At most one guard of each type is held:
+/// inserting another one releases the previous.
+///
+/// ```rust
+/// # #[cfg(not(feature = "explicit"))] {
+/// # use resources::{GuardSet, Resources};
+/// let mut resources = Resources::new();
+/// resources.insert(1u32);
+/// resources.insert(String::from("log"));
+///
+/// let mut guards = GuardSet::new();
+/// if resources.contains::<u32>() {
+///     guards.insert(resources.get::<u32>().unwrap());
+/// }
+/// guards.insert_mut(resources.get_mut::<String>().unwrap());
+/// guards.get_mut::<String>().unwrap().push_str(": 1");
+/// assert_eq!(guards.get::<u32>(), Some(&1));
+/// assert!(resources.get::<String>().is_err());
+///
+/// guards.clear();
+/// assert_eq!(*resources.get::<String>().unwrap(), "log: 1");
+/// # }
+/// ```
+///
+/// [`Ref`]: struct.Ref.html
+/// [`RefMut`]: struct.RefMut.html
+#[derive(Default)]
+pub struct GuardSet<'a> {
+    guards: Vec<(TypeId, Guard<'a>)>,
+}
+
+impl<'a> GuardSet<'a> {
+    /// Creates an empty set. Functionally identical to [`::default()`].
+    ///
+    /// [`::default()`]: #impl-Default-for-GuardSet%3C'a%3E
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of guards in the set.
+    pub fn len(&self) -> usize {
+        self.guards.len()
+    }
+
+    /// Returns `true` if the set holds no guards.
+    pub fn is_empty(&self) -> bool {
+        self.guards.is_empty()
+    }
+
+    fn position(&self, type_id: TypeId) -> Option<usize> {
+        self.guards
+            .iter()
+            .position(|(guarded, _)| *guarded == type_id)
+    }
+
+    fn insert_guard(&mut self, type_id: TypeId, guard: Guard<'a>) {
+        match self.position(type_id) {
+            Some(index) => self.guards[index].1 = guard,
+            None => self.guards.push((type_id, guard)),
+        }
+    }
+
+    /// Adds the immutable guard of a resource of type `T` to the set,
+    /// releasing the guard of that type already in it, if any.
+    pub fn insert<T: Resource>(&mut self, guard: Ref<'a, T>) {
+        let guard = Ref::map(guard, |resource| resource as &dyn Resource);
+        self.insert_guard(TypeId::of::<T>(), Guard::Shared(guard));
+    }
+
+    /// Adds the mutable guard of a resource of type `T` to the set,
+    /// releasing the guard of that type already in it, if any.
+    pub fn insert_mut<T: Resource>(&mut self, guard: RefMut<'a, T>) {
+        let guard = RefMut::map(guard, |resource| resource as &mut dyn Resource);
+        self.insert_guard(TypeId::of::<T>(), Guard::Exclusive(guard));
+    }
+
+    /// Returns `true` if the set holds a guard of a resource of type `T`.
+    pub fn contains<T: Resource>(&self) -> bool {
+        self.position(TypeId::of::<T>()).is_some()
+    }
+
+    /// Returns the resource of type `T` borrowed by a guard in the set, immutably or mutably.
+    pub fn get<T: Resource>(&self) -> Option<&T> {
+        let index = self.position(TypeId::of::<T>())?;
+        self.guards[index].1.resource().downcast_ref()
+    }
+
+    /// Returns the resource of type `T` borrowed mutably by a guard in the set,
+    /// or `None` if the set holds no guard of it, or holds an immutable one.
+    pub fn get_mut<T: Resource>(&mut self) -> Option<&mut T> {
+        let index = self.position(TypeId::of::<T>())?;
+        match &mut self.guards[index].1 {
+            Guard::Shared(_) => None,
+            Guard::Exclusive(guard) => guard.downcast_mut(),
+        }
+    }
+
+    /// Releases the guard of a resource of type `T`, returning `true` if the set held one.
+    pub fn remove<T: Resource>(&mut self) -> bool {
+        match self.position(TypeId::of::<T>()) {
+            Some(index) => {
+                self.guards.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Releases all guards in the set, in order of their insertion.
+    pub fn clear(&mut self) {
+        self.guards.clear();
+    }
+}
+
+impl Debug for GuardSet<'_> {
+    /// Lists names of types of the guarded resources.
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_list()
+            .entries(
+                self.guards
+                    .iter()
+                    .map(|(_, guard)| guard.resource().type_name()),
+            )
+            .finish()
+    }
+}
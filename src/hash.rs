@@ -0,0 +1,99 @@
+use fxhash::FxHasher;
+use hashbrown::Equivalent;
+use std::{
+    any::TypeId,
+    fmt::{Debug, Formatter, Result as FmtResult},
+    hash::{BuildHasher, BuildHasherDefault, Hash, Hasher},
+    marker::PhantomData,
+};
+
+use crate::map::Resource;
+
+/// Builds hashers used by maps keyed by resource types.
+pub(crate) type TypeIdBuildHasher = BuildHasherDefault<TypeIdHasher>;
+
+/// Hasher of `TypeId`s which, being hashes themselves, are passed through unchanged.
+#[derive(Default)]
+pub(crate) struct TypeIdHasher {
+    hash: u64,
+}
+
+impl Hasher for TypeIdHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        // `TypeId` writes a single `u64` at the moment, but that isn't guaranteed.
+        let mut hasher = FxHasher::default();
+        hasher.write_u64(self.hash);
+        hasher.write(bytes);
+        self.hash = hasher.finish();
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.hash = self.hash.rotate_left(5) ^ value;
+    }
+}
+
+/// Type of a resource along with its precomputed hash, for looking it up in
+/// a [`Resources`] container without hashing its `TypeId` each time.
+///
+/// The hash is the same as the one produced by the container's [`hasher`].
+///
+/// [`Resources`]: struct.Resources.html
+/// [`hasher`]: struct.Resources.html#method.hasher
+pub struct PreHashed<T: Resource> {
+    hash: u64,
+    phantom_data: PhantomData<fn() -> T>,
+}
+
+impl<T: Resource> PreHashed<T> {
+    /// Computes the hash of the resource type `T`.
+    pub fn new() -> Self {
+        Self {
+            hash: TypeIdBuildHasher::default().hash_one(TypeId::of::<T>()),
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// Returns the precomputed hash.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl<T: Resource> Default for PreHashed<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Resource> Clone for PreHashed<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Resource> Copy for PreHashed<T> {}
+
+impl<T: Resource> Debug for PreHashed<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("PreHashed")
+            .field("type_name", &std::any::type_name::<T>())
+            .field("hash", &self.hash)
+            .finish()
+    }
+}
+
+impl<T: Resource> Hash for PreHashed<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl<T: Resource> Equivalent<TypeId> for PreHashed<T> {
+    fn equivalent(&self, key: &TypeId) -> bool {
+        TypeId::of::<T>() == *key
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    any::TypeId,
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    mem,
+};
+
+use crate::{
+    error::{BudgetExceeded, MissingResources, Sealed},
+    map::Resource,
+    storage::TypeIdMap,
+    Resources,
+};
+
+/// Constructor of a resource, see [`Resources::add_initializer()`].
+type Init<M> = Box<dyn FnOnce(&Resources<M>) -> Box<dyn Resource> + Send + Sync>;
+
+/// Error returned by [`Resources::init_all()`].
+///
+/// [`Resources::init_all()`]: struct.Resources.html#method.init_all
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum InitError {
+    /// Initializers depend on each other in a cycle, so none were run. Contains names
+    /// of the types they construct, each depending on the next one, and the last one
+    /// on the first.
+    Cycle {
+        /// Compiler-provided names of the types in the cycle.
+        type_names: Vec<&'static str>,
+    },
+    /// Resources of types initializers depend on are neither present in the container
+    /// nor constructed by other initializers, so none were run.
+    Missing(MissingResources),
+    /// Inserting a constructed resource would exceed the container's memory budget.
+    /// Resources constructed before it were inserted.
+    Budget(BudgetExceeded),
+    /// The container is [sealed], so no initializers were run.
+    ///
+    /// [sealed]: struct.Resources.html#method.seal
+    Sealed(Sealed),
+}
+
+impl Display for InitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            InitError::Cycle { type_names } => write!(
+                f,
+                "initializers depend on each other in a cycle: {} -> {}",
+                type_names.join(" -> "),
+                type_names[0]
+            ),
+            InitError::Missing(error) => Display::fmt(error, f),
+            InitError::Budget(error) => Display::fmt(error, f),
+            InitError::Sealed(error) => Display::fmt(error, f),
+        }
+    }
+}
+
+impl Error for InitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            InitError::Cycle { .. } => None,
+            InitError::Missing(error) => Some(error),
+            InitError::Budget(error) => Some(error),
+            InitError::Sealed(error) => Some(error),
+        }
+    }
+}
+
+pub(crate) struct Initializer<M> {
+    type_id: TypeId,
+    type_name: &'static str,
+    dependencies: Vec<(TypeId, &'static str)>,
+    init: Init<M>,
+}
+
+impl<M> Initializer<M> {
+    pub(crate) fn new(
+        type_id: TypeId,
+        type_name: &'static str,
+        dependencies: Vec<(TypeId, &'static str)>,
+        init: Init<M>,
+    ) -> Self {
+        Self {
+            type_id,
+            type_name,
+            dependencies,
+            init,
+        }
+    }
+
+    pub(crate) fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub(crate) fn run(self, resources: &Resources<M>) -> Box<dyn Resource> {
+        (self.init)(resources)
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Mark {
+    Unvisited,
+    Visiting,
+    Done,
+}
+
+/// Initializers registered with a container, in order of registration.
+pub(crate) struct Initializers<M> {
+    initializers: Vec<Initializer<M>>,
+}
+
+impl<M> Default for Initializers<M> {
+    fn default() -> Self {
+        Self {
+            initializers: Vec::new(),
+        }
+    }
+}
+
+impl<M> Initializers<M> {
+    /// Adds the initializer, replacing the one constructing the same type, if any.
+    pub(crate) fn add(&mut self, initializer: Initializer<M>) {
+        match self
+            .initializers
+            .iter_mut()
+            .find(|existing| existing.type_id == initializer.type_id)
+        {
+            Some(existing) => *existing = initializer,
+            None => self.initializers.push(initializer),
+        }
+    }
+
+    /// Adds declared dependencies of the types the initializers construct to theirs.
+    pub(crate) fn add_dependencies(&mut self, declared: &TypeIdMap<Vec<(TypeId, &'static str)>>) {
+        for initializer in &mut self.initializers {
+            for dependency in declared.get(&initializer.type_id).into_iter().flatten() {
+                if !initializer.dependencies.contains(dependency) {
+                    initializer.dependencies.push(*dependency);
+                }
+            }
+        }
+    }
+
+    /// Takes all initializers of types that aren't present, ordered so that each comes after
+    /// the ones it depends on, keeping them if they can't be ordered.
+    pub(crate) fn take_ordered(
+        &mut self,
+        is_present: impl Fn(TypeId) -> bool,
+    ) -> Result<Vec<Initializer<M>>, InitError> {
+        let initializers = mem::take(&mut self.initializers)
+            .into_iter()
+            .filter(|initializer| !is_present(initializer.type_id))
+            .collect::<Vec<_>>();
+        match Self::order(&initializers, is_present) {
+            Ok(order) => {
+                let mut initializers = initializers.into_iter().map(Some).collect::<Vec<_>>();
+                Ok(order
+                    .into_iter()
+                    .filter_map(|index| initializers[index].take())
+                    .collect())
+            }
+            Err(error) => {
+                self.initializers = initializers;
+                Err(error)
+            }
+        }
+    }
+
+    /// Returns indices of the initializers in topological order of their dependencies.
+    fn order(
+        initializers: &[Initializer<M>],
+        is_present: impl Fn(TypeId) -> bool,
+    ) -> Result<Vec<usize>, InitError> {
+        let index_of = |type_id: TypeId| {
+            initializers
+                .iter()
+                .position(|initializer| initializer.type_id == type_id)
+        };
+        let mut missing = Vec::new();
+        for initializer in initializers {
+            for (type_id, type_name) in &initializer.dependencies {
+                if index_of(*type_id).is_none()
+                    && !is_present(*type_id)
+                    && !missing.contains(type_name)
+                {
+                    missing.push(*type_name);
+                }
+            }
+        }
+        if !missing.is_empty() {
+            return Err(InitError::Missing(MissingResources {
+                type_names: missing,
+            }));
+        }
+
+        let mut marks = vec![Mark::Unvisited; initializers.len()];
+        let mut order = Vec::with_capacity(initializers.len());
+        // Each entry is an initializer being visited, and the next dependency to visit.
+        let mut stack = Vec::<(usize, usize)>::new();
+        for root in 0..initializers.len() {
+            if marks[root] != Mark::Unvisited {
+                continue;
+            }
+            marks[root] = Mark::Visiting;
+            stack.push((root, 0));
+            while let Some((index, next)) = stack.last_mut() {
+                let index = *index;
+                let dependency = initializers[index].dependencies.get(*next);
+                *next += 1;
+                let dependency = match dependency {
+                    Some((type_id, _)) => index_of(*type_id),
+                    None => {
+                        marks[index] = Mark::Done;
+                        order.push(index);
+                        stack.pop();
+                        continue;
+                    }
+                };
+                match dependency.map(|dependency| (dependency, marks[dependency])) {
+                    Some((dependency, Mark::Unvisited)) => {
+                        marks[dependency] = Mark::Visiting;
+                        stack.push((dependency, 0));
+                    }
+                    Some((dependency, Mark::Visiting)) => {
+                        let start = stack
+                            .iter()
+                            .position(|(index, _)| *index == dependency)
+                            .unwrap_or_default();
+                        return Err(InitError::Cycle {
+                            type_names: stack[start..]
+                                .iter()
+                                .map(|(index, _)| initializers[*index].type_name)
+                                .collect(),
+                        });
+                    }
+                    Some((_, Mark::Done)) | None => {}
+                }
+            }
+        }
+        Ok(order)
+    }
+}
@@ -0,0 +1,93 @@
+#[cfg(any(feature = "metrics", feature = "chrome-tracing"))]
+use std::time::Instant;
+
+#[cfg(feature = "chrome-tracing")]
+use crate::chrome_trace;
+
+/// Kind of access to a resource, reported as the `access` label of metrics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Access {
+    Shared,
+    Exclusive,
+}
+
+#[cfg(feature = "metrics")]
+impl Access {
+    fn label(self) -> &'static str {
+        match self {
+            Access::Shared => "shared",
+            Access::Exclusive => "exclusive",
+        }
+    }
+}
+
+/// Reports a successful acquisition of a resource.
+pub(crate) fn acquired(type_name: &'static str, access: Access) {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(
+        "resources_acquisitions_total",
+        "resource" => type_name,
+        "access" => access.label()
+    )
+    .increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (type_name, access);
+}
+
+/// Reports an acquisition of a resource that failed due to a borrow conflict.
+pub(crate) fn conflict(type_name: &'static str, access: Access) {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(
+        "resources_conflicts_total",
+        "resource" => type_name,
+        "access" => access.label()
+    )
+    .increment(1);
+    #[cfg(feature = "chrome-tracing")]
+    chrome_trace::conflict(type_name, access);
+    #[cfg(not(any(feature = "metrics", feature = "chrome-tracing")))]
+    let _ = (type_name, access);
+}
+
+/// Reports a change of the number of resources stored across all containers.
+pub(crate) fn stored(delta: isize) {
+    #[cfg(feature = "metrics")]
+    ::metrics::gauge!("resources_stored").increment(delta as f64);
+    #[cfg(not(feature = "metrics"))]
+    let _ = delta;
+}
+
+/// Measures how long a guard holds its resource, reporting it when dropped.
+///
+/// Without the `metrics` and `chrome-tracing` features, this is zero-sized and does nothing.
+pub(crate) struct HoldTimer {
+    #[cfg(any(feature = "metrics", feature = "chrome-tracing"))]
+    started: (Instant, &'static str, Access),
+}
+
+impl HoldTimer {
+    /// Reports the acquisition of the resource, and starts measuring how long it's held.
+    pub(crate) fn start(type_name: &'static str, access: Access) -> Self {
+        acquired(type_name, access);
+        Self {
+            #[cfg(any(feature = "metrics", feature = "chrome-tracing"))]
+            started: (Instant::now(), type_name, access),
+        }
+    }
+}
+
+#[cfg(any(feature = "metrics", feature = "chrome-tracing"))]
+impl Drop for HoldTimer {
+    fn drop(&mut self) {
+        let (started, type_name, access) = self.started;
+        #[cfg(feature = "metrics")]
+        ::metrics::histogram!(
+            "resources_hold_seconds",
+            "resource" => type_name,
+            "access" => access.label()
+        )
+        .record(started.elapsed());
+        #[cfg(feature = "chrome-tracing")]
+        chrome_trace::span(type_name, access, started);
+    }
+}
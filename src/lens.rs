@@ -0,0 +1,135 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+use crate::{
+    hash::TypeIdBuildHasher,
+    lock::ResourceCell,
+    map::Resource,
+    refs::{Ref, RefMut},
+};
+
+/// Projects a borrow of the parent resource onto its field.
+type ProjectFn<F> = Box<dyn for<'a> Fn(Ref<'a, dyn Resource>) -> Ref<'a, F> + Send + Sync>;
+
+/// Projects a mutable borrow of the parent resource onto its field.
+type ProjectMutFn<F> = Box<dyn for<'a> Fn(RefMut<'a, dyn Resource>) -> RefMut<'a, F> + Send + Sync>;
+
+/// Projection of a resource onto its field of type `F`, see [`Resources::register_lens()`].
+///
+/// [`Resources::register_lens()`]: struct.Resources.html#method.register_lens
+pub(crate) struct Lens<F> {
+    parent: TypeId,
+    project: ProjectFn<F>,
+    project_mut: ProjectMutFn<F>,
+}
+
+impl<F: Resource> Lens<F> {
+    pub(crate) fn parent(&self) -> TypeId {
+        self.parent
+    }
+
+    /// Projects a borrow of the resource, which must be the parent, onto the field.
+    pub(crate) fn project<'a>(&self, parent: Ref<'a, dyn Resource>) -> Ref<'a, F> {
+        (self.project)(parent)
+    }
+
+    /// Projects a mutable borrow of the resource, which must be the parent, onto the field.
+    pub(crate) fn project_mut<'a>(&self, parent: RefMut<'a, dyn Resource>) -> RefMut<'a, F> {
+        (self.project_mut)(parent)
+    }
+}
+
+/// Cell of a resource of type `F`, or of the resource its lens projects,
+/// as found by `Resources::find()`.
+pub(crate) enum Found<'a, F> {
+    /// The resource is stored in the cell.
+    Stored(&'a ResourceCell),
+    /// The resource is a field of the one stored in the cell.
+    Lensed(&'a Lens<F>, &'a ResourceCell),
+}
+
+// Derived implementations would require `F: Copy`.
+impl<F> Clone for Found<'_, F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F> Copy for Found<'_, F> {}
+
+impl<'a, F: Resource> Found<'a, F> {
+    /// Returns the cell to borrow.
+    pub(crate) fn cell(&self) -> &'a ResourceCell {
+        match self {
+            Found::Stored(cell) | Found::Lensed(_, cell) => cell,
+        }
+    }
+
+    /// Projects a borrow of the cell's resource onto the resource of type `F`.
+    pub(crate) fn project(&self, resource: Ref<'a, dyn Resource>) -> Ref<'a, F> {
+        match self {
+            Found::Stored(_) => Ref::map(resource, downcast_ref),
+            Found::Lensed(lens, _) => lens.project(resource),
+        }
+    }
+
+    /// Projects a mutable borrow of the cell's resource onto the resource of type `F`.
+    pub(crate) fn project_mut(&self, resource: RefMut<'a, dyn Resource>) -> RefMut<'a, F> {
+        match self {
+            Found::Stored(_) => RefMut::map(resource, downcast_mut),
+            Found::Lensed(lens, _) => lens.project_mut(resource),
+        }
+    }
+}
+
+/// Lenses registered with a container, along with types of their parents,
+/// keyed by the types they project onto.
+#[derive(Default)]
+pub(crate) struct Lenses {
+    lenses: HashMap<TypeId, (TypeId, Box<dyn Any + Send + Sync>), TypeIdBuildHasher>,
+}
+
+impl Lenses {
+    pub(crate) fn register<P: Resource, F: Resource>(
+        &mut self,
+        get: fn(&P) -> &F,
+        get_mut: fn(&mut P) -> &mut F,
+    ) {
+        let lens = Lens::<F> {
+            parent: TypeId::of::<P>(),
+            project: Box::new(move |parent| Ref::map(parent, |parent| get(downcast_ref(parent)))),
+            project_mut: Box::new(move |parent| {
+                RefMut::map(parent, |parent| get_mut(downcast_mut(parent)))
+            }),
+        };
+        self.lenses
+            .insert(TypeId::of::<F>(), (TypeId::of::<P>(), Box::new(lens)));
+    }
+
+    pub(crate) fn unregister<F: Resource>(&mut self) -> bool {
+        self.lenses.remove(&TypeId::of::<F>()).is_some()
+    }
+
+    pub(crate) fn get<F: Resource>(&self) -> Option<&Lens<F>> {
+        self.lenses.get(&TypeId::of::<F>())?.1.downcast_ref()
+    }
+
+    /// Returns the type of the resource the lens onto the type projects.
+    pub(crate) fn parent_of(&self, type_id: TypeId) -> Option<TypeId> {
+        self.lenses.get(&type_id).map(|(parent, _)| *parent)
+    }
+}
+
+fn downcast_ref<P: Resource>(parent: &dyn Resource) -> &P {
+    parent
+        .downcast_ref()
+        .unwrap_or_else(|| panic!("downcasting resources should always succeed"))
+}
+
+fn downcast_mut<P: Resource>(parent: &mut dyn Resource) -> &mut P {
+    parent
+        .downcast_mut()
+        .unwrap_or_else(|| panic!("downcasting resources should always succeed"))
+}
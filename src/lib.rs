@@ -24,15 +24,103 @@
 //! # Cargo features
 //!
 //! - `fetch` - when enabled, exposes `Resources::fetch()` that allows
-//! retrieving up to 16 resources with a one-liner.
+//!   retrieving up to 16 resources with a one-liner, and `Resources::prepare()` that looks
+//!   them up once, for retrieving them repeatedly.
+//! - `blocking` - when enabled, exposes `Resources::get_blocking()` and similar methods
+//!   that wait for the resource to become available, in FIFO order,
+//!   and `Resources::set_backoff()` that configures how they wait.
+//! - `cache-padded` - when enabled, aligns storage of each resource to 128 bytes,
+//!   preventing false sharing when different threads access different resources.
+//! - `arena` - when enabled, allocates resources from a bump arena owned by the container,
+//!   instead of boxing each individually; memory is reclaimed only when the container is dropped.
+//! - `allocator-api` - when enabled, exposes `Resources::new_in()` that allows
+//!   allocating resources and the container's tables with a custom allocator.
+//! - `derive` - when enabled, exposes `#[derive(Resource)]`, `#[derive(TryFromResources)]`
+//!   that clones resources into the fields of an owned snapshot struct, and
+//!   `#[derive(RequiredResources)]` that lists types of fields for `Resources::validate()`.
+//! - `explicit` - when enabled, `Resource` is no longer implemented for all applicable types,
+//!   and has to be opted into via `#[derive(Resource)]`, preventing accidental insertion
+//!   of types like `usize`. Note that this affects every crate depending on `resources`.
+//! - `lock-order` - when enabled, every thread asserts that it acquires resources of a container
+//!   in the order they were inserted in when blocking or waiting asynchronously, panicking with
+//!   names of both offending types otherwise. Following such an order rules out deadlocks;
+//!   this is meant for debugging. Non-blocking acquisitions, such as `get()`, can't deadlock,
+//!   and so aren't checked. Guards acquired asynchronously aren't checked against, as tasks
+//!   holding them across `.await` points can move between threads.
+//! - `stream` - when enabled, exposes `Resources::changes()` and `Resources::structure_changes()`
+//!   that return `futures` streams of changes of a resource, and of the container's structure.
+//! - `metrics` - when enabled, reports acquisitions, borrow conflicts and hold times of resources,
+//!   labeled by type, and the number of stored resources, via the `metrics` crate.
+//! - `miette` - when enabled, implements `miette::Diagnostic` for `CantGetResource`,
+//!   `InvalidBorrow` and `NoSuchResource`, with error codes and help text.
+//! - `track-borrows` - when enabled, records where each guard was acquired, so that
+//!   `Resources::borrow_state()` and the panic hook installed by `Resources::install_panic_hook()`
+//!   can point at the code holding conflicting borrows, along with labels given to guards
+//!   acquired via `Resources::get_labeled()` and similar, which conflict errors include as well.
+//! - `watchdog` - when enabled, additionally records when each guard was acquired, and exposes
+//!   `Resources::long_holds()` and `Watchdog` that report guards held for too long.
+//!   Implies `track-borrows`.
+//! - `async` - when enabled, exposes `Resources::get_async()` and `Resources::get_mut_async()`
+//!   that wait asynchronously for the resource to become available, without depending on
+//!   any particular executor.
+//! - `tokio` - when enabled, exposes `Resources::read()` and `Resources::write()` that wait
+//!   asynchronously for the resource to become available, in FIFO order of a `tokio::sync::RwLock`,
+//!   returning references that can be held across `.await` points.
+//! - `epoch` - when enabled, exposes `Resources::insert_rcu()` and `Resources::snapshot()`
+//!   for read-mostly resources that are read without locking, via snapshots of versions
+//!   published by writers and reclaimed with `crossbeam-epoch`.
+//! - `hecs` - when enabled, exposes `Resources::with_world()` that pairs the container with
+//!   a `hecs::World`, for fetching resources and querying entities with unified error handling,
+//!   and allows `System`s to declare accesses to components. Implies `fetch`.
+//! - `rhai` - when enabled, exposes `Resources::register_script_resource()` and
+//!   `Resources::register_script_functions()` that allow `rhai` scripts to read and write
+//!   serializable resources by name and field path.
+//! - `abi_stable` - when enabled, exposes `Resources::as_ffi()` that returns an FFI-safe handle
+//!   to the container, for sharing it with dynamically loaded modules via `abi_stable`.
+//! - `inventory` - when enabled, exposes `register_default!` that registers resource types
+//!   from any crate, to be inserted with default values by `Resources::with_registered()`.
+//! - `test-util` - when enabled, exposes `Resources::test_with()`, `Resources::with_override()`,
+//!   `Resources::inject_failure()` and `Resources::inject_random_failures()` that make accesses
+//!   fail on demand, and `assert_contains!` and `assert_not_contains!` macros, for testing code
+//!   using resources.
+//! - `serde` - when enabled, implements `serde::Serialize` for `Ref` and `RefMut`
+//!   of serializable resources, and exposes `TypeVTable::with_serialize()`.
+//! - `replication` - when enabled, exposes `Resources::write_delta()` and
+//!   `Resources::apply_delta()` that serialize resources changed since the previous delta,
+//!   and apply them to another container, such as one on a client, and
+//!   `Resources::write_snapshot()` and `Resources::load_snapshot()` for versioned snapshots,
+//!   such as savegames, migrated via `Resources::add_migration()`. Implies `serde`.
+//! - `hot-reload` - when enabled, exposes `Resources::watch_file()` that binds a resource
+//!   to a file it's loaded from, and `Resources::poll_reloads()` that loads it again
+//!   whenever the file is modified.
+//! - `autosave` - when enabled, exposes `Autosave` that periodically serializes selected resources
+//!   on a background thread, borrowing all resources at once for a consistent view.
+//!   Implies `serde`.
+//! - `chrome-tracing` - when enabled, exposes `ChromeTrace` that records spans during which
+//!   guards hold resources, and borrow conflicts, for viewing in `chrome://tracing` or Perfetto
+//!   with one track per resource type.
+//! - `shred-compat` - when enabled, exposes the `shred` module, with adapters matching
+//!   the `World` and `SystemData` surface of `shred`, so that `specs`-based projects can migrate
+//!   one system at a time.
+//! - `schemars` - when enabled, exposes `Resources::register_schema()` and
+//!   `Resources::json_schema()` that describe registered serializable resource types
+//!   with a JSON schema, for external tools.
+//! - `im` - when enabled, exposes `ImResources`, a container backed by a persistent map
+//!   that's cloned in constant time, sharing resources with its clones until they're mutated.
+//! - `rayon` - when enabled, exposes `Resources::par_values_mut()`, visiting all resources
+//!   in parallel on the `rayon` thread pool.
+//! - `local-cache` - when enabled, exposes `Resources::with_local()`, reading resources
+//!   marked as read-mostly via thread-local snapshots, invalidated when any of them changes.
 //!
 //! # Example
 //!
 //! ```rust
 //!use resources::*;
 //!
+//!# #[cfg_attr(feature = "derive", derive(Resource))]
 //!struct SomeNumber(usize);
 //!
+//!# #[cfg_attr(feature = "derive", derive(Resource))]
 //!struct SomeString(&'static str);
 //!
 //!fn main() {
@@ -64,16 +152,140 @@
 
 #![warn(missing_docs)]
 
+mod access_log;
+mod all;
+mod any_map;
+#[cfg(feature = "autosave")]
+mod autosave;
+#[cfg(feature = "blocking")]
+mod backoff;
+mod borrows;
+mod branded;
+mod cache;
+#[cfg(feature = "chrome-tracing")]
+mod chrome_trace;
 mod entry;
+#[cfg(feature = "epoch")]
+mod epoch;
 mod error;
+mod events;
+mod exec;
+#[cfg(feature = "test-util")]
+mod failpoints;
 #[cfg(feature = "fetch")]
 mod fetch;
+#[cfg(feature = "abi_stable")]
+mod ffi;
+mod fixed;
+mod guards;
+mod hash;
+mod init;
+mod instrument;
+mod lens;
+#[cfg(feature = "local-cache")]
+mod local;
+mod lock;
+mod lock_order;
 mod map;
+#[cfg(feature = "im")]
+mod persistent;
+mod plugin;
+mod published;
 mod refs;
+#[cfg(feature = "inventory")]
+mod registered;
+#[cfg(feature = "hot-reload")]
+mod reload;
+#[cfg(feature = "replication")]
+mod replication;
+mod report;
+mod required;
+#[cfg(feature = "schemars")]
+mod schema;
+mod scoped;
+#[cfg(feature = "rhai")]
+mod script;
+#[cfg(feature = "shred-compat")]
+pub mod shred;
+mod stable_id;
+mod static_resources;
+mod storage;
+#[cfg(feature = "stream")]
+mod stream;
+mod subset;
+#[cfg(feature = "test-util")]
+mod test_util;
+mod transaction;
+mod visit;
+#[cfg(feature = "watchdog")]
+mod watchdog;
+mod weak;
+#[cfg(feature = "hecs")]
+mod world;
 
+pub use access_log::{AccessLog, AccessRecord, Divergence};
+pub use all::{AllMut, AllRef};
+pub use any_map::AnyMapConverter;
+#[cfg(feature = "autosave")]
+pub use autosave::{Autosave, AutosaveHandle, SavedResources};
+#[cfg(feature = "blocking")]
+pub use backoff::Backoff;
+pub use borrows::BorrowState;
+pub use branded::{AccessToken, Branded};
+#[cfg(feature = "chrome-tracing")]
+pub use chrome_trace::ChromeTrace;
 pub use entry::{Entry, OccupiedEntry, VacantEntry};
-pub use error::{CantGetResource, InvalidBorrow, NoSuchResource};
+#[cfg(feature = "epoch")]
+pub use epoch::{Rcu, Snapshot};
+pub use error::{
+    BorrowApi, BudgetExceeded, CantGetDyn, CantGetResource, Conflict, InvalidBorrow,
+    MissingDependencies, MissingResources, NoSuchResource, Sealed,
+};
+pub use events::{EventReader, EventWriter, Events};
+pub use exec::{AccessSet, System};
+#[cfg(feature = "test-util")]
+pub use failpoints::InjectedFailure;
 #[cfg(feature = "fetch")]
-pub use fetch::CantFetch;
-pub use map::{Resource, Resources};
+pub use fetch::{CantFetch, Prepared, Scoped};
+#[cfg(feature = "abi_stable")]
+pub use ffi::{FfiError, FfiRef, FfiRefMut, FfiResources};
+pub use fixed::FixedResources;
+pub use guards::GuardSet;
+pub use hash::PreHashed;
+pub use init::InitError;
+#[cfg(feature = "inventory")]
+#[doc(hidden)]
+pub use inventory as __inventory;
+pub use lock::LockKind;
+pub use map::{IntoIter, Resource, Resources};
+#[cfg(feature = "im")]
+pub use persistent::ImResources;
+pub use plugin::{PluginRegistry, TypeVTable};
+pub use published::{Published, Subscriber};
 pub use refs::{Ref, RefMut};
+#[cfg(feature = "inventory")]
+pub use registered::DefaultResource;
+#[cfg(feature = "hot-reload")]
+pub use reload::ReloadError;
+pub use report::{Report, ReportEntry};
+#[doc(hidden)]
+pub use required::__required;
+pub use required::RequiredResources;
+#[cfg(feature = "derive")]
+pub use resources_derive::{RequiredResources, Resource, TryFromResources};
+#[cfg(feature = "rhai")]
+pub use script::ScriptError;
+pub use stable_id::StableId;
+pub use static_resources::{Index, ResourceSet, Select, StaticCell, StaticResources};
+#[cfg(feature = "stream")]
+pub use stream::{Changes, StructureChange, StructureChanges};
+pub use subset::{CloneSubset, ResourceTypes};
+#[cfg(feature = "test-util")]
+pub use test_util::ResourceTuple;
+pub use transaction::Transaction;
+pub use visit::ResourceVisitor;
+#[cfg(feature = "watchdog")]
+pub use watchdog::{LongHold, Watchdog};
+pub use weak::WeakResource;
+#[cfg(feature = "hecs")]
+pub use world::{ComponentTypes, WorldError, WorldResources};
@@ -0,0 +1,164 @@
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+use crate::hash::TypeIdBuildHasher;
+
+/// Copy of a resource, shared by all threads reading it via `Resources::with_local()`.
+type Snapshot = Arc<dyn Any + Send + Sync>;
+
+/// Source of identifiers of containers, which, unlike their addresses, are never reused.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Snapshot taken by a thread, along with the epoch of its container it's valid for.
+struct LocalSnapshot {
+    epoch: u64,
+    snapshot: Snapshot,
+}
+
+thread_local! {
+    /// Snapshots taken by the current thread, keyed by identifiers of containers and types.
+    static SNAPSHOTS: RefCell<HashMap<(u64, TypeId), LocalSnapshot>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Types of resources read via thread-local snapshots, see [`Resources::cache_locally()`],
+/// and the epoch invalidating those snapshots.
+pub(crate) struct LocalCache {
+    id: u64,
+    /// Advanced by every write of a resource of a designated type, and by every structural
+    /// change of the container; shared with locks of designated resources.
+    epoch: Arc<AtomicU64>,
+    designated: HashSet<TypeId, TypeIdBuildHasher>,
+    /// Latest snapshots, so that threads taking one for the same epoch share it.
+    shared: Mutex<HashMap<TypeId, LocalSnapshot, TypeIdBuildHasher>>,
+}
+
+impl Default for LocalCache {
+    fn default() -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            epoch: Arc::new(AtomicU64::new(0)),
+            designated: HashSet::default(),
+            shared: Mutex::default(),
+        }
+    }
+}
+
+impl LocalCache {
+    fn shared(&self) -> MutexGuard<'_, HashMap<TypeId, LocalSnapshot, TypeIdBuildHasher>> {
+        self.shared
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    pub(crate) fn designate(&mut self, type_id: TypeId) {
+        self.designated.insert(type_id);
+    }
+
+    pub(crate) fn undesignate(&mut self, type_id: TypeId) {
+        self.designated.remove(&type_id);
+        self.invalidate();
+    }
+
+    pub(crate) fn is_designated(&self, type_id: TypeId) -> bool {
+        self.designated.contains(&type_id)
+    }
+
+    /// Returns the epoch to be advanced by writes of a resource of the type, if it's designated.
+    pub(crate) fn epoch_for(&self, type_id: TypeId) -> Option<Arc<AtomicU64>> {
+        if self.is_designated(type_id) {
+            Some(self.epoch.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Invalidates all snapshots.
+    pub(crate) fn invalidate(&self) {
+        self.epoch.fetch_add(1, Ordering::Release);
+    }
+
+    /// Returns the current epoch, which has to be read before taking a snapshot.
+    pub(crate) fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Acquire)
+    }
+
+    /// Calls the closure with the current thread's snapshot of the resource of type `T`,
+    /// if it's valid for the epoch, returning the closure back otherwise.
+    pub(crate) fn try_with<T: Any, R, F: FnOnce(&T) -> R>(&self, epoch: u64, f: F) -> Result<R, F> {
+        SNAPSHOTS.with(|snapshots| {
+            // Reentrant calls only ever borrow the snapshots immutably while calling the closure.
+            let snapshots = match snapshots.try_borrow() {
+                Ok(snapshots) => snapshots,
+                Err(_) => return Err(f),
+            };
+            match snapshots.get(&(self.id, TypeId::of::<T>())) {
+                Some(local) if local.epoch == epoch => Ok(f(downcast_ref(&*local.snapshot))),
+                _ => Err(f),
+            }
+        })
+    }
+
+    /// Returns the snapshot of the resource of type `T` for the epoch, taking it
+    /// if no thread did yet, and keeps it for the current thread.
+    pub(crate) fn snapshot<T: Any + Send + Sync, E>(
+        &self,
+        epoch: u64,
+        take: impl FnOnce() -> Result<T, E>,
+    ) -> Result<Arc<T>, E> {
+        let type_id = TypeId::of::<T>();
+        let snapshot = {
+            let mut shared = self.shared();
+            match shared.get(&type_id) {
+                Some(latest) if latest.epoch == epoch => latest.snapshot.clone(),
+                _ => {
+                    let snapshot: Snapshot = Arc::new(take()?);
+                    let latest = LocalSnapshot {
+                        epoch,
+                        snapshot: snapshot.clone(),
+                    };
+                    shared.insert(type_id, latest);
+                    snapshot
+                }
+            }
+        };
+        SNAPSHOTS.with(|snapshots| {
+            // This is skipped if called from within a closure passed to `try_with()`.
+            if let Ok(mut snapshots) = snapshots.try_borrow_mut() {
+                let local = LocalSnapshot {
+                    epoch,
+                    snapshot: snapshot.clone(),
+                };
+                snapshots.insert((self.id, type_id), local);
+            }
+        });
+        Ok(snapshot
+            .downcast()
+            .unwrap_or_else(|_| panic!("downcasting resources should always succeed")))
+    }
+}
+
+impl Drop for LocalCache {
+    /// Frees the current thread's snapshots; other threads keep theirs until they exit.
+    fn drop(&mut self) {
+        let id = self.id;
+        let _ = SNAPSHOTS.try_with(|snapshots| {
+            if let Ok(mut snapshots) = snapshots.try_borrow_mut() {
+                snapshots.retain(|(container, _), _| *container != id);
+            }
+        });
+    }
+}
+
+fn downcast_ref<T: Any>(snapshot: &(dyn Any + Send + Sync)) -> &T {
+    snapshot
+        .downcast_ref()
+        .unwrap_or_else(|| panic!("downcasting resources should always succeed"))
+}
@@ -0,0 +1,749 @@
+use std::{any::TypeId, mem::ManuallyDrop};
+
+#[cfg(feature = "local-cache")]
+use std::sync::{atomic::AtomicU64, Arc};
+#[cfg(feature = "stream")]
+use std::task::Waker;
+
+#[cfg(all(loom, any(feature = "async", feature = "tokio")))]
+use loom::sync::atomic::fence;
+#[cfg(all(loom, feature = "stream"))]
+use loom::sync::atomic::AtomicBool;
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(all(loom, feature = "blocking"))]
+use loom::sync::Condvar;
+#[cfg(all(loom, any(feature = "blocking", feature = "stream")))]
+use loom::sync::Mutex;
+#[cfg(all(not(loom), any(feature = "async", feature = "tokio")))]
+use std::sync::atomic::fence;
+#[cfg(all(not(loom), feature = "stream"))]
+use std::sync::atomic::AtomicBool;
+#[cfg(not(loom))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(all(not(loom), feature = "blocking"))]
+use std::sync::Condvar;
+#[cfg(all(not(loom), any(feature = "blocking", feature = "stream")))]
+use std::sync::Mutex;
+
+#[cfg(feature = "blocking")]
+use crate::backoff::Backoff;
+#[cfg(feature = "lock-order")]
+use crate::lock_order::LockOrder;
+use crate::{
+    borrows::BorrowSites,
+    error::{BorrowApi, Conflict},
+    instrument,
+    map::Resource,
+    storage::{Storage, StorageAllocator, ThinResource},
+};
+
+/// Bit of the lock state that is set while the lock is held exclusively.
+const WRITER: usize = !(usize::MAX >> 1);
+
+/// Strategy of the lock guarding a resource, chosen via [`Resources::insert_with_lock()`].
+///
+/// [`Resources::insert_with_lock()`]: struct.Resources.html#method.insert_with_lock
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum LockKind {
+    /// Allows any number of immutable accesses at once, or a single mutable one.
+    #[default]
+    RwLock,
+    /// Allows a single access at once, immutable or mutable; cheaper to acquire immutably,
+    /// for resources that are mostly accessed mutably, such as command queues.
+    Mutex,
+    /// Allows any number of immutable accesses at once, which skip the lock's bookkeeping,
+    /// and no mutable ones, see [`Resources::insert_readonly()`].
+    ///
+    /// [`Resources::insert_readonly()`]: struct.Resources.html#method.insert_readonly
+    ReadOnly,
+}
+
+/// Queue a blocking acquisition waits in.
+#[cfg(feature = "blocking")]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum Lane {
+    /// Served in FIFO order, after the priority lane is empty.
+    Normal,
+    /// Served in FIFO order, ahead of the normal lane.
+    Priority,
+}
+
+/// Reader-writer lock guarding a single resource.
+///
+/// The lock state is a single atomic, similar to that of an `AtomicRefCell`:
+/// the high bit marks an exclusive holder, the remaining bits count shared holders.
+///
+/// With the `blocking` feature, blocking acquisitions are additionally queued
+/// in FIFO order with a ticket pair, and the non-blocking ones fail while the queue
+/// is not empty, so that a waiting writer can't be starved by readers.
+/// A second ticket pair forms a priority lane: while it's not empty, acquisitions
+/// in the normal one wait even when it's their turn.
+/// Waiting follows the container's `Backoff` policy; acquisitions whose policy may give up
+/// aren't queued, as a ticket can't be abandoned.
+///
+/// Exclusive releases are also counted, and threads waiting for the next one are parked
+/// on a condition variable, which is only touched while there are any.
+///
+/// With the `stream` feature, exclusive releases are counted as well,
+/// and wake all tasks that registered their wakers since the previous one.
+/// With the `replication` feature, they're counted too, to tell which resources changed.
+///
+/// With the `async` or `tokio` feature, asynchronous acquisitions retry each time the lock
+/// is released, waiting in between on an `event_listener::Event` or a `tokio::sync::Notify`
+/// (preferring the former if both features are enabled), which is only touched
+/// while there are any. With the `tokio` feature, they first wait for their turn
+/// in a `tokio::sync::RwLock` queue, which serves them in FIFO order, so that
+/// writers aren't starved by a stream of readers; they keep their place in it
+/// until their guards are released.
+///
+/// With `LockKind::Mutex`, shared acquisitions only succeed while the lock is not held
+/// at all, so that there's at most one shared holder.
+///
+/// When compiled with `--cfg loom`, the atomics are provided by `loom`,
+/// so that the state machine can be model-checked.
+pub(crate) struct ResourcesRwLock {
+    state: AtomicUsize,
+    #[cfg(feature = "blocking")]
+    next_ticket: AtomicUsize,
+    #[cfg(feature = "blocking")]
+    now_serving: AtomicUsize,
+    #[cfg(feature = "blocking")]
+    next_priority_ticket: AtomicUsize,
+    #[cfg(feature = "blocking")]
+    priority_serving: AtomicUsize,
+    #[cfg(any(feature = "blocking", feature = "stream", feature = "replication"))]
+    writes: AtomicUsize,
+    #[cfg(feature = "blocking")]
+    write_waiters: AtomicUsize,
+    #[cfg(feature = "blocking")]
+    write_mutex: Mutex<()>,
+    #[cfg(feature = "blocking")]
+    write_condvar: Condvar,
+    #[cfg(feature = "stream")]
+    has_write_wakers: AtomicBool,
+    #[cfg(feature = "stream")]
+    write_wakers: Mutex<Vec<Waker>>,
+    #[cfg(any(feature = "async", feature = "tokio"))]
+    async_waiters: AtomicUsize,
+    #[cfg(feature = "async")]
+    released: event_listener::Event,
+    #[cfg(all(feature = "tokio", not(feature = "async")))]
+    released: tokio::sync::Notify,
+    #[cfg(feature = "tokio")]
+    queue: tokio::sync::RwLock<()>,
+    /// API through which the lock was last acquired, only used to describe conflicts;
+    /// deliberately not modelled by `loom`, as nothing synchronizes through it.
+    last_api: std::sync::atomic::AtomicU8,
+    kind: LockKind,
+    /// Epoch of thread-local snapshots of the resource, advanced by each exclusive release,
+    /// if the resource is read via them.
+    #[cfg(feature = "local-cache")]
+    local_epoch: Option<Arc<AtomicU64>>,
+}
+
+impl ResourcesRwLock {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            #[cfg(feature = "blocking")]
+            next_ticket: AtomicUsize::new(0),
+            #[cfg(feature = "blocking")]
+            now_serving: AtomicUsize::new(0),
+            #[cfg(feature = "blocking")]
+            next_priority_ticket: AtomicUsize::new(0),
+            #[cfg(feature = "blocking")]
+            priority_serving: AtomicUsize::new(0),
+            #[cfg(any(feature = "blocking", feature = "stream", feature = "replication"))]
+            writes: AtomicUsize::new(0),
+            #[cfg(feature = "blocking")]
+            write_waiters: AtomicUsize::new(0),
+            #[cfg(feature = "blocking")]
+            write_mutex: Mutex::new(()),
+            #[cfg(feature = "blocking")]
+            write_condvar: Condvar::new(),
+            #[cfg(feature = "stream")]
+            has_write_wakers: AtomicBool::new(false),
+            #[cfg(feature = "stream")]
+            write_wakers: Mutex::new(Vec::new()),
+            #[cfg(any(feature = "async", feature = "tokio"))]
+            async_waiters: AtomicUsize::new(0),
+            #[cfg(feature = "async")]
+            released: event_listener::Event::new(),
+            #[cfg(all(feature = "tokio", not(feature = "async")))]
+            released: tokio::sync::Notify::new(),
+            #[cfg(feature = "tokio")]
+            queue: tokio::sync::RwLock::new(()),
+            last_api: std::sync::atomic::AtomicU8::new(BorrowApi::Other as u8),
+            kind: LockKind::RwLock,
+            #[cfg(feature = "local-cache")]
+            local_epoch: None,
+        }
+    }
+
+    /// Creates a lock for a resource of the type in the storage.
+    fn with_storage(type_id: TypeId, storage: &Storage) -> Self {
+        #[cfg(feature = "local-cache")]
+        {
+            let mut lock = Self::new();
+            lock.local_epoch = storage.local_cache().epoch_for(type_id);
+            lock
+        }
+        #[cfg(not(feature = "local-cache"))]
+        {
+            let _ = (type_id, storage);
+            Self::new()
+        }
+    }
+
+    pub(crate) fn kind(&self) -> LockKind {
+        self.kind
+    }
+
+    pub(crate) fn set_kind(&mut self, kind: LockKind) {
+        self.kind = kind;
+    }
+
+    #[cfg(feature = "local-cache")]
+    pub(crate) fn is_cached_locally(&self) -> bool {
+        self.local_epoch.is_some()
+    }
+
+    #[cfg(feature = "local-cache")]
+    pub(crate) fn set_local_epoch(&mut self, epoch: Option<Arc<AtomicU64>>) {
+        self.local_epoch = epoch;
+    }
+
+    /// Invalidates thread-local snapshots of the resource, if there are any.
+    #[cfg(feature = "local-cache")]
+    pub(crate) fn invalidate_local(&self) {
+        if let Some(epoch) = &self.local_epoch {
+            epoch.fetch_add(1, std::sync::atomic::Ordering::Release);
+        }
+    }
+
+    /// Returns `true` if there are blocking acquisitions waiting for their turn.
+    #[cfg(feature = "blocking")]
+    fn is_queued(&self) -> bool {
+        self.next_ticket.load(Ordering::Relaxed) != self.now_serving.load(Ordering::Relaxed)
+            || self.is_priority_queued()
+    }
+
+    /// Returns `true` if there are blocking acquisitions waiting in the priority lane.
+    #[cfg(feature = "blocking")]
+    fn is_priority_queued(&self) -> bool {
+        self.next_priority_ticket.load(Ordering::Relaxed)
+            != self.priority_serving.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if there are blocking acquisitions that must go before one in the lane.
+    #[cfg(feature = "blocking")]
+    fn is_queued_ahead(&self, lane: Lane) -> bool {
+        match lane {
+            Lane::Normal => self.is_queued(),
+            Lane::Priority => self.is_priority_queued(),
+        }
+    }
+
+    #[cfg(not(feature = "blocking"))]
+    fn is_queued(&self) -> bool {
+        false
+    }
+
+    /// Waits until all blocking acquisitions queued before this one in the lane are done,
+    /// and, in the normal lane, until the priority lane is empty.
+    #[cfg(feature = "blocking")]
+    fn enqueue(&self, lane: Lane, backoff: &Backoff) {
+        match lane {
+            Lane::Normal => {
+                let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+                backoff.wait(|| {
+                    self.now_serving.load(Ordering::Acquire) == ticket
+                        && self.next_priority_ticket.load(Ordering::Relaxed)
+                            == self.priority_serving.load(Ordering::Acquire)
+                });
+            }
+            Lane::Priority => {
+                let ticket = self.next_priority_ticket.fetch_add(1, Ordering::Relaxed);
+                backoff.wait(|| self.priority_serving.load(Ordering::Acquire) == ticket);
+            }
+        }
+    }
+
+    /// Lets the next queued blocking acquisition in the lane proceed.
+    #[cfg(feature = "blocking")]
+    fn dequeue(&self, lane: Lane) {
+        match lane {
+            Lane::Normal => self.now_serving.fetch_add(1, Ordering::Release),
+            Lane::Priority => self.priority_serving.fetch_add(1, Ordering::Release),
+        };
+    }
+
+    /// Returns `true` if the lock is currently held exclusively.
+    pub(crate) fn is_locked_exclusive(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER != 0
+    }
+
+    /// Returns how many times the lock is currently held shared.
+    pub(crate) fn readers(&self) -> usize {
+        self.state.load(Ordering::Relaxed) & !WRITER
+    }
+
+    /// Records the API through which the lock was just acquired.
+    pub(crate) fn acquired_via(&self, api: BorrowApi) {
+        self.last_api
+            .store(api as u8, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Describes what a failed acquisition of the lock conflicted with.
+    pub(crate) fn conflict(&self) -> Conflict {
+        if self.kind == LockKind::ReadOnly {
+            return Conflict::ReadOnly;
+        }
+        let via = BorrowApi::from_u8(self.last_api.load(std::sync::atomic::Ordering::Relaxed));
+        let state = self.state.load(Ordering::Relaxed);
+        if state & WRITER != 0 {
+            Conflict::Writer { via, label: None }
+        } else if state != 0 {
+            Conflict::Readers {
+                count: state,
+                via,
+                label: None,
+            }
+        } else if self.is_queued() {
+            Conflict::Queued
+        } else {
+            Conflict::Released
+        }
+    }
+
+    /// Attempts to acquire the lock shared, returning `false` if it's held exclusively
+    /// or if there are blocking acquisitions waiting.
+    pub(crate) fn try_lock_shared(&self) -> bool {
+        !self.is_queued() && self.acquire_shared()
+    }
+
+    /// Attempts to acquire the lock shared, returning `false` if it's held exclusively.
+    ///
+    /// Unlike `try_lock_shared()`, this ignores waiting blocking acquisitions,
+    /// so it's guaranteed to succeed if the current thread already holds the lock shared.
+    pub(crate) fn try_lock_shared_recursive(&self) -> bool {
+        self.acquire_shared()
+    }
+
+    /// Attempts to acquire the lock exclusively, returning `false` if it's held in any way
+    /// or if there are blocking acquisitions waiting.
+    pub(crate) fn try_lock_exclusive(&self) -> bool {
+        !self.is_queued() && self.acquire_exclusive()
+    }
+
+    /// Acquires the lock shared, blocking until all acquisitions queued ahead of it are done
+    /// and the lock is not held exclusively.
+    ///
+    /// If the backoff policy may give up, this isn't queued, and returns `false` if it did.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn lock_shared(&self, lane: Lane, backoff: &Backoff) -> bool {
+        if backoff.may_fail() {
+            return backoff.wait(|| !self.is_queued_ahead(lane) && self.acquire_shared());
+        }
+        self.enqueue(lane, backoff);
+        backoff.wait(|| self.acquire_shared());
+        self.dequeue(lane);
+        true
+    }
+
+    /// Acquires the lock shared, blocking until it's not held exclusively.
+    ///
+    /// Unlike `lock_shared()`, this skips the queue if the lock can be acquired immediately,
+    /// so it will not deadlock if the current thread already holds the lock shared.
+    ///
+    /// If the backoff policy may give up, this isn't queued, and returns `false` if it did.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn lock_shared_recursive(&self, backoff: &Backoff) -> bool {
+        if backoff.may_fail() {
+            return backoff.wait(|| self.acquire_shared());
+        }
+        self.acquire_shared() || self.lock_shared(Lane::Normal, backoff)
+    }
+
+    /// Acquires the lock exclusively, blocking until all acquisitions queued ahead of it are done
+    /// and the lock is not held in any way.
+    ///
+    /// If the backoff policy may give up, this isn't queued, and returns `false` if it did.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn lock_exclusive(&self, lane: Lane, backoff: &Backoff) -> bool {
+        if self.kind == LockKind::ReadOnly {
+            return false;
+        }
+        if backoff.may_fail() {
+            return backoff.wait(|| !self.is_queued_ahead(lane) && self.acquire_exclusive());
+        }
+        self.enqueue(lane, backoff);
+        backoff.wait(|| self.acquire_exclusive());
+        self.dequeue(lane);
+        true
+    }
+
+    /// Blocks until the lock is next released from being held exclusively.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn wait_exclusive_release(&self) {
+        let observed = self.writes.load(Ordering::SeqCst);
+        let mut guard = self
+            .write_mutex
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        self.write_waiters.fetch_add(1, Ordering::SeqCst);
+        while self.writes.load(Ordering::SeqCst) == observed {
+            guard = self
+                .write_condvar
+                .wait(guard)
+                .unwrap_or_else(|error| error.into_inner());
+        }
+        self.write_waiters.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Returns how many times the lock was released from being held exclusively.
+    #[cfg(any(feature = "stream", feature = "replication"))]
+    pub(crate) fn writes(&self) -> usize {
+        self.writes.load(Ordering::SeqCst)
+    }
+
+    /// Registers the waker to be woken when the lock is next released from being held
+    /// exclusively. Has to be followed by a check of `writes()`, in case that already happened.
+    #[cfg(feature = "stream")]
+    pub(crate) fn register_write_waker(&self, waker: &Waker) {
+        let mut wakers = self
+            .write_wakers
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        if !wakers.iter().any(|registered| registered.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+        self.has_write_wakers.store(true, Ordering::SeqCst);
+    }
+
+    /// Counts an exclusive release, and wakes everything waiting for it.
+    #[cfg(any(feature = "blocking", feature = "stream", feature = "replication"))]
+    fn notify_exclusive_release(&self) {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        #[cfg(feature = "blocking")]
+        if self.write_waiters.load(Ordering::SeqCst) != 0 {
+            // Waiters check the counter while holding the mutex, so acquiring it here
+            // ensures that they're either waiting on the condition variable or will see the update.
+            drop(self.write_mutex.lock());
+            self.write_condvar.notify_all();
+        }
+        #[cfg(feature = "stream")]
+        if self.has_write_wakers.load(Ordering::SeqCst) {
+            let wakers = {
+                let mut wakers = self
+                    .write_wakers
+                    .lock()
+                    .unwrap_or_else(|error| error.into_inner());
+                self.has_write_wakers.store(false, Ordering::SeqCst);
+                std::mem::take(&mut *wakers)
+            };
+            wakers.into_iter().for_each(Waker::wake);
+        }
+    }
+
+    /// Acquires the lock shared, waiting asynchronously until it's not held exclusively
+    /// and there are no blocking acquisitions waiting.
+    #[cfg(any(feature = "async", feature = "tokio"))]
+    pub(crate) async fn lock_shared_async(&self) {
+        self.wait_async(|| self.try_lock_shared()).await
+    }
+
+    /// Acquires the lock exclusively, waiting asynchronously until it's not held in any way
+    /// and there are no blocking acquisitions waiting.
+    #[cfg(any(feature = "async", feature = "tokio"))]
+    pub(crate) async fn lock_exclusive_async(&self) {
+        self.wait_async(|| self.try_lock_exclusive()).await
+    }
+
+    /// Waits for the turn of a shared asynchronous acquisition in the FIFO queue.
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn queue_shared(&self) -> tokio::sync::RwLockReadGuard<'_, ()> {
+        self.queue.read().await
+    }
+
+    /// Waits for the turn of an exclusive asynchronous acquisition in the FIFO queue.
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn queue_exclusive(&self) -> tokio::sync::RwLockWriteGuard<'_, ()> {
+        self.queue.write().await
+    }
+
+    #[cfg(any(feature = "async", feature = "tokio"))]
+    async fn wait_async(&self, mut try_acquire: impl FnMut() -> bool) {
+        struct Waiting<'a>(&'a AtomicUsize);
+
+        impl Drop for Waiting<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+
+        self.async_waiters.fetch_add(1, Ordering::SeqCst);
+        let _waiting = Waiting(&self.async_waiters);
+        // Pairs with the fence in `notify_release()`: either the release sees this waiter,
+        // or the acquisition attempts below see the release.
+        fence(Ordering::SeqCst);
+        loop {
+            // Registering before the attempt ensures that a release right after it isn't missed.
+            #[cfg(feature = "async")]
+            let released = self.released.listen();
+            #[cfg(not(feature = "async"))]
+            let mut released = std::pin::pin!(self.released.notified());
+            #[cfg(not(feature = "async"))]
+            released.as_mut().enable();
+            if try_acquire() {
+                return;
+            }
+            released.await;
+        }
+    }
+
+    /// Wakes asynchronous acquisitions, if there are any.
+    #[cfg(any(feature = "async", feature = "tokio"))]
+    fn notify_release(&self) {
+        fence(Ordering::SeqCst);
+        if self.async_waiters.load(Ordering::SeqCst) != 0 {
+            #[cfg(feature = "async")]
+            self.released.notify(usize::MAX);
+            #[cfg(not(feature = "async"))]
+            self.released.notify_waiters();
+        }
+    }
+
+    fn acquire_shared(&self) -> bool {
+        if self.kind == LockKind::ReadOnly {
+            // Nothing can acquire the lock exclusively, so there's nothing to exclude.
+            return true;
+        }
+        if self.kind == LockKind::Mutex {
+            // A single shared holder at most, which is otherwise released as usual.
+            return self
+                .state
+                .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok();
+        }
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & WRITER != 0 || state == WRITER - 1 {
+                return false;
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    fn acquire_exclusive(&self) -> bool {
+        self.kind != LockKind::ReadOnly
+            && self
+                .state
+                .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+    }
+
+    /// Releases a shared hold of the lock.
+    ///
+    /// # Safety
+    /// The lock must be held shared by the caller.
+    pub(crate) unsafe fn unlock_shared(&self) {
+        if self.kind == LockKind::ReadOnly {
+            return;
+        }
+        let previous = self.state.fetch_sub(1, Ordering::Release);
+        debug_assert!(previous & !WRITER != 0 && previous & WRITER == 0);
+        #[cfg(any(feature = "async", feature = "tokio"))]
+        self.notify_release();
+    }
+
+    /// Releases an exclusive hold of the lock.
+    ///
+    /// # Safety
+    /// The lock must be held exclusively by the caller.
+    pub(crate) unsafe fn unlock_exclusive(&self) {
+        let previous = self.state.swap(0, Ordering::Release);
+        debug_assert_eq!(previous, WRITER);
+        #[cfg(feature = "local-cache")]
+        self.invalidate_local();
+        #[cfg(any(feature = "blocking", feature = "stream", feature = "replication"))]
+        self.notify_exclusive_release();
+        #[cfg(any(feature = "async", feature = "tokio"))]
+        self.notify_release();
+    }
+
+    /// Releases an exclusive hold of the lock, without counting it as a change
+    /// or waking anything waiting for one; thread-local snapshots are still invalidated.
+    ///
+    /// # Safety
+    /// The lock must be held exclusively by the caller.
+    pub(crate) unsafe fn unlock_exclusive_untracked(&self) {
+        let previous = self.state.swap(0, Ordering::Release);
+        debug_assert_eq!(previous, WRITER);
+        #[cfg(feature = "local-cache")]
+        self.invalidate_local();
+        #[cfg(any(feature = "async", feature = "tokio"))]
+        self.notify_release();
+    }
+}
+
+/// Storage of a single resource: an owning thin pointer to the type-erased value
+/// and the lock guarding it.
+///
+/// With the `cache-padded` feature, each cell is aligned to (and so occupies at least)
+/// 128 bytes, so that locks of different resources never share a cache line,
+/// or a pair of adjacent lines prefetched together.
+#[cfg_attr(feature = "cache-padded", repr(align(128)))]
+pub(crate) struct ResourceCell {
+    pub(crate) lock: ResourcesRwLock,
+    resource: ThinResource,
+    #[cfg(feature = "lock-order")]
+    lock_order: LockOrder,
+    borrow_sites: BorrowSites,
+    allocator: StorageAllocator,
+}
+
+// The contained resource is `Send + Sync`, it's owned by the cell,
+// and access to it is guarded by the lock.
+unsafe impl Send for ResourceCell {}
+unsafe impl Sync for ResourceCell {}
+
+impl ResourceCell {
+    pub(crate) fn new<T: Resource>(resource: T, storage: &mut Storage) -> Self {
+        instrument::stored(1);
+        Self {
+            lock: ResourcesRwLock::with_storage(TypeId::of::<T>(), storage),
+            resource: storage.allocate_resource(resource),
+            #[cfg(feature = "lock-order")]
+            lock_order: storage.next_lock_order(),
+            borrow_sites: BorrowSites::default(),
+            allocator: storage.allocator().clone(),
+        }
+    }
+
+    pub(crate) fn from_boxed(resource: Box<dyn Resource>, storage: &mut Storage) -> Self {
+        instrument::stored(1);
+        Self {
+            lock: ResourcesRwLock::with_storage((*resource).as_any().type_id(), storage),
+            #[cfg(feature = "lock-order")]
+            lock_order: storage.next_lock_order(),
+            resource: storage.allocate_boxed(resource),
+            borrow_sites: BorrowSites::default(),
+            allocator: storage.allocator().clone(),
+        }
+    }
+
+    /// Returns the name of the resource's type, recorded when it was inserted.
+    pub(crate) fn type_name(&self) -> &'static str {
+        self.resource.type_name()
+    }
+
+    /// Returns the size of the resource, in bytes.
+    pub(crate) fn size(&self) -> usize {
+        self.resource.size()
+    }
+
+    /// Describes what a failed access to the resource conflicted with,
+    /// including the label of the latest labeled guard borrowing it.
+    pub(crate) fn conflict(&self) -> Conflict {
+        let mut conflict = self.lock.conflict();
+        if let Conflict::Readers { label, .. } | Conflict::Writer { label, .. } = &mut conflict {
+            *label = self.borrow_sites.latest_label();
+        }
+        conflict
+    }
+
+    /// Returns locations where guards currently borrowing the resource were acquired.
+    #[cfg(feature = "track-borrows")]
+    pub(crate) fn borrow_sites(&self) -> &BorrowSites {
+        &self.borrow_sites
+    }
+
+    /// Returns the position of the resource in the order locks have to be acquired in.
+    #[cfg(feature = "lock-order")]
+    pub(crate) fn lock_order(&self) -> LockOrder {
+        self.lock_order
+    }
+
+    /// Returns a pointer to the resource, for use while holding the lock.
+    pub(crate) fn resource_ptr(&self) -> *mut dyn Resource {
+        self.resource.as_ptr().as_ptr()
+    }
+
+    fn assert_type<T: Resource>(&self) {
+        assert!(
+            unsafe { self.resource.as_ptr().as_ref() }
+                .as_any()
+                .is::<T>(),
+            "downcasting resources should always succeed"
+        );
+    }
+
+    /// Replaces the resource in place, returning the old one.
+    pub(crate) fn replace<T: Resource>(&mut self, resource: T) -> T {
+        self.assert_type::<T>();
+        unsafe { std::ptr::replace(self.resource.as_ptr().cast::<T>().as_ptr(), resource) }
+    }
+
+    /// Replaces the resource with a boxed one of the same type, reusing its memory,
+    /// and returns the old one boxed.
+    pub(crate) fn replace_boxed(
+        &mut self,
+        resource: Box<dyn Resource>,
+        storage: &mut Storage,
+    ) -> Box<dyn Resource> {
+        debug_assert_eq!(
+            (*resource).as_any().type_id(),
+            unsafe { self.resource.as_ptr().as_ref() }
+                .as_any()
+                .type_id()
+        );
+        let previous = unsafe { storage.take_boxed(self.resource) };
+        self.resource = storage.allocate_boxed(resource);
+        previous
+    }
+
+    /// Moves the resource out of the cell, returning its memory to the storage.
+    pub(crate) fn into_resource<T: Resource>(self, storage: &mut Storage) -> T {
+        self.assert_type::<T>();
+        unsafe { storage.take_resource(self.into_raw()) }
+    }
+
+    /// Moves the resource out of the cell into a new box, returning its memory to the storage.
+    pub(crate) fn into_boxed(self, storage: &mut Storage) -> Box<dyn Resource> {
+        unsafe { storage.take_boxed(self.into_raw()) }
+    }
+
+    /// Drops the cell and the resource, returning its memory to the storage.
+    pub(crate) fn drop_in(self, storage: &mut Storage) {
+        unsafe { storage.drop_in_place(self.into_raw()) }
+    }
+
+    /// Drops the cell without dropping the resource, returning the owning pointer to it.
+    fn into_raw(self) -> ThinResource {
+        instrument::stored(-1);
+        let mut cell = ManuallyDrop::new(self);
+        unsafe {
+            std::ptr::drop_in_place(&mut cell.lock);
+            std::ptr::drop_in_place(&mut cell.borrow_sites);
+            std::ptr::drop_in_place(&mut cell.allocator);
+        }
+        cell.resource
+    }
+}
+
+impl Drop for ResourceCell {
+    fn drop(&mut self) {
+        instrument::stored(-1);
+        unsafe { Storage::drop_resource(self.resource, &self.allocator) }
+    }
+}
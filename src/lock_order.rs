@@ -0,0 +1,174 @@
+#[cfg(feature = "lock-order")]
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+#[cfg(all(loom, feature = "lock-order"))]
+use loom::thread_local;
+
+use crate::lock::ResourceCell;
+
+/// Source of unique identifiers of containers and acquisitions.
+#[cfg(feature = "lock-order")]
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "lock-order")]
+fn next_id() -> usize {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[cfg(feature = "lock-order")]
+thread_local! {
+    /// Locks currently held by guards acquired on this thread.
+    static HELD: Arc<Mutex<Vec<Held>>> = Arc::new(Mutex::new(Vec::new()));
+}
+
+// Only blocking and asynchronous acquisitions are checked against held locks.
+#[cfg(feature = "lock-order")]
+#[cfg_attr(
+    not(any(feature = "blocking", feature = "async", feature = "tokio")),
+    allow(dead_code)
+)]
+struct Held {
+    id: usize,
+    order: LockOrder,
+    type_name: &'static str,
+}
+
+/// Generator of lock ordering indices for resources of a single container.
+#[cfg(feature = "lock-order")]
+pub(crate) struct LockOrders {
+    container: usize,
+    next: usize,
+}
+
+#[cfg(feature = "lock-order")]
+impl Default for LockOrders {
+    fn default() -> Self {
+        Self {
+            container: next_id(),
+            next: 0,
+        }
+    }
+}
+
+#[cfg(feature = "lock-order")]
+impl LockOrders {
+    /// Returns the index of a newly inserted resource, ordered after all existing ones.
+    pub(crate) fn next(&mut self) -> LockOrder {
+        let order = LockOrder {
+            container: self.container,
+            index: self.next,
+        };
+        self.next += 1;
+        order
+    }
+}
+
+/// Position of a resource in the order its lock has to be acquired in,
+/// relative to other resources of the same container.
+#[cfg(feature = "lock-order")]
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub(crate) struct LockOrder {
+    container: usize,
+    index: usize,
+}
+
+/// Record of a lock held by a guard, removed when dropped, on whichever thread that happens.
+///
+/// Without the `lock-order` feature, this is zero-sized and does nothing.
+pub(crate) struct HeldLock {
+    #[cfg(feature = "lock-order")]
+    record: Option<(usize, Arc<Mutex<Vec<Held>>>)>,
+}
+
+impl HeldLock {
+    /// Returns a record that isn't checked against any other.
+    pub(crate) fn unordered() -> Self {
+        Self {
+            #[cfg(feature = "lock-order")]
+            record: None,
+        }
+    }
+
+    /// Records a blocking acquisition of the lock of the cell by the current thread,
+    /// panicking if the thread holds a lock of a resource of the same container
+    /// that is ordered after it.
+    ///
+    /// This has to be called before attempting to acquire the lock,
+    /// so that a violation is reported instead of deadlocking.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn acquire(cell: &ResourceCell) -> Self {
+        Self::check(cell);
+        Self::record(cell)
+    }
+
+    /// Records a non-blocking acquisition of the lock of the cell by the current thread,
+    /// which can't deadlock, so it isn't checked, but is checked against by later ones.
+    #[cfg(not(feature = "lock-order"))]
+    pub(crate) fn record(_: &ResourceCell) -> Self {
+        Self {}
+    }
+
+    /// Records a non-blocking acquisition of the lock of the cell by the current thread,
+    /// which can't deadlock, so it isn't checked, but is checked against by later ones.
+    #[cfg(feature = "lock-order")]
+    pub(crate) fn record(cell: &ResourceCell) -> Self {
+        let held = HELD.with(Arc::clone);
+        let id = next_id();
+        held.lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .push(Held {
+                id,
+                order: cell.lock_order(),
+                type_name: cell.type_name(),
+            });
+        Self {
+            record: Some((id, held)),
+        }
+    }
+
+    /// Panics if the current thread holds a lock of a resource of the same container
+    /// as the cell's that is ordered after it, without recording anything.
+    #[cfg(all(
+        not(feature = "lock-order"),
+        any(feature = "blocking", feature = "async", feature = "tokio")
+    ))]
+    pub(crate) fn check(_: &ResourceCell) {}
+
+    /// Panics if the current thread holds a lock of a resource of the same container
+    /// as the cell's that is ordered after it, without recording anything.
+    #[cfg(all(
+        feature = "lock-order",
+        any(feature = "blocking", feature = "async", feature = "tokio")
+    ))]
+    pub(crate) fn check(cell: &ResourceCell) {
+        let order = cell.lock_order();
+        HELD.with(|held| {
+            let held = held.lock().unwrap_or_else(|error| error.into_inner());
+            if let Some(later) = held.iter().find(|held| {
+                held.order.container == order.container && held.order.index > order.index
+            }) {
+                panic!(
+                    "lock order violation: acquiring `{}` while holding `{}`, \
+                     which was inserted after it",
+                    cell.type_name(),
+                    later.type_name
+                );
+            }
+        });
+    }
+}
+
+#[cfg(feature = "lock-order")]
+impl Drop for HeldLock {
+    fn drop(&mut self) {
+        if let Some((id, held)) = &self.record {
+            let mut held = held.lock().unwrap_or_else(|error| error.into_inner());
+            if let Some(position) = held.iter().position(|held| held.id == *id) {
+                held.swap_remove(position);
+            }
+        }
+    }
+}
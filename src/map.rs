@@ -1,105 +1,3510 @@
 use downcast_rs::{impl_downcast, Downcast};
-use fxhash::FxHashMap;
-use parking_lot::RwLock;
-use std::any::TypeId;
+use fxhash::FxHasher64;
+use hashbrown::hash_map;
+use std::{
+    alloc::Layout,
+    any::{type_name, Any, TypeId},
+    cmp::Reverse,
+    collections::HashMap,
+    fmt::{Debug, Formatter, Result as FmtResult},
+    hash::{BuildHasher, Hasher},
+    iter::FromIterator,
+    marker::PhantomData,
+    mem::{self, size_of, size_of_val},
+    panic,
+    ptr::NonNull,
+    sync::Arc,
+};
+
+#[cfg(feature = "watchdog")]
+use std::time::Duration;
+
+#[cfg(feature = "allocator-api")]
+use allocator_api2::alloc::Allocator;
+
+#[cfg(feature = "rayon")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    access_log::{AccessLog, AccessRecorder},
+    all::{AllMut, AllRef},
+    any_map::AnyMapConverter,
+    borrows::BorrowState,
+    branded::{AccessToken, Branded},
+    cache::Caches,
+    entry::Entry,
+    error::{
+        BorrowApi, BudgetExceeded, CantGetDyn, CantGetResource, Conflict, InvalidBorrow,
+        MissingDependencies, MissingResources, NoSuchResource, Sealed,
+    },
+    events::{self, EventWriter, Events},
+    exec::{self, System},
+    hash::PreHashed,
+    init::{InitError, Initializer, Initializers},
+    instrument::{self, Access},
+    lens::{Found, Lenses},
+    lock::{LockKind, ResourceCell},
+    plugin::{self, DebugFn, PluginRegistry, Plugins, TypeVTable},
+    published::{Published, Subscriber},
+    refs::{Ref, RefMut},
+    report::Report,
+    required::{__required, RequiredResources},
+    scoped::{ScopeGuard, ScopedCell},
+    stable_id::{StableId, StableIds},
+    storage::{Storage, StorageAllocator, TypeIdMap},
+    subset::{CloneSubset, ResourceTypes},
+    transaction::Transaction,
+    visit::ResourceVisitor,
+    weak::WeakResource,
+};
+
+#[cfg(feature = "epoch")]
+use crate::epoch::{Rcu, Snapshot};
+#[cfg(feature = "test-util")]
+use crate::failpoints::{Failpoints, InjectedFailure};
+#[cfg(feature = "fetch")]
+use crate::fetch::{CantFetch, Fetch, Prepared};
+#[cfg(feature = "abi_stable")]
+use crate::ffi::{FfiResources, FfiType};
+#[cfg(feature = "inventory")]
+use crate::registered;
+#[cfg(feature = "hot-reload")]
+use crate::reload::{ReloadError, Reloads};
+#[cfg(feature = "replication")]
+use crate::replication::Replication;
+#[cfg(feature = "schemars")]
+use crate::schema::Schemas;
+#[cfg(feature = "rhai")]
+use crate::script::{Binding, ScriptError};
+#[cfg(feature = "stream")]
+use crate::stream::{Changes, StructureChanges};
+#[cfg(feature = "test-util")]
+use crate::test_util::{Override, ResourceTuple};
+#[cfg(feature = "watchdog")]
+use crate::watchdog::LongHold;
+#[cfg(feature = "hecs")]
+use crate::world::WorldResources;
+#[cfg(feature = "blocking")]
+use crate::{backoff::Backoff, lock::Lane};
+
+/// Types that can be stored in [`Resources`], automatically implemented for all applicable.
+///
+/// With the `explicit` feature, this is instead only implemented for types
+/// that opt in via `#[derive(Resource)]`.
+///
+/// [`Resources`]: struct.Resources.html
+pub trait Resource: Downcast + ErasedResource + Send + Sync + 'static {}
+
+/// Operations on type-erased resources, implemented for all sized types.
+///
+/// This lets type-erased resources be moved into and out of memory owned by a [`Resources`]
+/// container, and be identified, which wouldn't be possible otherwise,
+/// since their type is only known at runtime.
+///
+/// [`Resources`]: struct.Resources.html
+#[doc(hidden)]
+pub trait ErasedResource {
+    /// Returns the compiler-provided name of the resource's type.
+    fn type_name(&self) -> &'static str;
+
+    /// Moves the resource into memory returned by `allocate` for its type and layout.
+    fn move_into(
+        self: Box<Self>,
+        allocate: &mut dyn FnMut(TypeId, Layout) -> NonNull<u8>,
+    ) -> NonNull<dyn Resource>;
+
+    /// Moves the resource into a new box.
+    ///
+    /// # Safety
+    /// The resource must not be used or dropped afterwards.
+    unsafe fn move_out(&mut self) -> Box<dyn Resource>;
+
+    /// Converts the boxed resource into a box of `Any + Send + Sync`, without relying
+    /// on trait upcasting, which would require Rust 1.86.
+    fn into_any_send_sync(self: Box<Self>) -> Box<dyn Any + Send + Sync>;
+}
+
+impl<T: Resource> ErasedResource for T {
+    fn type_name(&self) -> &'static str {
+        type_name::<T>()
+    }
+
+    fn move_into(
+        self: Box<Self>,
+        allocate: &mut dyn FnMut(TypeId, Layout) -> NonNull<u8>,
+    ) -> NonNull<dyn Resource> {
+        let memory = allocate(TypeId::of::<T>(), Layout::new::<T>()).cast::<T>();
+        unsafe { memory.as_ptr().write(*self) };
+        memory
+    }
+
+    unsafe fn move_out(&mut self) -> Box<dyn Resource> {
+        Box::new(std::ptr::read(self))
+    }
+
+    fn into_any_send_sync(self: Box<Self>) -> Box<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+#[cfg(not(feature = "explicit"))]
+impl<T> Resource for T where T: Send + Sync + 'static {}
+
+/// Used by `#[derive(Resource)]` to emit an implementation only if it's not blanket-implemented.
+#[cfg(feature = "explicit")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_resource {
+    ($($implementation:tt)*) => {
+        $($implementation)*
+    };
+}
+
+/// Used by `#[derive(Resource)]` to emit an implementation only if it's not blanket-implemented.
+#[cfg(not(feature = "explicit"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_resource {
+    ($($implementation:tt)*) => {};
+}
+
+impl_downcast!(Resource);
+
+/// A [`Resource`] container, for storing at most one resource of each specific type.
+///
+/// Internally, this is a [`HashMap`] of [`TypeId`] to boxed resources, each guarded by
+/// a reader-writer lock. None of the methods are blocking, except those explicitly named so
+/// (available with the `blocking` feature): accessing a resource in a way that would break
+/// borrow rules will return the [`InvalidBorrow`] error instead.
+///
+/// [`Resource`]: trait.Resource.html
+/// [`HashMap`]: ../hashbrown/struct.HashMap.html
+/// [`TypeId`]: https://doc.rust-lang.org/std/any/struct.TypeId.html
+/// [`InvalidBorrow`]: enum.InvalidBorrow.html
+///
+/// # Markers
+///
+/// The container is optionally parametrized by a marker type, which makes containers
+/// with different markers distinct types, so that, for example, `Resources<RenderWorld>`
+/// can't be passed where `Resources<SimWorld>` is expected. The marker has no other effect;
+/// a marked container is created with [`::default()`]:
+/// ```rust
+/// # use resources::Resources;
+/// struct RenderWorld;
+///
+/// fn render(resources: &Resources<RenderWorld>) {}
+///
+/// let resources = Resources::<RenderWorld>::default();
+/// render(&resources);
+/// ```
+///
+/// [`::default()`]: #impl-Default-for-Resources%3CM%3E
+pub struct Resources<M = ()> {
+    resources: TypeIdMap<ResourceCell>,
+    finalizers: TypeIdMap<Finalizer<M>>,
+    state_hashers: TypeIdMap<StateHasher>,
+    debug_fns: TypeIdMap<DebugFn>,
+    access_recorder: AccessRecorder,
+    stable_ids: StableIds,
+    plugins: Plugins,
+    event_updaters: TypeIdMap<fn(&mut dyn Resource)>,
+    scoped: TypeIdMap<ScopedCell>,
+    lenses: Lenses,
+    dependencies: TypeIdMap<Vec<(TypeId, &'static str)>>,
+    initializers: Initializers<M>,
+    caches: Caches,
+    #[cfg(feature = "blocking")]
+    backoff: Backoff,
+    #[cfg(feature = "epoch")]
+    rcus: TypeIdMap<Box<dyn Any + Send + Sync>>,
+    #[cfg(feature = "rhai")]
+    scripted: HashMap<String, Binding<M>>,
+    #[cfg(feature = "abi_stable")]
+    ffi_types: TypeIdMap<FfiType<M>>,
+    #[cfg(feature = "replication")]
+    replication: Replication,
+    #[cfg(feature = "hot-reload")]
+    reloads: Reloads,
+    #[cfg(feature = "test-util")]
+    failpoints: Failpoints,
+    #[cfg(feature = "schemars")]
+    schemas: Schemas,
+    storage: Storage,
+    marker: PhantomData<fn() -> M>,
+}
+
+/// Hook run on a removed resource of a specific type, see [`Resources::set_finalizer()`].
+type Finalizer<M> = Box<dyn FnMut(&mut dyn Resource, &mut Resources<M>) + Send + Sync>;
+
+/// Stable hash function of resources of a specific type, see [`Resources::set_state_hasher()`].
+type StateHasher = Box<dyn Fn(&dyn Resource) -> u64 + Send + Sync>;
+
+/// Returns indices of the cells in the order their locks have to be acquired in.
+fn acquisition_order(cells: &[&ResourceCell]) -> Vec<usize> {
+    #[allow(unused_mut)]
+    let mut order = (0..cells.len()).collect::<Vec<_>>();
+    #[cfg(feature = "lock-order")]
+    order.sort_by_key(|index| cells[*index].lock_order());
+    order
+}
+
+impl<M> Default for Resources<M> {
+    fn default() -> Self {
+        Self {
+            resources: Default::default(),
+            finalizers: Default::default(),
+            state_hashers: Default::default(),
+            debug_fns: Default::default(),
+            access_recorder: Default::default(),
+            stable_ids: Default::default(),
+            plugins: Default::default(),
+            event_updaters: Default::default(),
+            scoped: Default::default(),
+            lenses: Default::default(),
+            dependencies: Default::default(),
+            initializers: Default::default(),
+            caches: Default::default(),
+            #[cfg(feature = "blocking")]
+            backoff: Backoff::default(),
+            #[cfg(feature = "epoch")]
+            rcus: Default::default(),
+            #[cfg(feature = "rhai")]
+            scripted: Default::default(),
+            #[cfg(feature = "abi_stable")]
+            ffi_types: Default::default(),
+            #[cfg(feature = "replication")]
+            replication: Default::default(),
+            #[cfg(feature = "hot-reload")]
+            reloads: Default::default(),
+            #[cfg(feature = "test-util")]
+            failpoints: Default::default(),
+            #[cfg(feature = "schemars")]
+            schemas: Default::default(),
+            storage: Default::default(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl Resources {
+    /// Creates an empty container. Functionally identical to [`::default()`].
+    ///
+    /// [`::default()`]: #impl-Default-for-Resources%3CM%3E
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a container holding default values of all resource types
+    /// registered with [`register_default!`].
+    ///
+    /// [`register_default!`]: macro.register_default.html
+    #[cfg(feature = "inventory")]
+    pub fn with_registered() -> Self {
+        let mut resources = Self::default();
+        resources.insert_registered();
+        resources
+    }
+
+    /// Creates a container holding the resources of the tuple, such as `(A, B, C)`,
+    /// as a fixture for tests.
+    #[cfg(feature = "test-util")]
+    pub fn test_with(resources: impl ResourceTuple) -> Self {
+        let mut container = Self::default();
+        resources.insert_into(&mut container);
+        container
+    }
+
+    /// Creates an empty container that allocates resources, and the tables it keeps
+    /// per resource type, with the given allocator.
+    ///
+    /// The allocator implements the [`Allocator`] trait from the `allocator-api2` crate,
+    /// which is the same as the standard one when its `nightly` feature is enabled.
+    ///
+    /// The rest of the container's memory comes from the global allocator:
+    /// - memory of resources allocated with the `arena` feature,
+    /// - values stored in the tables, such as boxed finalizers and state hashers,
+    ///   and lists of dependencies,
+    /// - state kept by stable identifiers, plugins, lenses and aliases, initializers,
+    ///   caches and the access recorder,
+    /// - bookkeeping of locks with some features, such as wakers of streams of changes,
+    ///   queues of asynchronous accesses, and sites where guards were acquired,
+    /// - with their features, lock orders, subscribers to structural changes, thread-local
+    ///   caches, injected failures, replication, hot reloading, schemas and scripting bindings.
+    ///
+    /// [`Allocator`]: ../allocator_api2/alloc/trait.Allocator.html
+    #[cfg(feature = "allocator-api")]
+    pub fn new_in(allocator: impl Allocator + Send + Sync + 'static) -> Self {
+        let allocator = StorageAllocator::new(allocator);
+        Self {
+            resources: TypeIdMap::with_hasher_in(Default::default(), allocator.clone()),
+            finalizers: TypeIdMap::with_hasher_in(Default::default(), allocator.clone()),
+            state_hashers: TypeIdMap::with_hasher_in(Default::default(), allocator.clone()),
+            debug_fns: TypeIdMap::with_hasher_in(Default::default(), allocator.clone()),
+            access_recorder: Default::default(),
+            stable_ids: Default::default(),
+            plugins: Default::default(),
+            event_updaters: TypeIdMap::with_hasher_in(Default::default(), allocator.clone()),
+            scoped: TypeIdMap::with_hasher_in(Default::default(), allocator.clone()),
+            lenses: Default::default(),
+            dependencies: TypeIdMap::with_hasher_in(Default::default(), allocator.clone()),
+            initializers: Default::default(),
+            caches: Default::default(),
+            #[cfg(feature = "blocking")]
+            backoff: Backoff::default(),
+            #[cfg(feature = "epoch")]
+            rcus: TypeIdMap::with_hasher_in(Default::default(), allocator.clone()),
+            #[cfg(feature = "rhai")]
+            scripted: Default::default(),
+            #[cfg(feature = "abi_stable")]
+            ffi_types: TypeIdMap::with_hasher_in(Default::default(), allocator.clone()),
+            #[cfg(feature = "replication")]
+            replication: Default::default(),
+            #[cfg(feature = "hot-reload")]
+            reloads: Default::default(),
+            #[cfg(feature = "test-util")]
+            failpoints: Default::default(),
+            #[cfg(feature = "schemars")]
+            schemas: Default::default(),
+            storage: Storage::new_in(allocator),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<M> Resources<M> {
+    /// Returns the resource's cell for the given access, recording it if enabled.
+    #[track_caller]
+    fn cell_opt<T: Resource>(&self, access: Access) -> Option<&ResourceCell> {
+        let cell = self.resources.get(&TypeId::of::<T>())?;
+        self.record_access(TypeId::of::<T>(), cell, access);
+        Some(cell)
+    }
+
+    /// Returns the error the access of the resource of the type fails with,
+    /// if a failure was injected into it.
+    #[cfg(feature = "test-util")]
+    fn injected_failure(
+        &self,
+        type_id: TypeId,
+        type_name: &'static str,
+        access: Access,
+    ) -> Option<CantGetResource> {
+        let failure = self.failpoints.check(type_id)?;
+        Some(match failure.into_invalid_borrow(type_name, access) {
+            Some(error) => error.into(),
+            None => NoSuchResource { type_name }.into(),
+        })
+    }
+
+    /// Finds the cell of the stored resource of type `T`, or of the resource its lens
+    /// projects, recording the access, unless a failure was injected into it.
+    #[track_caller]
+    fn find<T: Resource>(&self, access: Access) -> Result<Found<'_, T>, CantGetResource> {
+        #[cfg(feature = "test-util")]
+        if let Some(error) = self.injected_failure(TypeId::of::<T>(), type_name::<T>(), access) {
+            return Err(error);
+        }
+        Ok(self.lookup(access)?)
+    }
+
+    /// Like `find()`, but for accessors that wait out conflicting borrows instead of failing,
+    /// which only fail with injected `NoSuchResource` errors.
+    #[cfg(any(feature = "async", feature = "tokio"))]
+    #[track_caller]
+    fn find_waiting<T: Resource>(&self, access: Access) -> Result<Found<'_, T>, NoSuchResource> {
+        #[cfg(feature = "test-util")]
+        if let Some(CantGetResource::NoSuchResource(error)) =
+            self.injected_failure(TypeId::of::<T>(), type_name::<T>(), access)
+        {
+            return Err(error);
+        }
+        self.lookup(access)
+    }
+
+    #[track_caller]
+    fn lookup<T: Resource>(&self, access: Access) -> Result<Found<'_, T>, NoSuchResource> {
+        match self.cell_opt::<T>(access) {
+            Some(cell) => Ok(Found::Stored(cell)),
+            None => self.find_lensed(access),
+        }
+    }
+
+    /// Like `find()`, but looks the stored resource up via its precomputed hash.
+    #[track_caller]
+    fn find_prehashed<T: Resource>(
+        &self,
+        key: &PreHashed<T>,
+        access: Access,
+    ) -> Result<Found<'_, T>, CantGetResource> {
+        #[cfg(feature = "test-util")]
+        if let Some(error) = self.injected_failure(TypeId::of::<T>(), type_name::<T>(), access) {
+            return Err(error);
+        }
+        match self.resources.get(key) {
+            Some(cell) => {
+                self.record_access(TypeId::of::<T>(), cell, access);
+                Ok(Found::Stored(cell))
+            }
+            None => Ok(self.find_lensed(access)?),
+        }
+    }
+
+    /// Finds the cell of the resource the lens registered for the type projects.
+    #[track_caller]
+    fn find_lensed<T: Resource>(&self, access: Access) -> Result<Found<'_, T>, NoSuchResource> {
+        let lensed = self.lenses.get::<T>().and_then(|lens| {
+            let cell = self.resources.get(&lens.parent())?;
+            self.record_access(lens.parent(), cell, access);
+            Some(Found::Lensed(lens, cell))
+        });
+        lensed.ok_or(NoSuchResource {
+            type_name: type_name::<T>(),
+        })
+    }
+
+    /// Borrows the stored resource of type `T`, or the field projected by its lens,
+    /// labeling the guard.
+    #[track_caller]
+    fn borrow_opt<T: Resource>(
+        &self,
+        label: Option<&'static str>,
+    ) -> Option<Result<Ref<'_, T>, InvalidBorrow>> {
+        let found = match self.find::<T>(Access::Shared) {
+            Ok(found) => found,
+            Err(CantGetResource::InvalidBorrow(error)) => return Some(Err(error)),
+            Err(CantGetResource::NoSuchResource(_)) => return None,
+        };
+        Some(Ref::from_cell_labeled(found.cell(), label).map(|resource| found.project(resource)))
+    }
+
+    /// Borrows the stored resource of type `T`, or the field projected by its lens, mutably,
+    /// labeling the guard.
+    #[track_caller]
+    fn borrow_mut_opt<T: Resource>(
+        &self,
+        label: Option<&'static str>,
+    ) -> Option<Result<RefMut<'_, T>, InvalidBorrow>> {
+        let found = match self.find::<T>(Access::Exclusive) {
+            Ok(found) => found,
+            Err(CantGetResource::InvalidBorrow(error)) => return Some(Err(error)),
+            Err(CantGetResource::NoSuchResource(_)) => return None,
+        };
+        Some(
+            RefMut::from_cell_labeled(found.cell(), label)
+                .map(|resource| found.project_mut(resource)),
+        )
+    }
+
+    /// Finds the cell of the stored resource of type `T`, or of the resource its lens
+    /// projects, without recording an access, for later use via `access_prepared()`.
+    #[cfg(feature = "fetch")]
+    pub(crate) fn prepare_found<T: Resource>(&self) -> Result<Found<'_, T>, NoSuchResource> {
+        let found = match self.resources.get(&TypeId::of::<T>()) {
+            Some(cell) => Some(Found::Stored(cell)),
+            None => self.lenses.get::<T>().and_then(|lens| {
+                let cell = self.resources.get(&lens.parent())?;
+                Some(Found::Lensed(lens, cell))
+            }),
+        };
+        found.ok_or(NoSuchResource {
+            type_name: type_name::<T>(),
+        })
+    }
+
+    /// Records an access to the cell found by `prepare_found()`, like `find()` does,
+    /// unless a failure was injected into it.
+    #[cfg(feature = "fetch")]
+    #[track_caller]
+    pub(crate) fn access_prepared<T: Resource>(
+        &self,
+        found: &Found<'_, T>,
+        access: Access,
+    ) -> Result<(), CantGetResource> {
+        #[cfg(feature = "test-util")]
+        if let Some(error) = self.injected_failure(TypeId::of::<T>(), type_name::<T>(), access) {
+            return Err(error);
+        }
+        let type_id = match found {
+            Found::Stored(_) => TypeId::of::<T>(),
+            Found::Lensed(lens, _) => lens.parent(),
+        };
+        self.record_access(type_id, found.cell(), access);
+        Ok(())
+    }
+
+    /// Records an access to the resource's cell, if enabled, and to its cache, if it's one.
+    #[track_caller]
+    pub(crate) fn record_access(&self, type_id: TypeId, cell: &ResourceCell, access: Access) {
+        self.access_recorder.record(cell.type_name(), access);
+        self.caches.touch(type_id);
+    }
+
+    /// Returns `true` if a resource of type `T` exists in the container.
+    pub fn contains<T: Resource>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Checks that resources of all types required by `R` are present in the container,
+    /// either stored or projected by a [lens], returning names of all missing types otherwise.
+    ///
+    /// This is meant to be called once, for example during startup, instead of discovering
+    /// missing resources one at a time when they're first accessed.
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
+    /// # use resources::Resources;
+    /// let mut resources = Resources::new();
+    /// resources.insert(1u32);
+    /// let missing = resources.validate::<(u32, f32, bool)>().unwrap_err();
+    /// assert_eq!(missing.type_names, ["f32", "bool"]);
+    /// # }
+    /// ```
+    ///
+    /// [lens]: #method.register_lens
+    pub fn validate<R: RequiredResources>(&self) -> Result<(), MissingResources> {
+        let mut required = Vec::new();
+        R::required(&mut required);
+        self.check_present(&required)
+    }
+
+    /// Checks that resources of all given types are present, either stored or projected by a lens.
+    fn check_present(&self, required: &[(TypeId, &'static str)]) -> Result<(), MissingResources> {
+        let type_names = required
+            .iter()
+            .filter(|(type_id, _)| !self.is_present(*type_id))
+            .map(|(_, type_name)| *type_name)
+            .collect::<Vec<_>>();
+        if type_names.is_empty() {
+            Ok(())
+        } else {
+            Err(MissingResources { type_names })
+        }
+    }
+
+    /// Returns `true` if a resource of the type is present, either stored or projected by a lens.
+    fn is_present(&self, type_id: TypeId) -> bool {
+        self.resources.contains_key(&type_id)
+            || self
+                .lenses
+                .parent_of(type_id)
+                .is_some_and(|parent| self.resources.contains_key(&parent))
+    }
+
+    /// Returns the type of the resource borrowed when borrowing one of the type:
+    /// the type itself if it's stored, or the parent of its lens if one is registered.
+    pub(crate) fn resolve_lens(&self, type_id: TypeId) -> TypeId {
+        if self.resources.contains_key(&type_id) {
+            return type_id;
+        }
+        self.lenses.parent_of(type_id).unwrap_or(type_id)
+    }
+
+    /// Seals the container, forbidding further structural changes of it: until [`unseal`]
+    /// is called, [`checked_insert`] returns a [`Sealed`] error instead of inserting
+    /// a resource of a type not present in it, and [`checked_remove`] instead of removing
+    /// a resource, as do [`Transaction::insert`] and [`Transaction::remove`].
+    ///
+    /// Other methods that insert or remove resources, such as [`insert`], [`remove`],
+    /// the entry API, `extend()` and [`move_to`], panic instead, and [`init_all`] returns
+    /// an error without running any initializers.
+    /// Resources can still be accessed and replaced, [`resource_scope`] can still be used,
+    /// since it puts the resource back, and [transient resources] are still removed
+    /// by [`maintain`], as are all resources by [`clear`]. This is meant to be called
+    /// once startup is done, when changing the structure of the container is a bug.
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
+    /// # use resources::Resources;
+    /// let mut resources = Resources::new();
+    /// resources.insert(1u32);
+    /// resources.seal();
+    /// assert_eq!(resources.checked_insert(2u32).unwrap(), Some(1));
+    /// assert!(resources.checked_insert(1.0f32).is_err());
+    /// assert!(resources.checked_remove::<u32>().is_err());
+    /// assert_eq!(resources.insert(3u32), Some(2));
+    /// # }
+    /// ```
+    ///
+    /// [`unseal`]: #method.unseal
+    /// [`checked_insert`]: #method.checked_insert
+    /// [`checked_remove`]: #method.checked_remove
+    /// [`Sealed`]: struct.Sealed.html
+    /// [`Transaction::insert`]: struct.Transaction.html#method.insert
+    /// [`Transaction::remove`]: struct.Transaction.html#method.remove
+    /// [`insert`]: #method.insert
+    /// [`remove`]: #method.remove
+    /// [`move_to`]: #method.move_to
+    /// [`init_all`]: #method.init_all
+    /// [`resource_scope`]: #method.resource_scope
+    /// [transient resources]: #method.insert_transient
+    /// [`maintain`]: #method.maintain
+    /// [`clear`]: #method.clear
+    pub fn seal(&mut self) {
+        self.storage.set_sealed(true);
+    }
+
+    /// Allows structural changes of the container again after a call to [`seal`].
+    ///
+    /// [`seal`]: #method.seal
+    pub fn unseal(&mut self) {
+        self.storage.set_sealed(false);
+    }
+
+    /// Panics if the container is sealed and holds a resource of the type.
+    #[track_caller]
+    fn assert_removable(&self, type_id: TypeId) {
+        if let Some(cell) = self.resources.get(&type_id) {
+            self.storage.assert_unsealed(cell.type_name());
+        }
+    }
+
+    /// Returns `true` if the container is [sealed].
+    ///
+    /// [sealed]: #method.seal
+    pub fn is_sealed(&self) -> bool {
+        self.storage.is_sealed()
+    }
+
+    /// Declares that resources of type `T` depend on resources of type `U`, so that inserting
+    /// a `T` when none is present via [`try_insert_dependent`] fails unless a `U` is present
+    /// in the container, and [`init_all`] runs initializers of `U` before the one of `T`.
+    ///
+    /// This catches initialization order bugs, such as inserting a cache before the resource
+    /// it caches, where they happen. Other methods that insert a `T` when none is present,
+    /// such as [`insert`], the entry API and `extend()`, panic in debug builds instead.
+    /// Replacing or removing resources doesn't check dependencies.
+    ///
+    /// [`try_insert_dependent`]: #method.try_insert_dependent
+    /// [`init_all`]: #method.init_all
+    /// [`insert`]: #method.insert
+    pub fn add_dependency<T: Resource, U: Resource>(&mut self) {
+        let dependencies = self.dependencies.entry(TypeId::of::<T>()).or_default();
+        if !dependencies
+            .iter()
+            .any(|(type_id, _)| *type_id == TypeId::of::<U>())
+        {
+            dependencies.push(__required::<U>());
+        }
+    }
+
+    /// Checks that resources of all types that resources of type `T` [depend on]
+    /// are present in the container, returning names of all missing types otherwise.
+    ///
+    /// [depend on]: #method.add_dependency
+    pub fn check_dependencies<T: Resource>(&self) -> Result<(), MissingResources> {
+        match self.dependencies.get(&TypeId::of::<T>()) {
+            Some(dependencies) => self.check_present(dependencies),
+            None => Ok(()),
+        }
+    }
+
+    /// Registers a constructor of the resource of type `T`, to be run by [`init_all`] once
+    /// resources of all types required by `D` are present, replacing the previously
+    /// registered one.
+    ///
+    /// The constructor receives the container, for accessing the resources it depends on.
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
+    /// # use resources::Resources;
+    /// let mut resources = Resources::new();
+    /// resources.add_initializer::<String, (u32,)>(|resources| {
+    ///     format!("{} entries", *resources.get::<u32>().unwrap())
+    /// });
+    /// resources.add_initializer::<u32, ()>(|_| 3);
+    /// resources.init_all().unwrap();
+    /// assert_eq!(*resources.get::<String>().unwrap(), "3 entries");
+    /// # }
+    /// ```
+    ///
+    /// [`init_all`]: #method.init_all
+    pub fn add_initializer<T: Resource, D: RequiredResources>(
+        &mut self,
+        init: impl FnOnce(&Self) -> T + Send + Sync + 'static,
+    ) where
+        M: 'static,
+    {
+        let mut dependencies = Vec::new();
+        D::required(&mut dependencies);
+        self.initializers.add(Initializer::new(
+            TypeId::of::<T>(),
+            type_name::<T>(),
+            dependencies,
+            Box::new(move |resources| Box::new(init(resources))),
+        ));
+    }
+
+    /// Runs all constructors registered via [`add_initializer`], inserting the resources
+    /// they return, in an order such that each runs after the ones it depends on.
+    ///
+    /// Constructors of resources already present are skipped, and all are discarded afterwards.
+    /// If their dependencies form a cycle, or resources they depend on are neither present
+    /// nor constructed, returns an error without running any, keeping them registered.
+    /// If a constructed resource would exceed the [memory budget], returns an error without
+    /// inserting it, keeping constructors that haven't run yet registered. If the container
+    /// is [sealed] and any constructors would run, returns an error without running any.
+    ///
+    /// [`add_initializer`]: #method.add_initializer
+    /// [memory budget]: #method.set_memory_budget
+    /// [sealed]: #method.seal
+    pub fn init_all(&mut self) -> Result<(), InitError> {
+        let mut initializers = mem::take(&mut self.initializers);
+        initializers.add_dependencies(&self.dependencies);
+        let ordered = initializers.take_ordered(|type_id| self.is_present(type_id));
+        self.initializers = initializers;
+        let mut ordered = ordered?.into_iter();
+        if self.storage.is_sealed() {
+            if let Some(first) = ordered.next() {
+                let type_name = first.type_name();
+                self.initializers.add(first);
+                ordered.for_each(|initializer| self.initializers.add(initializer));
+                return Err(InitError::Sealed(Sealed {
+                    resource: (),
+                    type_name,
+                }));
+            }
+        }
+        while let Some(initializer) = ordered.next() {
+            let resource = initializer.run(self);
+            if let Err(error) = self.check_budget(&*resource) {
+                ordered.for_each(|initializer| self.initializers.add(initializer));
+                return Err(InitError::Budget(error));
+            }
+            self.extend(Some(resource));
+        }
+        Ok(())
+    }
+
+    /// Returns an error if inserting the resource would exceed the memory budget.
+    fn check_budget(&self, resource: &dyn Resource) -> Result<(), BudgetExceeded> {
+        if self.resources.contains_key(&resource.as_any().type_id()) {
+            return Ok(());
+        }
+        self.storage.check_budget(
+            resource.type_name(),
+            size_of_val(resource),
+            self.storage.memory_usage(),
+        )
+    }
+
+    /// Returns the hasher the container uses for `TypeId`s of resources.
+    ///
+    /// Hashes it produces are the same as the ones precomputed by [`PreHashed`],
+    /// so frameworks can use it to key their own maps of resource types consistently.
+    ///
+    /// [`PreHashed`]: struct.PreHashed.html
+    pub fn hasher(&self) -> &impl BuildHasher {
+        self.resources.hasher()
+    }
+
+    /// Returns `true` if a resource of type `T` exists in the container,
+    /// looking it up via its precomputed hash.
+    pub fn contains_prehashed<T: Resource>(&self, key: &PreHashed<T>) -> bool {
+        self.resources.contains_key(key)
+    }
+
+    /// Returns a reference to the stored resource of type `T`,
+    /// looking it up via its precomputed hash.
+    ///
+    /// Otherwise behaves like [`get`].
+    ///
+    /// [`get`]: #method.get
+    #[track_caller]
+    pub fn get_prehashed<T: Resource>(
+        &self,
+        key: &PreHashed<T>,
+    ) -> Result<Ref<'_, T>, CantGetResource> {
+        let found = self.find_prehashed(key, Access::Shared)?;
+        Ok(found.project(Ref::from_cell(found.cell())?))
+    }
+
+    /// Returns a mutable reference to the stored resource of type `T`,
+    /// looking it up via its precomputed hash.
+    ///
+    /// Otherwise behaves like [`get_mut`].
+    ///
+    /// [`get_mut`]: #method.get_mut
+    #[track_caller]
+    pub fn get_mut_prehashed<T: Resource>(
+        &self,
+        key: &PreHashed<T>,
+    ) -> Result<RefMut<'_, T>, CantGetResource> {
+        let found = self.find_prehashed(key, Access::Exclusive)?;
+        Ok(found.project_mut(RefMut::from_cell(found.cell())?))
+    }
+
+    /// Returns an iterator over `TypeId`s of all resources in the container, in arbitrary order.
+    ///
+    /// This doesn't access the resources, and so never conflicts with references to them.
+    pub fn type_ids(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.resources.keys().copied()
+    }
+
+    /// Returns an iterator over compiler-provided names of types of all resources
+    /// in the container, in arbitrary order.
+    ///
+    /// The names are recorded when the resources are inserted, and are meant for diagnostics
+    /// only: they're not guaranteed to be unique, or stable between compiler versions.
+    pub fn type_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.resources.values().map(ResourceCell::type_name)
+    }
+
+    /// Returns the compiler-provided name of the type with given `TypeId`,
+    /// if a resource of this type exists in the container.
+    pub fn type_name_of(&self, type_id: TypeId) -> Option<&'static str> {
+        self.resources.get(&type_id).map(ResourceCell::type_name)
+    }
+
+    /// Registers the stable identifier of the resource type `T`, such as its fully-qualified
+    /// name or UUID, replacing previous registrations of either the type or the identifier.
+    ///
+    /// Unlike `TypeId`s, stable identifiers stay the same across dynamic libraries and
+    /// compiler versions, so resources can be looked up by them in hot-reloaded plugins,
+    /// or matched up with ones serialized by a different build.
+    pub fn register_stable_id<T: Resource>(&mut self, id: impl Into<StableId>) {
+        self.stable_ids.register(id.into(), TypeId::of::<T>());
+    }
+
+    /// Returns the stable identifier registered for the resource type `T`, if any.
+    pub fn stable_id<T: Resource>(&self) -> Option<&StableId> {
+        self.stable_ids.stable_id(TypeId::of::<T>())
+    }
+
+    /// Returns the stable identifier registered for the type with given `TypeId`, if any.
+    pub fn stable_id_of(&self, type_id: TypeId) -> Option<&StableId> {
+        self.stable_ids.stable_id(type_id)
+    }
+
+    /// Returns the `TypeId`, in this build, of the type registered with the stable identifier.
+    pub fn type_id_of(&self, id: &StableId) -> Option<TypeId> {
+        self.stable_ids.type_id(id)
+    }
+
+    /// Returns `true` if a resource of the type registered with the stable identifier
+    /// exists in the container.
+    pub fn contains_stable(&self, id: &StableId) -> bool {
+        self.type_id_of(id)
+            .is_some_and(|type_id| self.resources.contains_key(&type_id))
+    }
+
+    /// Returns a type-erased reference to the stored resource of the type registered with
+    /// the stable identifier, or `None` if it's not registered or not present in the container.
+    ///
+    /// If the resource is currently accessed mutably elsewhere, returns an error, same as [`get`].
+    ///
+    /// [`get`]: #method.get
+    #[track_caller]
+    pub fn get_stable(
+        &self,
+        id: &StableId,
+    ) -> Result<Option<Ref<'_, dyn Resource>>, InvalidBorrow> {
+        let type_id = match self.type_id_of(id) {
+            Some(type_id) => type_id,
+            None => return Ok(None),
+        };
+        let cell = match self.resources.get(&type_id) {
+            Some(cell) => cell,
+            None => return Ok(None),
+        };
+        #[cfg(feature = "test-util")]
+        match self.injected_failure(type_id, cell.type_name(), Access::Shared) {
+            Some(CantGetResource::InvalidBorrow(error)) => return Err(error),
+            Some(CantGetResource::NoSuchResource(_)) => return Ok(None),
+            None => {}
+        }
+        self.record_access(type_id, cell, Access::Shared);
+        Ref::<dyn Resource>::from_cell(cell).map(Some)
+    }
+
+    /// Returns type-erased references to the stored resources of the types with the given
+    /// `TypeId`s, in the same order, for systems that determine their access at runtime.
+    ///
+    /// If any of these resources is not present in the container, or is already borrowed
+    /// mutably, returns an error, and none stay borrowed.
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
+    /// # use resources::Resources;
+    /// # use std::any::TypeId;
+    /// let mut resources = Resources::new();
+    /// resources.insert(1u32);
+    /// resources.insert(2u64);
+    /// let refs = resources
+    ///     .get_many_dyn(&[TypeId::of::<u64>(), TypeId::of::<u32>()])
+    ///     .unwrap();
+    /// assert_eq!(refs[0].downcast_ref::<u64>(), Some(&2));
+    /// # }
+    /// ```
+    pub fn get_many_dyn(
+        &self,
+        type_ids: &[TypeId],
+    ) -> Result<Vec<Ref<'_, dyn Resource>>, CantGetDyn> {
+        let cells = self.cells_dyn(type_ids, Access::Shared)?;
+        let mut refs = cells.iter().map(|_| None).collect::<Vec<_>>();
+        for index in acquisition_order(&cells) {
+            refs[index] = Some(Ref::<dyn Resource>::from_cell(cells[index])?);
+        }
+        Ok(refs.into_iter().flatten().collect())
+    }
+
+    /// Returns type-erased mutable references to the stored resources of the types
+    /// with the given `TypeId`s, in the same order, like [`get_many_dyn`].
+    ///
+    /// If any of the types is given more than once, returns an error.
+    ///
+    /// [`get_many_dyn`]: #method.get_many_dyn
+    pub fn get_many_dyn_mut(
+        &self,
+        type_ids: &[TypeId],
+    ) -> Result<Vec<RefMut<'_, dyn Resource>>, CantGetDyn> {
+        // Checked before looking the resources up, so that a failing call records no accesses.
+        for (index, type_id) in type_ids.iter().enumerate() {
+            if type_ids[..index].contains(type_id) {
+                if let Some(cell) = self.resources.get(type_id) {
+                    return Err(CantGetDyn::Duplicate {
+                        type_name: cell.type_name(),
+                    });
+                }
+            }
+        }
+        let cells = self.cells_dyn(type_ids, Access::Exclusive)?;
+        let mut refs = cells.iter().map(|_| None).collect::<Vec<_>>();
+        for index in acquisition_order(&cells) {
+            refs[index] = Some(RefMut::<dyn Resource>::from_cell(cells[index])?);
+        }
+        Ok(refs.into_iter().flatten().collect())
+    }
+
+    /// Returns cells of resources of all given types, recording accesses to them.
+    fn cells_dyn(
+        &self,
+        type_ids: &[TypeId],
+        access: Access,
+    ) -> Result<Vec<&ResourceCell>, CantGetDyn> {
+        type_ids
+            .iter()
+            .map(|type_id| {
+                let cell = self
+                    .resources
+                    .get(type_id)
+                    .ok_or(CantGetDyn::NoSuchResource(*type_id))?;
+                #[cfg(feature = "test-util")]
+                match self.injected_failure(*type_id, cell.type_name(), access) {
+                    Some(CantGetResource::InvalidBorrow(error)) => return Err(error.into()),
+                    Some(CantGetResource::NoSuchResource(_)) => {
+                        return Err(CantGetDyn::NoSuchResource(*type_id))
+                    }
+                    None => {}
+                }
+                self.record_access(*type_id, cell, access);
+                Ok(cell)
+            })
+            .collect()
+    }
+
+    /// Removes the resource of the type registered with the stable identifier
+    /// from the container, running its [finalizer].
+    ///
+    /// If the resource was present in the container, it will be returned boxed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the container is [sealed] and such a resource is present.
+    ///
+    /// [finalizer]: #method.set_finalizer
+    /// [sealed]: #method.seal
+    #[track_caller]
+    pub fn remove_stable(&mut self, id: &StableId) -> Option<Box<dyn Resource>> {
+        let type_id = self.type_id_of(id)?;
+        self.assert_removable(type_id);
+        let mut resource = self.take_boxed(type_id)?;
+        self.finalize(type_id, &mut *resource);
+        Some(resource)
+    }
+
+    pub(crate) fn stable_ids_mut(&mut self) -> &mut StableIds {
+        &mut self.stable_ids
+    }
+
+    pub(crate) fn plugins_mut(&mut self) -> &mut Plugins {
+        &mut self.plugins
+    }
+
+    /// Loads the plugin with the given name, passing a [`PluginRegistry`] to the closure
+    /// for registering tables of the plugin's resource types and inserting its resources.
+    ///
+    /// If a plugin with this name is already loaded, it's [unloaded] first.
+    ///
+    /// [`PluginRegistry`]: struct.PluginRegistry.html
+    /// [unloaded]: #method.unload_plugin
+    pub fn load_plugin(
+        &mut self,
+        name: impl Into<String>,
+        register: impl FnOnce(&mut PluginRegistry<'_, M>),
+    ) {
+        let name = name.into();
+        self.unload_plugin(&name);
+        register(&mut PluginRegistry::new(self, name));
+    }
+
+    /// Unloads the plugin with the given name, returning `false` if it wasn't loaded.
+    ///
+    /// Resources of the types it registered are removed, running their [finalizers],
+    /// and every hook set for these types is dropped along with their tables
+    /// and stable identifiers, so that no code of the plugin is called afterwards.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the container is [sealed] and holds resources of these types.
+    ///
+    /// [finalizers]: #method.set_finalizer
+    /// [sealed]: #method.seal
+    #[track_caller]
+    pub fn unload_plugin(&mut self, name: &str) -> bool {
+        let types = match self.plugins.types_of(name) {
+            Some(types) => types,
+            None => return false,
+        };
+        for type_id in types {
+            self.assert_removable(*type_id);
+        }
+        for type_id in self.plugins.unload(name) {
+            if let Some(mut resource) = self.take_boxed(type_id) {
+                self.finalize(type_id, &mut *resource);
+            }
+            self.finalizers.remove(&type_id);
+            self.state_hashers.remove(&type_id);
+            self.dependencies.remove(&type_id);
+            self.stable_ids.unregister(type_id);
+            #[cfg(feature = "rhai")]
+            self.scripted
+                .retain(|_, binding| binding.type_id != type_id);
+            #[cfg(feature = "abi_stable")]
+            self.ffi_types.remove(&type_id);
+            #[cfg(feature = "replication")]
+            self.replication.unregister(type_id);
+        }
+        true
+    }
+
+    /// Returns `TypeId`s of resource types registered by the plugin with the given name,
+    /// or `None` if it's not loaded.
+    pub fn plugin_types(&self, name: &str) -> Option<&[TypeId]> {
+        self.plugins.types_of(name)
+    }
+
+    /// Returns the table of the resource type with given `TypeId`, if a plugin registered one.
+    pub fn type_vtable(&self, type_id: TypeId) -> Option<&TypeVTable> {
+        self.plugins.vtable(type_id)
+    }
+
+    /// Registers the resource type `T` for replication via [`write_delta`] and [`apply_delta`],
+    /// under the given identifier, which is also registered as its [stable identifier].
+    ///
+    /// [`write_delta`]: #method.write_delta
+    /// [`apply_delta`]: #method.apply_delta
+    /// [stable identifier]: #method.register_stable_id
+    #[cfg(feature = "replication")]
+    pub fn register_replicated<T>(&mut self, id: impl Into<StableId>)
+    where
+        T: Resource + serde::Serialize + serde::de::DeserializeOwned,
+    {
+        let id = id.into();
+        self.replication.register::<T>(id.to_string());
+        self.stable_ids.register(id, TypeId::of::<T>());
+    }
+
+    /// Registers the resource type `T` for describing via [`json_schema`],
+    /// under the given identifier, which is also registered as its [stable identifier].
+    ///
+    /// [`json_schema`]: #method.json_schema
+    /// [stable identifier]: #method.register_stable_id
+    #[cfg(feature = "schemars")]
+    pub fn register_schema<T>(&mut self, id: impl Into<StableId>)
+    where
+        T: Resource + serde::Serialize + schemars::JsonSchema,
+    {
+        let id = id.into();
+        self.schemas.register::<T>(id.to_string());
+        self.stable_ids.register(id, TypeId::of::<T>());
+    }
+
+    /// Returns a JSON schema of an object mapping identifiers of resource types registered
+    /// via [`register_schema`] to resources of those types, for external tools that read
+    /// or write the state of the container.
+    ///
+    /// Schemas of types that resources are made of are placed under `$defs`; the output
+    /// is the same for the same registrations, regardless of their order.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
+    /// use resources::Resources;
+    /// use schemars::JsonSchema;
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize, JsonSchema)]
+    /// struct Settings {
+    ///     volume: f32,
+    /// }
+    ///
+    /// let mut resources = Resources::new();
+    /// resources.register_schema::<Settings>("settings");
+    /// let schema = resources.json_schema();
+    /// assert!(schema.pointer("/properties/settings").is_some());
+    /// # }
+    /// ```
+    ///
+    /// [`register_schema`]: #method.register_schema
+    #[cfg(feature = "schemars")]
+    pub fn json_schema(&self) -> schemars::Schema {
+        self.schemas.generate()
+    }
+
+    /// Serializes resources of types registered via [`register_replicated`] that changed
+    /// since the previous call, for applying to another container via [`apply_delta`].
+    ///
+    /// The delta is a map from stable identifiers to resources, or to `None` for removed ones.
+    /// A resource counts as changed when it's inserted, replaced, removed, or released
+    /// by a `RefMut`, except for ones returned by [`get_mut_untracked`].
+    ///
+    /// [`register_replicated`]: #method.register_replicated
+    /// [`apply_delta`]: #method.apply_delta
+    /// [`get_mut_untracked`]: #method.get_mut_untracked
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
+    /// use resources::Resources;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    /// struct Score(u32);
+    ///
+    /// let mut server = Resources::new();
+    /// let mut client = Resources::new();
+    /// server.register_replicated::<Score>("score");
+    /// client.register_replicated::<Score>("score");
+    ///
+    /// server.insert(Score(1));
+    /// let delta = server.write_delta(serde_json::value::Serializer).unwrap();
+    /// assert_eq!(delta.to_string(), r#"{"score":1}"#);
+    /// client.apply_delta(delta).unwrap();
+    /// assert_eq!(*client.get::<Score>().unwrap(), Score(1));
+    ///
+    /// // Nothing changed since.
+    /// assert_eq!(server.write_delta(serde_json::value::Serializer).unwrap().to_string(), "{}");
+    /// # }
+    /// ```
+    #[cfg(feature = "replication")]
+    pub fn write_delta<S: serde::Serializer>(&mut self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut changed = Vec::new();
+        for type_id in self.replication.type_ids() {
+            let cell = self.resources.get(&type_id);
+            let restructured = self.storage.take_restructured(type_id);
+            let writes = cell.map(|cell| cell.lock.writes());
+            if self.replication.mark_sent(type_id, writes, restructured) {
+                // The container is borrowed mutably, so nothing else can access the resource.
+                changed.push((type_id, cell.map(|cell| unsafe { &*cell.resource_ptr() })));
+            }
+        }
+        self.replication.serialize(serializer, &changed)
+    }
+
+    /// Inserts, replaces, and removes resources as described by the delta
+    /// written by [`write_delta`] of another container.
+    ///
+    /// Changes applied this way aren't included in deltas written by this container.
+    /// Removed resources are passed to their [finalizers]. Fails if the delta includes
+    /// a resource of a type that wasn't registered via [`register_replicated`], or lists
+    /// a resource more than once, or if applying it would exceed the [memory budget],
+    /// or insert or remove a resource of a [sealed] container, applying nothing in that case.
+    ///
+    /// [`write_delta`]: #method.write_delta
+    /// [finalizers]: #method.set_finalizer
+    /// [`register_replicated`]: #method.register_replicated
+    /// [memory budget]: #method.set_memory_budget
+    /// [sealed]: #method.seal
+    #[cfg(feature = "replication")]
+    pub fn apply_delta<'de, D: serde::Deserializer<'de>>(
+        &mut self,
+        deserializer: D,
+    ) -> Result<(), D::Error> {
+        let delta = self.replication.deserialize(deserializer)?;
+        self.apply_replicated(delta)
+    }
+
+    /// Returns the version written to snapshots, see [`set_snapshot_version`].
+    ///
+    /// [`set_snapshot_version`]: #method.set_snapshot_version
+    #[cfg(feature = "replication")]
+    pub fn snapshot_version(&self) -> u32 {
+        self.replication.version()
+    }
+
+    /// Sets the version written to snapshots by [`write_snapshot`], `0` by default,
+    /// which should be incremented whenever a replicated resource type changes in a way
+    /// that breaks deserialization of earlier snapshots.
+    ///
+    /// Snapshots of earlier versions are brought up to date by [migrations] when loaded.
+    ///
+    /// [`write_snapshot`]: #method.write_snapshot
+    /// [migrations]: #method.add_migration
+    #[cfg(feature = "replication")]
+    pub fn set_snapshot_version(&mut self, version: u32) {
+        self.replication.set_version(version);
+    }
+
+    /// Registers a migration of the resource with the given [stable identifier] in snapshots
+    /// of the given version, from the type `Old` it had in them to the type `New`
+    /// it has in the next version, replacing a previously registered one.
+    ///
+    /// When a snapshot of an earlier version than the current one is loaded via
+    /// [`load_snapshot`], each resource that has migrations from that version or later
+    /// is deserialized as the `Old` type of the first one, and passed through all of them
+    /// in order; the last has to produce the resource's registered type. Versions in which
+    /// the resource didn't change need no migrations.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
+    /// use resources::Resources;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Deserialize)]
+    /// struct HealthV0(u32);
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// struct Health {
+    ///     current: u32,
+    ///     max: u32,
+    /// }
+    ///
+    /// let mut resources = Resources::new();
+    /// resources.register_replicated::<Health>("health");
+    /// resources.set_snapshot_version(1);
+    /// resources.add_migration("health", 0, |old: HealthV0| Health {
+    ///     current: old.0,
+    ///     max: 100,
+    /// });
+    /// let saved = r#"{"version":0,"resources":{"health":50}}"#;
+    /// resources
+    ///     .load_snapshot(&mut serde_json::Deserializer::from_str(saved))
+    ///     .unwrap();
+    /// assert_eq!(resources.get::<Health>().unwrap().max, 100);
+    /// # }
+    /// ```
+    ///
+    /// [stable identifier]: #method.register_stable_id
+    /// [`load_snapshot`]: #method.load_snapshot
+    #[cfg(feature = "replication")]
+    pub fn add_migration<Old, New>(
+        &mut self,
+        id: impl Into<StableId>,
+        from_version: u32,
+        migrate: impl Fn(Old) -> New + Send + Sync + 'static,
+    ) where
+        Old: serde::de::DeserializeOwned + Send + 'static,
+        New: Send + 'static,
+    {
+        self.replication
+            .add_migration(id.into().to_string(), from_version, migrate);
+    }
+
+    /// Serializes all resources of types registered via [`register_replicated`],
+    /// along with the [snapshot version], for restoring via [`load_snapshot`],
+    /// such as a savegame.
+    ///
+    /// The snapshot is a struct of the `version`, and of `resources` in the format of deltas
+    /// written by [`write_delta`], which isn't affected by this.
+    ///
+    /// [`register_replicated`]: #method.register_replicated
+    /// [snapshot version]: #method.set_snapshot_version
+    /// [`load_snapshot`]: #method.load_snapshot
+    /// [`write_delta`]: #method.write_delta
+    #[cfg(feature = "replication")]
+    pub fn write_snapshot<S: serde::Serializer>(
+        &mut self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let resources = self
+            .replication
+            .type_ids()
+            .into_iter()
+            .map(|type_id| {
+                let cell = self.resources.get(&type_id);
+                // The container is borrowed mutably, so nothing else can access the resource.
+                (type_id, cell.map(|cell| unsafe { &*cell.resource_ptr() }))
+            })
+            .collect::<Vec<_>>();
+        self.replication.serialize_snapshot(serializer, &resources)
+    }
+
+    /// Inserts, replaces, and removes resources as described by the snapshot written
+    /// by [`write_snapshot`], like [`apply_delta`], running [migrations] on resources
+    /// written by earlier versions.
+    ///
+    /// Fails if the snapshot is of a later version than the current one, if it includes
+    /// a resource of a type that wasn't registered via [`register_replicated`], or lists
+    /// a resource more than once, or if loading it would exceed the [memory budget],
+    /// or insert or remove a resource of a [sealed] container, applying nothing in that case.
+    ///
+    /// [`write_snapshot`]: #method.write_snapshot
+    /// [`apply_delta`]: #method.apply_delta
+    /// [migrations]: #method.add_migration
+    /// [`register_replicated`]: #method.register_replicated
+    /// [memory budget]: #method.set_memory_budget
+    /// [sealed]: #method.seal
+    #[cfg(feature = "replication")]
+    pub fn load_snapshot<'de, D: serde::Deserializer<'de>>(
+        &mut self,
+        deserializer: D,
+    ) -> Result<(), D::Error> {
+        let resources = self.replication.deserialize_snapshot(deserializer)?;
+        self.apply_replicated(resources)
+    }
+
+    /// Applies replicated changes, unless that would exceed the memory budget,
+    /// or change the structure of a sealed container.
+    #[cfg(feature = "replication")]
+    fn apply_replicated<E: serde::de::Error>(
+        &mut self,
+        resources: Vec<(TypeId, Option<Box<dyn Resource>>)>,
+    ) -> Result<(), E> {
+        // Removals and replacements free memory, so the changes are checked in order,
+        // tracking the size each type would have after the changes before it.
+        let mut memory_usage = self.storage.memory_usage();
+        let mut planned = HashMap::<TypeId, Option<usize>>::new();
+        for (type_id, resource) in &resources {
+            let present = match planned.get(type_id) {
+                Some(size) => *size,
+                None => self.resources.get(type_id).map(ResourceCell::size),
+            };
+            memory_usage -= present.unwrap_or(0);
+            if self.storage.is_sealed() && present.is_some() != resource.is_some() {
+                let type_name = match resource {
+                    Some(resource) => (**resource).type_name(),
+                    None => self.type_name_of(*type_id).unwrap_or("unknown"),
+                };
+                return Err(E::custom(Sealed {
+                    resource: (),
+                    type_name,
+                }));
+            }
+            let size = resource.as_ref().map(|resource| size_of_val(&**resource));
+            if let (Some(resource), Some(size)) = (resource, size) {
+                if present.is_none() {
+                    self.storage
+                        .check_budget((**resource).type_name(), size, memory_usage)
+                        .map_err(E::custom)?;
+                }
+                memory_usage += size;
+            }
+            planned.insert(*type_id, size);
+        }
+        for (type_id, resource) in resources {
+            match resource {
+                Some(resource) => self.extend(Some(resource)),
+                None => {
+                    if let Some(mut resource) = self.take_boxed(type_id) {
+                        self.finalize(type_id, &mut *resource);
+                    }
+                }
+            }
+            let writes = self.resources.get(&type_id).map(|cell| cell.lock.writes());
+            self.storage.take_restructured(type_id);
+            self.replication.mark_sent(type_id, writes, false);
+        }
+        Ok(())
+    }
+
+    /// Binds the resource type `T` to the file at the given path, loading the resource
+    /// from its contents via the function, such as a deserializer, and inserting it,
+    /// returning the previous one, if any.
+    ///
+    /// The resource is loaded again by [`poll_reloads`] each time the file is modified.
+    /// Fails if the file can't be read or loaded, or if inserting the resource would exceed
+    /// the [memory budget], inserting nothing in that case.
+    ///
+    /// [`poll_reloads`]: #method.poll_reloads
+    /// [memory budget]: #method.set_memory_budget
+    #[cfg(feature = "hot-reload")]
+    pub fn watch_file<T, E>(
+        &mut self,
+        path: impl Into<std::path::PathBuf>,
+        load: impl Fn(&[u8]) -> Result<T, E> + Send + Sync + 'static,
+    ) -> Result<Option<T>, ReloadError>
+    where
+        T: Resource,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let path = path.into();
+        if !self.contains::<T>() {
+            let memory_usage = self.storage.memory_usage();
+            self.storage
+                .check_budget(type_name::<T>(), size_of::<T>(), memory_usage)
+                .map_err(|error| ReloadError::Budget {
+                    path: path.clone(),
+                    error,
+                })?;
+        }
+        let resource = self.reloads.watch(path, load)?;
+        Ok(self.insert(resource))
+    }
+
+    /// Unbinds the resource type `T` from the file bound to it via [`watch_file`],
+    /// returning `false` if there was none. The resource itself stays in the container.
+    ///
+    /// [`watch_file`]: #method.watch_file
+    #[cfg(feature = "hot-reload")]
+    pub fn unwatch_file<T: Resource>(&mut self) -> bool {
+        self.reloads.unwatch(TypeId::of::<T>())
+    }
+
+    /// Loads resources again from files bound to them via [`watch_file`] that were modified
+    /// since they were last loaded, replacing the current values (or inserting them again,
+    /// if they were removed), and returns errors of those that failed to load, or to be
+    /// inserted again without exceeding the [memory budget].
+    ///
+    /// A resource that fails to load keeps its current value, and the error is reported once
+    /// per modification of the file. Files that are missing are skipped until they reappear.
+    ///
+    /// [`watch_file`]: #method.watch_file
+    /// [memory budget]: #method.set_memory_budget
+    #[cfg(feature = "hot-reload")]
+    pub fn poll_reloads(&mut self) -> Vec<ReloadError> {
+        let mut errors = Vec::new();
+        for (path, reloaded) in self.reloads.poll() {
+            let checked = reloaded.and_then(|resource| match self.check_budget(&*resource) {
+                Ok(()) => Ok(resource),
+                Err(error) => Err(ReloadError::Budget { path, error }),
+            });
+            match checked {
+                Ok(resource) => self.extend(Some(resource)),
+                Err(error) => errors.push(error),
+            }
+        }
+        errors
+    }
+
+    /// Registers the resource type `T` for access by dynamically loaded modules
+    /// via [`as_ffi`], under the given name, which is also registered as its [stable identifier].
+    ///
+    /// [`as_ffi`]: #method.as_ffi
+    /// [stable identifier]: #method.register_stable_id
+    #[cfg(feature = "abi_stable")]
+    pub fn register_ffi_resource<T>(&mut self, name: impl Into<String>)
+    where
+        T: Resource + abi_stable::StableAbi,
+    {
+        self.register_stable_id::<T>(name.into());
+        self.ffi_types
+            .insert(TypeId::of::<T>(), FfiType::new::<T>());
+    }
+
+    /// Returns an FFI-safe handle to the container, through which dynamically loaded modules
+    /// can access resources registered with [`register_ffi_resource`].
+    ///
+    /// [`register_ffi_resource`]: #method.register_ffi_resource
+    #[cfg(feature = "abi_stable")]
+    pub fn as_ffi(&self) -> FfiResources<'_> {
+        FfiResources::new(self)
+    }
+
+    #[cfg(feature = "abi_stable")]
+    pub(crate) fn ffi_type(&self, name: &str) -> Option<&FfiType<M>> {
+        let type_id = self.type_id_of(&StableId::Name(name.to_string().into()))?;
+        self.ffi_types.get(&type_id)
+    }
+
+    /// Inserts the given resource of type `T` into the container.
+    ///
+    /// If a resource of this type was already present,
+    /// it will be updated, and the original returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no such resource is present, and the container is [sealed], or, in debug
+    /// builds, resources of types that `T` [depends on] are missing.
+    ///
+    /// [sealed]: #method.seal
+    /// [depends on]: #method.add_dependency
+    #[track_caller]
+    pub fn insert<T: Resource>(&mut self, resource: T) -> Option<T> {
+        self.caches.touch(TypeId::of::<T>());
+        match self.entry::<T>() {
+            Entry::Occupied(mut occupied) => Some(occupied.insert(resource)),
+            Entry::Vacant(vacant) => {
+                vacant.insert(resource);
+                None
+            }
+        }
+    }
+
+    /// Inserts the given resource of type `T` into the container, like [`insert`],
+    /// and sets the strategy of the lock guarding it.
+    ///
+    /// The strategy is kept if the resource is later replaced via [`insert`], and reset
+    /// if it's removed. With [`LockKind::Mutex`], immutable accesses exclude each other:
+    /// this includes recursive ones, such as via [`get_recursive`] while holding a `Ref`.
+    ///
+    /// [`insert`]: #method.insert
+    /// [`LockKind::Mutex`]: enum.LockKind.html#variant.Mutex
+    /// [`get_recursive`]: #method.get_recursive
+    #[track_caller]
+    pub fn insert_with_lock<T: Resource>(&mut self, resource: T, kind: LockKind) -> Option<T> {
+        let previous = self.insert(resource);
+        if let Some(cell) = self.resources.get_mut(&TypeId::of::<T>()) {
+            cell.lock.set_kind(kind);
+        }
+        previous
+    }
+
+    /// Inserts the given resource of type `T` into the container as read-only, such as
+    /// a static lookup table, returning the previous one, if any.
+    ///
+    /// Mutable accesses of a read-only resource fail with [`Conflict::ReadOnly`], including
+    /// [`lock_all`], and asynchronous ones never complete; immutable ones skip the lock's
+    /// bookkeeping, so they're cheaper, but aren't reported by [`borrow_state`].
+    /// The resource can still be replaced via [`insert`], which keeps it read-only,
+    /// or made writable again via [`insert_with_lock`].
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
+    /// # use resources::Resources;
+    /// let mut resources = Resources::new();
+    /// resources.insert_readonly([0u8; 256]);
+    /// assert_eq!(resources.get::<[u8; 256]>().unwrap()[42], 0);
+    /// assert!(resources.get_mut::<[u8; 256]>().unwrap_err().is_read_only());
+    /// # }
+    /// ```
+    ///
+    /// [`Conflict::ReadOnly`]: enum.Conflict.html#variant.ReadOnly
+    /// [`lock_all`]: #method.lock_all
+    /// [`borrow_state`]: #method.borrow_state
+    /// [`insert`]: #method.insert
+    /// [`insert_with_lock`]: #method.insert_with_lock
+    #[track_caller]
+    pub fn insert_readonly<T: Resource>(&mut self, resource: T) -> Option<T> {
+        self.insert_with_lock(resource, LockKind::ReadOnly)
+    }
+
+    /// Returns `true` if the resource of type `T` is present, and read-only,
+    /// having been inserted via [`insert_readonly`].
+    ///
+    /// [`insert_readonly`]: #method.insert_readonly
+    pub fn is_readonly<T: Resource>(&self) -> bool {
+        self.lock_kind::<T>() == Some(LockKind::ReadOnly)
+    }
+
+    /// Marks resources of type `T` as read-mostly, to be read via thread-local snapshots
+    /// by [`with_local`].
+    ///
+    /// Each thread keeps its own handle to a snapshot of the resource, shared by all threads
+    /// that read it since it last changed, and checks it against a single epoch of the
+    /// container, so that reads don't modify any memory shared with other threads.
+    /// Releasing a mutable borrow of a marked resource advances the epoch, invalidating
+    /// snapshots of all marked resources, as does any insertion, replacement, or removal
+    /// of a resource.
+    ///
+    /// [`with_local`]: #method.with_local
+    #[cfg(feature = "local-cache")]
+    pub fn cache_locally<T: Resource>(&mut self) {
+        let type_id = TypeId::of::<T>();
+        self.storage.local_cache_mut().designate(type_id);
+        let epoch = self.storage.local_cache().epoch_for(type_id);
+        if let Some(cell) = self.resources.get_mut(&type_id) {
+            cell.lock.set_local_epoch(epoch);
+        }
+    }
+
+    /// Unmarks resources of type `T` as [read-mostly], invalidating their snapshots.
+    ///
+    /// [read-mostly]: #method.cache_locally
+    #[cfg(feature = "local-cache")]
+    pub fn uncache_locally<T: Resource>(&mut self) {
+        let type_id = TypeId::of::<T>();
+        self.storage.local_cache_mut().undesignate(type_id);
+        if let Some(cell) = self.resources.get_mut(&type_id) {
+            cell.lock.set_local_epoch(None);
+        }
+    }
+
+    /// Returns `true` if resources of type `T` are marked as [read-mostly].
+    ///
+    /// [read-mostly]: #method.cache_locally
+    #[cfg(feature = "local-cache")]
+    pub fn is_cached_locally<T: Resource>(&self) -> bool {
+        self.storage.local_cache().is_designated(TypeId::of::<T>())
+    }
+
+    /// Calls the closure with the current thread's snapshot of the stored resource of type `T`,
+    /// if it's marked as [read-mostly], or with the resource itself, borrowed as by [`get`],
+    /// otherwise, returning the closure's result.
+    ///
+    /// While the snapshot is up to date, this doesn't lock the resource, or modify any memory
+    /// shared with other threads. Otherwise, the resource is borrowed and cloned, unless
+    /// another thread already did that since it last changed.
+    ///
+    /// If a new snapshot has to be taken, and such a resource is currently accessed mutably
+    /// elsewhere, or is not present in the container, returns the appropriate error.
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
+    /// # use resources::Resources;
+    /// #[derive(Clone)]
+    /// struct Config {
+    ///     volume: f32,
+    /// }
+    ///
+    /// let mut resources = Resources::new();
+    /// resources.insert(Config { volume: 0.5 });
+    /// resources.cache_locally::<Config>();
+    /// // Takes a snapshot, which later calls on this thread read without locking.
+    /// assert_eq!(resources.with_local(|config: &Config| config.volume), Ok(0.5));
+    /// resources.get_mut::<Config>().unwrap().volume = 1.0;
+    /// assert_eq!(resources.with_local(|config: &Config| config.volume), Ok(1.0));
+    /// # }
+    /// ```
+    ///
+    /// [read-mostly]: #method.cache_locally
+    /// [`get`]: #method.get
+    #[cfg(feature = "local-cache")]
+    #[track_caller]
+    pub fn with_local<T: Resource + Clone, R>(
+        &self,
+        f: impl FnOnce(&T) -> R,
+    ) -> Result<R, CantGetResource> {
+        let local_cache = self.storage.local_cache();
+        let epoch = local_cache.epoch();
+        let f = match local_cache.try_with(epoch, f) {
+            Ok(result) => return Ok(result),
+            Err(f) => f,
+        };
+        let cell = match self.find::<T>(Access::Shared)? {
+            Found::Stored(cell) if cell.lock.is_cached_locally() => cell,
+            found => return Ok(f(&*found.project(Ref::from_cell(found.cell())?))),
+        };
+        let snapshot = local_cache.snapshot(epoch, || {
+            Ref::<T>::from_cell(cell).map(|resource| T::clone(&resource))
+        })?;
+        Ok(f(&*snapshot))
+    }
+
+    /// Inserts the given resource of type `T` into the container, marked to be removed
+    /// by the next call to [`maintain`], such as per-frame scratch data, returning
+    /// the previous one, if any.
+    ///
+    /// The mark is cleared if the resource is removed before then; replacing it
+    /// via [`insert`] keeps it.
+    ///
+    /// [`maintain`]: #method.maintain
+    /// [`insert`]: #method.insert
+    pub fn insert_transient<T: Resource>(&mut self, resource: T) -> Option<T> {
+        let previous = self.insert(resource);
+        self.storage.mark_transient(TypeId::of::<T>());
+        previous
+    }
+
+    /// Returns `true` if the resource of type `T` is present, and will be removed
+    /// by the next call to [`maintain`], having been inserted via [`insert_transient`].
+    ///
+    /// [`maintain`]: #method.maintain
+    /// [`insert_transient`]: #method.insert_transient
+    pub fn is_transient<T: Resource>(&self) -> bool {
+        self.storage.is_transient(TypeId::of::<T>())
+    }
+
+    /// Returns the strategy of the lock guarding the stored resource of type `T`,
+    /// or `None` if it's not present in the container.
+    pub fn lock_kind<T: Resource>(&self) -> Option<LockKind> {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .map(|cell| cell.lock.kind())
+    }
+
+    /// Inserts the given value into the container as a resource of type `Arc<T>`,
+    /// so that weak handles to it can be obtained via [`downgrade`], returning the previous one,
+    /// if any.
+    ///
+    /// The value can be borrowed via `get::<Arc<T>>()` like any other resource, and modified
+    /// through `get_mut::<Arc<T>>()` with `Arc::make_mut()` or `Arc::get_mut()`. With the
+    /// `explicit` feature, `Arc<T>` implements [`Resource`] only if `T` does.
+    ///
+    /// [`downgrade`]: #method.downgrade
+    /// [`Resource`]: trait.Resource.html
+    pub fn insert_arc<T: Resource>(&mut self, value: T) -> Option<Arc<T>> {
+        self.insert(Arc::new(value))
+    }
+
+    /// Returns a [`WeakResource`] handle to the resource of type `Arc<T>`,
+    /// inserted via [`insert_arc`], that doesn't keep it alive.
+    ///
+    /// [`WeakResource`]: struct.WeakResource.html
+    /// [`insert_arc`]: #method.insert_arc
+    pub fn downgrade<T: Resource>(&self) -> Result<WeakResource<T>, CantGetResource> {
+        self.get::<Arc<T>>()
+            .map(|strong| WeakResource::new(&strong))
+    }
+
+    /// Inserts default values of resource types registered with [`register_default!`],
+    /// except for types already present in the container.
+    ///
+    /// [`register_default!`]: macro.register_default.html
+    #[cfg(feature = "inventory")]
+    pub fn insert_registered(&mut self) {
+        for registered in registered::registered() {
+            if !self.resources.contains_key(&registered.type_id()) {
+                self.extend(Some(registered.create()));
+            }
+        }
+    }
+
+    /// Inserts the given resource of type `T` into the container, without checking
+    /// if a resource of this type is already present.
+    ///
+    /// This is faster than [`insert`], for building large containers at startup
+    /// where types of resources are known to be unique. Presence is still checked
+    /// in debug builds, panicking if a resource of this type is present.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the container is [sealed].
+    ///
+    /// # Safety
+    /// No resource of type `T` may be present in the container. Otherwise, subsequent
+    /// operations on the container may panic, loop forever, or return arbitrary results,
+    /// including references to the wrong resource of type `T`.
+    ///
+    /// [`insert`]: #method.insert
+    /// [sealed]: #method.seal
+    #[track_caller]
+    pub unsafe fn insert_unique_unchecked<T: Resource>(&mut self, resource: T) {
+        self.storage.assert_unsealed(type_name::<T>());
+        debug_assert!(
+            !self.contains::<T>(),
+            "resource `{}` is already present",
+            type_name::<T>()
+        );
+        self.storage
+            .resource_inserted(TypeId::of::<T>(), type_name::<T>());
+        let cell = ResourceCell::new(resource, &mut self.storage);
+        self.resources
+            .insert_unique_unchecked(TypeId::of::<T>(), cell);
+    }
+
+    /// Inserts the given resource of type `T` into the container, unless that would exceed
+    /// its [memory budget], in which case the resource is returned in the error.
+    ///
+    /// If a resource of this type was already present,
+    /// it will be updated, and the original returned; this never exceeds the budget.
+    ///
+    /// [memory budget]: #method.set_memory_budget
+    pub fn try_insert<T: Resource>(&mut self, resource: T) -> Result<Option<T>, BudgetExceeded<T>> {
+        if !self.contains::<T>() {
+            let memory_usage = self.storage.memory_usage();
+            if let Err(error) =
+                self.storage
+                    .check_budget(type_name::<T>(), size_of::<T>(), memory_usage)
+            {
+                return Err(BudgetExceeded {
+                    resource,
+                    type_name: error.type_name,
+                    size: error.size,
+                    memory_usage: error.memory_usage,
+                    memory_budget: error.memory_budget,
+                });
+            }
+        }
+        Ok(self.insert(resource))
+    }
+
+    /// Inserts the given resource of type `T` into the container, like [`insert`], unless
+    /// the container is [sealed] and no resource of this type is present, in which case
+    /// the resource is returned in the error.
+    ///
+    /// [`insert`]: #method.insert
+    /// [sealed]: #method.seal
+    pub fn checked_insert<T: Resource>(&mut self, resource: T) -> Result<Option<T>, Sealed<T>> {
+        if self.storage.is_sealed() && !self.contains::<T>() {
+            return Err(Sealed {
+                resource,
+                type_name: type_name::<T>(),
+            });
+        }
+        Ok(self.insert(resource))
+    }
+
+    /// Inserts the given resource of type `T` into the container, like [`insert`], unless
+    /// no resource of this type is present and resources of types that `T` [depends on]
+    /// are missing, in which case the resource is returned in the error, with their names.
+    ///
+    /// [`insert`]: #method.insert
+    /// [depends on]: #method.add_dependency
+    pub fn try_insert_dependent<T: Resource>(
+        &mut self,
+        resource: T,
+    ) -> Result<Option<T>, MissingDependencies<T>> {
+        if !self.contains::<T>() {
+            if let Err(missing) = self.check_dependencies::<T>() {
+                return Err(MissingDependencies {
+                    resource,
+                    type_name: type_name::<T>(),
+                    missing,
+                });
+            }
+        }
+        Ok(self.insert(resource))
+    }
+
+    /// Returns the total size of resources in the container, in bytes.
+    ///
+    /// This is the sum of their `size_of`s: it doesn't include memory they own indirectly,
+    /// such as contents of a `Vec`, or memory used by the container itself.
+    pub fn memory_usage(&self) -> usize {
+        self.storage.memory_usage()
+    }
+
+    /// Returns the memory budget of the container, in bytes, if one was set.
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.storage.memory_budget()
+    }
+
+    /// Limits the total size of resources in the container, as reported by [`memory_usage`],
+    /// or lifts the limit if `None`.
+    ///
+    /// The budget is only enforced by methods that can fail: inserting a resource of
+    /// a new type that would exceed it via [`try_insert`], [`init_all`], [`watch_file`]
+    /// or [`poll_reloads`], or via [`apply_delta`] or [`load_snapshot`], returns
+    /// a [`BudgetExceeded`] error instead.
+    ///
+    /// Methods that can't fail aren't limited by the budget, and may exceed it:
+    /// - [`insert`], [`insert_with_lock`], [`insert_readonly`], [`insert_transient`]
+    ///   and other `insert_*()` methods, including [`insert_any_map`]
+    ///   and [`insert_registered`],
+    /// - the entry API, such as `entry().or_insert()`,
+    /// - `extend()`, [`move_to`], [`move_types_to`] and [`split_filter`],
+    /// - replacing a resource with a larger one of the same type, by any method.
+    ///
+    /// Setting a budget below the current usage doesn't remove anything.
+    ///
+    /// [`memory_usage`]: #method.memory_usage
+    /// [`try_insert`]: #method.try_insert
+    /// [`init_all`]: #method.init_all
+    /// [`watch_file`]: #method.watch_file
+    /// [`poll_reloads`]: #method.poll_reloads
+    /// [`apply_delta`]: #method.apply_delta
+    /// [`load_snapshot`]: #method.load_snapshot
+    /// [`BudgetExceeded`]: struct.BudgetExceeded.html
+    /// [`insert`]: #method.insert
+    /// [`insert_with_lock`]: #method.insert_with_lock
+    /// [`insert_readonly`]: #method.insert_readonly
+    /// [`insert_transient`]: #method.insert_transient
+    /// [`insert_any_map`]: #method.insert_any_map
+    /// [`insert_registered`]: #method.insert_registered
+    /// [`move_to`]: #method.move_to
+    /// [`move_types_to`]: #method.move_types_to
+    /// [`split_filter`]: #method.split_filter
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+        self.storage.set_memory_budget(budget);
+    }
+
+    /// Returns the policy blocking accessors of the container wait with.
+    #[cfg(feature = "blocking")]
+    pub fn backoff(&self) -> Backoff {
+        self.backoff
+    }
+
+    /// Sets the policy blocking accessors, such as [`get_blocking`], wait with;
+    /// see [`Backoff`] for details.
+    ///
+    /// [`get_blocking`]: #method.get_blocking
+    /// [`Backoff`]: struct.Backoff.html
+    #[cfg(feature = "blocking")]
+    pub fn set_backoff(&mut self, backoff: Backoff) {
+        self.backoff = backoff;
+    }
+
+    /// Inserts the given resource of type `T` into the container as an [`Rcu`] resource,
+    /// read without locking via [`rcu`] or [`snapshot`].
+    ///
+    /// `Rcu` resources are stored separately from other resources,
+    /// and are only accessible via methods that mention them;
+    /// they are, however, removed by [`clear`].
+    ///
+    /// If a resource of this type was already present as an `Rcu` resource,
+    /// it will be replaced, and its current version will be returned.
+    ///
+    /// [`Rcu`]: struct.Rcu.html
+    /// [`rcu`]: #method.rcu
+    /// [`snapshot`]: #method.snapshot
+    /// [`clear`]: #method.clear
+    #[cfg(feature = "epoch")]
+    pub fn insert_rcu<T: Resource>(&mut self, resource: T) -> Option<T> {
+        self.rcus
+            .insert(TypeId::of::<T>(), Box::new(Rcu::new(resource)))
+            .map(|rcu| {
+                rcu.downcast::<Rcu<T>>()
+                    .unwrap_or_else(|_| panic!("downcasting resources should always succeed"))
+                    .into_inner()
+            })
+    }
+
+    /// Removes the [`Rcu`] resource of type `T` from the container.
+    ///
+    /// If a resource of this type was present in the container, its current version
+    /// will be returned.
+    ///
+    /// [`Rcu`]: struct.Rcu.html
+    #[cfg(feature = "epoch")]
+    pub fn remove_rcu<T: Resource>(&mut self) -> Option<T> {
+        self.rcus.remove(&TypeId::of::<T>()).map(|rcu| {
+            rcu.downcast::<Rcu<T>>()
+                .unwrap_or_else(|_| panic!("downcasting resources should always succeed"))
+                .into_inner()
+        })
+    }
+
+    /// Returns the stored [`Rcu`] resource of type `T`, for reading its snapshots
+    /// and publishing its new versions, or an error if it's not present in the container.
+    ///
+    /// This never conflicts with any other access.
+    ///
+    /// [`Rcu`]: struct.Rcu.html
+    #[cfg(feature = "epoch")]
+    pub fn rcu<T: Resource>(&self) -> Result<&Rcu<T>, NoSuchResource> {
+        self.rcus
+            .get(&TypeId::of::<T>())
+            .map(|rcu| {
+                rcu.downcast_ref::<Rcu<T>>()
+                    .unwrap_or_else(|| panic!("downcasting resources should always succeed"))
+            })
+            .ok_or(NoSuchResource {
+                type_name: type_name::<T>(),
+            })
+    }
+
+    /// Returns a snapshot of the current version of the stored [`Rcu`] resource of type `T`,
+    /// or an error if it's not present in the container.
+    ///
+    /// Unlike [`get`], this doesn't modify any memory shared with other threads.
+    ///
+    /// [`Rcu`]: struct.Rcu.html
+    /// [`get`]: #method.get
+    #[cfg(feature = "epoch")]
+    pub fn snapshot<T: Resource>(&self) -> Result<Snapshot<'_, T>, NoSuchResource> {
+        Ok(self.rcu::<T>()?.load())
+    }
+
+    /// Removes the resource of type `T` from the container.
+    ///
+    /// If a resource of this type was present in the container, it will be returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the container is [sealed] and such a resource is present.
+    ///
+    /// [sealed]: #method.seal
+    #[track_caller]
+    pub fn remove<T: Resource>(&mut self) -> Option<T> {
+        self.assert_removable(TypeId::of::<T>());
+        let cell = self.resources.remove(&TypeId::of::<T>())?;
+        self.storage
+            .resource_removed(TypeId::of::<T>(), cell.type_name());
+        let mut resource = cell.into_resource(&mut self.storage);
+        self.finalize(TypeId::of::<T>(), &mut resource);
+        Some(resource)
+    }
+
+    /// Removes the resource of type `T` from the container, like [`remove`], unless
+    /// the container is [sealed] and such a resource is present, in which case
+    /// an error is returned.
+    ///
+    /// [`remove`]: #method.remove
+    /// [sealed]: #method.seal
+    pub fn checked_remove<T: Resource>(&mut self) -> Result<Option<T>, Sealed> {
+        if self.storage.is_sealed() && self.contains::<T>() {
+            return Err(Sealed {
+                resource: (),
+                type_name: type_name::<T>(),
+            });
+        }
+        Ok(self.remove())
+    }
+
+    /// Removes the resource of type `T` from the container for the duration of the closure,
+    /// which is passed both the resource and the rest of the container, then reinserts it.
+    ///
+    /// This allows mutating the resource while freely accessing others, even ones that
+    /// the resource could alias through. The resource is reinserted as it was, even if
+    /// the closure panics, without running its [finalizer] or recording a removal;
+    /// this counts as a mutable access of it.
+    ///
+    /// If such a resource is not present in the container, or is [read-only], returns an error.
+    ///
+    /// [finalizer]: #method.set_finalizer
+    /// [read-only]: #method.insert_readonly
+    pub fn resource_scope<T: Resource, R>(
+        &mut self,
+        f: impl FnOnce(&Resources<M>, &mut T) -> R,
+    ) -> Result<R, CantGetResource> {
+        let type_id = TypeId::of::<T>();
+        if self.lock_kind::<T>() == Some(LockKind::ReadOnly) {
+            return Err(InvalidBorrow::Mutable {
+                type_name: type_name::<T>(),
+                conflict: Conflict::ReadOnly,
+            }
+            .into());
+        }
+        let cell = self.resources.remove(&type_id).ok_or(NoSuchResource {
+            type_name: type_name::<T>(),
+        })?;
+        // The cell is owned here, so nothing else can access the resource.
+        let resource = unsafe { &mut *cell.resource_ptr() }
+            .downcast_mut::<T>()
+            .unwrap_or_else(|| panic!("downcasting resources should always succeed"));
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| f(self, resource)));
+        if cell.lock.try_lock_exclusive() {
+            unsafe { cell.lock.unlock_exclusive() };
+        }
+        self.resources.insert(type_id, cell);
+        Ok(result.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+    }
+
+    /// Removes all resources from the container, dropping them in order of their
+    /// [drop priorities], after running their [finalizers].
+    ///
+    /// Resources inserted by finalizers are kept.
+    ///
+    /// [drop priorities]: #method.set_drop_priority
+    /// [finalizers]: #method.set_finalizer
+    pub fn clear(&mut self) {
+        let mut type_ids = self.resources.keys().copied().collect::<Vec<_>>();
+        type_ids.sort_by_key(|type_id| Reverse(self.storage.drop_priority(*type_id)));
+        for type_id in type_ids {
+            if let Some(cell) = self.resources.remove(&type_id) {
+                self.storage.resource_removed(type_id, cell.type_name());
+                // The cell is owned here, so nothing else can access the resource.
+                self.finalize(type_id, unsafe { &mut *cell.resource_ptr() });
+                cell.drop_in(&mut self.storage);
+            }
+        }
+        #[cfg(feature = "epoch")]
+        self.rcus.clear();
+    }
+
+    /// Inserts an empty [`Events`] queue of events of type `T`, unless one is present,
+    /// and registers it to have its buffers swapped by [`update_events`].
+    ///
+    /// [`Events`]: struct.Events.html
+    /// [`update_events`]: #method.update_events
+    pub fn add_events<T: Send + Sync + 'static>(&mut self) {
+        self.event_updaters
+            .insert(TypeId::of::<Events<T>>(), events::update::<T>);
+        if !self.contains::<Events<T>>() {
+            self.insert(Events::<T>::default());
+        }
+    }
+
+    /// Swaps the buffers of all [`Events`] queues registered via [`add_events`],
+    /// discarding events sent before the previous call.
+    ///
+    /// This is meant to be called once per frame, or other unit of the application's update.
+    ///
+    /// [`Events`]: struct.Events.html
+    /// [`add_events`]: #method.add_events
+    pub fn update_events(&mut self) {
+        for (type_id, update) in &self.event_updaters {
+            if let Some(cell) = self.resources.get(type_id) {
+                // The container is borrowed mutably, so nothing else can access the resource.
+                update(unsafe { &mut *cell.resource_ptr() });
+            }
+        }
+    }
+
+    /// Returns an [`EventWriter`] for sending events of type `T`,
+    /// borrowing the [`Events`] queue mutably.
+    ///
+    /// [`EventWriter`]: struct.EventWriter.html
+    /// [`Events`]: struct.Events.html
+    pub fn event_writer<T: Send + Sync + 'static>(
+        &self,
+    ) -> Result<EventWriter<'_, T>, CantGetResource> {
+        self.get_mut::<Events<T>>().map(EventWriter::new)
+    }
+
+    /// Returns a new [`Subscriber`] reading the [`Published`] value of type `T`,
+    /// borrowing it mutably for the duration of the call.
+    ///
+    /// [`Subscriber`]: struct.Subscriber.html
+    /// [`Published`]: struct.Published.html
+    pub fn subscribe<T: Clone + Send + Sync + 'static>(
+        &self,
+    ) -> Result<Subscriber<T>, CantGetResource> {
+        self.get_mut::<Published<T>>()
+            .map(|mut published| published.subscribe())
+    }
+
+    /// Returns `true` if a resource of type `T` was removed from the container since
+    /// the last call to [`maintain`] or [`drained_removals`], even if one was inserted again.
+    ///
+    /// Replacing a resource doesn't count as removing it.
+    ///
+    /// [`maintain`]: #method.maintain
+    /// [`drained_removals`]: #method.drained_removals
+    pub fn removed<T: Resource>(&self) -> bool {
+        self.storage.was_removed(TypeId::of::<T>())
+    }
+
+    /// Returns an iterator over `TypeId`s of resources removed since the last call
+    /// to [`maintain`] or this method, clearing the record of removals.
+    ///
+    /// [`maintain`]: #method.maintain
+    pub fn drained_removals(&mut self) -> impl Iterator<Item = TypeId> + '_ {
+        self.storage.drain_removed()
+    }
+
+    /// Clears the record of removed resources, see [`removed`], then removes
+    /// [transient resources], and least recently accessed [evictable caches] while they
+    /// exceed their budget, running their [finalizers].
+    ///
+    /// Resources removed this way are recorded as removed until the next call.
+    ///
+    /// [`removed`]: #method.removed
+    /// [transient resources]: #method.insert_transient
+    /// [evictable caches]: #method.set_cache_cost
+    /// [finalizers]: #method.set_finalizer
+    pub fn maintain(&mut self) {
+        self.storage.clear_removed();
+        for type_id in self.storage.take_transient() {
+            self.discard(type_id);
+        }
+        let evictions = self
+            .caches
+            .evictions(|type_id| self.resources.contains_key(&type_id));
+        for type_id in evictions {
+            self.discard(type_id);
+        }
+    }
+
+    /// Removes the resource of the type, if present, running its finalizer and dropping it.
+    fn discard(&mut self, type_id: TypeId) {
+        if let Some(cell) = self.resources.remove(&type_id) {
+            self.storage.resource_removed(type_id, cell.type_name());
+            // The cell is owned here, so nothing else can access the resource.
+            self.finalize(type_id, unsafe { &mut *cell.resource_ptr() });
+            cell.drop_in(&mut self.storage);
+        }
+    }
+
+    /// Sets a hook to run on the resource of type `T` when it's removed from the container
+    /// via [`remove`] or [`clear`], or when the container is dropped,
+    /// replacing the previously set one.
+    ///
+    /// The hook receives the resource after it's removed, along with the container,
+    /// so that it can, for example, unregister the resource from others, or flush
+    /// its contents into them, before it's dropped or returned. It doesn't run when
+    /// the resource is replaced, removed via the entry API, or moved out via `into_iter()`.
+    ///
+    /// [`remove`]: #method.remove
+    /// [`clear`]: #method.clear
+    pub fn set_finalizer<T: Resource>(
+        &mut self,
+        mut finalizer: impl FnMut(&mut T, &mut Self) + Send + Sync + 'static,
+    ) where
+        M: 'static,
+    {
+        self.finalizers.insert(
+            TypeId::of::<T>(),
+            Box::new(move |resource, resources| {
+                let resource = resource
+                    .downcast_mut::<T>()
+                    .unwrap_or_else(|| panic!("downcasting resources should always succeed"));
+                finalizer(resource, resources)
+            }),
+        );
+    }
+
+    /// Registers the `Debug` implementation of the resource type `T`, for including values
+    /// of such resources in [`report`]s.
+    ///
+    /// [`report`]: #method.report
+    pub fn register_debug<T: Resource + Debug>(&mut self) {
+        self.debug_fns
+            .insert(TypeId::of::<T>(), plugin::debug_fn::<T>());
+    }
+
+    /// Sets a function computing a stable hash of the resource of type `T`, to be combined
+    /// with others by [`state_hash`], replacing the previously set one.
+    ///
+    /// The function should return the same hash for equal states of the resource
+    /// across runs and platforms, so `Hash` implementations of `std` types
+    /// and its `RandomState` hashers are unsuitable.
+    ///
+    /// [`state_hash`]: #method.state_hash
+    pub fn set_state_hasher<T: Resource>(
+        &mut self,
+        hasher: impl Fn(&T) -> u64 + Send + Sync + 'static,
+    ) {
+        self.state_hashers.insert(
+            TypeId::of::<T>(),
+            Box::new(move |resource| {
+                hasher(
+                    resource
+                        .downcast_ref::<T>()
+                        .unwrap_or_else(|| panic!("downcasting resources should always succeed")),
+                )
+            }),
+        );
+    }
+
+    /// Returns a checksum of the state of the container, combining hashes of resources
+    /// computed by functions set via [`set_state_hasher`], along with names of their types.
+    ///
+    /// Resources are combined in order of their type names, so the checksum is deterministic,
+    /// and, as long as the functions are stable, is the same across runs and platforms
+    /// for the same state, making it suitable for detecting desynchronization
+    /// of lockstep simulations. Resources without a hash function are ignored.
+    ///
+    /// If any of the hashed resources is currently accessed mutably elsewhere,
+    /// returns the error for it instead.
+    ///
+    /// [`set_state_hasher`]: #method.set_state_hasher
+    pub fn state_hash(&self) -> Result<u64, InvalidBorrow> {
+        let mut hashed = Vec::with_capacity(self.state_hashers.len());
+        for (type_id, hasher) in &self.state_hashers {
+            if let Some(cell) = self.resources.get(type_id) {
+                hashed.push((cell, hasher));
+            }
+        }
+        hashed.sort_unstable_by_key(|&(cell, _)| cell.type_name());
+        let mut state = FxHasher64::default();
+        for (cell, hasher) in hashed {
+            let resource = Ref::<dyn Resource>::from_cell(cell)?;
+            // Bytes are written one by one, as words would be read in the platform's byte order.
+            for &byte in cell.type_name().as_bytes() {
+                state.write_u8(byte);
+            }
+            state.write_u64(hasher(&*resource));
+        }
+        Ok(state.finish())
+    }
+
+    /// Runs the finalizer of resources of the type, if there is one, on the removed resource.
+    fn finalize(&mut self, type_id: TypeId, resource: &mut dyn Resource) {
+        if let Some(mut finalizer) = self.finalizers.remove(&type_id) {
+            finalizer(resource, self);
+            // The finalizer may have set a different one for its type.
+            self.finalizers.entry(type_id).or_insert(finalizer);
+        }
+    }
+
+    /// Sets the drop priority of resources of type `T`, which determines the order
+    /// resources are dropped in by [`clear`] and when the container is dropped.
+    ///
+    /// Resources are dropped in descending order of priority, so that a resource outlives
+    /// all resources with higher priority; ones with equal priorities are dropped
+    /// in arbitrary order. The default priority is `0`.
+    ///
+    /// [`clear`]: #method.clear
+    pub fn set_drop_priority<T: Resource>(&mut self, priority: i32) {
+        self.storage.set_drop_priority(TypeId::of::<T>(), priority);
+    }
+
+    /// Marks resources of type `T` as evictable caches, each counting `cost` towards
+    /// the [cache budget], replacing the previously set cost.
+    ///
+    /// When caches exceed the budget, [`maintain`] removes least recently accessed ones,
+    /// running their [finalizers], until they fit. Marking a type, and inserting a resource
+    /// of it, counts as accessing it.
+    ///
+    /// [cache budget]: #method.set_cache_budget
+    /// [`maintain`]: #method.maintain
+    /// [finalizers]: #method.set_finalizer
+    pub fn set_cache_cost<T: Resource>(&mut self, cost: usize) {
+        self.caches.set_cost(TypeId::of::<T>(), cost);
+    }
+
+    /// Unmarks resources of type `T` as [evictable caches].
+    ///
+    /// [evictable caches]: #method.set_cache_cost
+    pub fn unset_cache_cost<T: Resource>(&mut self) {
+        self.caches.unset(TypeId::of::<T>());
+    }
+
+    /// Returns the cost of resources of type `T`, if they're marked as [evictable caches].
+    ///
+    /// [evictable caches]: #method.set_cache_cost
+    pub fn cache_cost<T: Resource>(&self) -> Option<usize> {
+        self.caches.cost(TypeId::of::<T>())
+    }
+
+    /// Returns the total cost of [evictable caches] present in the container.
+    ///
+    /// [evictable caches]: #method.set_cache_cost
+    pub fn cache_usage(&self) -> usize {
+        self.caches
+            .usage(|type_id| self.resources.contains_key(&type_id))
+    }
+
+    /// Returns the budget of the total cost of [evictable caches], if one was set.
+    ///
+    /// [evictable caches]: #method.set_cache_cost
+    pub fn cache_budget(&self) -> Option<usize> {
+        self.caches.budget()
+    }
+
+    /// Sets the budget of the total cost of [evictable caches] in the container,
+    /// enforced by [`maintain`], or removes it with `None`.
+    ///
+    /// [evictable caches]: #method.set_cache_cost
+    /// [`maintain`]: #method.maintain
+    pub fn set_cache_budget(&mut self, budget: Option<usize>) {
+        self.caches.set_budget(budget);
+    }
+
+    /// Shrinks the capacity of the container as much as possible,
+    /// and frees memory kept for reuse by resources of removed types.
+    ///
+    /// Normally, memory of a removed resource is kept, so that re-inserting a resource
+    /// of the same type doesn't allocate, and places it at the same address.
+    pub fn shrink_to_fit(&mut self) {
+        self.resources.shrink_to_fit();
+        self.storage.shrink_to_fit();
+    }
+
+    /// Gets the type `T`'s corresponding entry for in-place manipulation.
+    pub fn entry<T: Resource>(&mut self) -> Entry<'_, T> {
+        let type_id = TypeId::of::<T>();
+        let missing = if cfg!(debug_assertions) && !self.resources.contains_key(&type_id) {
+            self.check_dependencies::<T>().err()
+        } else {
+            None
+        };
+        Entry::from_hash_map_entry(self.resources.entry(type_id), &mut self.storage, missing)
+    }
+
+    /// Returns a reference to the stored resource of type `T`.
+    ///
+    /// If such a resource is currently accessed mutably elsewhere,
+    /// or is not present in the container, returns the appropriate error.
+    ///
+    /// This will also fail if a mutable access via [`get_mut_blocking`] is waiting,
+    /// even if the current thread already holds a reference to the resource;
+    /// see [`get_recursive`] for an alternative.
+    ///
+    /// [`get_mut_blocking`]: #method.get_mut_blocking
+    /// [`get_recursive`]: #method.get_recursive
+    #[track_caller]
+    pub fn get<T: Resource>(&self) -> Result<Ref<'_, T>, CantGetResource> {
+        Ok(self.borrow_opt::<T>(None).ok_or(NoSuchResource {
+            type_name: type_name::<T>(),
+        })??)
+    }
+
+    /// Returns a reference to the stored resource of type `T`,
+    /// or `None` if it's not present in the container.
+    ///
+    /// This is meant for optional resources, which are expected to be absent,
+    /// but not to be borrowed in a conflicting way.
+    ///
+    /// # Panics
+    /// Panics if the resource is currently accessed mutably elsewhere; see [`get`]
+    /// for a non-panicking alternative.
+    ///
+    /// [`get`]: #method.get
+    #[track_caller]
+    pub fn get_opt<T: Resource>(&self) -> Option<Ref<'_, T>> {
+        match self.borrow_opt::<T>(None)? {
+            Ok(resource) => Some(resource),
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    /// Returns a copy of the stored resource of type `T`.
+    ///
+    /// The resource is only briefly locked for the duration of the copy, without
+    /// the bookkeeping of a guard, so the access isn't tracked as a borrow.
+    /// If it's currently accessed mutably elsewhere, or is not present in the container,
+    /// returns the appropriate error.
+    #[track_caller]
+    pub fn get_copy<T: Resource + Copy>(&self) -> Result<T, CantGetResource> {
+        let cell = match self.find::<T>(Access::Shared)? {
+            Found::Stored(cell) => cell,
+            found => return Ok(*found.project(Ref::from_cell(found.cell())?)),
+        };
+        if !cell.lock.try_lock_shared() {
+            instrument::conflict(cell.type_name(), Access::Shared);
+            return Err(InvalidBorrow::Immutable {
+                type_name: cell.type_name(),
+                conflict: cell.conflict(),
+            }
+            .into());
+        }
+        cell.lock.acquired_via(BorrowApi::Get);
+        instrument::acquired(cell.type_name(), Access::Shared);
+        // Resources are keyed by their types, so the cast is valid.
+        let copy = unsafe { *(cell.resource_ptr() as *const T) };
+        unsafe { cell.lock.unlock_shared() };
+        Ok(copy)
+    }
+
+    /// Returns a reference to the stored resource of type `T`.
+    ///
+    /// If such a resource is currently accessed mutably elsewhere,
+    /// or is not present in the container, returns the appropriate error.
+    ///
+    /// Unlike [`get`], this ignores waiting blocking accesses, and so is guaranteed to succeed
+    /// if the current thread already holds a reference to the resource. This makes it suitable
+    /// for nested code that may re-borrow the same resource, at the cost of potentially
+    /// delaying a waiting [`get_mut_blocking`] indefinitely.
+    ///
+    /// The exception is a resource inserted with [`LockKind::Mutex`], which allows a single
+    /// access at once, so this fails while any other reference to it is held.
+    ///
+    /// [`get`]: #method.get
+    /// [`get_mut_blocking`]: #method.get_mut_blocking
+    /// [`LockKind::Mutex`]: enum.LockKind.html#variant.Mutex
+    #[track_caller]
+    pub fn get_recursive<T: Resource>(&self) -> Result<Ref<'_, T>, CantGetResource> {
+        let found = self.find::<T>(Access::Shared)?;
+        Ok(found.project(Ref::from_cell_recursive(found.cell())?))
+    }
+
+    /// Returns a mutable reference to the stored resource of type `T`.
+    ///
+    /// If such a resource is currently accessed immutably or mutably elsewhere,
+    /// or is not present in the container, returns the appropriate error.
+    #[track_caller]
+    pub fn get_mut<T: Resource>(&self) -> Result<RefMut<'_, T>, CantGetResource> {
+        Ok(self.borrow_mut_opt::<T>(None).ok_or(NoSuchResource {
+            type_name: type_name::<T>(),
+        })??)
+    }
+
+    /// Like [`get`], but labels the returned guard, such as with the name of the system
+    /// acquiring it.
+    ///
+    /// With the `track-borrows` feature, the label is included in errors of conflicting
+    /// accesses, in [`borrow_state`], and in [`long_holds`]; otherwise, it's ignored.
+    ///
+    /// [`get`]: #method.get
+    /// [`borrow_state`]: #method.borrow_state
+    /// [`long_holds`]: #method.long_holds
+    #[track_caller]
+    pub fn get_labeled<T: Resource>(
+        &self,
+        label: &'static str,
+    ) -> Result<Ref<'_, T>, CantGetResource> {
+        Ok(self.borrow_opt::<T>(Some(label)).ok_or(NoSuchResource {
+            type_name: type_name::<T>(),
+        })??)
+    }
+
+    /// Like [`get_mut`], but labels the returned guard, such as with the name of the system
+    /// acquiring it; see [`get_labeled`].
+    ///
+    /// [`get_mut`]: #method.get_mut
+    /// [`get_labeled`]: #method.get_labeled
+    #[track_caller]
+    pub fn get_mut_labeled<T: Resource>(
+        &self,
+        label: &'static str,
+    ) -> Result<RefMut<'_, T>, CantGetResource> {
+        Ok(self
+            .borrow_mut_opt::<T>(Some(label))
+            .ok_or(NoSuchResource {
+                type_name: type_name::<T>(),
+            })??)
+    }
+
+    /// Returns a mutable reference to the stored resource of type `T`, which doesn't count
+    /// as a change of the resource when released.
+    ///
+    /// Unlike with [`get_mut`], releasing the reference doesn't wake [`wait_changed`]
+    /// or [`changes`]; this is meant for resources mutated constantly without anyone being
+    /// interested, such as statistics updated by profilers and debug overlays.
+    ///
+    /// [`get_mut`]: #method.get_mut
+    /// [`wait_changed`]: #method.wait_changed
+    /// [`changes`]: #method.changes
+    #[track_caller]
+    pub fn get_mut_untracked<T: Resource>(&self) -> Result<RefMut<'_, T>, CantGetResource> {
+        let found = self.find::<T>(Access::Exclusive)?;
+        Ok(found.project_mut(RefMut::from_cell_untracked(found.cell())?))
+    }
+
+    /// Returns a reference to the stored resource of type `T` without locking it,
+    /// or an error if it's not present in the container.
+    ///
+    /// This skips the atomic operations of [`get`], for executors that already guarantee
+    /// accesses to be disjoint, such as by scheduling systems via [`AccessSet`].
+    ///
+    /// # Safety
+    /// The resource must not be accessed mutably, by any means, while the returned reference
+    /// is alive; doing so is undefined behavior. Guards acquired elsewhere are not checked.
+    ///
+    /// [`get`]: #method.get
+    /// [`AccessSet`]: struct.AccessSet.html
+    pub unsafe fn get_unchecked<T: Resource>(&self) -> Result<&T, NoSuchResource> {
+        let cell = self.unlocked_cell::<T>()?;
+        Ok((*cell.resource_ptr())
+            .downcast_ref::<T>()
+            .unwrap_or_else(|| panic!("downcasting resources should always succeed")))
+    }
+
+    /// Returns a mutable reference to the stored resource of type `T` without locking it,
+    /// or an error if it's not present in the container.
+    ///
+    /// This skips the atomic operations of [`get_mut`], for executors that already guarantee
+    /// accesses to be disjoint. The access doesn't count as a change of the resource.
+    ///
+    /// # Safety
+    /// The resource must not be accessed at all, by any means, while the returned reference
+    /// is alive; doing so is undefined behavior. Guards acquired elsewhere are not checked.
+    ///
+    /// The resource must not be [read-only]: immutable borrows of those don't hold their locks,
+    /// and are free to assume the resource never changes.
+    ///
+    /// Writes through the returned reference don't invalidate thread-local snapshots of
+    /// the resource taken by [`with_local`]; for a [read-mostly] one, they won't be observed
+    /// by it until the resource is next borrowed mutably by other means.
+    ///
+    /// [`get_mut`]: #method.get_mut
+    /// [read-only]: #method.insert_readonly
+    /// [`with_local`]: #method.with_local
+    /// [read-mostly]: #method.cache_locally
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get_mut_unchecked<T: Resource>(&self) -> Result<&mut T, NoSuchResource> {
+        let cell = self.unlocked_cell::<T>()?;
+        Ok((*cell.resource_ptr())
+            .downcast_mut::<T>()
+            .unwrap_or_else(|| panic!("downcasting resources should always succeed")))
+    }
+
+    fn unlocked_cell<T: Resource>(&self) -> Result<&ResourceCell, NoSuchResource> {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .ok_or(NoSuchResource {
+                type_name: type_name::<T>(),
+            })
+    }
+
+    /// Registers a lens that projects the resource of type `P` onto its field of type `F`,
+    /// so that borrowing `F` via any accessor that locks it, such as [`get`], [`get_mut`],
+    /// [`get_opt`], [`get_recursive`], [`get_blocking`] or [`get_async`] (and so [`fetch`]),
+    /// borrows that field when no resource of type `F` is stored, replacing the previously
+    /// registered lens onto `F`. Unchecked accessors, such as [`get_unchecked`],
+    /// don't resolve lenses.
+    ///
+    /// This allows consuming parts of a large resource, such as a configuration struct,
+    /// without splitting it into many small ones. A borrow of a field is a borrow of the whole
+    /// resource: immutable borrows of it and its fields don't conflict, but a mutable borrow
+    /// of a field conflicts with any other borrow of the resource or its fields.
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
+    /// # use resources::Resources;
+    /// struct PhysicsSettings {
+    ///     gravity: PhysicsGravity,
+    ///     substeps: u32,
+    /// }
+    ///
+    /// struct PhysicsGravity(f32);
+    ///
+    /// let mut resources = Resources::new();
+    /// resources.insert(PhysicsSettings {
+    ///     gravity: PhysicsGravity(-9.8),
+    ///     substeps: 4,
+    /// });
+    /// resources.register_lens(
+    ///     |settings: &PhysicsSettings| &settings.gravity,
+    ///     |settings: &mut PhysicsSettings| &mut settings.gravity,
+    /// );
+    /// resources.get_mut::<PhysicsGravity>().unwrap().0 = -1.6;
+    /// assert_eq!(resources.get::<PhysicsSettings>().unwrap().gravity.0, -1.6);
+    /// # }
+    /// ```
+    ///
+    /// [`get`]: #method.get
+    /// [`get_mut`]: #method.get_mut
+    /// [`get_opt`]: #method.get_opt
+    /// [`get_recursive`]: #method.get_recursive
+    /// [`get_blocking`]: #method.get_blocking
+    /// [`get_async`]: #method.get_async
+    /// [`fetch`]: #method.fetch
+    /// [`get_unchecked`]: #method.get_unchecked
+    pub fn register_lens<P: Resource, F: Resource>(
+        &mut self,
+        get: fn(&P) -> &F,
+        get_mut: fn(&mut P) -> &mut F,
+    ) {
+        self.lenses.register(get, get_mut);
+    }
+
+    /// Unregisters the lens onto the type `F`, returning `true` if one was registered.
+    pub fn unregister_lens<F: Resource>(&mut self) -> bool {
+        self.lenses.unregister::<F>()
+    }
+
+    /// Registers the type `A` as an alias of the canonical type `C`, so that borrowing a resource
+    /// of type `A` borrows the resource of type `C` instead, through the given converters,
+    /// when no resource of type `A` is stored.
+    ///
+    /// This is meant as a deprecation bridge when renaming a widely used resource type:
+    /// the resource is stored once, under its canonical type, while code still using the old
+    /// name keeps working. Aliases are resolved like [lenses], and replace the lens onto `A`,
+    /// if any; borrows of the alias conflict with borrows of the canonical resource the same way.
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
+    /// # use resources::Resources;
+    /// /// Deprecated name, still used by some systems.
+    /// struct RenderConfig {
+    ///     vsync: bool,
+    /// }
+    ///
+    /// /// Canonical name, wrapping the old type while code migrates.
+    /// struct RenderSettings(RenderConfig);
+    ///
+    /// let mut resources = Resources::new();
+    /// resources.insert(RenderSettings(RenderConfig { vsync: true }));
+    /// resources.alias::<RenderConfig, RenderSettings>(
+    ///     |settings| &settings.0,
+    ///     |settings| &mut settings.0,
+    /// );
+    /// resources.get_mut::<RenderConfig>().unwrap().vsync = false;
+    /// assert!(!resources.get::<RenderSettings>().unwrap().0.vsync);
+    /// # }
+    /// ```
+    ///
+    /// [lenses]: #method.register_lens
+    pub fn alias<A: Resource, C: Resource>(
+        &mut self,
+        as_alias: fn(&C) -> &A,
+        as_alias_mut: fn(&mut C) -> &mut A,
+    ) {
+        self.lenses.register(as_alias, as_alias_mut);
+    }
+
+    /// Unregisters the alias `A`, returning `true` if one, or a lens onto `A`, was registered.
+    pub fn unalias<A: Resource>(&mut self) -> bool {
+        self.lenses.unregister::<A>()
+    }
+
+    /// Calls the closure with the container, through which the external value is accessible
+    /// as if it was a resource, via [`get_scoped`] and [`get_scoped_mut`], or by fetching
+    /// `Scoped<&E>` and `Scoped<&mut E>` with the `fetch` feature.
+    ///
+    /// Unlike resources, the value doesn't have to be owned by the container: this is meant for
+    /// exposing objects owned elsewhere, such as by a foreign engine, for the duration of a call.
+    /// The value is removed when the closure returns or panics. The closure only gets a shared
+    /// reference to the container, so that the value can't be moved out of the scope;
+    /// to expose several values at once, group them in a struct.
+    ///
+    /// ```rust
+    /// # use resources::Resources;
+    /// struct Engine {
+    ///     frame: u64,
+    /// }
+    ///
+    /// let mut engine = Engine { frame: 0 };
+    /// let mut resources = Resources::new();
+    /// resources.scope_with(&mut engine, |resources| {
+    ///     resources.get_scoped_mut::<Engine>().unwrap().frame += 1;
+    /// });
+    /// assert_eq!(engine.frame, 1);
+    /// assert!(resources.get_scoped::<Engine>().is_err());
+    /// ```
+    ///
+    /// [`get_scoped`]: #method.get_scoped
+    /// [`get_scoped_mut`]: #method.get_scoped_mut
+    pub fn scope_with<E: Send + Sync + 'static, R>(
+        &mut self,
+        external: &mut E,
+        f: impl FnOnce(&Self) -> R,
+    ) -> R {
+        let guard = ScopeGuard::new(self, external);
+        f(guard.resources)
+    }
+
+    /// Returns a reference to the external value of type `E` exposed by [`scope_with`].
+    ///
+    /// If it's currently accessed mutably elsewhere, or isn't exposed, returns the appropriate error.
+    ///
+    /// [`scope_with`]: #method.scope_with
+    pub fn get_scoped<E: Send + Sync + 'static>(&self) -> Result<Ref<'_, E>, CantGetResource> {
+        let cell = self.scoped_cell::<E>()?;
+        // Values are keyed by their types.
+        Ok(unsafe { Ref::from_lock(&cell.lock, cell.value()) }?)
+    }
+
+    /// Returns a mutable reference to the external value of type `E` exposed by [`scope_with`].
+    ///
+    /// If it's currently accessed immutably or mutably elsewhere, or isn't exposed,
+    /// returns the appropriate error.
+    ///
+    /// [`scope_with`]: #method.scope_with
+    pub fn get_scoped_mut<E: Send + Sync + 'static>(
+        &self,
+    ) -> Result<RefMut<'_, E>, CantGetResource> {
+        let cell = self.scoped_cell::<E>()?;
+        // Values are keyed by their types.
+        Ok(unsafe { RefMut::from_lock(&cell.lock, cell.value()) }?)
+    }
+
+    pub(crate) fn scoped_cell<E: 'static>(&self) -> Result<&ScopedCell, NoSuchResource> {
+        self.scoped.get(&TypeId::of::<E>()).ok_or(NoSuchResource {
+            type_name: type_name::<E>(),
+        })
+    }
+
+    pub(crate) fn scoped_mut(&mut self) -> &mut TypeIdMap<ScopedCell> {
+        &mut self.scoped
+    }
+
+    /// Returns a mutable reference to the stored resource of type `T`,
+    /// or `None` if it's not present in the container.
+    ///
+    /// This is meant for optional resources, which are expected to be absent,
+    /// but not to be borrowed in a conflicting way.
+    ///
+    /// # Panics
+    /// Panics if the resource is currently accessed elsewhere; see [`get_mut`]
+    /// for a non-panicking alternative.
+    ///
+    /// [`get_mut`]: #method.get_mut
+    #[track_caller]
+    pub fn get_mut_opt<T: Resource>(&self) -> Option<RefMut<'_, T>> {
+        match self.borrow_mut_opt::<T>(None)? {
+            Ok(resource) => Some(resource),
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    /// Returns a reference to the stored resource of type `T`,
+    /// waiting until it's no longer accessed mutably elsewhere.
+    ///
+    /// Waiting accesses are served in the order they started waiting in:
+    /// while a mutable access is waiting, all following immutable accesses
+    /// (including ones via [`get`]) will wait for it or fail, respectively.
+    ///
+    /// This will deadlock if the current thread holds a mutable reference to the resource,
+    /// or if it holds an immutable one while a mutable access is waiting;
+    /// see [`get_blocking_recursive`] for an alternative.
+    ///
+    /// This fails if the container's [backoff policy] gives up waiting.
+    ///
+    /// [`get`]: #method.get
+    /// [`get_blocking_recursive`]: #method.get_blocking_recursive
+    /// [backoff policy]: #method.set_backoff
+    #[cfg(feature = "blocking")]
+    #[track_caller]
+    pub fn get_blocking<T: Resource>(&self) -> Result<Ref<'_, T>, CantGetResource> {
+        let found = self.find::<T>(Access::Shared)?;
+        Ok(found.project(Ref::from_cell_blocking(
+            found.cell(),
+            Lane::Normal,
+            &self.backoff,
+        )?))
+    }
+
+    /// Returns a reference to the stored resource of type `T`,
+    /// waiting until it's no longer accessed mutably elsewhere.
+    ///
+    /// Unlike [`get_blocking`], this doesn't wait behind other waiting accesses
+    /// if the resource is available right away, and so will not deadlock if the current thread
+    /// already holds a reference to the resource, at the cost of potentially delaying
+    /// a waiting [`get_mut_blocking`] indefinitely.
+    ///
+    /// This will deadlock if the current thread holds a mutable reference to the resource,
+    /// or any reference to one inserted with [`LockKind::Mutex`], which allows a single
+    /// access at once.
+    ///
+    /// This fails if the container's [backoff policy] gives up waiting.
+    ///
+    /// [`get_blocking`]: #method.get_blocking
+    /// [`get_mut_blocking`]: #method.get_mut_blocking
+    /// [`LockKind::Mutex`]: enum.LockKind.html#variant.Mutex
+    /// [backoff policy]: #method.set_backoff
+    #[cfg(feature = "blocking")]
+    #[track_caller]
+    pub fn get_blocking_recursive<T: Resource>(&self) -> Result<Ref<'_, T>, CantGetResource> {
+        let found = self.find::<T>(Access::Shared)?;
+        Ok(found.project(Ref::from_cell_blocking_recursive(
+            found.cell(),
+            &self.backoff,
+        )?))
+    }
+
+    /// Returns a mutable reference to the stored resource of type `T`,
+    /// waiting until it's no longer accessed elsewhere.
+    ///
+    /// Waiting accesses are served in the order they started waiting in:
+    /// while a mutable access is waiting, all following accesses
+    /// (including ones via [`get`] and [`get_mut`]) will wait for it or fail, respectively.
+    ///
+    /// This will deadlock if the current thread holds any reference to the resource.
+    ///
+    /// This fails if the container's [backoff policy] gives up waiting.
+    ///
+    /// [`get`]: #method.get
+    /// [`get_mut`]: #method.get_mut
+    /// [backoff policy]: #method.set_backoff
+    #[cfg(feature = "blocking")]
+    #[track_caller]
+    pub fn get_mut_blocking<T: Resource>(&self) -> Result<RefMut<'_, T>, CantGetResource> {
+        let found = self.find::<T>(Access::Exclusive)?;
+        Ok(found.project_mut(RefMut::from_cell_blocking(
+            found.cell(),
+            Lane::Normal,
+            &self.backoff,
+        )?))
+    }
 
-use crate::{
-    entry::Entry,
-    error::{CantGetResource, NoSuchResource},
-    refs::{Ref, RefMut},
-};
+    /// Like [`get_blocking`], but waits in the priority lane: ahead of all accesses
+    /// waiting via other blocking methods, behind only earlier ones waiting via this method
+    /// or [`get_mut_blocking_priority`].
+    ///
+    /// This is meant for latency-sensitive threads, such as a render thread, that would
+    /// otherwise wait behind background work; using it pervasively starves the normal lane.
+    ///
+    /// [`get_blocking`]: #method.get_blocking
+    /// [`get_mut_blocking_priority`]: #method.get_mut_blocking_priority
+    #[cfg(feature = "blocking")]
+    #[track_caller]
+    pub fn get_blocking_priority<T: Resource>(&self) -> Result<Ref<'_, T>, CantGetResource> {
+        let found = self.find::<T>(Access::Shared)?;
+        Ok(found.project(Ref::from_cell_blocking(
+            found.cell(),
+            Lane::Priority,
+            &self.backoff,
+        )?))
+    }
 
-#[cfg(feature = "fetch")]
-use crate::fetch::{CantFetch, Fetch};
+    /// Like [`get_mut_blocking`], but waits in the priority lane: ahead of all accesses
+    /// waiting via other blocking methods, behind only earlier ones waiting via this method
+    /// or [`get_blocking_priority`].
+    ///
+    /// This is meant for latency-sensitive threads, such as a render thread, that would
+    /// otherwise wait behind background work; using it pervasively starves the normal lane.
+    ///
+    /// [`get_mut_blocking`]: #method.get_mut_blocking
+    /// [`get_blocking_priority`]: #method.get_blocking_priority
+    #[cfg(feature = "blocking")]
+    #[track_caller]
+    pub fn get_mut_blocking_priority<T: Resource>(&self) -> Result<RefMut<'_, T>, CantGetResource> {
+        let found = self.find::<T>(Access::Exclusive)?;
+        Ok(found.project_mut(RefMut::from_cell_blocking(
+            found.cell(),
+            Lane::Priority,
+            &self.backoff,
+        )?))
+    }
 
-/// Types that can be stored in [`Resources`], automatically implemented for all applicable.
-///
-/// [`Resources`]: struct.Resources.html
-pub trait Resource: Downcast + Send + Sync + 'static {}
+    /// Returns a reference to the stored resource of type `T`,
+    /// asynchronously waiting until it's no longer accessed mutably elsewhere.
+    ///
+    /// The returned future doesn't depend on any particular executor. The reference
+    /// can be held across `.await` points; a task waiting for it is woken whenever
+    /// the resource is released, and retries.
+    ///
+    /// This will never complete if the current task holds a mutable reference to the resource.
+    #[cfg(feature = "async")]
+    pub async fn get_async<T: Resource>(&self) -> Result<Ref<'_, T>, NoSuchResource> {
+        self.borrow_async().await
+    }
 
-impl<T> Resource for T where T: Send + Sync + 'static {}
+    /// Returns a mutable reference to the stored resource of type `T`,
+    /// asynchronously waiting until it's no longer accessed elsewhere.
+    ///
+    /// The returned future doesn't depend on any particular executor. The reference
+    /// can be held across `.await` points; a task waiting for it is woken whenever
+    /// the resource is released, and retries.
+    ///
+    /// This will never complete if the current task holds any reference to the resource.
+    #[cfg(feature = "async")]
+    pub async fn get_mut_async<T: Resource>(&self) -> Result<RefMut<'_, T>, NoSuchResource> {
+        self.borrow_mut_async().await
+    }
 
-impl_downcast!(Resource);
+    /// Returns a reference to the stored resource of type `T`,
+    /// asynchronously waiting until it's no longer accessed mutably elsewhere.
+    ///
+    /// The returned reference can be held across `.await` points. Tasks waiting for the resource
+    /// take turns in a `tokio::sync::RwLock` queue, which serves them in FIFO order,
+    /// so that a stream of readers can't starve a waiting writer; the one whose turn it is
+    /// is woken whenever the resource is released elsewhere, and retries.
+    ///
+    /// This will never complete if the current task holds a mutable reference to the resource,
+    /// or holds an immutable one while a mutable acquisition is queued.
+    #[cfg(feature = "tokio")]
+    pub async fn read<T: Resource>(&self) -> Result<Ref<'_, T>, NoSuchResource> {
+        self.borrow_async().await
+    }
 
-/// A [`Resource`] container, for storing at most one resource of each specific type.
-///
-/// Internally, this is a [`FxHashMap`] of [`TypeId`] to [`RwLock`]. None of the methods are
-/// blocking, however: accessing a resource in a way that would break borrow rules will
-/// return the [`InvalidBorrow`] error instead.
-///
-/// [`Resource`]: trait.Resource.html
-/// [`FxHashMap`]: ../fxhash/type.FxHashMap.html
-/// [`TypeId`]: https://doc.rust-lang.org/std/any/struct.TypeId.html
-/// [`RwLock`]: ../parking_lot/type.RwLock.html
-/// [`InvalidBorrow`]: enum.InvalidBorrow.html
-#[derive(Default)]
-pub struct Resources {
-    resources: FxHashMap<TypeId, RwLock<Box<dyn Resource>>>,
-}
+    /// Returns a mutable reference to the stored resource of type `T`,
+    /// asynchronously waiting until it's no longer accessed elsewhere.
+    ///
+    /// The returned reference can be held across `.await` points. Tasks waiting for the resource
+    /// are served in FIFO order, see [`read`].
+    ///
+    /// This will never complete if the current task holds any reference to the resource.
+    ///
+    /// [`read`]: #method.read
+    #[cfg(feature = "tokio")]
+    pub async fn write<T: Resource>(&self) -> Result<RefMut<'_, T>, NoSuchResource> {
+        self.borrow_mut_async().await
+    }
 
-fn downcast_resource<T: Resource>(resource: Box<dyn Resource>) -> T {
-    *resource
-        .downcast::<T>()
-        .unwrap_or_else(|_| panic!("downcasting resources should always succeed"))
-}
+    /// Shared implementation of `get_async()` and `read()`.
+    #[cfg(any(feature = "async", feature = "tokio"))]
+    async fn borrow_async<T: Resource>(&self) -> Result<Ref<'_, T>, NoSuchResource> {
+        let found = self.find_waiting::<T>(Access::Shared)?;
+        Ok(found.project(Ref::from_cell_async(found.cell()).await))
+    }
 
-impl Resources {
-    /// Creates an empty container. Functionally identical to [`::default()`].
+    /// Shared implementation of `get_mut_async()` and `write()`.
+    #[cfg(any(feature = "async", feature = "tokio"))]
+    async fn borrow_mut_async<T: Resource>(&self) -> Result<RefMut<'_, T>, NoSuchResource> {
+        let found = self.find_waiting::<T>(Access::Exclusive)?;
+        Ok(found.project_mut(RefMut::from_cell_async(found.cell()).await))
+    }
+
+    /// Consumes the container, moving each resource into a map of `TypeId`s to `Any` boxes,
+    /// like the ones used by `anymap`-style containers.
     ///
-    /// [`default`]: #method.default
-    pub fn new() -> Self {
-        Self::default()
+    /// See [`insert_any_map`] for the conversion in the other direction.
+    ///
+    /// [`insert_any_map`]: #method.insert_any_map
+    pub fn into_any_map(self) -> HashMap<TypeId, Box<dyn Any + Send + Sync>> {
+        self.into_iter()
+            .map(|(type_id, resource)| (type_id, resource.into_any_send_sync()))
+            .collect()
     }
 
-    /// Returns `true` if a resource of type `T` exists in the container.
-    pub fn contains<T: Resource>(&self) -> bool {
-        self.resources.contains_key(&TypeId::of::<T>())
+    /// Inserts values of a map of `TypeId`s to `Any` boxes, like the ones returned by
+    /// [`into_any_map`], as resources, returning the entries it couldn't convert.
+    ///
+    /// A resource can only be stored if its concrete type is known, which a boxed `Any`
+    /// can't provide, so values are converted by the [`AnyMapConverter`], for the types
+    /// registered with it. Entries of other types, and ones whose value isn't of the type
+    /// of their key, are returned as is. Otherwise behaves like [`extend`].
+    ///
+    /// [`into_any_map`]: #method.into_any_map
+    /// [`AnyMapConverter`]: struct.AnyMapConverter.html
+    /// [`extend`]: #impl-Extend%3CBox%3Cdyn%20Resource%3E%3E
+    pub fn insert_any_map(
+        &mut self,
+        map: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+        converter: &AnyMapConverter,
+    ) -> HashMap<TypeId, Box<dyn Any + Send + Sync>> {
+        let mut leftovers = HashMap::new();
+        for (type_id, boxed) in map {
+            match converter.convert(type_id, boxed) {
+                Ok(resource) => self.extend(Some(resource)),
+                Err(boxed) => {
+                    leftovers.insert(type_id, boxed);
+                }
+            }
+        }
+        leftovers
     }
 
-    /// Inserts the given resource of type `T` into the container.
+    /// Returns a description of which resources are currently borrowed, and how.
     ///
-    /// If a resource of this type was already present,
-    /// it will be updated, and the original returned.
-    pub fn insert<T: Resource>(&mut self, resource: T) -> Option<T> {
-        self.resources
-            .insert(TypeId::of::<T>(), RwLock::new(Box::new(resource)))
-            .map(|resource| downcast_resource(resource.into_inner()))
+    /// See [`BorrowState`] for details.
+    ///
+    /// [`BorrowState`]: struct.BorrowState.html
+    pub fn borrow_state(&self) -> BorrowState<'_> {
+        BorrowState::new(&self.resources)
     }
 
-    /// Removes the resource of type `T` from the container.
+    /// Returns a listing of all resources of the container, sorted by type name,
+    /// with their sizes, how they're borrowed and, if registered via [`register_debug`],
+    /// their values.
     ///
-    /// If a resource of this type was present in the container, it will be returned.
-    pub fn remove<T: Resource>(&mut self) -> Option<T> {
-        self.resources
-            .remove(&TypeId::of::<T>())
-            .map(|resource| downcast_resource(resource.into_inner()))
+    /// See [`Report`] for details.
+    ///
+    /// [`register_debug`]: #method.register_debug
+    /// [`Report`]: struct.Report.html
+    pub fn report(&self) -> Report {
+        Report::new(self.resources.iter().map(|(type_id, cell)| {
+            let debug = self
+                .debug_fns
+                .get(type_id)
+                .copied()
+                .or_else(|| self.plugins.vtable(*type_id).and_then(TypeVTable::debug_fn));
+            (cell, debug)
+        }))
     }
 
-    /// Gets the type `T`'s corresponding entry for in-place manipulation.
-    pub fn entry<T: Resource>(&mut self) -> Entry<T> {
-        Entry::from_hash_map_entry(self.resources.entry(TypeId::of::<T>()))
+    /// Returns descriptions of guards that have been holding resources of the container
+    /// for longer than the threshold, longest first.
+    ///
+    /// Only guards acquired via synchronous methods, such as `get()`, are tracked;
+    /// see [`Watchdog`] for reporting each such hold once.
+    ///
+    /// [`Watchdog`]: struct.Watchdog.html
+    #[cfg(feature = "watchdog")]
+    pub fn long_holds(&self, threshold: Duration) -> Vec<LongHold> {
+        let mut holds = Vec::new();
+        for cell in self.resources.values() {
+            let mutable = cell.lock.is_locked_exclusive();
+            cell.borrow_sites()
+                .for_each(|id, location, label, acquired| {
+                    let held_for = acquired.elapsed();
+                    if held_for > threshold {
+                        holds.push(LongHold {
+                            id,
+                            type_name: cell.type_name(),
+                            location,
+                            label,
+                            mutable,
+                            held_for,
+                        });
+                    }
+                });
+        }
+        holds.sort_unstable_by_key(|hold| Reverse(hold.held_for));
+        holds
     }
 
-    /// Returns a reference to the stored resource of type `T`.
+    /// Starts recording accesses to resources of the container, discarding
+    /// any previously recorded ones, until [`finish_access_log`] is called.
     ///
-    /// If such a resource is currently accessed mutably elsewhere,
-    /// or is not present in the container, returns the appropriate error.
-    pub fn get<T: Resource>(&self) -> Result<Ref<T>, CantGetResource> {
+    /// Each access records the resource's type, whether it's mutable,
+    /// and the location of the call to `get()` or a similar synchronous method;
+    /// asynchronous accesses aren't recorded, as their order depends on the executor.
+    /// Comparing logs recorded during, for example, different runs of the same tick
+    /// with [`AccessLog::first_divergence`] then points out nondeterminism in the order
+    /// of accesses.
+    ///
+    /// [`finish_access_log`]: #method.finish_access_log
+    /// [`AccessLog::first_divergence`]: struct.AccessLog.html#method.first_divergence
+    pub fn start_access_log(&self) {
+        self.access_recorder.start();
+    }
+
+    /// Stops recording accesses to resources of the container, returning the log of ones
+    /// recorded since [`start_access_log`] was called.
+    ///
+    /// [`start_access_log`]: #method.start_access_log
+    pub fn finish_access_log(&self) -> AccessLog {
+        self.access_recorder.finish()
+    }
+
+    /// Installs a panic hook that prints the [`borrow_state`] of the container to `stderr`,
+    /// after running the previously installed hook.
+    ///
+    /// The hook only holds a weak reference to the container, and prints nothing
+    /// once it's dropped. With the `track-borrows` feature, the state includes locations
+    /// where the borrowing guards were acquired.
+    ///
+    /// [`borrow_state`]: #method.borrow_state
+    pub fn install_panic_hook(resources: &Arc<Self>)
+    where
+        M: 'static,
+    {
+        let resources = Arc::downgrade(resources);
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous(info);
+            if let Some(resources) = resources.upgrade() {
+                eprint!(
+                    "resources borrowed during the panic:\n{}",
+                    resources.borrow_state()
+                );
+            }
+        }));
+    }
+
+    /// Borrows every resource in the container immutably at once, returning a guard
+    /// through which any of them can be read without further locking.
+    ///
+    /// This either borrows all of the resources, or none of them: if any is currently
+    /// accessed mutably elsewhere, the ones already borrowed are released, and the error
+    /// for the first conflicting one is returned.
+    pub fn read_all(&self) -> Result<AllRef<'_>, InvalidBorrow> {
+        AllRef::from_cells(&self.resources)
+    }
+
+    /// Borrows every resource in the container mutably at once, returning a guard
+    /// through which any of them can be accessed without further locking.
+    ///
+    /// This either borrows all of the resources, or none of them: if any is currently
+    /// accessed elsewhere, the ones already borrowed are released, and the error
+    /// for the first conflicting one is returned.
+    pub fn lock_all(&self) -> Result<AllMut<'_>, InvalidBorrow> {
+        AllMut::from_cells(&self.resources)
+    }
+
+    /// Calls the closure with a [`Branded`] view of the container's resources and
+    /// an [`AccessToken`] with a brand unique to this call, returning the closure's result.
+    ///
+    /// Borrow rules are enforced at compile time against the token, so accessing resources
+    /// through the view never touches their locks and never fails due to borrow conflicts.
+    /// Borrowing the container mutably guarantees that no other references to resources exist.
+    ///
+    /// ```rust
+    /// # use resources::Resources;
+    /// # #[cfg_attr(feature = "derive", derive(resources::Resource))]
+    /// # struct Speed(f32);
+    /// # #[cfg_attr(feature = "derive", derive(resources::Resource))]
+    /// # struct Position(f32);
+    /// let mut resources = Resources::new();
+    /// resources.insert(Speed(2.0));
+    /// resources.insert(Position(0.0));
+    /// resources.branded(|view, mut token| {
+    ///     let speed = view.get::<Speed>(&token).unwrap().0;
+    ///     view.get_mut::<Position>(&mut token).unwrap().0 += speed;
+    /// });
+    /// assert_eq!(resources.get::<Position>().unwrap().0, 2.0);
+    /// ```
+    ///
+    /// [`Branded`]: struct.Branded.html
+    /// [`AccessToken`]: struct.AccessToken.html
+    pub fn branded<R>(
+        &mut self,
+        f: impl for<'brand> FnOnce(Branded<'_, 'brand>, AccessToken<'brand>) -> R,
+    ) -> R {
+        f(Branded::new(&self.resources), AccessToken::new())
+    }
+
+    /// Replaces the resource of type `T` with the given value for the duration of the closure,
+    /// restoring the original afterwards, or removing the value if there was none.
+    ///
+    /// The original is restored even if the closure panics, so that tests can override
+    /// a resource for a single system without affecting others. This works on [sealed]
+    /// containers too, since their structure is the same once the original is restored.
+    ///
+    /// [sealed]: #method.seal
+    #[cfg(feature = "test-util")]
+    pub fn with_override<T: Resource, R>(&mut self, value: T, f: impl FnOnce(&mut Self) -> R) -> R {
+        let guard = Override::new(self, value);
+        f(guard.resources)
+    }
+
+    /// Makes accesses of the resource of type `T` fail with the given failure,
+    /// until cleared via [`clear_injected_failures`], for testing code handling such errors.
+    ///
+    /// Failures are injected into all accessors that lock resources, such as [`get`],
+    /// [`get_mut`], [`get_opt`], [`get_blocking`], [`get_many_dyn`], and [`get_stable`],
+    /// regardless of whether the resource is present. Asynchronous ones, such as
+    /// [`get_async`], wait out conflicting borrows, and so only fail with
+    /// [`InjectedFailure::Missing`].
+    ///
+    /// [`clear_injected_failures`]: #method.clear_injected_failures
+    /// [`get`]: #method.get
+    /// [`get_mut`]: #method.get_mut
+    /// [`get_opt`]: #method.get_opt
+    /// [`get_blocking`]: #method.get_blocking
+    /// [`get_many_dyn`]: #method.get_many_dyn
+    /// [`get_stable`]: #method.get_stable
+    /// [`get_async`]: #method.get_async
+    /// [`InjectedFailure::Missing`]: enum.InjectedFailure.html#variant.Missing
+    #[cfg(feature = "test-util")]
+    pub fn inject_failure<T: Resource>(&self, failure: InjectedFailure) {
+        self.failpoints.inject(TypeId::of::<T>(), failure);
+    }
+
+    /// Makes each access of any resource fail with the given failure with the given
+    /// probability, between `0.0` and `1.0`, until cleared via [`clear_injected_failures`].
+    ///
+    /// Which accesses fail is decided by a pseudorandom generator initialized with the seed,
+    /// so that a sequence of accesses fails the same way each time. Failures set
+    /// via [`inject_failure`] take precedence.
+    ///
+    /// [`clear_injected_failures`]: #method.clear_injected_failures
+    /// [`inject_failure`]: #method.inject_failure
+    #[cfg(feature = "test-util")]
+    pub fn inject_random_failures(&self, failure: InjectedFailure, probability: f64, seed: u64) {
+        self.failpoints.inject_random(failure, probability, seed);
+    }
+
+    /// Stops injecting failures set via [`inject_failure`] and [`inject_random_failures`].
+    ///
+    /// [`inject_failure`]: #method.inject_failure
+    /// [`inject_random_failures`]: #method.inject_random_failures
+    #[cfg(feature = "test-util")]
+    pub fn clear_injected_failures(&self) {
+        self.failpoints.clear();
+    }
+
+    /// Creates a new container holding clones of resources of the types in the tuple `S`,
+    /// such as `(A, B, C)`.
+    ///
+    /// All of the resources are borrowed immutably at once while being cloned. If any of them
+    /// is currently accessed mutably elsewhere, or is not present in the container,
+    /// returns the appropriate error.
+    pub fn clone_subset<S: CloneSubset>(&self) -> Result<Self, CantGetResource> {
+        S::clone_subset(self)
+    }
+
+    /// Moves resources of the given types into the other container, without cloning them,
+    /// replacing resources of the same types in it. Types not present in this container
+    /// are skipped.
+    ///
+    /// Moved resources are not considered removed, so their [finalizers] don't run.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either container is [sealed] and its structure would change.
+    ///
+    /// [finalizers]: #method.set_finalizer
+    /// [sealed]: #method.seal
+    #[track_caller]
+    pub fn move_to<N>(&mut self, destination: &mut Resources<N>, types: &[TypeId]) {
+        // Checked before moving any, so that a panic doesn't leave the move half-done.
+        for type_id in types {
+            if let Some(cell) = self.resources.get(type_id) {
+                self.storage.assert_unsealed(cell.type_name());
+                if !destination.resources.contains_key(type_id) {
+                    destination.storage.assert_unsealed(cell.type_name());
+                }
+            }
+        }
+        for type_id in types {
+            destination.extend(self.take_boxed(*type_id));
+        }
+    }
+
+    /// Removes the resource of the type from the container, without running its finalizer,
+    /// returning it boxed.
+    pub(crate) fn take_boxed(&mut self, type_id: TypeId) -> Option<Box<dyn Resource>> {
+        let cell = self.resources.remove(&type_id)?;
+        self.storage.resource_removed(type_id, cell.type_name());
+        Some(cell.into_boxed(&mut self.storage))
+    }
+
+    /// Like `resource_ptr()`, but for writing to the resource, without releasing its lock
+    /// afterwards: invalidates thread-local snapshots of it beforehand.
+    pub(crate) fn resource_ptr_mut<T: Resource>(&mut self) -> Option<NonNull<T>> {
+        let cell = self.resources.get(&TypeId::of::<T>())?;
+        #[cfg(feature = "local-cache")]
+        cell.lock.invalidate_local();
+        Some(unsafe { NonNull::new_unchecked(cell.resource_ptr() as *mut T) })
+    }
+
+    /// Removes the cell of the resource of the type from the container, keeping its lock's
+    /// strategy, so that it can be put back via `restore_cell()`.
+    pub(crate) fn take_cell(&mut self, type_id: TypeId) -> Option<ResourceCell> {
+        let cell = self.resources.remove(&type_id)?;
+        self.storage.resource_removed(type_id, cell.type_name());
+        Some(cell)
+    }
+
+    /// Puts back a cell taken via `take_cell()`, dropping the one that took its place, if any.
+    pub(crate) fn restore_cell(&mut self, type_id: TypeId, cell: ResourceCell) {
+        let type_name = cell.type_name();
+        match self.resources.insert(type_id, cell) {
+            Some(replaced) => {
+                replaced.drop_in(&mut self.storage);
+                self.storage.resource_replaced(type_id);
+            }
+            None => self.storage.resource_inserted(type_id, type_name),
+        }
+    }
+
+    /// Drops a cell taken via `take_cell()`, without running the resource's finalizer.
+    pub(crate) fn drop_cell(&mut self, cell: ResourceCell) {
+        cell.drop_in(&mut self.storage);
+    }
+
+    /// Changes the resource of the type in place, counting it as a replacement.
+    pub(crate) fn replace_in_cell(&mut self, type_id: TypeId, f: impl FnOnce(&mut ResourceCell)) {
+        if let Some(cell) = self.resources.get_mut(&type_id) {
+            f(cell);
+            self.storage.resource_replaced(type_id);
+        }
+    }
+
+    /// Returns a pointer to the stored resource of type `T`, without borrowing it.
+    pub(crate) fn resource_ptr<T: Resource>(&self) -> Option<NonNull<T>> {
+        let cell = self.resources.get(&TypeId::of::<T>())?;
+        // Resources are keyed by their types, so the cast is valid.
+        Some(unsafe { NonNull::new_unchecked(cell.resource_ptr() as *mut T) })
+    }
+
+    /// Calls the closure with a [`Transaction`] handle, through which resources
+    /// of the container can be changed, returning the closure's result.
+    ///
+    /// If the closure returns an error or panics, all changes made through the handle
+    /// are rolled back, restoring the resources to their original states;
+    /// see [`Transaction`] for details.
+    ///
+    /// [`Transaction`]: struct.Transaction.html
+    pub fn transaction<R, E>(
+        &mut self,
+        f: impl FnOnce(&mut Transaction<'_, M>) -> Result<R, E>,
+    ) -> Result<R, E> {
+        let mut transaction = Transaction::new(self);
+        let result = f(&mut transaction);
+        if result.is_ok() {
+            transaction.commit();
+        }
+        result
+    }
+
+    /// Moves resources of the types in the tuple `S`, such as `(A, B, C)`,
+    /// into the other container; see [`move_to`] for details.
+    ///
+    /// [`move_to`]: #method.move_to
+    pub fn move_types_to<S: ResourceTypes, N>(&mut self, destination: &mut Resources<N>) {
+        self.move_to(destination, &S::type_ids());
+    }
+
+    /// Consumes the container, splitting it in two: a new container holding resources
+    /// whose types satisfy the predicate, and the remainder of this one.
+    ///
+    /// Settings of the container, such as [finalizers], are kept by the remainder only.
+    ///
+    /// [finalizers]: #method.set_finalizer
+    pub fn split_filter(mut self, predicate: impl Fn(TypeId) -> bool) -> (Self, Self) {
+        let matching = self
+            .type_ids()
+            .filter(|type_id| predicate(*type_id))
+            .collect::<Vec<_>>();
+        let mut split = Self::default();
+        self.move_to(&mut split, &matching);
+        (split, self)
+    }
+
+    /// Runs the systems, running ones that don't conflict concurrently on scoped threads,
+    /// and returns once all of them are done.
+    ///
+    /// Systems conflict if one of them declares writing a resource that the other
+    /// declares reading or writing; declaring the type of a [lens] or an [alias] counts
+    /// as declaring the resource it projects. Conflicting systems run in the order they were
+    /// given in, and a system doesn't start before every earlier system it conflicts with
+    /// is done. Any panic in a system is propagated after all of the running ones are done.
+    ///
+    /// ```rust
+    /// # use resources::{Resources, System};
+    /// # #[cfg_attr(feature = "derive", derive(resources::Resource))]
+    /// # struct Speed(f32);
+    /// # #[cfg_attr(feature = "derive", derive(resources::Resource))]
+    /// # struct Position(f32);
+    /// let mut resources = Resources::new();
+    /// resources.insert(Speed(2.0));
+    /// resources.insert(Position(0.0));
+    /// resources.par_exec(vec![
+    ///     System::new(|resources| {
+    ///         let speed = resources.get::<Speed>().unwrap();
+    ///         resources.get_mut::<Position>().unwrap().0 += speed.0;
+    ///     })
+    ///     .reads::<(Speed,)>()
+    ///     .writes::<(Position,)>(),
+    ///     System::new(|resources| resources.get_mut::<Speed>().unwrap().0 = 1.0)
+    ///         .writes::<(Speed,)>(),
+    /// ]);
+    /// assert_eq!(resources.get::<Position>().unwrap().0, 2.0);
+    /// ```
+    ///
+    /// [lens]: #method.register_lens
+    /// [alias]: #method.alias
+    pub fn par_exec<'a>(&self, systems: impl IntoIterator<Item = System<'a, M>>) {
+        exec::par_exec(self, systems.into_iter().collect());
+    }
+
+    /// Passes each resource in the container to the given visitor, borrowing it immutably
+    /// for the duration of the call.
+    ///
+    /// See [`ResourceVisitor`] for details.
+    ///
+    /// [`ResourceVisitor`]: trait.ResourceVisitor.html
+    pub fn visit(&self, visitor: &mut dyn ResourceVisitor) {
+        for (&type_id, cell) in &self.resources {
+            match Ref::<dyn Resource>::from_cell(cell) {
+                Ok(resource) => visitor.resource(type_id, cell.type_name(), resource),
+                Err(_) => visitor.borrowed(type_id, cell.type_name()),
+            }
+        }
+    }
+
+    /// Returns a parallel iterator over mutable references to all resources in the container,
+    /// for visiting them on the `rayon` thread pool.
+    ///
+    /// The container is borrowed mutably, so no resource can be borrowed elsewhere.
+    /// Each resource counts as accessed mutably; [read-only] ones are skipped.
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
+    /// # use resources::Resources;
+    /// use rayon::iter::ParallelIterator;
+    ///
+    /// let mut resources = Resources::new();
+    /// resources.insert(vec![1u32; 16]);
+    /// resources.insert(vec![1u64; 16]);
+    /// resources.par_values_mut().for_each(|resource| {
+    ///     if let Some(values) = resource.downcast_mut::<Vec<u32>>() {
+    ///         values.truncate(1);
+    ///     }
+    /// });
+    /// assert_eq!(resources.get::<Vec<u32>>().unwrap().len(), 1);
+    /// # }
+    /// ```
+    ///
+    /// [read-only]: #method.insert_readonly
+    #[cfg(feature = "rayon")]
+    pub fn par_values_mut(&mut self) -> impl ParallelIterator<Item = &mut dyn Resource> + '_ {
+        self.resources
+            .values_mut()
+            .filter(|cell| cell.lock.kind() != LockKind::ReadOnly)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|cell| {
+                if cell.lock.try_lock_exclusive() {
+                    unsafe { cell.lock.unlock_exclusive() };
+                }
+                // The container is borrowed mutably, so nothing else can access the resource.
+                unsafe { &mut *cell.resource_ptr() }
+            })
+    }
+
+    /// Blocks until a mutable reference to the stored resource of type `T` is dropped,
+    /// after this method was called, except for references returned by [`get_mut_untracked`].
+    ///
+    /// This will deadlock if the current thread holds a mutable reference to the resource.
+    ///
+    /// [`get_mut_untracked`]: #method.get_mut_untracked
+    #[cfg(feature = "blocking")]
+    pub fn wait_changed<T: Resource>(&self) -> Result<(), NoSuchResource> {
         self.resources
             .get(&TypeId::of::<T>())
-            .ok_or_else(|| NoSuchResource.into())
-            .and_then(|lock| Ref::from_lock(lock).map_err(|error| error.into()))
+            .ok_or(NoSuchResource {
+                type_name: type_name::<T>(),
+            })
+            .map(|cell| cell.lock.wait_exclusive_release())
     }
 
-    /// Returns a mutable reference to the stored resource of type `T`.
+    /// Returns a stream that yields each time a mutable reference to the stored resource
+    /// of type `T` is dropped, after this method was called, except for references returned
+    /// by [`get_mut_untracked`].
     ///
-    /// If such a resource is currently accessed immutably or mutably elsewhere,
-    /// or is not present in the container, returns the appropriate error.
-    pub fn get_mut<T: Resource>(&self) -> Result<RefMut<T>, CantGetResource> {
+    /// See [`Changes`] for details.
+    ///
+    /// [`Changes`]: struct.Changes.html
+    /// [`get_mut_untracked`]: #method.get_mut_untracked
+    #[cfg(feature = "stream")]
+    pub fn changes<T: Resource>(&self) -> Result<Changes<'_>, NoSuchResource> {
         self.resources
             .get(&TypeId::of::<T>())
-            .ok_or_else(|| NoSuchResource.into())
-            .and_then(|lock| RefMut::from_lock(lock).map_err(|error| error.into()))
+            .ok_or(NoSuchResource {
+                type_name: type_name::<T>(),
+            })
+            .map(|cell| Changes::new(&cell.lock))
+    }
+
+    /// Returns a stream of structural changes of the container made after this method
+    /// was called: insertions of resources of new types, and removals.
+    ///
+    /// See [`StructureChanges`] for details.
+    ///
+    /// [`StructureChanges`]: struct.StructureChanges.html
+    #[cfg(feature = "stream")]
+    pub fn structure_changes(&self) -> StructureChanges {
+        self.storage.structure_changes()
     }
 
     /// Retrieves up to 16 resources of any combination of mutability.
@@ -109,6 +3514,7 @@ impl Resources {
     ///
     /// # Example
     /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
     /// # use resources::Resources;
     /// let mut resources = Resources::new();
     /// assert!(resources.insert(0f32).is_none());
@@ -130,12 +3536,275 @@ impl Resources {
     ///     assert!(resources.fetch::<&mut f32>().is_err()); // f32 is already borrowed.
     /// }
     /// assert!(resources.fetch::<&bool>().is_err());// There is no bool in the container.
+    /// # }
     /// ```
     #[cfg(feature = "fetch")]
-    pub fn fetch<R>(&self) -> Result<<R as Fetch>::Refs, CantFetch>
+    pub fn fetch<R>(&self) -> Result<<R as Fetch<'_>>::Refs, CantFetch>
     where
         for<'a> R: Fetch<'a>,
     {
         R::fetch(self)
     }
+
+    /// Looks up the resources to be retrieved with [`fetch`] once, returning a plan
+    /// that borrows them repeatedly without hashing their types each time.
+    ///
+    /// Resources projected by [lenses] are looked up along with the resources they're
+    /// projected from. Fails if any of the resources isn't present in the container;
+    /// conflicting borrows are only checked when the plan is fetched.
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "explicit"))] {
+    /// # use resources::Resources;
+    /// let mut resources = Resources::new();
+    /// resources.insert(1u32);
+    /// resources.insert(0f32);
+    /// let prepared = resources.prepare::<(&u32, &mut f32)>().unwrap();
+    /// for _ in 0..3 {
+    ///     let (res_u32, mut res_f32) = prepared.fetch().unwrap();
+    ///     *res_f32 += *res_u32 as f32;
+    /// }
+    /// assert_eq!(*resources.get::<f32>().unwrap(), 3.0);
+    /// # }
+    /// ```
+    ///
+    /// [`fetch`]: #method.fetch
+    /// [lenses]: #method.register_lens
+    #[cfg(feature = "fetch")]
+    pub fn prepare<'a, R: Fetch<'a>>(&'a self) -> Result<Prepared<'a, R, M>, CantFetch> {
+        Prepared::new(self)
+    }
+
+    /// Pairs the container with the `hecs::World`, for systems that fetch resources
+    /// and query entities in one call, with unified error handling.
+    ///
+    /// See [`WorldResources`] for details.
+    ///
+    /// [`WorldResources`]: struct.WorldResources.html
+    #[cfg(feature = "hecs")]
+    pub fn with_world<'a>(&'a self, world: &'a hecs::World) -> WorldResources<'a, M> {
+        WorldResources::new(world, self)
+    }
+
+    /// Makes the resource of type `T` accessible by scripts under the given name,
+    /// replacing the resource previously registered under it, if any.
+    ///
+    /// The resource is converted to and from script values via its `serde` implementations;
+    /// it doesn't have to be present in the container when registered.
+    #[cfg(feature = "rhai")]
+    pub fn register_script_resource<T>(&mut self, name: impl Into<String>)
+    where
+        T: Resource + serde::Serialize + serde::de::DeserializeOwned,
+    {
+        self.scripted.insert(name.into(), Binding::new::<T>());
+    }
+
+    /// Returns the field at the given path of the resource registered under the name,
+    /// converted to a script value.
+    ///
+    /// The path consists of field names and array indices separated by dots,
+    /// such as `position.x` or `items.0`; an empty path returns the whole resource.
+    #[cfg(feature = "rhai")]
+    pub fn script_get(&self, name: &str, path: &str) -> Result<rhai::Dynamic, ScriptError> {
+        self.binding(name)?.get_path(self, path)
+    }
+
+    /// Replaces the field at the given path of the resource registered under the name
+    /// with the script value; see [`script_get`] for the path format.
+    ///
+    /// The resource is borrowed mutably for the duration of the call, and is left unchanged
+    /// if the value can't be converted back to its type.
+    ///
+    /// [`script_get`]: #method.script_get
+    #[cfg(feature = "rhai")]
+    pub fn script_set(
+        &self,
+        name: &str,
+        path: &str,
+        value: rhai::Dynamic,
+    ) -> Result<(), ScriptError> {
+        self.binding(name)?.set_path(self, path, value)
+    }
+
+    #[cfg(feature = "rhai")]
+    fn binding(&self, name: &str) -> Result<&Binding<M>, ScriptError> {
+        self.scripted
+            .get(name)
+            .ok_or_else(|| ScriptError::NotRegistered {
+                name: name.to_string(),
+            })
+    }
+
+    /// Registers `get_resource(name, path)` and `set_resource(name, path, value)` functions
+    /// with the `rhai` engine, calling [`script_get`] and [`script_set`] on the container.
+    ///
+    /// Errors are raised as runtime errors of the script.
+    ///
+    /// ```rust
+    /// # use resources::Resources;
+    /// # use serde::{Deserialize, Serialize};
+    /// # use std::sync::Arc;
+    /// #[derive(Serialize, Deserialize)]
+    /// # #[cfg_attr(feature = "derive", derive(resources::Resource))]
+    /// struct Player {
+    ///     health: i64,
+    /// }
+    ///
+    /// let mut resources = Resources::new();
+    /// resources.insert(Player { health: 10 });
+    /// resources.register_script_resource::<Player>("player");
+    /// let resources = Arc::new(resources);
+    ///
+    /// let mut engine = rhai::Engine::new();
+    /// Resources::register_script_functions(&resources, &mut engine);
+    /// engine
+    ///     .run(r#"set_resource("player", "health", get_resource("player", "health") - 3)"#)
+    ///     .unwrap();
+    /// assert_eq!(resources.get::<Player>().unwrap().health, 7);
+    /// ```
+    ///
+    /// [`script_get`]: #method.script_get
+    /// [`script_set`]: #method.script_set
+    #[cfg(feature = "rhai")]
+    pub fn register_script_functions(resources: &Arc<Self>, engine: &mut rhai::Engine)
+    where
+        M: 'static,
+    {
+        let getter = Arc::clone(resources);
+        engine.register_fn(
+            "get_resource",
+            move |name: &str, path: &str| -> Result<rhai::Dynamic, Box<rhai::EvalAltResult>> {
+                getter
+                    .script_get(name, path)
+                    .map_err(|error| error.to_string().into())
+            },
+        );
+        let setter = Arc::clone(resources);
+        engine.register_fn(
+            "set_resource",
+            move |name: &str,
+                  path: &str,
+                  value: rhai::Dynamic|
+                  -> Result<(), Box<rhai::EvalAltResult>> {
+                setter
+                    .script_set(name, path, value)
+                    .map_err(|error| error.to_string().into())
+            },
+        );
+    }
+}
+
+impl<M> Debug for Resources<M> {
+    /// Lists names of types of the resources, without accessing them.
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut type_names = self.type_names().collect::<Vec<_>>();
+        type_names.sort_unstable();
+        f.debug_set().entries(type_names).finish()
+    }
+}
+
+impl<M> Drop for Resources<M> {
+    fn drop(&mut self) {
+        // Without priorities or finalizers, resources can be dropped along with the map,
+        // in any order.
+        if self.storage.has_drop_priorities() || !self.finalizers.is_empty() {
+            self.clear();
+        }
+    }
+}
+
+impl<M> Extend<Box<dyn Resource>> for Resources<M> {
+    /// Inserts the given boxed resources, each keyed by the type of its value.
+    ///
+    /// Resources of types that were already present are replaced. Panics if a resource
+    /// of a new type would be inserted, and the container is [sealed], or, in debug builds,
+    /// resources of types it [depends on] are missing.
+    ///
+    /// [sealed]: #method.seal
+    /// [depends on]: #method.add_dependency
+    #[track_caller]
+    fn extend<I: IntoIterator<Item = Box<dyn Resource>>>(&mut self, resources: I) {
+        for resource in resources {
+            let type_id = (*resource).as_any().type_id();
+            // A replaced resource is swapped in place, keeping its lock, like `insert()` does.
+            if let Some(cell) = self.resources.get_mut(&type_id) {
+                cell.replace_boxed(resource, &mut self.storage);
+                self.storage.resource_replaced(type_id);
+            } else {
+                self.storage.assert_unsealed((*resource).type_name());
+                if cfg!(debug_assertions) {
+                    if let Some(dependencies) = self.dependencies.get(&type_id) {
+                        if let Err(missing) = self.check_present(dependencies) {
+                            panic!("cannot insert `{}`: {}", (*resource).type_name(), missing);
+                        }
+                    }
+                }
+                let cell = ResourceCell::from_boxed(resource, &mut self.storage);
+                let type_name = cell.type_name();
+                self.resources.insert(type_id, cell);
+                self.storage.resource_inserted(type_id, type_name);
+            }
+        }
+    }
+}
+
+impl<M> IntoIterator for Resources<M> {
+    type Item = (TypeId, Box<dyn Resource>);
+    type IntoIter = IntoIter;
+
+    /// Consumes the container, yielding each resource boxed, along with the `TypeId` of its type.
+    ///
+    /// Resources are yielded in arbitrary order, and moved out of the container, so their
+    /// [finalizers] don't run, and [drop priorities] don't order dropping them,
+    /// unlike when the container is dropped or [cleared].
+    ///
+    /// [finalizers]: struct.Resources.html#method.set_finalizer
+    /// [drop priorities]: struct.Resources.html#method.set_drop_priority
+    /// [cleared]: struct.Resources.html#method.clear
+    fn into_iter(mut self) -> IntoIter {
+        IntoIter {
+            resources: mem::take(&mut self.resources).into_iter(),
+            storage: mem::take(&mut self.storage),
+        }
+    }
+}
+
+/// An owning iterator over resources of a [`Resources`] container,
+/// yielding each boxed, along with the `TypeId` of its type.
+/// This is returned by the `into_iter()` method of [`Resources`].
+///
+/// Resources not yet yielded are dropped along with the iterator, in arbitrary order,
+/// without running their [finalizers].
+///
+/// [`Resources`]: struct.Resources.html
+/// [finalizers]: struct.Resources.html#method.set_finalizer
+pub struct IntoIter {
+    // Declared first, so that the remaining resources are dropped before their storage.
+    resources: hash_map::IntoIter<TypeId, ResourceCell, StorageAllocator>,
+    storage: Storage,
+}
+
+impl Iterator for IntoIter {
+    type Item = (TypeId, Box<dyn Resource>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let storage = &mut self.storage;
+        self.resources
+            .next()
+            .map(|(type_id, cell)| (type_id, cell.into_boxed(storage)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.resources.size_hint()
+    }
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+impl<M> FromIterator<Box<dyn Resource>> for Resources<M> {
+    fn from_iter<I: IntoIterator<Item = Box<dyn Resource>>>(resources: I) -> Self {
+        let mut container = Self::default();
+        container.extend(resources);
+        container
+    }
 }
@@ -0,0 +1,174 @@
+use im::HashMap;
+use std::{
+    any::{type_name, Any, TypeId},
+    fmt::{Debug, Formatter, Result as FmtResult},
+    sync::Arc,
+};
+
+use crate::{error::NoSuchResource, hash::TypeIdBuildHasher, map::Resource};
+
+/// Resource of an [`ImResources`] container, shared with its clones until it's mutated.
+///
+/// [`ImResources`]: struct.ImResources.html
+#[derive(Clone)]
+struct SharedResource {
+    resource: Arc<dyn Any + Send + Sync>,
+    type_name: &'static str,
+    clone: fn(&(dyn Any + Send + Sync)) -> Arc<dyn Any + Send + Sync>,
+}
+
+fn downcast_ref<T: Resource>(resource: &(dyn Any + Send + Sync)) -> &T {
+    resource
+        .downcast_ref::<T>()
+        .unwrap_or_else(|| panic!("downcasting resources should always succeed"))
+}
+
+fn clone_resource<T: Resource + Clone>(
+    resource: &(dyn Any + Send + Sync),
+) -> Arc<dyn Any + Send + Sync> {
+    Arc::new(downcast_ref::<T>(resource).clone())
+}
+
+impl SharedResource {
+    fn new<T: Resource + Clone>(resource: T) -> Self {
+        Self {
+            resource: Arc::new(resource),
+            type_name: type_name::<T>(),
+            clone: clone_resource::<T>,
+        }
+    }
+
+    fn get<T: Resource>(&self) -> &T {
+        downcast_ref(&*self.resource)
+    }
+
+    /// Returns the resource mutably, cloning it first if it's shared.
+    fn get_mut<T: Resource>(&mut self) -> &mut T {
+        if Arc::get_mut(&mut self.resource).is_none() {
+            self.resource = (self.clone)(&*self.resource);
+        }
+        Arc::get_mut(&mut self.resource)
+            .and_then(|resource| resource.downcast_mut::<T>())
+            .unwrap_or_else(|| panic!("downcasting resources should always succeed"))
+    }
+
+    /// Returns the resource, cloning it if it's shared.
+    fn into_inner<T: Resource + Clone>(self) -> T {
+        let resource = self
+            .resource
+            .downcast::<T>()
+            .unwrap_or_else(|_| panic!("downcasting resources should always succeed"));
+        Arc::try_unwrap(resource).unwrap_or_else(|resource| (*resource).clone())
+    }
+}
+
+/// A [`Resource`] container backed by a persistent hash map, that is cloned in constant time.
+///
+/// Clones share the map's structure and all resources, and mutating a clone copies only
+/// what changes: the path to the resource in the map, and the resource itself, if it's
+/// still shared with other clones. This makes keeping many past versions of the container
+/// cheap, such as for rolling back to an earlier state. Since any access could be shared,
+/// resources are borrowed along with the container, instead of being locked individually,
+/// and have to be `Clone`.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(not(feature = "explicit"))] {
+/// # use resources::ImResources;
+/// let mut resources = ImResources::new();
+/// resources.insert(0u32);
+/// resources.insert(String::from("state"));
+/// let snapshot = resources.clone();
+/// *resources.get_mut::<u32>().unwrap() += 1; // Copies only the `u32`.
+/// assert_eq!(*resources.get::<u32>().unwrap(), 1);
+/// assert_eq!(*snapshot.get::<u32>().unwrap(), 0);
+/// # }
+/// ```
+///
+/// [`Resource`]: trait.Resource.html
+#[derive(Clone, Default)]
+pub struct ImResources {
+    resources: HashMap<TypeId, SharedResource, TypeIdBuildHasher>,
+}
+
+impl ImResources {
+    /// Creates an empty container. Functionally identical to [`::default()`].
+    ///
+    /// [`::default()`]: #impl-Default-for-ImResources
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of resources in the container.
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    /// Returns `true` if the container holds no resources.
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    /// Returns `true` if a resource of type `T` exists in the container.
+    pub fn contains<T: Resource>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Inserts the given resource of type `T` into the container.
+    ///
+    /// If a resource of this type was already present,
+    /// it will be updated, and the original returned.
+    pub fn insert<T: Resource + Clone>(&mut self, resource: T) -> Option<T> {
+        self.resources
+            .insert(TypeId::of::<T>(), SharedResource::new(resource))
+            .map(SharedResource::into_inner)
+    }
+
+    /// Removes the resource of type `T` from the container.
+    ///
+    /// If a resource of this type was present in the container, it will be returned,
+    /// cloned if it's shared with other containers.
+    pub fn remove<T: Resource + Clone>(&mut self) -> Option<T> {
+        self.resources
+            .remove(&TypeId::of::<T>())
+            .map(SharedResource::into_inner)
+    }
+
+    /// Returns a reference to the stored resource of type `T`.
+    ///
+    /// If such a resource is not present in the container, returns an error.
+    pub fn get<T: Resource>(&self) -> Result<&T, NoSuchResource> {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .map(SharedResource::get)
+            .ok_or(NoSuchResource {
+                type_name: type_name::<T>(),
+            })
+    }
+
+    /// Returns a mutable reference to the stored resource of type `T`,
+    /// cloning it first if it's shared with other containers.
+    ///
+    /// If such a resource is not present in the container, returns an error.
+    pub fn get_mut<T: Resource + Clone>(&mut self) -> Result<&mut T, NoSuchResource> {
+        self.resources
+            .get_mut(&TypeId::of::<T>())
+            .map(SharedResource::get_mut)
+            .ok_or(NoSuchResource {
+                type_name: type_name::<T>(),
+            })
+    }
+}
+
+impl Debug for ImResources {
+    /// Lists names of types of the resources.
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut type_names = self
+            .resources
+            .values()
+            .map(|resource| resource.type_name)
+            .collect::<Vec<_>>();
+        type_names.sort_unstable();
+        f.debug_set().entries(type_names).finish()
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+    fmt::{Debug, Formatter, Result as FmtResult},
+};
+
+use crate::{
+    hash::TypeIdBuildHasher,
+    map::{Resource, Resources},
+    stable_id::StableId,
+};
+
+/// Clones a resource of a specific type, see [`TypeVTable::with_clone()`].
+type CloneFn = fn(&dyn Resource) -> Option<Box<dyn Resource>>;
+
+/// Formats a resource of a specific type, see [`TypeVTable::with_debug()`].
+pub(crate) type DebugFn = fn(&dyn Resource, &mut Formatter<'_>) -> FmtResult;
+
+/// Erases a resource of a specific type for serialization, see [`TypeVTable::with_serialize()`].
+#[cfg(feature = "serde")]
+type SerializeFn = fn(&dyn Resource) -> Option<&dyn erased_serde::Serialize>;
+
+/// Table of operations on resources of a specific type, registered by a plugin
+/// via [`PluginRegistry::register()`], for the host to use without compile-time
+/// knowledge of the type.
+///
+/// [`PluginRegistry::register()`]: struct.PluginRegistry.html#method.register
+#[derive(Clone)]
+pub struct TypeVTable {
+    type_id: TypeId,
+    type_name: &'static str,
+    stable_id: Option<StableId>,
+    clone: Option<CloneFn>,
+    debug: Option<DebugFn>,
+    #[cfg(feature = "serde")]
+    serialize: Option<SerializeFn>,
+}
+
+impl TypeVTable {
+    /// Creates a table for the resource type `T`, with no operations.
+    ///
+    /// Methods adding operations panic if given a type other than `T`.
+    pub fn of<T: Resource>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+            stable_id: None,
+            clone: None,
+            debug: None,
+            #[cfg(feature = "serde")]
+            serialize: None,
+        }
+    }
+
+    fn assert_type<T: Resource>(&self) {
+        assert_eq!(
+            self.type_id,
+            TypeId::of::<T>(),
+            "table of `{}` can't operate on `{}`",
+            self.type_name,
+            type_name::<T>()
+        );
+    }
+
+    /// Sets the stable identifier of the type, registered along with the table.
+    pub fn with_stable_id(mut self, id: impl Into<StableId>) -> Self {
+        self.stable_id = Some(id.into());
+        self
+    }
+
+    /// Adds cloning, via the type's `Clone` implementation.
+    pub fn with_clone<T: Resource + Clone>(mut self) -> Self {
+        self.assert_type::<T>();
+        self.clone = Some(|resource| {
+            resource
+                .downcast_ref::<T>()
+                .map(|resource| Box::new(resource.clone()) as Box<dyn Resource>)
+        });
+        self
+    }
+
+    /// Adds debug formatting, via the type's `Debug` implementation.
+    pub fn with_debug<T: Resource + Debug>(mut self) -> Self {
+        self.assert_type::<T>();
+        self.debug = Some(debug_fn::<T>());
+        self
+    }
+
+    /// Adds serialization, via the type's `serde::Serialize` implementation.
+    #[cfg(feature = "serde")]
+    pub fn with_serialize<T: Resource + serde::Serialize>(mut self) -> Self {
+        self.assert_type::<T>();
+        self.serialize = Some(|resource| {
+            resource
+                .downcast_ref::<T>()
+                .map(|resource| resource as &dyn erased_serde::Serialize)
+        });
+        self
+    }
+
+    /// Returns the `TypeId` of the type.
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Returns the compiler-provided name of the type.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns the stable identifier of the type, if it was set.
+    pub fn stable_id(&self) -> Option<&StableId> {
+        self.stable_id.as_ref()
+    }
+
+    /// Returns a boxed clone of the resource, or `None` if cloning wasn't added
+    /// or the resource is of a different type.
+    pub fn clone_resource(&self, resource: &dyn Resource) -> Option<Box<dyn Resource>> {
+        self.clone.and_then(|clone| clone(resource))
+    }
+
+    /// Returns a value that formats the resource with its `Debug` implementation,
+    /// or `None` if debug formatting wasn't added or the resource is of a different type.
+    pub fn debug<'a>(&self, resource: &'a dyn Resource) -> Option<impl Debug + 'a> {
+        let debug = self.debug?;
+        if (*resource).as_any().type_id() != self.type_id {
+            return None;
+        }
+        Some(DebugResource { resource, debug })
+    }
+
+    /// Returns the debug formatting function, if it was added.
+    pub(crate) fn debug_fn(&self) -> Option<DebugFn> {
+        self.debug
+    }
+
+    /// Returns the resource as a type-erased `serde::Serialize`, or `None` if serialization
+    /// wasn't added or the resource is of a different type.
+    #[cfg(feature = "serde")]
+    pub fn serialize<'a>(
+        &self,
+        resource: &'a dyn Resource,
+    ) -> Option<&'a dyn erased_serde::Serialize> {
+        self.serialize.and_then(|serialize| serialize(resource))
+    }
+}
+
+impl Debug for TypeVTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut debug = f.debug_struct("TypeVTable");
+        debug
+            .field("type_name", &self.type_name)
+            .field("stable_id", &self.stable_id)
+            .field("clone", &self.clone.is_some())
+            .field("debug", &self.debug.is_some());
+        #[cfg(feature = "serde")]
+        debug.field("serialize", &self.serialize.is_some());
+        debug.finish()
+    }
+}
+
+/// Returns a function formatting resources of type `T` with its `Debug` implementation.
+pub(crate) fn debug_fn<T: Resource + Debug>() -> DebugFn {
+    |resource, f| match resource.downcast_ref::<T>() {
+        Some(resource) => resource.fmt(f),
+        None => f.write_str(type_name::<T>()),
+    }
+}
+
+pub(crate) struct DebugResource<'a> {
+    pub(crate) resource: &'a dyn Resource,
+    pub(crate) debug: DebugFn,
+}
+
+impl<'a> Debug for DebugResource<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        (self.debug)(self.resource, f)
+    }
+}
+
+/// Tables of types registered by plugins, and which plugin registered each.
+#[derive(Default)]
+pub(crate) struct Plugins {
+    vtables: HashMap<TypeId, TypeVTable, TypeIdBuildHasher>,
+    loaded: HashMap<String, Vec<TypeId>>,
+}
+
+impl Plugins {
+    pub(crate) fn vtable(&self, type_id: TypeId) -> Option<&TypeVTable> {
+        self.vtables.get(&type_id)
+    }
+
+    pub(crate) fn types_of(&self, plugin: &str) -> Option<&[TypeId]> {
+        self.loaded.get(plugin).map(Vec::as_slice)
+    }
+
+    /// Records the table as registered by the plugin, replacing any previous registration.
+    fn register(&mut self, plugin: &str, vtable: TypeVTable) {
+        let type_id = vtable.type_id;
+        for types in self.loaded.values_mut() {
+            types.retain(|registered| *registered != type_id);
+        }
+        self.loaded
+            .entry(plugin.to_string())
+            .or_default()
+            .push(type_id);
+        self.vtables.insert(type_id, vtable);
+    }
+
+    /// Forgets the plugin and tables of its types, returning their `TypeId`s.
+    pub(crate) fn unload(&mut self, plugin: &str) -> Vec<TypeId> {
+        let types = self.loaded.remove(plugin).unwrap_or_default();
+        for type_id in &types {
+            self.vtables.remove(type_id);
+        }
+        types
+    }
+}
+
+/// Handle for registering resource types of a plugin and inserting its resources,
+/// as passed to the closure given to [`Resources::load_plugin()`].
+///
+/// [`Resources::load_plugin()`]: struct.Resources.html#method.load_plugin
+pub struct PluginRegistry<'a, M> {
+    resources: &'a mut Resources<M>,
+    plugin: String,
+}
+
+impl<'a, M> PluginRegistry<'a, M> {
+    pub(crate) fn new(resources: &'a mut Resources<M>, plugin: String) -> Self {
+        Self { resources, plugin }
+    }
+
+    /// Returns the name of the plugin being loaded.
+    pub fn plugin(&self) -> &str {
+        &self.plugin
+    }
+
+    /// Registers the type's table as belonging to the plugin, along with its stable identifier,
+    /// if one was set.
+    pub fn register(&mut self, vtable: TypeVTable) -> &mut Self {
+        if let Some(id) = vtable.stable_id.clone() {
+            self.resources.stable_ids_mut().register(id, vtable.type_id);
+        }
+        self.resources.plugins_mut().register(&self.plugin, vtable);
+        self
+    }
+
+    /// Returns the container, for inserting the plugin's resources.
+    pub fn resources(&mut self) -> &mut Resources<M> {
+        self.resources
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    cell::UnsafeCell,
+    fmt::{Debug, Formatter, Result as FmtResult},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+#[cfg(feature = "explicit")]
+use crate::map::Resource;
+
+/// Set in the index of the middle slot while it holds a value the reader hasn't taken yet.
+const FRESH: usize = 0b100;
+
+/// Three slots shared by the publisher and one subscriber. At any time, each slot
+/// is owned by exactly one of the publisher, the subscriber, and the middle,
+/// which is exchanged atomically by either side.
+struct TripleBuffer<T> {
+    slots: [UnsafeCell<T>; 3],
+    /// Index of the slot in the middle, possibly with the `FRESH` bit set.
+    middle: AtomicUsize,
+}
+
+// Slots are only accessed by whichever side owns them, see above.
+unsafe impl<T: Send> Send for TripleBuffer<T> {}
+unsafe impl<T: Send> Sync for TripleBuffer<T> {}
+
+/// Publisher's end of a triple buffer.
+struct Back<T> {
+    buffer: Arc<TripleBuffer<T>>,
+    /// Index of the slot owned by the publisher.
+    index: usize,
+}
+
+impl<T> Back<T> {
+    fn publish(&mut self, value: T) {
+        // The publisher owns the slot, so nothing else can access it.
+        unsafe { *self.buffer.slots[self.index].get() = value };
+        // Acquiring makes the subscriber's last reads of the slot it gives up
+        // happen before the publisher's writes to it, and releasing makes this write visible.
+        let middle = self
+            .buffer
+            .middle
+            .swap(self.index | FRESH, Ordering::AcqRel);
+        self.index = middle & !FRESH;
+    }
+}
+
+/// Snapshot of a value of type `T`, stored as a resource, that a single publisher commits
+/// and any number of [`Subscriber`]s read, without either side ever waiting for the other.
+///
+/// Each subscriber is backed by its own triple buffer: a committed value is copied
+/// into a slot only the publisher uses, which is then atomically exchanged with the one
+/// in the middle, from where the subscriber takes it the next time it looks. Subscribers
+/// always see the latest complete value, skipping intermediate ones if they fall behind,
+/// and the publisher never blocks on a slow subscriber.
+///
+/// ```rust
+/// # #[cfg(not(feature = "explicit"))] {
+/// # use resources::{Published, Resources};
+/// let mut resources = Resources::new();
+/// resources.insert(Published::new(0u64));
+/// let mut subscriber = resources.subscribe::<u64>().unwrap();
+/// let renderer = std::thread::spawn(move || {
+///     // Never blocks, yielding whichever value was committed last.
+///     *subscriber.latest()
+/// });
+/// resources.get_mut::<Published<u64>>().unwrap().publish(1);
+/// assert!(renderer.join().unwrap() <= 1);
+/// # }
+/// ```
+///
+/// [`Subscriber`]: struct.Subscriber.html
+pub struct Published<T> {
+    current: T,
+    backs: Vec<Back<T>>,
+}
+
+#[cfg(feature = "explicit")]
+impl<T: Clone + Send + Sync + 'static> Resource for Published<T> {}
+
+impl<T> Published<T> {
+    /// Creates the publication, with the given value committed.
+    pub fn new(value: T) -> Self {
+        Self {
+            current: value,
+            backs: Vec::new(),
+        }
+    }
+
+    /// Returns the value committed last.
+    pub fn get(&self) -> &T {
+        &self.current
+    }
+
+    /// Returns the number of live subscribers.
+    pub fn subscribers(&self) -> usize {
+        self.backs
+            .iter()
+            .filter(|back| Arc::strong_count(&back.buffer) > 1)
+            .count()
+    }
+}
+
+impl<T: Clone> Published<T> {
+    /// Commits the value, making it the latest one seen by all subscribers.
+    ///
+    /// The value is cloned for each subscriber, and buffers of dropped subscribers are freed.
+    pub fn publish(&mut self, value: T) {
+        // The subscriber's end is the only other reference to the buffer.
+        self.backs
+            .retain(|back| Arc::strong_count(&back.buffer) > 1);
+        for back in &mut self.backs {
+            back.publish(value.clone());
+        }
+        self.current = value;
+    }
+
+    /// Returns a new subscriber, seeing the value committed last.
+    pub fn subscribe(&mut self) -> Subscriber<T> {
+        let buffer = Arc::new(TripleBuffer {
+            slots: [
+                UnsafeCell::new(self.current.clone()),
+                UnsafeCell::new(self.current.clone()),
+                UnsafeCell::new(self.current.clone()),
+            ],
+            middle: AtomicUsize::new(1),
+        });
+        self.backs.push(Back {
+            buffer: buffer.clone(),
+            index: 2,
+        });
+        Subscriber { buffer, index: 0 }
+    }
+}
+
+impl<T: Debug> Debug for Published<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Published")
+            .field("current", &self.current)
+            .field("subscribers", &self.subscribers())
+            .finish()
+    }
+}
+
+/// Reader of a [`Published`] value, obtained via [`Published::subscribe()`]
+/// or [`Resources::subscribe()`], usually moved to the thread that reads it.
+///
+/// [`Published`]: struct.Published.html
+/// [`Published::subscribe()`]: struct.Published.html#method.subscribe
+/// [`Resources::subscribe()`]: struct.Resources.html#method.subscribe
+pub struct Subscriber<T> {
+    buffer: Arc<TripleBuffer<T>>,
+    /// Index of the slot owned by the subscriber.
+    index: usize,
+}
+
+impl<T> Subscriber<T> {
+    /// Returns `true` if a value was committed since the last call to [`latest`].
+    ///
+    /// [`latest`]: #method.latest
+    pub fn has_update(&self) -> bool {
+        self.buffer.middle.load(Ordering::Relaxed) & FRESH != 0
+    }
+
+    /// Returns the value committed last, without waiting.
+    pub fn latest(&mut self) -> &T {
+        if self.has_update() {
+            // Releasing makes this side's reads of the slot it gives up happen before
+            // the publisher's writes to it, and acquiring makes the new value visible.
+            let middle = self.buffer.middle.swap(self.index, Ordering::AcqRel);
+            self.index = middle & !FRESH;
+        }
+        // The subscriber owns the slot, so nothing else can access it mutably.
+        unsafe { &*self.buffer.slots[self.index].get() }
+    }
+}
+
+impl<T> Debug for Subscriber<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Subscriber")
+            .field("has_update", &self.has_update())
+            .finish()
+    }
+}
@@ -1,72 +1,676 @@
-use parking_lot::{
-    MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
+use std::{
+    any::type_name,
+    borrow::{Borrow, BorrowMut},
+    cmp::Ordering,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    mem::{self, ManuallyDrop},
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
 };
-use std::ops::{Deref, DerefMut};
 
-use crate::{InvalidBorrow, Resource};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
 
-/// Immutable borrow of a [`Resource`] stored in a [`Resources`] container.
+#[cfg(feature = "blocking")]
+use crate::{backoff::Backoff, lock::Lane};
+use crate::{
+    borrows::BorrowSite,
+    instrument::{self, Access, HoldTimer},
+    lock::{ResourceCell, ResourcesRwLock},
+    lock_order::HeldLock,
+    BorrowApi, InvalidBorrow, Resource,
+};
+
+fn downcast_ref<T: Resource>(resource: &dyn Resource) -> NonNull<T> {
+    NonNull::from(
+        resource
+            .downcast_ref::<T>()
+            .unwrap_or_else(|| panic!("downcasting resources should always succeed")),
+    )
+}
+
+fn downcast_mut<T: Resource>(resource: &mut dyn Resource) -> NonNull<T> {
+    NonNull::from(
+        resource
+            .downcast_mut::<T>()
+            .unwrap_or_else(|| panic!("downcasting resources should always succeed")),
+    )
+}
+
+/// Types guards made from a resource's cell can borrow: the resource, downcast to its type,
+/// or the type-erased resource as is.
+pub trait FromCell {
+    /// Returns a pointer to the resource, for use while holding the cell's lock shared.
+    fn shared(resource: &dyn Resource) -> NonNull<Self>;
+
+    /// Returns a pointer to the resource, for use while holding the cell's lock exclusively.
+    fn exclusive(resource: &mut dyn Resource) -> NonNull<Self>;
+}
+
+impl<T: Resource> FromCell for T {
+    fn shared(resource: &dyn Resource) -> NonNull<Self> {
+        downcast_ref(resource)
+    }
+
+    fn exclusive(resource: &mut dyn Resource) -> NonNull<Self> {
+        downcast_mut(resource)
+    }
+}
+
+impl FromCell for dyn Resource {
+    fn shared(resource: &dyn Resource) -> NonNull<Self> {
+        NonNull::from(resource)
+    }
+
+    fn exclusive(resource: &mut dyn Resource) -> NonNull<Self> {
+        NonNull::from(resource)
+    }
+}
+
+/// Immutable borrow of a [`Resource`] stored in a [`Resources`] container,
+/// or of a part of it, as projected by [`map`] or [`try_map`].
+///
+/// Like `&T`, this is `Send` and `Sync` if `T` is `Sync`, which is always the case
+/// for resources: it can be shared between threads, and moved to and released
+/// on a different thread than the one that acquired it.
 ///
 /// [`Resource`]: trait.Resource.html
 /// [`Resources`]: struct.Resources.html
-pub struct Ref<'a, T: Resource> {
-    read_guard: MappedRwLockReadGuard<'a, T>,
-}
-
-impl<'a, T: Resource> Ref<'a, T> {
-    pub(crate) fn from_lock(lock: &'a RwLock<Box<dyn Resource>>) -> Result<Self, InvalidBorrow> {
-        lock.try_read()
-            .map(|guard| Self {
-                read_guard: RwLockReadGuard::map(guard, |resource| {
-                    resource
-                        .downcast_ref::<T>()
-                        .unwrap_or_else(|| panic!("downcasting resources should always succeed"))
-                }),
+/// [`map`]: #method.map
+/// [`try_map`]: #method.try_map
+pub struct Ref<'a, T: ?Sized> {
+    lock: &'a ResourcesRwLock,
+    held: HeldLock,
+    site: BorrowSite<'a>,
+    timer: HoldTimer,
+    /// Place in the lock's queue of asynchronous acquisitions, released after the lock.
+    #[cfg(feature = "tokio")]
+    queued: Option<tokio::sync::RwLockReadGuard<'a, ()>>,
+    resource: NonNull<T>,
+    phantom_data: PhantomData<&'a T>,
+}
+
+// Behaves like `&T`, and the lock may be released from any thread.
+unsafe impl<'a, T: ?Sized + Sync> Send for Ref<'a, T> {}
+unsafe impl<'a, T: ?Sized + Sync> Sync for Ref<'a, T> {}
+
+impl<'a, T: ?Sized + FromCell> Ref<'a, T> {
+    #[track_caller]
+    pub(crate) fn from_cell(cell: &'a ResourceCell) -> Result<Self, InvalidBorrow> {
+        Self::from_cell_labeled(cell, None)
+    }
+
+    /// Like `from_cell()`, but records the label along with the location of the borrow.
+    #[track_caller]
+    pub(crate) fn from_cell_labeled(
+        cell: &'a ResourceCell,
+        label: Option<&'static str>,
+    ) -> Result<Self, InvalidBorrow> {
+        let held = HeldLock::record(cell);
+        if cell.lock.try_lock_shared() {
+            let site = BorrowSite::record_labeled(cell, label);
+            Ok(unsafe { Self::from_locked_cell(cell, held, site, BorrowApi::Get) })
+        } else {
+            instrument::conflict(cell.type_name(), Access::Shared);
+            Err(InvalidBorrow::Immutable {
+                type_name: cell.type_name(),
+                conflict: cell.conflict(),
+            })
+        }
+    }
+
+    #[track_caller]
+    pub(crate) fn from_cell_recursive(cell: &'a ResourceCell) -> Result<Self, InvalidBorrow> {
+        let held = HeldLock::record(cell);
+        if cell.lock.try_lock_shared_recursive() {
+            Ok(unsafe {
+                Self::from_locked_cell(cell, held, BorrowSite::record(cell), BorrowApi::Recursive)
+            })
+        } else {
+            instrument::conflict(cell.type_name(), Access::Shared);
+            Err(InvalidBorrow::Immutable {
+                type_name: cell.type_name(),
+                conflict: cell.conflict(),
+            })
+        }
+    }
+
+    #[cfg(feature = "blocking")]
+    #[track_caller]
+    pub(crate) fn from_cell_blocking(
+        cell: &'a ResourceCell,
+        lane: Lane,
+        backoff: &Backoff,
+    ) -> Result<Self, InvalidBorrow> {
+        let held = HeldLock::acquire(cell);
+        if cell.lock.lock_shared(lane, backoff) {
+            Ok(unsafe {
+                Self::from_locked_cell(cell, held, BorrowSite::record(cell), BorrowApi::Blocking)
+            })
+        } else {
+            instrument::conflict(cell.type_name(), Access::Shared);
+            Err(InvalidBorrow::Immutable {
+                type_name: cell.type_name(),
+                conflict: cell.conflict(),
+            })
+        }
+    }
+
+    #[cfg(feature = "blocking")]
+    #[track_caller]
+    pub(crate) fn from_cell_blocking_recursive(
+        cell: &'a ResourceCell,
+        backoff: &Backoff,
+    ) -> Result<Self, InvalidBorrow> {
+        let held = HeldLock::acquire(cell);
+        if cell.lock.lock_shared_recursive(backoff) {
+            Ok(unsafe {
+                Self::from_locked_cell(cell, held, BorrowSite::record(cell), BorrowApi::Recursive)
             })
-            .ok_or_else(|| InvalidBorrow::Immutable)
+        } else {
+            instrument::conflict(cell.type_name(), Access::Shared);
+            Err(InvalidBorrow::Immutable {
+                type_name: cell.type_name(),
+                conflict: cell.conflict(),
+            })
+        }
+    }
+
+    #[cfg(any(feature = "async", feature = "tokio"))]
+    pub(crate) async fn from_cell_async(cell: &'a ResourceCell) -> Self {
+        // Tasks move between threads, so their guards can't be attributed to the current one,
+        // but the acquisition can still deadlock with locks the thread holds.
+        HeldLock::check(cell);
+        let held = HeldLock::unordered();
+        #[cfg(feature = "tokio")]
+        let queued = cell.lock.queue_shared().await;
+        cell.lock.lock_shared_async().await;
+        #[allow(unused_mut)]
+        let mut this = unsafe {
+            Self::from_locked_cell(cell, held, BorrowSite::untracked(), BorrowApi::Async)
+        };
+        #[cfg(feature = "tokio")]
+        {
+            this.queued = Some(queued);
+        }
+        this
+    }
+
+    /// # Safety
+    /// The cell's lock must be held shared, and will be released when the `Ref` is dropped.
+    unsafe fn from_locked_cell(
+        cell: &'a ResourceCell,
+        held: HeldLock,
+        site: BorrowSite<'a>,
+        api: BorrowApi,
+    ) -> Self {
+        let resource = T::shared(&*cell.resource_ptr());
+        Self::from_locked_parts(cell, held, site, api, resource)
+    }
+}
+
+impl<'a, T: ?Sized> Ref<'a, T> {
+    /// # Safety
+    /// The cell's lock must be held shared, and will be released when the `Ref` is dropped.
+    /// The resource must be the cell's, or a part of it.
+    unsafe fn from_locked_parts(
+        cell: &'a ResourceCell,
+        held: HeldLock,
+        site: BorrowSite<'a>,
+        api: BorrowApi,
+        resource: NonNull<T>,
+    ) -> Self {
+        cell.lock.acquired_via(api);
+        Self {
+            lock: &cell.lock,
+            held,
+            site,
+            timer: HoldTimer::start(cell.type_name(), Access::Shared),
+            #[cfg(feature = "tokio")]
+            queued: None,
+            resource,
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// # Safety
+    /// The resource must be guarded by the lock, and be valid for the lifetime.
+    pub(crate) unsafe fn from_lock(
+        lock: &'a ResourcesRwLock,
+        resource: NonNull<T>,
+    ) -> Result<Self, InvalidBorrow> {
+        if lock.try_lock_shared() {
+            lock.acquired_via(BorrowApi::Other);
+            Ok(Self {
+                lock,
+                held: HeldLock::unordered(),
+                site: BorrowSite::untracked(),
+                timer: HoldTimer::start(type_name::<T>(), Access::Shared),
+                #[cfg(feature = "tokio")]
+                queued: None,
+                resource,
+                phantom_data: PhantomData,
+            })
+        } else {
+            instrument::conflict(type_name::<T>(), Access::Shared);
+            Err(InvalidBorrow::Immutable {
+                type_name: type_name::<T>(),
+                conflict: lock.conflict(),
+            })
+        }
+    }
+
+    /// Makes a new `Ref` for a part of the borrowed resource.
+    ///
+    /// This is an associated function, so that it doesn't shadow a method of the resource.
+    pub fn map<U: ?Sized>(this: Self, f: impl FnOnce(&T) -> &U) -> Ref<'a, U> {
+        match Self::try_map(this, |resource| Some(f(resource))) {
+            Ok(mapped) => mapped,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Makes a new `Ref` for an optional part of the borrowed resource,
+    /// returning the original `Ref` if the closure returns `None`.
+    ///
+    /// This is an associated function, so that it doesn't shadow a method of the resource.
+    pub fn try_map<U: ?Sized>(
+        this: Self,
+        f: impl FnOnce(&T) -> Option<&U>,
+    ) -> Result<Ref<'a, U>, Self> {
+        match f(unsafe { this.resource.as_ref() }).map(NonNull::from) {
+            Some(resource) => {
+                let this = ManuallyDrop::new(this);
+                Ok(Ref {
+                    lock: this.lock,
+                    held: unsafe { ptr::read(&this.held) },
+                    site: unsafe { ptr::read(&this.site) },
+                    timer: unsafe { ptr::read(&this.timer) },
+                    #[cfg(feature = "tokio")]
+                    queued: unsafe { ptr::read(&this.queued) },
+                    resource,
+                    phantom_data: PhantomData,
+                })
+            }
+            None => Err(this),
+        }
     }
 }
 
-impl<'a, T: Resource> Deref for Ref<'a, T> {
+impl<'a, T: ?Sized> Deref for Ref<'a, T> {
     type Target = T;
 
     fn deref(&self) -> &T {
-        self.read_guard.deref()
+        unsafe { self.resource.as_ref() }
+    }
+}
+
+impl<'a, T: ?Sized> AsRef<T> for Ref<'a, T> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<'a, T: ?Sized> Borrow<T> for Ref<'a, T> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<'a, T: ?Sized> Drop for Ref<'a, T> {
+    fn drop(&mut self) {
+        unsafe { self.lock.unlock_shared() }
+    }
+}
+
+impl<'a, T: ?Sized + Debug> Debug for Ref<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(&**self, f)
     }
 }
 
-/// Mutable borrow of a [`Resource`] stored in a [`Resources`] container.
+impl<'a, T: ?Sized + Display> Display for Ref<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Display::fmt(&**self, f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, T: ?Sized + Serialize> Serialize for Ref<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<'a, T: ?Sized + PartialEq> PartialEq<T> for Ref<'a, T> {
+    fn eq(&self, other: &T) -> bool {
+        **self == *other
+    }
+}
+
+impl<'a, 'b, T: ?Sized + PartialEq> PartialEq<Ref<'b, T>> for Ref<'a, T> {
+    fn eq(&self, other: &Ref<'b, T>) -> bool {
+        **self == **other
+    }
+}
+
+impl<'a, T: ?Sized + PartialOrd> PartialOrd<T> for Ref<'a, T> {
+    fn partial_cmp(&self, other: &T) -> Option<Ordering> {
+        (**self).partial_cmp(other)
+    }
+}
+
+impl<'a, 'b, T: ?Sized + PartialOrd> PartialOrd<Ref<'b, T>> for Ref<'a, T> {
+    fn partial_cmp(&self, other: &Ref<'b, T>) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<'a, T: ?Sized + Hash> Hash for Ref<'a, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+/// Mutable borrow of a [`Resource`] stored in a [`Resources`] container,
+/// or of a part of it, as projected by [`map`] or [`try_map`].
+///
+/// Like `&mut T`, this is `Send` if `T` is `Send`, and `Sync` if `T` is `Sync`,
+/// which is always the case for resources: it can be shared between threads,
+/// and moved to and released on a different thread than the one that acquired it.
 ///
 /// [`Resource`]: trait.Resource.html
 /// [`Resources`]: struct.Resources.html
-pub struct RefMut<'a, T: Resource> {
-    write_guard: MappedRwLockWriteGuard<'a, T>,
-}
-
-impl<'a, T: Resource> RefMut<'a, T> {
-    pub(crate) fn from_lock(lock: &'a RwLock<Box<dyn Resource>>) -> Result<Self, InvalidBorrow> {
-        lock.try_write()
-            .map(|guard| Self {
-                write_guard: RwLockWriteGuard::map(guard, |resource| {
-                    resource
-                        .downcast_mut::<T>()
-                        .unwrap_or_else(|| panic!("downcasting resources should always succeed"))
-                }),
+/// [`map`]: #method.map
+/// [`try_map`]: #method.try_map
+pub struct RefMut<'a, T: ?Sized> {
+    lock: &'a ResourcesRwLock,
+    held: HeldLock,
+    site: BorrowSite<'a>,
+    timer: HoldTimer,
+    /// Place in the lock's queue of asynchronous acquisitions, released after the lock.
+    #[cfg(feature = "tokio")]
+    queued: Option<tokio::sync::RwLockWriteGuard<'a, ()>>,
+    resource: NonNull<T>,
+    /// Whether releasing the lock counts as a change of the resource.
+    tracked: bool,
+    phantom_data: PhantomData<&'a mut T>,
+}
+
+// Behaves like `&mut T`, and the lock may be released from any thread.
+unsafe impl<'a, T: ?Sized + Send> Send for RefMut<'a, T> {}
+unsafe impl<'a, T: ?Sized + Sync> Sync for RefMut<'a, T> {}
+
+impl<'a, T: ?Sized + FromCell> RefMut<'a, T> {
+    #[track_caller]
+    pub(crate) fn from_cell(cell: &'a ResourceCell) -> Result<Self, InvalidBorrow> {
+        Self::from_cell_labeled(cell, None)
+    }
+
+    /// Like `from_cell()`, but records the label along with the location of the borrow.
+    #[track_caller]
+    pub(crate) fn from_cell_labeled(
+        cell: &'a ResourceCell,
+        label: Option<&'static str>,
+    ) -> Result<Self, InvalidBorrow> {
+        let held = HeldLock::record(cell);
+        if cell.lock.try_lock_exclusive() {
+            let site = BorrowSite::record_labeled(cell, label);
+            Ok(unsafe { Self::from_locked_cell(cell, held, site, BorrowApi::Get) })
+        } else {
+            instrument::conflict(cell.type_name(), Access::Exclusive);
+            Err(InvalidBorrow::Mutable {
+                type_name: cell.type_name(),
+                conflict: cell.conflict(),
+            })
+        }
+    }
+
+    #[cfg(feature = "blocking")]
+    #[track_caller]
+    pub(crate) fn from_cell_blocking(
+        cell: &'a ResourceCell,
+        lane: Lane,
+        backoff: &Backoff,
+    ) -> Result<Self, InvalidBorrow> {
+        let held = HeldLock::acquire(cell);
+        if cell.lock.lock_exclusive(lane, backoff) {
+            Ok(unsafe {
+                Self::from_locked_cell(cell, held, BorrowSite::record(cell), BorrowApi::Blocking)
+            })
+        } else {
+            instrument::conflict(cell.type_name(), Access::Exclusive);
+            Err(InvalidBorrow::Mutable {
+                type_name: cell.type_name(),
+                conflict: cell.conflict(),
             })
-            .ok_or_else(|| InvalidBorrow::Mutable)
+        }
+    }
+
+    #[cfg(any(feature = "async", feature = "tokio"))]
+    pub(crate) async fn from_cell_async(cell: &'a ResourceCell) -> Self {
+        // Tasks move between threads, so their guards can't be attributed to the current one,
+        // but the acquisition can still deadlock with locks the thread holds.
+        HeldLock::check(cell);
+        let held = HeldLock::unordered();
+        #[cfg(feature = "tokio")]
+        let queued = cell.lock.queue_exclusive().await;
+        cell.lock.lock_exclusive_async().await;
+        #[allow(unused_mut)]
+        let mut this = unsafe {
+            Self::from_locked_cell(cell, held, BorrowSite::untracked(), BorrowApi::Async)
+        };
+        #[cfg(feature = "tokio")]
+        {
+            this.queued = Some(queued);
+        }
+        this
+    }
+
+    /// Like `from_cell()`, but releasing the returned reference won't count as a change
+    /// of the resource.
+    #[track_caller]
+    pub(crate) fn from_cell_untracked(cell: &'a ResourceCell) -> Result<Self, InvalidBorrow> {
+        let mut this = Self::from_cell(cell)?;
+        this.tracked = false;
+        Ok(this)
+    }
+
+    /// # Safety
+    /// The cell's lock must be held exclusively, and will be released when the `RefMut` is dropped.
+    unsafe fn from_locked_cell(
+        cell: &'a ResourceCell,
+        held: HeldLock,
+        site: BorrowSite<'a>,
+        api: BorrowApi,
+    ) -> Self {
+        let resource = T::exclusive(&mut *cell.resource_ptr());
+        Self::from_locked_parts(cell, held, site, api, resource)
     }
 }
 
-impl<'a, T: Resource> Deref for RefMut<'a, T> {
+impl<'a, T: ?Sized> RefMut<'a, T> {
+    /// # Safety
+    /// The cell's lock must be held exclusively, and will be released when the `RefMut` is dropped.
+    /// The resource must be the cell's, or a part of it.
+    unsafe fn from_locked_parts(
+        cell: &'a ResourceCell,
+        held: HeldLock,
+        site: BorrowSite<'a>,
+        api: BorrowApi,
+        resource: NonNull<T>,
+    ) -> Self {
+        cell.lock.acquired_via(api);
+        Self {
+            lock: &cell.lock,
+            held,
+            site,
+            timer: HoldTimer::start(cell.type_name(), Access::Exclusive),
+            #[cfg(feature = "tokio")]
+            queued: None,
+            resource,
+            tracked: true,
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// # Safety
+    /// The resource must be guarded by the lock, and be valid for the lifetime.
+    pub(crate) unsafe fn from_lock(
+        lock: &'a ResourcesRwLock,
+        resource: NonNull<T>,
+    ) -> Result<Self, InvalidBorrow> {
+        if lock.try_lock_exclusive() {
+            lock.acquired_via(BorrowApi::Other);
+            Ok(Self {
+                lock,
+                held: HeldLock::unordered(),
+                site: BorrowSite::untracked(),
+                timer: HoldTimer::start(type_name::<T>(), Access::Exclusive),
+                #[cfg(feature = "tokio")]
+                queued: None,
+                resource,
+                tracked: true,
+                phantom_data: PhantomData,
+            })
+        } else {
+            instrument::conflict(type_name::<T>(), Access::Exclusive);
+            Err(InvalidBorrow::Mutable {
+                type_name: type_name::<T>(),
+                conflict: lock.conflict(),
+            })
+        }
+    }
+
+    /// Makes a new `RefMut` for a part of the borrowed resource.
+    ///
+    /// This is an associated function, so that it doesn't shadow a method of the resource.
+    pub fn map<U: ?Sized>(this: Self, f: impl FnOnce(&mut T) -> &mut U) -> RefMut<'a, U> {
+        match Self::try_map(this, |resource| Some(f(resource))) {
+            Ok(mapped) => mapped,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Makes a new `RefMut` for an optional part of the borrowed resource,
+    /// returning the original `RefMut` if the closure returns `None`.
+    ///
+    /// This is an associated function, so that it doesn't shadow a method of the resource.
+    pub fn try_map<U: ?Sized>(
+        mut this: Self,
+        f: impl FnOnce(&mut T) -> Option<&mut U>,
+    ) -> Result<RefMut<'a, U>, Self> {
+        match f(unsafe { this.resource.as_mut() }).map(NonNull::from) {
+            Some(resource) => {
+                let this = ManuallyDrop::new(this);
+                Ok(RefMut {
+                    lock: this.lock,
+                    held: unsafe { ptr::read(&this.held) },
+                    site: unsafe { ptr::read(&this.site) },
+                    timer: unsafe { ptr::read(&this.timer) },
+                    #[cfg(feature = "tokio")]
+                    queued: unsafe { ptr::read(&this.queued) },
+                    resource,
+                    tracked: this.tracked,
+                    phantom_data: PhantomData,
+                })
+            }
+            None => Err(this),
+        }
+    }
+}
+
+impl<'a, T> RefMut<'a, T> {
+    /// Overwrites the borrowed value, dropping the old one.
+    ///
+    /// This is an associated function, so that it doesn't shadow a method of the resource.
+    pub fn set(this: &mut Self, value: T) {
+        **this = value;
+    }
+
+    /// Replaces the borrowed value, returning the old one.
+    ///
+    /// This is an associated function, so that it doesn't shadow a method of the resource.
+    pub fn replace(this: &mut Self, value: T) -> T {
+        mem::replace(&mut **this, value)
+    }
+
+    /// Takes the borrowed value, leaving its default in its place.
+    ///
+    /// This is an associated function, so that it doesn't shadow a method of the resource.
+    pub fn take(this: &mut Self) -> T
+    where
+        T: Default,
+    {
+        mem::take(&mut **this)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RefMut<'a, T> {
     type Target = T;
 
     fn deref(&self) -> &T {
-        self.write_guard.deref()
+        unsafe { self.resource.as_ref() }
     }
 }
 
-impl<'a, T: Resource> DerefMut for RefMut<'a, T> {
+impl<'a, T: ?Sized> DerefMut for RefMut<'a, T> {
     fn deref_mut(&mut self) -> &mut T {
-        self.write_guard.deref_mut()
+        unsafe { self.resource.as_mut() }
+    }
+}
+
+impl<'a, T: ?Sized> AsRef<T> for RefMut<'a, T> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<'a, T: ?Sized> AsMut<T> for RefMut<'a, T> {
+    fn as_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<'a, T: ?Sized> Borrow<T> for RefMut<'a, T> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<'a, T: ?Sized> BorrowMut<T> for RefMut<'a, T> {
+    fn borrow_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RefMut<'a, T> {
+    fn drop(&mut self) {
+        if self.tracked {
+            unsafe { self.lock.unlock_exclusive() }
+        } else {
+            unsafe { self.lock.unlock_exclusive_untracked() }
+        }
+    }
+}
+
+impl<'a, T: ?Sized + Debug> Debug for RefMut<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized + Display> Display for RefMut<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Display::fmt(&**self, f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, T: ?Sized + Serialize> Serialize for RefMut<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
     }
 }
@@ -0,0 +1,77 @@
+use std::any::TypeId;
+
+use crate::map::Resource;
+
+/// Resource type registered with [`register_default!`], to be inserted with its default value
+/// by [`Resources::insert_registered()`].
+///
+/// [`register_default!`]: macro.register_default.html
+/// [`Resources::insert_registered()`]: struct.Resources.html#method.insert_registered
+pub struct DefaultResource {
+    type_id: fn() -> TypeId,
+    create: fn() -> Box<dyn Resource>,
+}
+
+fn create_default<T: Resource + Default>() -> Box<dyn Resource> {
+    Box::new(T::default())
+}
+
+impl DefaultResource {
+    #[doc(hidden)]
+    pub const fn new<T: Resource + Default>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>,
+            create: create_default::<T>,
+        }
+    }
+
+    pub(crate) fn type_id(&self) -> TypeId {
+        (self.type_id)()
+    }
+
+    /// Returns the default value of the registered type.
+    pub(crate) fn create(&self) -> Box<dyn Resource> {
+        (self.create)()
+    }
+}
+
+inventory::collect!(DefaultResource);
+
+/// Registers resource types to be inserted with their default values by
+/// [`Resources::insert_registered()`] and [`Resources::with_registered()`],
+/// from any crate linked into the binary.
+///
+/// Available with the `inventory` feature.
+///
+/// ```rust
+/// # #[cfg(not(feature = "explicit"))] {
+/// # use resources::{register_default, Resources};
+/// #[derive(Default)]
+/// struct Settings {
+///     volume: u8,
+/// }
+///
+/// register_default!(Settings);
+///
+/// let resources = Resources::with_registered();
+/// assert_eq!(resources.get::<Settings>().unwrap().volume, 0);
+/// # }
+/// ```
+///
+/// [`Resources::insert_registered()`]: struct.Resources.html#method.insert_registered
+/// [`Resources::with_registered()`]: struct.Resources.html#method.with_registered
+#[macro_export]
+macro_rules! register_default {
+    ($($resource:ty),+ $(,)?) => {
+        $(
+            $crate::__inventory::submit! {
+                $crate::DefaultResource::new::<$resource>()
+            }
+        )+
+    };
+}
+
+/// Returns an iterator over all registered resource types.
+pub(crate) fn registered() -> impl Iterator<Item = &'static DefaultResource> {
+    inventory::iter::<DefaultResource>.into_iter()
+}
@@ -0,0 +1,201 @@
+use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{error::BudgetExceeded, hash::TypeIdBuildHasher, map::Resource};
+
+/// Error boxed by a loading function of [`Resources::watch_file()`].
+///
+/// [`Resources::watch_file()`]: struct.Resources.html#method.watch_file
+type LoadError = Box<dyn Error + Send + Sync>;
+
+/// Parses contents of a watched file into a boxed resource.
+type LoadFn = Box<dyn Fn(&[u8]) -> Result<Box<dyn Resource>, LoadError> + Send + Sync>;
+
+/// Path of a watched file that was loaded again, and the resulting resource or error.
+type Reloaded = (PathBuf, Result<Box<dyn Resource>, ReloadError>);
+
+/// Errors that may occur when loading a resource from a file bound to it
+/// via [`Resources::watch_file()`].
+///
+/// [`Resources::watch_file()`]: struct.Resources.html#method.watch_file
+#[derive(Debug)]
+pub enum ReloadError {
+    /// The file couldn't be read.
+    Io {
+        /// Compiler-provided name of the resource's type.
+        type_name: &'static str,
+        /// Path of the file.
+        path: PathBuf,
+        /// The underlying error.
+        error: io::Error,
+    },
+    /// The loading function rejected contents of the file.
+    Load {
+        /// Compiler-provided name of the resource's type.
+        type_name: &'static str,
+        /// Path of the file.
+        path: PathBuf,
+        /// The error returned by the loading function.
+        error: LoadError,
+    },
+    /// Inserting the loaded resource would exceed the container's memory budget.
+    Budget {
+        /// Path of the file.
+        path: PathBuf,
+        /// The underlying error, naming the resource's type.
+        error: BudgetExceeded,
+    },
+}
+
+impl ReloadError {
+    /// Returns the compiler-provided name of the resource's type.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ReloadError::Io { type_name, .. } | ReloadError::Load { type_name, .. } => type_name,
+            ReloadError::Budget { error, .. } => error.type_name,
+        }
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        match self {
+            ReloadError::Io { path, .. }
+            | ReloadError::Load { path, .. }
+            | ReloadError::Budget { path, .. } => path,
+        }
+    }
+}
+
+impl Display for ReloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ReloadError::Io {
+                type_name,
+                path,
+                error,
+            } => write!(
+                f,
+                "cannot read `{}` for {}: {}",
+                path.display(),
+                type_name,
+                error
+            ),
+            ReloadError::Load {
+                type_name,
+                path,
+                error,
+            } => write!(
+                f,
+                "cannot load {} from `{}`: {}",
+                type_name,
+                path.display(),
+                error
+            ),
+            ReloadError::Budget { path, error } => {
+                write!(f, "cannot reload `{}`: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+impl Error for ReloadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReloadError::Io { error, .. } => Some(error),
+            ReloadError::Load { error, .. } => Some(&**error),
+            ReloadError::Budget { error, .. } => Some(error),
+        }
+    }
+}
+
+/// File bound to a resource, and when it was last loaded.
+struct Watch {
+    type_name: &'static str,
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    load: LoadFn,
+}
+
+impl Watch {
+    /// Reads and loads the file, remembering its modification time.
+    fn load(&mut self, modified: Option<SystemTime>) -> Result<Box<dyn Resource>, ReloadError> {
+        // Recorded even if loading fails, so that each change is reported once.
+        self.modified = modified;
+        let bytes = fs::read(&self.path).map_err(|error| ReloadError::Io {
+            type_name: self.type_name,
+            path: self.path.clone(),
+            error,
+        })?;
+        (self.load)(&bytes).map_err(|error| ReloadError::Load {
+            type_name: self.type_name,
+            path: self.path.clone(),
+            error,
+        })
+    }
+}
+
+/// Files bound to resources via [`Resources::watch_file()`], keyed by types of the resources.
+///
+/// [`Resources::watch_file()`]: struct.Resources.html#method.watch_file
+#[derive(Default)]
+pub(crate) struct Reloads {
+    watches: HashMap<TypeId, Watch, TypeIdBuildHasher>,
+}
+
+impl Reloads {
+    /// Binds the file to the resource type, and loads it for the first time.
+    pub(crate) fn watch<T: Resource, E: Into<LoadError>>(
+        &mut self,
+        path: PathBuf,
+        load: impl Fn(&[u8]) -> Result<T, E> + Send + Sync + 'static,
+    ) -> Result<T, ReloadError> {
+        let mut watch = Watch {
+            type_name: type_name::<T>(),
+            path,
+            modified: None,
+            load: Box::new(move |bytes| {
+                load(bytes)
+                    .map(|resource| Box::new(resource) as Box<dyn Resource>)
+                    .map_err(Into::into)
+            }),
+        };
+        let modified = modified(&watch.path);
+        let resource = watch.load(modified)?;
+        self.watches.insert(TypeId::of::<T>(), watch);
+        Ok(*resource
+            .downcast::<T>()
+            .unwrap_or_else(|_| panic!("downcasting resources should always succeed")))
+    }
+
+    pub(crate) fn unwatch(&mut self, type_id: TypeId) -> bool {
+        self.watches.remove(&type_id).is_some()
+    }
+
+    /// Loads files modified since they were last loaded,
+    /// returning their paths with the resulting resources or errors.
+    pub(crate) fn poll(&mut self) -> Vec<Reloaded> {
+        let mut reloaded = Vec::new();
+        for watch in self.watches.values_mut() {
+            // A file that's missing, such as while an editor replaces it, is retried later.
+            if let Some(modified) = modified(&watch.path) {
+                if watch.modified != Some(modified) {
+                    reloaded.push((watch.path.clone(), watch.load(Some(modified))));
+                }
+            }
+        }
+        reloaded
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
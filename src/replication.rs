@@ -0,0 +1,419 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    fmt::{Formatter, Result as FmtResult},
+    marker::PhantomData,
+};
+
+use serde::{
+    de::{DeserializeOwned, DeserializeSeed, Error as _, MapAccess, SeqAccess, Visitor},
+    ser::{SerializeMap, SerializeStruct},
+    Deserializer, Serialize, Serializer,
+};
+
+use crate::{hash::TypeIdBuildHasher, map::Resource};
+
+/// Deserializes an optional resource of a specific type.
+type DeserializeFn = for<'de> fn(
+    &mut dyn erased_serde::Deserializer<'de>,
+) -> Result<Option<Box<dyn Resource>>, erased_serde::Error>;
+
+/// Resources of types listed in a delta, or `None` for ones that were absent.
+type Delta = Vec<(TypeId, Option<Box<dyn Resource>>)>;
+
+/// Erases a resource of a specific type for serialization.
+type SerializeFn = fn(&dyn Resource) -> Option<&dyn erased_serde::Serialize>;
+
+/// Type-erased value of a resource's type as of an earlier snapshot version.
+type OldValue = Box<dyn Any + Send>;
+
+/// Deserializes an optional value of a resource's type as of an earlier snapshot version.
+type DeserializeOldFn = for<'de> fn(
+    &mut dyn erased_serde::Deserializer<'de>,
+) -> Result<Option<OldValue>, erased_serde::Error>;
+
+/// Converts a value of a resource's type into one of its type as of the next snapshot version,
+/// or returns `None` if the value is of a different type than expected.
+type MigrateFn = Box<dyn Fn(OldValue) -> Option<OldValue> + Send + Sync>;
+
+/// Names of the fields of a snapshot, see [`Resources::write_snapshot()`].
+///
+/// [`Resources::write_snapshot()`]: struct.Resources.html#method.write_snapshot
+const SNAPSHOT_FIELDS: &[&str] = &["version", "resources"];
+
+/// A resource type registered for replication, see [`Resources::register_replicated()`].
+///
+/// [`Resources::register_replicated()`]: struct.Resources.html#method.register_replicated
+struct Replicated {
+    key: String,
+    type_name: &'static str,
+    serialize: SerializeFn,
+    deserialize: DeserializeFn,
+    /// Downcasts the value produced by the last migration of the resource.
+    from_migrated: fn(OldValue) -> Option<Box<dyn Resource>>,
+    /// Number of exclusive releases of the resource when it was last written to a delta,
+    /// or `None` if it was absent then.
+    sent: Option<usize>,
+}
+
+/// Conversion of a resource's value from the type it had in snapshots of one version
+/// to the type it has in the next, see [`Resources::add_migration()`].
+///
+/// [`Resources::add_migration()`]: struct.Resources.html#method.add_migration
+struct Migration {
+    from_version: u32,
+    input: &'static str,
+    deserialize: DeserializeOldFn,
+    migrate: MigrateFn,
+}
+
+/// Resource types registered for replication, keyed both by type and by the key
+/// they're written to deltas under, and migrations of their snapshots.
+#[derive(Default)]
+pub(crate) struct Replication {
+    types: HashMap<TypeId, Replicated, TypeIdBuildHasher>,
+    by_key: HashMap<String, TypeId>,
+    version: u32,
+    /// Migrations of each key, in order of versions they migrate from.
+    migrations: HashMap<String, Vec<Migration>>,
+}
+
+impl Replication {
+    pub(crate) fn register<T: Resource + Serialize + DeserializeOwned>(&mut self, key: String) {
+        if let Some(previous) = self.types.remove(&TypeId::of::<T>()) {
+            self.by_key.remove(&previous.key);
+        }
+        if let Some(previous) = self.by_key.remove(&key) {
+            self.types.remove(&previous);
+        }
+        self.by_key.insert(key.clone(), TypeId::of::<T>());
+        self.types.insert(
+            TypeId::of::<T>(),
+            Replicated {
+                key,
+                type_name: type_name::<T>(),
+                serialize: |resource| {
+                    resource
+                        .downcast_ref::<T>()
+                        .map(|resource| resource as &dyn erased_serde::Serialize)
+                },
+                deserialize: |deserializer| {
+                    erased_serde::deserialize::<Option<T>>(deserializer)
+                        .map(|resource| resource.map(|resource| Box::new(resource) as _))
+                },
+                from_migrated: |value| {
+                    value
+                        .downcast::<T>()
+                        .ok()
+                        .map(|resource| resource as Box<dyn Resource>)
+                },
+                sent: None,
+            },
+        );
+    }
+
+    pub(crate) fn unregister(&mut self, type_id: TypeId) {
+        if let Some(replicated) = self.types.remove(&type_id) {
+            self.by_key.remove(&replicated.key);
+        }
+    }
+
+    /// Returns `TypeId`s of registered types, in order of their keys.
+    pub(crate) fn type_ids(&self) -> Vec<TypeId> {
+        let mut types = self.types.iter().collect::<Vec<_>>();
+        types.sort_unstable_by(|(_, a), (_, b)| a.key.cmp(&b.key));
+        types.into_iter().map(|(type_id, _)| *type_id).collect()
+    }
+
+    /// Records the state the resource of the type was written to a delta in, returning `true`
+    /// if it differs from the previous one, or if `restructured` is `true`.
+    pub(crate) fn mark_sent(
+        &mut self,
+        type_id: TypeId,
+        writes: Option<usize>,
+        restructured: bool,
+    ) -> bool {
+        match self.types.get_mut(&type_id) {
+            Some(replicated) => {
+                let changed = restructured || replicated.sent != writes;
+                replicated.sent = writes;
+                changed
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub(crate) fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
+
+    pub(crate) fn add_migration<Old, New>(
+        &mut self,
+        key: String,
+        from_version: u32,
+        migrate: impl Fn(Old) -> New + Send + Sync + 'static,
+    ) where
+        Old: DeserializeOwned + Send + 'static,
+        New: Send + 'static,
+    {
+        let migrations = self.migrations.entry(key).or_default();
+        migrations.retain(|migration| migration.from_version != from_version);
+        let index = migrations.partition_point(|migration| migration.from_version < from_version);
+        migrations.insert(
+            index,
+            Migration {
+                from_version,
+                input: type_name::<Old>(),
+                deserialize: |deserializer| {
+                    erased_serde::deserialize::<Option<Old>>(deserializer)
+                        .map(|value| value.map(|value| Box::new(value) as _))
+                },
+                migrate: Box::new(move |value| {
+                    value
+                        .downcast::<Old>()
+                        .ok()
+                        .map(|value| Box::new(migrate(*value)) as _)
+                }),
+            },
+        );
+    }
+
+    /// Serializes the resources of the types, or their absence, as a map keyed by type keys.
+    pub(crate) fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+        changed: &[(TypeId, Option<&dyn Resource>)],
+    ) -> Result<S::Ok, S::Error> {
+        Entries {
+            replication: self,
+            resources: changed,
+        }
+        .serialize(serializer)
+    }
+
+    /// Serializes the resources like `serialize()`, along with the current version.
+    pub(crate) fn serialize_snapshot<S: Serializer>(
+        &self,
+        serializer: S,
+        resources: &[(TypeId, Option<&dyn Resource>)],
+    ) -> Result<S::Ok, S::Error> {
+        let mut snapshot = serializer.serialize_struct("Snapshot", SNAPSHOT_FIELDS.len())?;
+        snapshot.serialize_field("version", &self.version)?;
+        snapshot.serialize_field(
+            "resources",
+            &Entries {
+                replication: self,
+                resources,
+            },
+        )?;
+        snapshot.end()
+    }
+
+    /// Deserializes a map written by `serialize()`, returning each type's resource,
+    /// or `None` for ones that were absent.
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        &self,
+        deserializer: D,
+    ) -> Result<Delta, D::Error> {
+        DeltaSeed {
+            replication: self,
+            version: self.version,
+        }
+        .deserialize(deserializer)
+    }
+
+    /// Deserializes a snapshot written by `serialize_snapshot()`, migrating resources
+    /// written by earlier versions.
+    pub(crate) fn deserialize_snapshot<'de, D: Deserializer<'de>>(
+        &self,
+        deserializer: D,
+    ) -> Result<Delta, D::Error> {
+        deserializer.deserialize_struct(
+            "Snapshot",
+            SNAPSHOT_FIELDS,
+            SnapshotVisitor {
+                replication: self,
+                phantom_data: PhantomData,
+            },
+        )
+    }
+
+    /// Returns migrations of the key needed to bring its value from the version up to date.
+    fn migrations_since(&self, key: &str, version: u32) -> &[Migration] {
+        match self.migrations.get(key) {
+            Some(migrations) => {
+                let start =
+                    migrations.partition_point(|migration| migration.from_version < version);
+                &migrations[start..]
+            }
+            None => &[],
+        }
+    }
+}
+
+struct Entries<'a> {
+    replication: &'a Replication,
+    resources: &'a [(TypeId, Option<&'a dyn Resource>)],
+}
+
+impl<'a> Serialize for Entries<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.resources.len()))?;
+        for (type_id, resource) in self.resources {
+            let replicated = &self.replication.types[type_id];
+            map.serialize_entry(
+                &replicated.key,
+                &resource.and_then(|resource| (replicated.serialize)(resource)),
+            )?;
+        }
+        map.end()
+    }
+}
+
+struct SnapshotVisitor<'a, 'de> {
+    replication: &'a Replication,
+    phantom_data: PhantomData<&'de ()>,
+}
+
+impl<'a, 'de> SnapshotVisitor<'a, 'de> {
+    fn resources_seed<E: serde::de::Error>(&self, version: u32) -> Result<DeltaSeed<'a>, E> {
+        if version > self.replication.version {
+            return Err(E::custom(format_args!(
+                "snapshot version {} is newer than the current version {}",
+                version, self.replication.version
+            )));
+        }
+        Ok(DeltaSeed {
+            replication: self.replication,
+            version,
+        })
+    }
+}
+
+impl<'a, 'de> Visitor<'de> for SnapshotVisitor<'a, 'de> {
+    type Value = Delta;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("a snapshot of replicated resources")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let version = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        seq.next_element_seed(self.resources_seed(version)?)?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        // The version decides how resources are deserialized, so it has to come first.
+        match map.next_key::<String>()?.as_deref() {
+            Some("version") => {}
+            _ => return Err(A::Error::missing_field("version")),
+        }
+        let version = map.next_value()?;
+        match map.next_key::<String>()?.as_deref() {
+            Some("resources") => map.next_value_seed(self.resources_seed(version)?),
+            _ => Err(A::Error::missing_field("resources")),
+        }
+    }
+}
+
+struct DeltaSeed<'a> {
+    replication: &'a Replication,
+    version: u32,
+}
+
+impl<'a, 'de> DeserializeSeed<'de> for DeltaSeed<'a> {
+    type Value = Delta;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(DeltaVisitor {
+            replication: self.replication,
+            version: self.version,
+            phantom_data: PhantomData,
+        })
+    }
+}
+
+struct DeltaVisitor<'a, 'de> {
+    replication: &'a Replication,
+    version: u32,
+    phantom_data: PhantomData<&'de ()>,
+}
+
+impl<'a, 'de> Visitor<'de> for DeltaVisitor<'a, 'de> {
+    type Value = Delta;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("a map of replicated resources")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut resources = Vec::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let type_id = *self.replication.by_key.get(&key).ok_or_else(|| {
+                A::Error::custom(format_args!("unknown replicated resource `{}`", key))
+            })?;
+            // Each type is changed at most once, which applying the delta relies on.
+            if resources.iter().any(|(listed, _)| *listed == type_id) {
+                return Err(A::Error::custom(format_args!(
+                    "duplicate replicated resource `{}`",
+                    key
+                )));
+            }
+            let resource = map.next_value_seed(ResourceSeed {
+                key: &key,
+                replicated: &self.replication.types[&type_id],
+                migrations: self.replication.migrations_since(&key, self.version),
+            })?;
+            resources.push((type_id, resource));
+        }
+        Ok(resources)
+    }
+}
+
+struct ResourceSeed<'a> {
+    key: &'a str,
+    replicated: &'a Replicated,
+    /// Migrations to run on the value, in order, which is then of the registered type.
+    migrations: &'a [Migration],
+}
+
+impl<'a, 'de> DeserializeSeed<'de> for ResourceSeed<'a> {
+    type Value = Option<Box<dyn Resource>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        let first = match self.migrations.first() {
+            Some(first) => first,
+            None => {
+                return (self.replicated.deserialize)(&mut deserializer).map_err(D::Error::custom)
+            }
+        };
+        let mut value = match (first.deserialize)(&mut deserializer).map_err(D::Error::custom)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        for migration in self.migrations {
+            value = (migration.migrate)(value).ok_or_else(|| {
+                D::Error::custom(format_args!(
+                    "cannot migrate `{}` from version {}: expected a value of `{}`",
+                    self.key, migration.from_version, migration.input
+                ))
+            })?;
+        }
+        (self.replicated.from_migrated)(value)
+            .map(Some)
+            .ok_or_else(|| {
+                D::Error::custom(format_args!(
+                    "cannot migrate `{}`: the last migration doesn't produce `{}`",
+                    self.key, self.replicated.type_name
+                ))
+            })
+    }
+}
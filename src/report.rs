@@ -0,0 +1,110 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::{
+    lock::ResourceCell,
+    map::Resource,
+    plugin::{DebugFn, DebugResource},
+    refs::Ref,
+};
+
+/// State of a single resource, as listed in a [`Report`].
+///
+/// [`Report`]: struct.Report.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReportEntry {
+    /// Compiler-provided name of the resource's type.
+    pub type_name: &'static str,
+    /// Size of the resource, in bytes, as counted by [`Resources::memory_usage()`].
+    ///
+    /// [`Resources::memory_usage()`]: struct.Resources.html#method.memory_usage
+    pub size: usize,
+    /// Number of guards borrowing the resource immutably.
+    pub readers: usize,
+    /// Whether a guard is borrowing the resource mutably.
+    pub borrowed_mutably: bool,
+    /// The resource formatted via `Debug`, if formatting of its type was registered,
+    /// and it wasn't borrowed mutably.
+    pub value: Option<String>,
+}
+
+/// Listing of all resources of a [`Resources`] container, as returned by [`report`],
+/// meant to be attached to bug reports.
+///
+/// Resources are sorted by type name, so that reports of the same state are identical.
+/// It's formatted via `Display`: a summary line, followed by one line per resource naming
+/// its type, size, how it's borrowed and, if formatting of its type was registered
+/// via [`register_debug`] or a plugin's [`TypeVTable`], its value.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`report`]: struct.Resources.html#method.report
+/// [`register_debug`]: struct.Resources.html#method.register_debug
+/// [`TypeVTable`]: struct.TypeVTable.html
+#[derive(Debug, Clone)]
+pub struct Report {
+    entries: Vec<ReportEntry>,
+}
+
+impl Report {
+    pub(crate) fn new<'a>(
+        cells: impl Iterator<Item = (&'a ResourceCell, Option<DebugFn>)>,
+    ) -> Self {
+        let mut entries = cells
+            .map(|(cell, debug)| {
+                let borrowed_mutably = cell.lock.is_locked_exclusive();
+                let readers = cell.lock.readers();
+                let value = debug.and_then(|debug| {
+                    let resource = Ref::<dyn Resource>::from_cell(cell).ok()?;
+                    Some(format!(
+                        "{:?}",
+                        DebugResource {
+                            resource: &*resource,
+                            debug,
+                        }
+                    ))
+                });
+                ReportEntry {
+                    type_name: cell.type_name(),
+                    size: cell.size(),
+                    readers,
+                    borrowed_mutably,
+                    value,
+                }
+            })
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|entry| entry.type_name);
+        Self { entries }
+    }
+
+    /// Returns the listed resources, sorted by type name.
+    pub fn entries(&self) -> &[ReportEntry] {
+        &self.entries
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let count = self.entries.len();
+        let size = self.entries.iter().map(|entry| entry.size).sum::<usize>();
+        writeln!(
+            f,
+            "{} resource{}, {} bytes",
+            count,
+            if count == 1 { "" } else { "s" },
+            size
+        )?;
+        for entry in &self.entries {
+            write!(f, "{} ({} bytes, ", entry.type_name, entry.size)?;
+            match (entry.borrowed_mutably, entry.readers) {
+                (true, _) => f.write_str("borrowed mutably)")?,
+                (false, 0) => f.write_str("not borrowed)")?,
+                (false, 1) => f.write_str("borrowed immutably)")?,
+                (false, readers) => write!(f, "borrowed immutably {} times)", readers)?,
+            }
+            if let Some(value) = &entry.value {
+                write!(f, " = {}", value)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use std::any::{type_name, TypeId};
+
+use crate::map::Resource;
+
+/// Set of resource types required to be present in a [`Resources`] container,
+/// as checked by [`Resources::validate()`].
+///
+/// Implemented for `()`, tuples of up to 16 [`Resource`]s, and for structs via
+/// `#[derive(RequiredResources)]` (available with the `derive` feature),
+/// which requires the types of all fields.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`Resources::validate()`]: struct.Resources.html#method.validate
+/// [`Resource`]: trait.Resource.html
+pub trait RequiredResources {
+    /// Appends `TypeId`s and names of the required types.
+    fn required(types: &mut Vec<(TypeId, &'static str)>);
+}
+
+/// Returns the `TypeId` and name of the resource type, for implementing [`RequiredResources`].
+///
+/// [`RequiredResources`]: trait.RequiredResources.html
+#[doc(hidden)]
+pub fn __required<T: Resource>() -> (TypeId, &'static str) {
+    (TypeId::of::<T>(), type_name::<T>())
+}
+
+macro_rules! expand {
+    ($macro:ident, $letter:ident) => {
+        $macro!($letter);
+    };
+    ($macro:ident, $letter:ident, $($tail:ident),*) => {
+        $macro!($letter, $($tail),*);
+        expand!($macro, $($tail),*);
+    };
+}
+
+macro_rules! impl_for_tuples {
+    ($macro:ident) => {
+        expand!($macro, P, O, N, M, L, K, J, I, H, G, F, E, D, C, B, A);
+    };
+}
+
+macro_rules! impl_required_resources {
+    ($($letter:ident),*) => {
+        impl<$($letter: Resource),*> RequiredResources for ($($letter,)*) {
+            fn required(types: &mut Vec<(TypeId, &'static str)>) {
+                $(types.push(__required::<$letter>());)*
+            }
+        }
+    }
+}
+
+impl_for_tuples!(impl_required_resources);
+
+impl RequiredResources for () {
+    fn required(_: &mut Vec<(TypeId, &'static str)>) {}
+}
@@ -0,0 +1,53 @@
+use std::{
+    any::TypeId,
+    collections::{BTreeMap, HashMap},
+};
+
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+
+use crate::{hash::TypeIdBuildHasher, map::Resource};
+
+/// Generates the schema of a resource of a specific type, see [`Resources::register_schema()`].
+///
+/// [`Resources::register_schema()`]: struct.Resources.html#method.register_schema
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+/// Resource types registered via [`Resources::register_schema()`], and their identifiers.
+///
+/// [`Resources::register_schema()`]: struct.Resources.html#method.register_schema
+#[derive(Default)]
+pub(crate) struct Schemas {
+    by_type: HashMap<TypeId, (String, SchemaFn), TypeIdBuildHasher>,
+}
+
+impl Schemas {
+    pub(crate) fn register<T: Resource + JsonSchema>(&mut self, id: String) {
+        self.by_type.insert(
+            TypeId::of::<T>(),
+            (id, |generator| generator.subschema_for::<T>()),
+        );
+    }
+
+    /// Generates the schema of an object mapping identifiers to registered types.
+    pub(crate) fn generate(&self) -> Schema {
+        let mut entries = self.by_type.values().collect::<Vec<_>>();
+        // Names of definitions depend on the order they're generated in.
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        let mut generator = SchemaGenerator::default();
+        let properties = entries
+            .into_iter()
+            .map(|(id, schema)| (id.as_str(), schema(&mut generator)))
+            .collect::<BTreeMap<_, _>>();
+        let definitions = generator.take_definitions(true);
+        let mut schema = json_schema!({
+            "$schema": generator.settings().meta_schema,
+            "title": "Resources",
+            "type": "object",
+            "properties": properties,
+        });
+        if !definitions.is_empty() {
+            schema.insert("$defs".to_owned(), definitions.into());
+        }
+        schema
+    }
+}
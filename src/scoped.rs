@@ -0,0 +1,62 @@
+use std::{any::TypeId, ptr::NonNull};
+
+use crate::{lock::ResourcesRwLock, map::Resources};
+
+/// Borrowed external value exposed by [`Resources::scope_with()`], and the lock guarding it.
+///
+/// [`Resources::scope_with()`]: struct.Resources.html#method.scope_with
+pub struct ScopedCell {
+    pub(crate) lock: ResourcesRwLock,
+    value: NonNull<u8>,
+}
+
+// The value is `Send + Sync`, and access to it is guarded by the lock.
+unsafe impl Send for ScopedCell {}
+unsafe impl Sync for ScopedCell {}
+
+impl ScopedCell {
+    fn new<E: Send + Sync>(value: &mut E) -> Self {
+        Self {
+            lock: ResourcesRwLock::new(),
+            value: NonNull::from(value).cast(),
+        }
+    }
+
+    /// Returns a pointer to the value, for use while holding the lock.
+    ///
+    /// # Safety
+    /// The value must be of type `E`.
+    pub(crate) unsafe fn value<E>(&self) -> NonNull<E> {
+        self.value.cast()
+    }
+}
+
+/// Removes the scoped value when dropped, see [`Resources::scope_with()`].
+///
+/// [`Resources::scope_with()`]: struct.Resources.html#method.scope_with
+pub(crate) struct ScopeGuard<'a, M> {
+    pub(crate) resources: &'a mut Resources<M>,
+    type_id: TypeId,
+}
+
+impl<'a, M> ScopeGuard<'a, M> {
+    pub(crate) fn new<E: Send + Sync + 'static>(
+        resources: &'a mut Resources<M>,
+        value: &mut E,
+    ) -> Self {
+        // The closure only gets a shared reference, so scopes can't nest.
+        resources
+            .scoped_mut()
+            .insert(TypeId::of::<E>(), ScopedCell::new(value));
+        Self {
+            resources,
+            type_id: TypeId::of::<E>(),
+        }
+    }
+}
+
+impl<'a, M> Drop for ScopeGuard<'a, M> {
+    fn drop(&mut self) {
+        self.resources.scoped_mut().remove(&self.type_id);
+    }
+}
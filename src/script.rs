@@ -0,0 +1,193 @@
+use rhai::{Array, Dynamic, Map};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    any::TypeId,
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+use crate::{
+    error::CantGetResource,
+    map::{Resource, Resources},
+};
+
+/// Function applying a change to a resource converted to a `Dynamic`.
+type Update<'a> = &'a mut dyn FnMut(&mut Dynamic) -> Result<(), ScriptError>;
+
+/// Conversions of a resource of a specific type to and from a `rhai::Dynamic`,
+/// see `Resources::register_script_resource()`.
+pub(crate) struct Binding<M> {
+    pub(crate) type_id: TypeId,
+    get: fn(&Resources<M>) -> Result<Dynamic, ScriptError>,
+    update: fn(&Resources<M>, Update<'_>) -> Result<(), ScriptError>,
+}
+
+impl<M> Binding<M> {
+    pub(crate) fn new<T: Resource + Serialize + DeserializeOwned>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            get: get::<T, M>,
+            update: update::<T, M>,
+        }
+    }
+
+    /// Returns the field of the resource at the path, converted to a `Dynamic`.
+    pub(crate) fn get_path(
+        &self,
+        resources: &Resources<M>,
+        path: &str,
+    ) -> Result<Dynamic, ScriptError> {
+        let mut value = (self.get)(resources)?;
+        for segment in segments(path) {
+            value = field(value, segment).ok_or_else(|| ScriptError::NoSuchField {
+                path: path.to_string(),
+            })?;
+        }
+        Ok(value)
+    }
+
+    /// Replaces the field of the resource at the path with the value,
+    /// converting the resource back from a `Dynamic`.
+    pub(crate) fn set_path(
+        &self,
+        resources: &Resources<M>,
+        path: &str,
+        value: Dynamic,
+    ) -> Result<(), ScriptError> {
+        let segments = segments(path).collect::<Vec<_>>();
+        let mut value = Some(value);
+        (self.update)(resources, &mut |resource| {
+            if set_field(resource, &segments, value.take().unwrap_or_default()) {
+                Ok(())
+            } else {
+                Err(ScriptError::NoSuchField {
+                    path: path.to_string(),
+                })
+            }
+        })
+    }
+}
+
+fn get<T: Resource + Serialize, M>(resources: &Resources<M>) -> Result<Dynamic, ScriptError> {
+    let resource = resources.get::<T>()?;
+    rhai::serde::to_dynamic(&*resource).map_err(|error| ScriptError::Conversion(error.to_string()))
+}
+
+fn update<T: Resource + Serialize + DeserializeOwned, M>(
+    resources: &Resources<M>,
+    f: Update<'_>,
+) -> Result<(), ScriptError> {
+    // The resource stays borrowed, so that it can't change between conversions.
+    let mut resource = resources.get_mut::<T>()?;
+    let mut value = rhai::serde::to_dynamic(&*resource)
+        .map_err(|error| ScriptError::Conversion(error.to_string()))?;
+    f(&mut value)?;
+    *resource = rhai::serde::from_dynamic(&value)
+        .map_err(|error| ScriptError::Conversion(error.to_string()))?;
+    Ok(())
+}
+
+/// Splits a path of fields, such as `position.x` or `items.0`, into its segments.
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('.').filter(|segment| !segment.is_empty())
+}
+
+fn field(value: Dynamic, segment: &str) -> Option<Dynamic> {
+    if value.is_map() {
+        value.cast::<Map>().remove(segment)
+    } else if value.is_array() {
+        let index = segment.parse::<usize>().ok()?;
+        let mut array = value.cast::<Array>();
+        if index < array.len() {
+            Some(array.swap_remove(index))
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+/// Replaces the field at the path with the value, returning `false` if there's no such field.
+fn set_field(value: &mut Dynamic, segments: &[&str], field: Dynamic) -> bool {
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => {
+            *value = field;
+            return true;
+        }
+    };
+    if value.is_map() {
+        match value.as_map_mut() {
+            Ok(mut map) => match map.get_mut(*segment) {
+                Some(value) => set_field(value, rest, field),
+                None => false,
+            },
+            Err(_) => false,
+        }
+    } else if value.is_array() {
+        match (segment.parse::<usize>(), value.as_array_mut()) {
+            (Ok(index), Ok(mut array)) => match array.get_mut(index) {
+                Some(value) => set_field(value, rest, field),
+                None => false,
+            },
+            _ => false,
+        }
+    } else {
+        false
+    }
+}
+
+/// Error that may occur when accessing resources by name via [`Resources::script_get()`],
+/// [`Resources::script_set()`], or functions registered by [`Resources::register_script_functions()`].
+///
+/// [`Resources::script_get()`]: struct.Resources.html#method.script_get
+/// [`Resources::script_set()`]: struct.Resources.html#method.script_set
+/// [`Resources::register_script_functions()`]: struct.Resources.html#method.register_script_functions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+    /// No resource was registered under the name.
+    NotRegistered {
+        /// Name of the resource.
+        name: String,
+    },
+    /// The resource couldn't be accessed.
+    Access(CantGetResource),
+    /// The resource has no field at the path.
+    NoSuchField {
+        /// Path of the field.
+        path: String,
+    },
+    /// The resource couldn't be converted to or from a script value.
+    Conversion(String),
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ScriptError::NotRegistered { name } => {
+                write!(f, "no resource is registered for scripts as `{}`", name)
+            }
+            ScriptError::Access(error) => error.fmt(f),
+            ScriptError::NoSuchField { path } => write!(f, "no field at path `{}`", path),
+            ScriptError::Conversion(error) => {
+                write!(f, "cannot convert resource for scripts: {}", error)
+            }
+        }
+    }
+}
+
+impl Error for ScriptError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ScriptError::Access(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<CantGetResource> for ScriptError {
+    fn from(error: CantGetResource) -> Self {
+        ScriptError::Access(error)
+    }
+}
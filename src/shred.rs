@@ -0,0 +1,512 @@
+//! Adapters matching the `Resources` and `SystemData` surface of [`shred`], so that projects
+//! built on `specs` can migrate one system at a time.
+//!
+//! The [`World`] wraps a [`Resources`] container, dereferencing to it, and adds `shred`'s
+//! panicking accessors. Systems implement [`System`] with tuples of [`Read`], [`Write`],
+//! [`ReadExpect`], [`WriteExpect`], or `Option`s of the former two, as their [`SystemData`],
+//! and are run either directly via [`RunNow`], or in parallel with native systems
+//! via [`into_system()`] and [`Resources::par_exec()`].
+//!
+//! Unlike in `shred`, fetching system data that conflicts with a borrow held elsewhere
+//! panics right away rather than blocking.
+//!
+//! # Example
+//! ```rust
+//! # #[cfg(not(feature = "explicit"))] {
+//! use resources::shred::{Read, RunNow, System, World, Write};
+//!
+//! #[derive(Default)]
+//! struct Gravity(f32);
+//!
+//! #[derive(Default)]
+//! struct Velocity(f32);
+//!
+//! struct Fall;
+//!
+//! impl<'a> System<'a> for Fall {
+//!     type SystemData = (Read<'a, Gravity>, Write<'a, Velocity>);
+//!
+//!     fn run(&mut self, (gravity, mut velocity): Self::SystemData) {
+//!         velocity.0 += gravity.0;
+//!     }
+//! }
+//!
+//! let mut world = World::new();
+//! world.insert(Gravity(-9.8));
+//! let mut fall = Fall;
+//! System::setup(&mut fall, &mut world); // Inserts the default `Velocity`.
+//! fall.run_now(&world);
+//! assert_eq!(world.fetch::<Velocity>().0, -9.8);
+//! # }
+//! ```
+//!
+//! [`shred`]: https://docs.rs/shred
+//! [`World`]: struct.World.html
+//! [`Resources`]: ../struct.Resources.html
+//! [`System`]: trait.System.html
+//! [`Read`]: struct.Read.html
+//! [`Write`]: struct.Write.html
+//! [`ReadExpect`]: struct.ReadExpect.html
+//! [`WriteExpect`]: struct.WriteExpect.html
+//! [`SystemData`]: trait.SystemData.html
+//! [`RunNow`]: trait.RunNow.html
+//! [`into_system()`]: fn.into_system.html
+//! [`Resources::par_exec()`]: ../struct.Resources.html#method.par_exec
+
+use std::{
+    any::TypeId,
+    fmt::{Debug, Formatter, Result as FmtResult},
+    ops::{Deref, DerefMut},
+};
+
+use crate::{
+    error::CantGetResource,
+    exec::{AccessSet, System as ExecSystem},
+    map::{Resource, Resources},
+    refs::{Ref, RefMut},
+};
+
+/// A [`Resources`] container with the accessors of `shred`'s `World`.
+///
+/// Everything else is available through dereferencing to the container.
+///
+/// [`Resources`]: ../struct.Resources.html
+#[derive(Debug, Default)]
+pub struct World<M = ()> {
+    resources: Resources<M>,
+}
+
+impl World {
+    /// Creates an empty world.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<M> World<M> {
+    /// Returns the underlying container.
+    pub fn into_inner(self) -> Resources<M> {
+        self.resources
+    }
+
+    /// Returns `true` if a resource of type `T` exists in the world.
+    pub fn has_value<T: Resource>(&self) -> bool {
+        self.resources.contains::<T>()
+    }
+
+    /// Returns a reference to the stored resource of type `T`.
+    ///
+    /// # Panics
+    /// Panics if such a resource is not present in the world,
+    /// or is currently accessed mutably elsewhere.
+    #[track_caller]
+    pub fn fetch<T: Resource>(&self) -> Ref<'_, T> {
+        self.resources
+            .get()
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Returns a mutable reference to the stored resource of type `T`.
+    ///
+    /// # Panics
+    /// Panics if such a resource is not present in the world,
+    /// or is currently accessed immutably or mutably elsewhere.
+    #[track_caller]
+    pub fn fetch_mut<T: Resource>(&self) -> RefMut<'_, T> {
+        self.resources
+            .get_mut()
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Returns a reference to the stored resource of type `T`, or `None` if it's not present.
+    ///
+    /// # Panics
+    /// Panics if such a resource is currently accessed mutably elsewhere.
+    #[track_caller]
+    pub fn try_fetch<T: Resource>(&self) -> Option<Ref<'_, T>> {
+        fetch_optional(self.resources.get())
+    }
+
+    /// Returns a mutable reference to the stored resource of type `T`,
+    /// or `None` if it's not present.
+    ///
+    /// # Panics
+    /// Panics if such a resource is currently accessed immutably or mutably elsewhere.
+    #[track_caller]
+    pub fn try_fetch_mut<T: Resource>(&self) -> Option<RefMut<'_, T>> {
+        fetch_optional(self.resources.get_mut())
+    }
+
+    /// Fetches the system data `D`, such as `(Read<A>, Write<B>)`.
+    ///
+    /// # Panics
+    /// Panics if any of the data can't be fetched.
+    #[track_caller]
+    pub fn system_data<'a, D: SystemData<'a>>(&'a self) -> D {
+        D::fetch(&self.resources)
+    }
+
+    /// Sets up and fetches the system data `D`, passing it to the closure.
+    ///
+    /// # Panics
+    /// Panics if any of the data can't be fetched.
+    #[track_caller]
+    pub fn exec<'a, D: SystemData<'a>, R>(&'a mut self, f: impl FnOnce(D) -> R) -> R {
+        D::setup(&mut self.resources);
+        f(D::fetch(&self.resources))
+    }
+}
+
+impl<M> Deref for World<M> {
+    type Target = Resources<M>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.resources
+    }
+}
+
+impl<M> DerefMut for World<M> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.resources
+    }
+}
+
+impl<M> From<Resources<M>> for World<M> {
+    fn from(resources: Resources<M>) -> Self {
+        Self { resources }
+    }
+}
+
+/// Data that a [`System`] fetches from a container each time it runs.
+///
+/// This is implemented for [`Read`], [`Write`], [`ReadExpect`], [`WriteExpect`],
+/// `Option`s of the former two, and tuples of up to 16 of any of those.
+///
+/// [`System`]: trait.System.html
+/// [`Read`]: struct.Read.html
+/// [`Write`]: struct.Write.html
+/// [`ReadExpect`]: struct.ReadExpect.html
+/// [`WriteExpect`]: struct.WriteExpect.html
+pub trait SystemData<'a> {
+    /// Prepares the container for fetching the data, such as by inserting default resources.
+    fn setup<M>(resources: &mut Resources<M>);
+
+    /// Fetches the data from the container.
+    ///
+    /// # Panics
+    /// Panics if the data can't be fetched.
+    fn fetch<M>(resources: &'a Resources<M>) -> Self;
+
+    /// Returns `TypeId`s of the resources the data reads.
+    fn reads() -> Vec<TypeId>;
+
+    /// Returns `TypeId`s of the resources the data writes.
+    fn writes() -> Vec<TypeId>;
+}
+
+/// Shared access to a resource of type `T`, which is inserted by [`SystemData::setup()`]
+/// if it's missing.
+///
+/// [`SystemData::setup()`]: trait.SystemData.html#tymethod.setup
+pub struct Read<'a, T: Resource> {
+    inner: Ref<'a, T>,
+}
+
+/// Exclusive access to a resource of type `T`, which is inserted by [`SystemData::setup()`]
+/// if it's missing.
+///
+/// [`SystemData::setup()`]: trait.SystemData.html#tymethod.setup
+pub struct Write<'a, T: Resource> {
+    inner: RefMut<'a, T>,
+}
+
+/// Shared access to a resource of type `T`, which is expected to have been inserted
+/// before fetching.
+pub struct ReadExpect<'a, T: Resource> {
+    inner: Ref<'a, T>,
+}
+
+/// Exclusive access to a resource of type `T`, which is expected to have been inserted
+/// before fetching.
+pub struct WriteExpect<'a, T: Resource> {
+    inner: RefMut<'a, T>,
+}
+
+macro_rules! impl_shared {
+    ($name:ident) => {
+        impl<'a, T: Resource> Deref for $name<'a, T> {
+            type Target = T;
+
+            fn deref(&self) -> &Self::Target {
+                &self.inner
+            }
+        }
+
+        impl<'a, T: Resource + Debug> Debug for $name<'a, T> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+                Debug::fmt(&self.inner, f)
+            }
+        }
+    };
+}
+
+macro_rules! impl_exclusive {
+    ($name:ident) => {
+        impl_shared!($name);
+
+        impl<'a, T: Resource> DerefMut for $name<'a, T> {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.inner
+            }
+        }
+    };
+}
+
+impl_shared!(Read);
+impl_shared!(ReadExpect);
+impl_exclusive!(Write);
+impl_exclusive!(WriteExpect);
+
+impl<'a, T: Resource + Default> SystemData<'a> for Read<'a, T> {
+    fn setup<M>(resources: &mut Resources<M>) {
+        resources.entry::<T>().or_default();
+    }
+
+    #[track_caller]
+    fn fetch<M>(resources: &'a Resources<M>) -> Self {
+        Self {
+            inner: resources.get().unwrap_or_else(|error| panic!("{}", error)),
+        }
+    }
+
+    fn reads() -> Vec<TypeId> {
+        vec![TypeId::of::<T>()]
+    }
+
+    fn writes() -> Vec<TypeId> {
+        Vec::new()
+    }
+}
+
+impl<'a, T: Resource + Default> SystemData<'a> for Write<'a, T> {
+    fn setup<M>(resources: &mut Resources<M>) {
+        resources.entry::<T>().or_default();
+    }
+
+    #[track_caller]
+    fn fetch<M>(resources: &'a Resources<M>) -> Self {
+        Self {
+            inner: resources
+                .get_mut()
+                .unwrap_or_else(|error| panic!("{}", error)),
+        }
+    }
+
+    fn reads() -> Vec<TypeId> {
+        Vec::new()
+    }
+
+    fn writes() -> Vec<TypeId> {
+        vec![TypeId::of::<T>()]
+    }
+}
+
+impl<'a, T: Resource> SystemData<'a> for ReadExpect<'a, T> {
+    fn setup<M>(_: &mut Resources<M>) {}
+
+    #[track_caller]
+    fn fetch<M>(resources: &'a Resources<M>) -> Self {
+        Self {
+            inner: resources.get().unwrap_or_else(|error| panic!("{}", error)),
+        }
+    }
+
+    fn reads() -> Vec<TypeId> {
+        vec![TypeId::of::<T>()]
+    }
+
+    fn writes() -> Vec<TypeId> {
+        Vec::new()
+    }
+}
+
+impl<'a, T: Resource> SystemData<'a> for WriteExpect<'a, T> {
+    fn setup<M>(_: &mut Resources<M>) {}
+
+    #[track_caller]
+    fn fetch<M>(resources: &'a Resources<M>) -> Self {
+        Self {
+            inner: resources
+                .get_mut()
+                .unwrap_or_else(|error| panic!("{}", error)),
+        }
+    }
+
+    fn reads() -> Vec<TypeId> {
+        Vec::new()
+    }
+
+    fn writes() -> Vec<TypeId> {
+        vec![TypeId::of::<T>()]
+    }
+}
+
+impl<'a, T: Resource> SystemData<'a> for Option<Read<'a, T>> {
+    fn setup<M>(_: &mut Resources<M>) {}
+
+    #[track_caller]
+    fn fetch<M>(resources: &'a Resources<M>) -> Self {
+        fetch_optional(resources.get()).map(|inner| Read { inner })
+    }
+
+    fn reads() -> Vec<TypeId> {
+        vec![TypeId::of::<T>()]
+    }
+
+    fn writes() -> Vec<TypeId> {
+        Vec::new()
+    }
+}
+
+impl<'a, T: Resource> SystemData<'a> for Option<Write<'a, T>> {
+    fn setup<M>(_: &mut Resources<M>) {}
+
+    #[track_caller]
+    fn fetch<M>(resources: &'a Resources<M>) -> Self {
+        fetch_optional(resources.get_mut()).map(|inner| Write { inner })
+    }
+
+    fn reads() -> Vec<TypeId> {
+        Vec::new()
+    }
+
+    fn writes() -> Vec<TypeId> {
+        vec![TypeId::of::<T>()]
+    }
+}
+
+impl<'a> SystemData<'a> for () {
+    fn setup<M>(_: &mut Resources<M>) {}
+
+    fn fetch<M>(_: &'a Resources<M>) -> Self {}
+
+    fn reads() -> Vec<TypeId> {
+        Vec::new()
+    }
+
+    fn writes() -> Vec<TypeId> {
+        Vec::new()
+    }
+}
+
+macro_rules! expand {
+    ($macro:ident, $letter:ident) => {
+        $macro!($letter);
+    };
+    ($macro:ident, $letter:ident, $($tail:ident),*) => {
+        $macro!($letter, $($tail),*);
+        expand!($macro, $($tail),*);
+    };
+}
+
+macro_rules! impl_for_tuples {
+    ($macro:ident) => {
+        expand!($macro, P, O, N, M, L, K, J, I, H, G, F, E, D, C, B, A);
+    };
+}
+
+macro_rules! impl_system_data {
+    ($($letter:ident),*) => {
+        impl<'a, $($letter: SystemData<'a>),*> SystemData<'a> for ($($letter,)*) {
+            fn setup<Marker>(resources: &mut Resources<Marker>) {
+                $($letter::setup(resources);)*
+            }
+
+            #[track_caller]
+            fn fetch<Marker>(resources: &'a Resources<Marker>) -> Self {
+                ($($letter::fetch(resources),)*)
+            }
+
+            fn reads() -> Vec<TypeId> {
+                let mut reads = Vec::new();
+                $(reads.extend($letter::reads());)*
+                reads
+            }
+
+            fn writes() -> Vec<TypeId> {
+                let mut writes = Vec::new();
+                $(writes.extend($letter::writes());)*
+                writes
+            }
+        }
+    }
+}
+
+impl_for_tuples!(impl_system_data);
+
+/// A system in the style of `shred`, which fetches its [`SystemData`] each time it runs.
+///
+/// [`SystemData`]: trait.SystemData.html
+pub trait System<'a> {
+    /// Data the system fetches each time it runs.
+    type SystemData: SystemData<'a>;
+
+    /// Runs the system with the fetched data.
+    fn run(&mut self, data: Self::SystemData);
+
+    /// Prepares the container for running the system, by setting up its data.
+    fn setup<M>(&mut self, resources: &mut Resources<M>) {
+        Self::SystemData::setup(resources);
+    }
+}
+
+/// A system that can be run immediately on a container, implemented for every [`System`].
+///
+/// [`System`]: trait.System.html
+pub trait RunNow<'a> {
+    /// Fetches the system's data and runs it.
+    ///
+    /// # Panics
+    /// Panics if any of the data can't be fetched.
+    fn run_now<M>(&mut self, resources: &'a Resources<M>);
+
+    /// Prepares the container for running the system.
+    fn setup<M>(&mut self, resources: &mut Resources<M>);
+}
+
+impl<'a, S: System<'a>> RunNow<'a> for S {
+    #[track_caller]
+    fn run_now<M>(&mut self, resources: &'a Resources<M>) {
+        self.run(S::SystemData::fetch(resources));
+    }
+
+    fn setup<M>(&mut self, resources: &mut Resources<M>) {
+        System::setup(self, resources);
+    }
+}
+
+/// Converts the `shred`-style system into a native [`System`], with accesses declared
+/// by its [`SystemData`], to be run alongside others via [`Resources::par_exec()`].
+///
+/// [`System`]: ../struct.System.html
+/// [`SystemData`]: trait.SystemData.html
+/// [`Resources::par_exec()`]: ../struct.Resources.html#method.par_exec
+pub fn into_system<'s, S, M>(mut system: S) -> ExecSystem<'s, M>
+where
+    S: for<'a> System<'a> + Send + 's,
+{
+    let access = AccessSet::from_type_ids(
+        <S as System<'static>>::SystemData::reads(),
+        <S as System<'static>>::SystemData::writes(),
+    );
+    ExecSystem::with_access(access, move |resources| system.run_now(resources))
+}
+
+#[track_caller]
+fn fetch_optional<T>(result: Result<T, CantGetResource>) -> Option<T> {
+    match result {
+        Ok(guard) => Some(guard),
+        Err(error) if error.is_missing() => None,
+        Err(error) => panic!("{}", error),
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    any::TypeId,
+    borrow::Cow,
+    collections::HashMap,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+use crate::hash::TypeIdBuildHasher;
+
+/// Identifier of a resource type that, unlike its `TypeId`, stays the same across
+/// dynamic libraries and compiler versions; see [`Resources::register_stable_id()`].
+///
+/// It's either a name, such as a fully-qualified path of the type, or a UUID.
+///
+/// [`Resources::register_stable_id()`]: struct.Resources.html#method.register_stable_id
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StableId {
+    /// Name of the type.
+    Name(Cow<'static, str>),
+    /// UUID of the type.
+    Uuid(u128),
+}
+
+impl From<&'static str> for StableId {
+    fn from(name: &'static str) -> Self {
+        StableId::Name(Cow::Borrowed(name))
+    }
+}
+
+impl From<String> for StableId {
+    fn from(name: String) -> Self {
+        StableId::Name(Cow::Owned(name))
+    }
+}
+
+impl From<u128> for StableId {
+    fn from(uuid: u128) -> Self {
+        StableId::Uuid(uuid)
+    }
+}
+
+impl Display for StableId {
+    /// Writes the name as is, or the UUID in its hyphenated form.
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            StableId::Name(name) => f.write_str(name),
+            StableId::Uuid(uuid) => write!(
+                f,
+                "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+                uuid >> 96,
+                (uuid >> 80) & 0xffff,
+                (uuid >> 64) & 0xffff,
+                (uuid >> 48) & 0xffff,
+                uuid & 0xffff_ffff_ffff,
+            ),
+        }
+    }
+}
+
+/// Two-way mapping between stable identifiers and `TypeId`s of registered types.
+#[derive(Default)]
+pub(crate) struct StableIds {
+    by_id: HashMap<StableId, TypeId>,
+    by_type: HashMap<TypeId, StableId, TypeIdBuildHasher>,
+}
+
+impl StableIds {
+    /// Maps the identifier to the type, replacing previous mappings of either.
+    pub(crate) fn register(&mut self, id: StableId, type_id: TypeId) {
+        if let Some(previous) = self.by_type.remove(&type_id) {
+            self.by_id.remove(&previous);
+        }
+        if let Some(previous) = self.by_id.remove(&id) {
+            self.by_type.remove(&previous);
+        }
+        self.by_type.insert(type_id, id.clone());
+        self.by_id.insert(id, type_id);
+    }
+
+    /// Removes the mapping of the type, if any.
+    pub(crate) fn unregister(&mut self, type_id: TypeId) {
+        if let Some(id) = self.by_type.remove(&type_id) {
+            self.by_id.remove(&id);
+        }
+    }
+
+    pub(crate) fn type_id(&self, id: &StableId) -> Option<TypeId> {
+        self.by_id.get(id).copied()
+    }
+
+    pub(crate) fn stable_id(&self, type_id: TypeId) -> Option<&StableId> {
+        self.by_type.get(&type_id)
+    }
+}
@@ -0,0 +1,182 @@
+use std::{cell::UnsafeCell, ptr::NonNull};
+
+use crate::{
+    error::InvalidBorrow,
+    lock::ResourcesRwLock,
+    map::Resource,
+    refs::{Ref, RefMut},
+};
+
+/// A [`Resource`] container for a fixed set of types, known at compile time.
+///
+/// Resources are stored inline, each with its own lock, and are looked up by type
+/// at compile time, rather than by hashing [`TypeId`]s at runtime. Every listed resource
+/// is always present, so the only possible error is [`InvalidBorrow`].
+///
+/// Since the position of a type in the set is inferred, accessors take it as
+/// a second generic parameter, to be left as `_`; listing a type more than once
+/// makes accessing it ambiguous.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(not(feature = "explicit"))] {
+/// # use resources::StaticResources;
+/// let resources = StaticResources::new((0u32, 1.0f32));
+/// {
+///     let mut res_u32 = resources.get_mut::<u32, _>().unwrap();
+///     assert!(resources.get::<u32, _>().is_err()); // u32 is already borrowed.
+///     *res_u32 += 1;
+/// }
+/// assert_eq!(*resources.get::<u32, _>().unwrap(), 1);
+/// assert_eq!(resources.into_inner(), (1, 1.0));
+/// # }
+/// ```
+///
+/// [`Resource`]: trait.Resource.html
+/// [`TypeId`]: https://doc.rust-lang.org/std/any/struct.TypeId.html
+/// [`InvalidBorrow`]: enum.InvalidBorrow.html
+pub struct StaticResources<R: ResourceSet> {
+    cells: R::Cells,
+}
+
+impl<R: ResourceSet> StaticResources<R> {
+    /// Creates a container holding the given tuple of resources.
+    pub fn new(resources: R) -> Self {
+        Self {
+            cells: resources.into_cells(),
+        }
+    }
+
+    /// Returns the tuple of resources held by the container.
+    pub fn into_inner(self) -> R {
+        R::from_cells(self.cells)
+    }
+
+    /// Returns a reference to the stored resource of type `T`.
+    ///
+    /// If such a resource is currently accessed mutably elsewhere, returns an error.
+    pub fn get<T: Resource, I>(&self) -> Result<Ref<'_, T>, InvalidBorrow>
+    where
+        R::Cells: Select<T, I>,
+    {
+        let cell = self.cells.select();
+        unsafe { Ref::from_lock(&cell.lock, cell.resource_ptr()) }
+    }
+
+    /// Returns a mutable reference to the stored resource of type `T`.
+    ///
+    /// If such a resource is currently accessed immutably or mutably elsewhere,
+    /// returns an error.
+    pub fn get_mut<T: Resource, I>(&self) -> Result<RefMut<'_, T>, InvalidBorrow>
+    where
+        R::Cells: Select<T, I>,
+    {
+        let cell = self.cells.select();
+        unsafe { RefMut::from_lock(&cell.lock, cell.resource_ptr()) }
+    }
+}
+
+/// Storage of a single resource in a [`StaticResources`] container.
+///
+/// [`StaticResources`]: struct.StaticResources.html
+pub struct StaticCell<T> {
+    lock: ResourcesRwLock,
+    resource: UnsafeCell<T>,
+}
+
+// Access to the resource is guarded by the lock.
+unsafe impl<T: Send + Sync> Sync for StaticCell<T> {}
+
+impl<T> StaticCell<T> {
+    fn new(resource: T) -> Self {
+        Self {
+            lock: ResourcesRwLock::new(),
+            resource: UnsafeCell::new(resource),
+        }
+    }
+
+    fn into_inner(self) -> T {
+        self.resource.into_inner()
+    }
+
+    fn resource_ptr(&self) -> NonNull<T> {
+        unsafe { NonNull::new_unchecked(self.resource.get()) }
+    }
+}
+
+/// Tuples of resource types that can be stored in a [`StaticResources`] container;
+/// implemented for tuples of up to 16 [`Resource`]s.
+///
+/// [`StaticResources`]: struct.StaticResources.html
+/// [`Resource`]: trait.Resource.html
+pub trait ResourceSet {
+    /// Tuple of cells storing the resources.
+    type Cells;
+
+    #[doc(hidden)]
+    fn into_cells(self) -> Self::Cells;
+
+    #[doc(hidden)]
+    fn from_cells(cells: Self::Cells) -> Self;
+}
+
+/// Marker of a position in a [`ResourceSet`], used to look up resources by type.
+///
+/// [`ResourceSet`]: trait.ResourceSet.html
+pub struct Index<const N: usize>;
+
+/// Lookup of the cell storing resource of type `T`, which is at position `I` of a tuple.
+pub trait Select<T, I> {
+    #[doc(hidden)]
+    fn select(&self) -> &StaticCell<T>;
+}
+
+macro_rules! impl_select {
+    (@one [$($all:ident),*] $letter:ident $index:tt) => {
+        impl<$($all),*> Select<$letter, Index<$index>> for ($(StaticCell<$all>,)*) {
+            fn select(&self) -> &StaticCell<$letter> {
+                &self.$index
+            }
+        }
+    };
+    ($all:tt) => {};
+    ($all:tt $letter:ident $index:tt $(, $tail_letter:ident $tail_index:tt)*) => {
+        impl_select!(@one $all $letter $index);
+        impl_select!($all $($tail_letter $tail_index),*);
+    };
+}
+
+macro_rules! impl_resource_set {
+    ($($letter:ident $index:tt),*) => {
+        impl<$($letter: Resource),*> ResourceSet for ($($letter,)*) {
+            type Cells = ($(StaticCell<$letter>,)*);
+
+            fn into_cells(self) -> Self::Cells {
+                ($(StaticCell::new(self.$index),)*)
+            }
+
+            fn from_cells(cells: Self::Cells) -> Self {
+                ($(cells.$index.into_inner(),)*)
+            }
+        }
+
+        impl_select!([$($letter),*] $($letter $index),*);
+    };
+}
+
+impl_resource_set!(A 0);
+impl_resource_set!(A 0, B 1);
+impl_resource_set!(A 0, B 1, C 2);
+impl_resource_set!(A 0, B 1, C 2, D 3);
+impl_resource_set!(A 0, B 1, C 2, D 3, E 4);
+impl_resource_set!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_resource_set!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_resource_set!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+impl_resource_set!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+impl_resource_set!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+impl_resource_set!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+impl_resource_set!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+impl_resource_set!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12);
+impl_resource_set!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13);
+impl_resource_set!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14);
+impl_resource_set!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14, P 15);
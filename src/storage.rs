@@ -0,0 +1,533 @@
+use allocator_api2::alloc::{AllocError, Allocator, Global};
+use hashbrown::{HashMap, HashSet};
+use std::{
+    alloc::Layout,
+    any::{type_name, TypeId},
+    mem::{size_of, size_of_val},
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+#[cfg(feature = "allocator-api")]
+use std::sync::Arc;
+
+use crate::error::BudgetExceeded;
+use crate::hash::TypeIdBuildHasher;
+#[cfg(feature = "local-cache")]
+use crate::local::LocalCache;
+#[cfg(feature = "lock-order")]
+use crate::lock_order::{LockOrder, LockOrders};
+use crate::map::Resource;
+#[cfg(feature = "stream")]
+use crate::stream::{StructureChange, StructureChanges, StructureSubscribers};
+
+/// Map keyed by resource types, allocated by a [`StorageAllocator`].
+pub(crate) type TypeIdMap<V> = HashMap<TypeId, V, TypeIdBuildHasher, StorageAllocator>;
+
+/// Allocator used for all memory owned by a [`Resources`] container.
+///
+/// This is the global allocator, unless the container was created with [`new_in()`]
+/// (available with the `allocator-api` feature).
+///
+/// [`Resources`]: struct.Resources.html
+/// [`new_in()`]: struct.Resources.html#method.new_in
+#[derive(Clone, Default)]
+pub(crate) struct StorageAllocator {
+    #[cfg(feature = "allocator-api")]
+    allocator: Option<Arc<dyn Allocator + Send + Sync>>,
+}
+
+impl StorageAllocator {
+    #[cfg(feature = "allocator-api")]
+    pub(crate) fn new(allocator: impl Allocator + Send + Sync + 'static) -> Self {
+        Self {
+            allocator: Some(Arc::new(allocator)),
+        }
+    }
+
+    fn get(&self) -> &dyn Allocator {
+        #[cfg(feature = "allocator-api")]
+        if let Some(allocator) = &self.allocator {
+            return allocator.as_ref();
+        }
+        &Global
+    }
+}
+
+unsafe impl Allocator for StorageAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.get().allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.get().deallocate(ptr, layout)
+    }
+}
+
+/// Header preceding each resource in its allocation, holding what a thin pointer lacks.
+struct Header {
+    resource: NonNull<dyn Resource>,
+    type_name: &'static str,
+    size: usize,
+}
+
+/// Returns the layout of an allocation holding a resource of the given layout
+/// along with its header, and the offset of the resource within it.
+///
+/// The header ends right before the resource, and is aligned since the offset is
+/// a multiple of the resource's alignment, and is the header's own size when that's smaller.
+fn block_layout(layout: Layout) -> (Layout, usize) {
+    let header = Layout::new::<Header>();
+    let offset = header.size().next_multiple_of(layout.align());
+    let block = Layout::from_size_align(offset + layout.size(), header.align().max(layout.align()))
+        .unwrap_or_else(|_| panic!("resource of {} bytes is too large", layout.size()));
+    (block, offset)
+}
+
+/// Owning thin pointer to a resource, allocated by a [`Storage`].
+///
+/// The metadata of the resource's trait object is stored in a header in front of it,
+/// so that cells of resources, and so entries of the map, hold a single pointer.
+#[derive(Clone, Copy)]
+pub(crate) struct ThinResource(NonNull<u8>);
+
+impl ThinResource {
+    /// Writes the header of the resource, which must be in memory returned by `allocate_for()`.
+    unsafe fn new(resource: NonNull<dyn Resource>, type_name: &'static str, size: usize) -> Self {
+        let thin = Self(resource.cast());
+        thin.header().write(Header {
+            resource,
+            type_name,
+            size,
+        });
+        thin
+    }
+
+    fn header(self) -> *mut Header {
+        unsafe { self.0.as_ptr().sub(size_of::<Header>()) as *mut Header }
+    }
+
+    /// Returns a pointer to the resource as a trait object.
+    pub(crate) fn as_ptr(self) -> NonNull<dyn Resource> {
+        unsafe { (*self.header()).resource }
+    }
+
+    /// Returns the compiler-provided name of the resource's type.
+    pub(crate) fn type_name(self) -> &'static str {
+        unsafe { (*self.header()).type_name }
+    }
+
+    /// Returns the size of the resource, in bytes.
+    pub(crate) fn size(self) -> usize {
+        unsafe { (*self.header()).size }
+    }
+
+    /// Returns the start of the allocation holding the resource of the given layout.
+    fn block(self, layout: Layout) -> NonNull<u8> {
+        unsafe { NonNull::new_unchecked(self.0.as_ptr().sub(block_layout(layout).1)) }
+    }
+}
+
+/// Allocator of memory for resources, owned by a [`Resources`] container.
+///
+/// By default, every resource is allocated individually. With the `arena` feature, resources
+/// are instead allocated from a bump arena, so that they're laid out contiguously and freed
+/// all at once when the container is dropped.
+///
+/// Each resource is preceded by a header in its allocation, see [`ThinResource`].
+///
+/// Memory of a removed resource is kept for reuse by the next resource of the same type,
+/// so that removing and re-inserting it doesn't allocate, and places it at the same address.
+///
+/// The total size of stored resources is tracked, and can be limited by a budget,
+/// which is checked by callers before allocating memory for a resource via `check_budget()`.
+///
+/// [`Resources`]: struct.Resources.html
+#[derive(Default)]
+pub(crate) struct Storage {
+    #[cfg(feature = "arena")]
+    arena: Arena,
+    vacant: Vacant,
+    memory_usage: usize,
+    memory_budget: Option<usize>,
+    drop_priorities: TypeIdMap<i32>,
+    /// Types of resources removed since the record was last cleared.
+    removed: HashSet<TypeId, TypeIdBuildHasher, StorageAllocator>,
+    /// Types of resources to be removed by the next `Resources::maintain()`.
+    transient: HashSet<TypeId, TypeIdBuildHasher, StorageAllocator>,
+    /// Types of resources inserted, replaced, or removed since they were last replicated.
+    #[cfg(feature = "replication")]
+    restructured: HashSet<TypeId, TypeIdBuildHasher, StorageAllocator>,
+    #[cfg(feature = "lock-order")]
+    lock_orders: LockOrders,
+    #[cfg(feature = "stream")]
+    structure_subscribers: StructureSubscribers,
+    /// Whether inserting resources of new types and removing resources is forbidden.
+    sealed: bool,
+    #[cfg(feature = "local-cache")]
+    local_cache: LocalCache,
+}
+
+/// Bump arena resources are allocated from, with the `arena` feature.
+#[cfg(feature = "arena")]
+#[derive(Default)]
+struct Arena(bumpalo::Bump);
+
+// The arena is only ever allocated from via a mutable reference,
+// despite allocating requiring only a shared one.
+#[cfg(feature = "arena")]
+unsafe impl Sync for Arena {}
+
+#[cfg(feature = "arena")]
+impl Arena {
+    fn alloc_layout(&mut self, layout: Layout) -> NonNull<u8> {
+        self.0.alloc_layout(layout)
+    }
+}
+
+/// Memory of removed resources kept for reuse, keyed by their types.
+#[derive(Default)]
+struct Vacant(TypeIdMap<(NonNull<u8>, Layout)>);
+
+// Vacant memory is owned by the storage, and nothing else points into it.
+unsafe impl Send for Vacant {}
+unsafe impl Sync for Vacant {}
+
+impl Deref for Vacant {
+    type Target = TypeIdMap<(NonNull<u8>, Layout)>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Vacant {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Storage {
+    #[cfg(feature = "allocator-api")]
+    pub(crate) fn new_in(allocator: StorageAllocator) -> Self {
+        Self {
+            #[cfg(feature = "arena")]
+            arena: Arena::default(),
+            vacant: Vacant(TypeIdMap::with_hasher_in(
+                TypeIdBuildHasher::default(),
+                allocator.clone(),
+            )),
+            memory_usage: 0,
+            memory_budget: None,
+            drop_priorities: TypeIdMap::with_hasher_in(
+                TypeIdBuildHasher::default(),
+                allocator.clone(),
+            ),
+            #[cfg(feature = "replication")]
+            restructured: HashSet::with_hasher_in(TypeIdBuildHasher::default(), allocator.clone()),
+            transient: HashSet::with_hasher_in(TypeIdBuildHasher::default(), allocator.clone()),
+            removed: HashSet::with_hasher_in(TypeIdBuildHasher::default(), allocator),
+            #[cfg(feature = "lock-order")]
+            lock_orders: LockOrders::default(),
+            #[cfg(feature = "stream")]
+            structure_subscribers: StructureSubscribers::default(),
+            sealed: false,
+            #[cfg(feature = "local-cache")]
+            local_cache: LocalCache::default(),
+        }
+    }
+
+    pub(crate) fn allocator(&self) -> &StorageAllocator {
+        self.vacant.allocator()
+    }
+
+    /// Returns the total size of stored resources, in bytes.
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.memory_usage
+    }
+
+    pub(crate) fn memory_budget(&self) -> Option<usize> {
+        self.memory_budget
+    }
+
+    pub(crate) fn set_memory_budget(&mut self, budget: Option<usize>) {
+        self.memory_budget = budget;
+    }
+
+    /// Returns an error if storing a resource of the type and size would exceed the budget
+    /// with `memory_usage` bytes used, which differs from the current usage when checking
+    /// several changes before making them.
+    pub(crate) fn check_budget(
+        &self,
+        type_name: &'static str,
+        size: usize,
+        memory_usage: usize,
+    ) -> Result<(), BudgetExceeded> {
+        match self.memory_budget {
+            Some(memory_budget) if memory_usage + size > memory_budget => Err(BudgetExceeded {
+                resource: (),
+                type_name,
+                size,
+                memory_usage,
+                memory_budget,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the drop priority of resources of the type, `0` unless set otherwise.
+    pub(crate) fn drop_priority(&self, type_id: TypeId) -> i32 {
+        self.drop_priorities.get(&type_id).copied().unwrap_or(0)
+    }
+
+    pub(crate) fn set_drop_priority(&mut self, type_id: TypeId, priority: i32) {
+        if priority == 0 {
+            self.drop_priorities.remove(&type_id);
+        } else {
+            self.drop_priorities.insert(type_id, priority);
+        }
+    }
+
+    /// Returns `true` if any type has a drop priority other than the default.
+    pub(crate) fn has_drop_priorities(&self) -> bool {
+        !self.drop_priorities.is_empty()
+    }
+
+    /// Returns the lock ordering index of a newly inserted resource.
+    #[cfg(feature = "lock-order")]
+    pub(crate) fn next_lock_order(&mut self) -> LockOrder {
+        self.lock_orders.next()
+    }
+
+    /// Returns a stream of subsequent structural changes of the container.
+    #[cfg(feature = "stream")]
+    pub(crate) fn structure_changes(&self) -> StructureChanges {
+        self.structure_subscribers.subscribe()
+    }
+
+    #[cfg(feature = "local-cache")]
+    pub(crate) fn local_cache(&self) -> &LocalCache {
+        &self.local_cache
+    }
+
+    #[cfg(feature = "local-cache")]
+    pub(crate) fn local_cache_mut(&mut self) -> &mut LocalCache {
+        &mut self.local_cache
+    }
+
+    pub(crate) fn is_sealed(&self) -> bool {
+        self.sealed
+    }
+
+    pub(crate) fn set_sealed(&mut self, sealed: bool) {
+        self.sealed = sealed;
+    }
+
+    /// Panics if the container is sealed, for structural changes made by methods
+    /// that don't return `Sealed` errors.
+    #[track_caller]
+    pub(crate) fn assert_unsealed(&self, type_name: &'static str) {
+        assert!(
+            !self.sealed,
+            "container is sealed: cannot insert or remove {}",
+            type_name
+        );
+    }
+
+    /// Notifies subscribers to structural changes of the container about an insertion
+    /// of a resource of a new type.
+    pub(crate) fn resource_inserted(&mut self, type_id: TypeId, type_name: &'static str) {
+        #[cfg(feature = "local-cache")]
+        self.local_cache.invalidate();
+        #[cfg(feature = "replication")]
+        self.restructured.insert(type_id);
+        #[cfg(feature = "stream")]
+        self.structure_subscribers
+            .send(StructureChange::Inserted { type_id, type_name });
+        #[cfg(not(feature = "stream"))]
+        let _ = (type_id, type_name);
+    }
+
+    /// Records a removal, and notifies subscribers to structural changes of the container about it.
+    pub(crate) fn resource_removed(&mut self, type_id: TypeId, type_name: &'static str) {
+        self.removed.insert(type_id);
+        self.transient.remove(&type_id);
+        #[cfg(feature = "local-cache")]
+        self.local_cache.invalidate();
+        #[cfg(feature = "replication")]
+        self.restructured.insert(type_id);
+        #[cfg(feature = "stream")]
+        self.structure_subscribers
+            .send(StructureChange::Removed { type_id, type_name });
+        #[cfg(not(feature = "stream"))]
+        let _ = type_name;
+    }
+
+    /// Records a replacement of a resource by a new one of the same type.
+    pub(crate) fn resource_replaced(&mut self, type_id: TypeId) {
+        #[cfg(feature = "local-cache")]
+        self.local_cache.invalidate();
+        #[cfg(feature = "replication")]
+        self.restructured.insert(type_id);
+        #[cfg(not(feature = "replication"))]
+        let _ = type_id;
+    }
+
+    /// Returns `true` if a resource of the type was inserted, replaced, or removed
+    /// since the last call, for the same type.
+    #[cfg(feature = "replication")]
+    pub(crate) fn take_restructured(&mut self, type_id: TypeId) -> bool {
+        self.restructured.remove(&type_id)
+    }
+
+    /// Returns `true` if a resource of the type was removed since the record was last cleared.
+    pub(crate) fn was_removed(&self, type_id: TypeId) -> bool {
+        self.removed.contains(&type_id)
+    }
+
+    pub(crate) fn clear_removed(&mut self) {
+        self.removed.clear();
+    }
+
+    /// Clears the record of removals, returning an iterator over the removed types.
+    pub(crate) fn drain_removed(&mut self) -> impl Iterator<Item = TypeId> + '_ {
+        self.removed.drain()
+    }
+
+    /// Marks the resource of the type to be removed by the next `Resources::maintain()`.
+    pub(crate) fn mark_transient(&mut self, type_id: TypeId) {
+        self.transient.insert(type_id);
+    }
+
+    pub(crate) fn is_transient(&self, type_id: TypeId) -> bool {
+        self.transient.contains(&type_id)
+    }
+
+    /// Clears the marks of transient resources, returning the marked types.
+    pub(crate) fn take_transient(&mut self) -> Vec<TypeId> {
+        self.transient.drain().collect()
+    }
+
+    /// Moves the resource into allocated memory, returning an owning pointer to it.
+    pub(crate) fn allocate_resource<T: Resource>(&mut self, resource: T) -> ThinResource {
+        let memory = self
+            .allocate_for(TypeId::of::<T>(), Layout::new::<T>())
+            .cast::<T>();
+        unsafe {
+            memory.as_ptr().write(resource);
+            ThinResource::new(memory, type_name::<T>(), size_of::<T>())
+        }
+    }
+
+    /// Moves the boxed resource into allocated memory, returning an owning pointer to it.
+    pub(crate) fn allocate_boxed(&mut self, resource: Box<dyn Resource>) -> ThinResource {
+        let type_name = (*resource).type_name();
+        let size = size_of_val(&*resource);
+        let memory = resource.move_into(&mut |type_id, layout| self.allocate_for(type_id, layout));
+        unsafe { ThinResource::new(memory, type_name, size) }
+    }
+
+    /// Returns memory for a resource of the type and layout, after room for its header.
+    fn allocate_for(&mut self, type_id: TypeId, layout: Layout) -> NonNull<u8> {
+        self.memory_usage += layout.size();
+        let (block, offset) = block_layout(layout);
+        let memory = match self.vacant.remove(&type_id) {
+            Some((memory, _)) => memory,
+            None => self.allocate_memory(block),
+        };
+        unsafe { NonNull::new_unchecked(memory.as_ptr().add(offset)) }
+    }
+
+    fn allocate_memory(&mut self, layout: Layout) -> NonNull<u8> {
+        #[cfg(feature = "arena")]
+        return self.arena.alloc_layout(layout);
+        #[cfg(not(feature = "arena"))]
+        return self
+            .allocator()
+            .allocate(layout)
+            .unwrap_or_else(|_| std::alloc::handle_alloc_error(layout))
+            .cast();
+    }
+
+    /// Moves the resource out of its memory, keeping the memory for reuse.
+    ///
+    /// # Safety
+    /// The pointer must have been returned by `allocate_resource::<T>()` (or `allocate_boxed()`
+    /// with a resource of type `T`) of this storage,
+    /// and must not be used afterwards.
+    pub(crate) unsafe fn take_resource<T: Resource>(&mut self, resource: ThinResource) -> T {
+        let value = resource.as_ptr().cast::<T>().as_ptr().read();
+        self.vacate(TypeId::of::<T>(), resource, Layout::new::<T>());
+        value
+    }
+
+    /// Moves the resource out of its memory into a new box, keeping the memory for reuse.
+    ///
+    /// # Safety
+    /// The pointer must have been returned by `allocate_resource()` or `allocate_boxed()`
+    /// of this storage, and must not be used afterwards.
+    pub(crate) unsafe fn take_boxed(&mut self, resource: ThinResource) -> Box<dyn Resource> {
+        let pointer = resource.as_ptr();
+        let type_id = pointer.as_ref().as_any().type_id();
+        let layout = Layout::for_value(pointer.as_ref());
+        let value = (*pointer.as_ptr()).move_out();
+        self.vacate(type_id, resource, layout);
+        value
+    }
+
+    /// Drops the resource in place, keeping its memory for reuse.
+    ///
+    /// # Safety
+    /// The pointer must have been returned by `allocate_resource()` or `allocate_boxed()`
+    /// of this storage, and must not be used afterwards.
+    pub(crate) unsafe fn drop_in_place(&mut self, resource: ThinResource) {
+        let pointer = resource.as_ptr();
+        let type_id = pointer.as_ref().as_any().type_id();
+        let layout = Layout::for_value(pointer.as_ref());
+        std::ptr::drop_in_place(pointer.as_ptr());
+        self.vacate(type_id, resource, layout);
+    }
+
+    unsafe fn vacate(&mut self, type_id: TypeId, resource: ThinResource, layout: Layout) {
+        self.memory_usage -= layout.size();
+        let memory = resource.block(layout);
+        if let Some((memory, layout)) = self.vacant.insert(type_id, (memory, layout)) {
+            Self::free(memory, layout, self.allocator());
+        }
+    }
+
+    /// Drops the resource in place, freeing its memory.
+    ///
+    /// # Safety
+    /// The pointer must have been returned by `allocate_resource()` or `allocate_boxed()` of a storage
+    /// that's still alive and uses the given allocator, and must not be used afterwards.
+    pub(crate) unsafe fn drop_resource(resource: ThinResource, allocator: &StorageAllocator) {
+        let pointer = resource.as_ptr();
+        let layout = Layout::for_value(pointer.as_ref());
+        std::ptr::drop_in_place(pointer.as_ptr());
+        Self::free(resource.block(layout), layout, allocator);
+    }
+
+    /// Frees memory kept for reuse.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        let allocator = self.allocator().clone();
+        for (_, (memory, layout)) in self.vacant.drain() {
+            unsafe { Self::free(memory, layout, &allocator) };
+        }
+        self.vacant.shrink_to_fit();
+    }
+
+    /// Frees the allocation holding a resource of the given layout.
+    unsafe fn free(memory: NonNull<u8>, layout: Layout, allocator: &StorageAllocator) {
+        #[cfg(not(feature = "arena"))]
+        allocator.deallocate(memory, block_layout(layout).0);
+        #[cfg(feature = "arena")]
+        let _ = (memory, layout, allocator);
+    }
+}
+
+impl Drop for Storage {
+    fn drop(&mut self) {
+        self.shrink_to_fit();
+    }
+}
@@ -0,0 +1,180 @@
+use futures_core::Stream;
+use std::{
+    any::TypeId,
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, Weak},
+    task::{Context, Poll, Waker},
+};
+
+use crate::lock::ResourcesRwLock;
+
+/// Stream of changes of a single resource in a [`Resources`] container,
+/// yielding each time a mutable reference to it is dropped, unless it was untracked.
+/// This is returned by the [`changes`] method on [`Resources`].
+///
+/// Changes that happen while the stream isn't polled are coalesced into a single item.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`changes`]: struct.Resources.html#method.changes
+pub struct Changes<'a> {
+    lock: &'a ResourcesRwLock,
+    observed: usize,
+}
+
+impl<'a> Changes<'a> {
+    pub(crate) fn new(lock: &'a ResourcesRwLock) -> Self {
+        Self {
+            lock,
+            observed: lock.writes(),
+        }
+    }
+
+    fn poll_changed(&mut self) -> bool {
+        let writes = self.lock.writes();
+        let changed = writes != self.observed;
+        self.observed = writes;
+        changed
+    }
+}
+
+impl<'a> Stream for Changes<'a> {
+    type Item = ();
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<()>> {
+        if self.poll_changed() {
+            return Poll::Ready(Some(()));
+        }
+        self.lock.register_write_waker(cx.waker());
+        if self.poll_changed() {
+            Poll::Ready(Some(()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Structural change of a [`Resources`] container, yielded by [`StructureChanges`].
+///
+/// [`Resources`]: struct.Resources.html
+/// [`StructureChanges`]: struct.StructureChanges.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum StructureChange {
+    /// A resource of a type that wasn't present was inserted.
+    Inserted {
+        /// `TypeId` of the resource's type.
+        type_id: TypeId,
+        /// Compiler-provided name of the resource's type.
+        type_name: &'static str,
+    },
+    /// A resource was removed.
+    Removed {
+        /// `TypeId` of the resource's type.
+        type_id: TypeId,
+        /// Compiler-provided name of the resource's type.
+        type_name: &'static str,
+    },
+}
+
+#[derive(Default)]
+struct Subscription {
+    changes: VecDeque<StructureChange>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+fn lock(subscription: &Mutex<Subscription>) -> MutexGuard<'_, Subscription> {
+    subscription
+        .lock()
+        .unwrap_or_else(|error| error.into_inner())
+}
+
+/// Stream of structural changes of a [`Resources`] container: insertions of resources
+/// of new types, and removals. This is returned by the [`structure_changes`] method
+/// on [`Resources`], and ends when the container is dropped.
+///
+/// Unlike [`Changes`], this doesn't borrow the container, since it can't be changed
+/// structurally while borrowed; every change is buffered until it's yielded.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`structure_changes`]: struct.Resources.html#method.structure_changes
+/// [`Changes`]: struct.Changes.html
+pub struct StructureChanges {
+    subscription: Arc<Mutex<Subscription>>,
+}
+
+impl Stream for StructureChanges {
+    type Item = StructureChange;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<StructureChange>> {
+        let mut subscription = lock(&self.subscription);
+        if let Some(change) = subscription.changes.pop_front() {
+            Poll::Ready(Some(change))
+        } else if subscription.closed {
+            Poll::Ready(None)
+        } else {
+            subscription.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Subscriptions to structural changes of a single container.
+#[derive(Default)]
+pub(crate) struct StructureSubscribers {
+    subscriptions: Mutex<Vec<Weak<Mutex<Subscription>>>>,
+}
+
+impl StructureSubscribers {
+    pub(crate) fn subscribe(&self) -> StructureChanges {
+        let subscription = Arc::default();
+        self.subscriptions
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .push(Arc::downgrade(&subscription));
+        StructureChanges { subscription }
+    }
+
+    /// Sends the change to all live subscriptions, forgetting the dropped ones.
+    pub(crate) fn send(&mut self, change: StructureChange) {
+        let subscriptions = self
+            .subscriptions
+            .get_mut()
+            .unwrap_or_else(|error| error.into_inner());
+        subscriptions.retain(|subscription| match subscription.upgrade() {
+            Some(subscription) => {
+                let waker = {
+                    let mut subscription = lock(&subscription);
+                    subscription.changes.push_back(change.clone());
+                    subscription.waker.take()
+                };
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+impl Drop for StructureSubscribers {
+    fn drop(&mut self) {
+        let subscriptions = self
+            .subscriptions
+            .get_mut()
+            .unwrap_or_else(|error| error.into_inner());
+        for subscription in subscriptions.drain(..) {
+            if let Some(subscription) = subscription.upgrade() {
+                let waker = {
+                    let mut subscription = lock(&subscription);
+                    subscription.closed = true;
+                    subscription.waker.take()
+                };
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::any::TypeId;
+
+use crate::{
+    error::CantGetResource,
+    map::{Resource, Resources},
+};
+
+/// Tuples of resource types that can be cloned into a new container by
+/// [`Resources::clone_subset()`]; implemented for tuples of up to 16 `Clone` [`Resource`]s.
+///
+/// [`Resources::clone_subset()`]: struct.Resources.html#method.clone_subset
+/// [`Resource`]: trait.Resource.html
+pub trait CloneSubset {
+    #[doc(hidden)]
+    fn clone_subset<M>(resources: &Resources<M>) -> Result<Resources<M>, CantGetResource>;
+}
+
+/// Tuples of resource types, such as `(A, B, C)`, used to select resources by type,
+/// for example by [`Resources::move_types_to()`];
+/// implemented for tuples of up to 16 [`Resource`]s.
+///
+/// [`Resources::move_types_to()`]: struct.Resources.html#method.move_types_to
+/// [`Resource`]: trait.Resource.html
+pub trait ResourceTypes {
+    /// Returns `TypeId`s of the types in the tuple, in order.
+    fn type_ids() -> Vec<TypeId>;
+}
+
+macro_rules! expand {
+    ($macro:ident, $letter:ident) => {
+        $macro!($letter);
+    };
+    ($macro:ident, $letter:ident, $($tail:ident),*) => {
+        $macro!($letter, $($tail),*);
+        expand!($macro, $($tail),*);
+    };
+}
+
+macro_rules! impl_for_tuples {
+    ($macro:ident) => {
+        expand!($macro, P, O, N, M, L, K, J, I, H, G, F, E, D, C, B, A);
+    };
+}
+
+macro_rules! impl_clone_subset {
+    ($($letter:ident),*) => {
+        impl<$($letter: Resource + Clone),*> CloneSubset for ($($letter,)*) {
+            #[allow(non_snake_case)]
+            fn clone_subset<Marker>(
+                resources: &Resources<Marker>,
+            ) -> Result<Resources<Marker>, CantGetResource> {
+                // All of the resources are borrowed at once, so that the clones are consistent.
+                let ($($letter,)*) = ($(resources.get::<$letter>()?,)*);
+                let mut subset = Resources::default();
+                $(subset.insert((*$letter).clone());)*
+                Ok(subset)
+            }
+        }
+    }
+}
+
+impl_for_tuples!(impl_clone_subset);
+
+macro_rules! impl_resource_types {
+    ($($letter:ident),*) => {
+        impl<$($letter: Resource),*> ResourceTypes for ($($letter,)*) {
+            fn type_ids() -> Vec<TypeId> {
+                vec![$(TypeId::of::<$letter>()),*]
+            }
+        }
+    }
+}
+
+impl_for_tuples!(impl_resource_types);
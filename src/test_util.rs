@@ -0,0 +1,151 @@
+use std::any::TypeId;
+
+use crate::map::{Resource, Resources};
+
+/// Tuples of resources, such as `(A, B, C)`, that can be inserted into a container at once,
+/// for example by [`Resources::test_with()`]; implemented for tuples of up to 16 [`Resource`]s.
+///
+/// [`Resources::test_with()`]: struct.Resources.html#method.test_with
+/// [`Resource`]: trait.Resource.html
+pub trait ResourceTuple {
+    /// Inserts each resource of the tuple into the container, replacing present ones.
+    fn insert_into<M>(self, resources: &mut Resources<M>);
+}
+
+macro_rules! expand {
+    ($macro:ident, $letter:ident) => {
+        $macro!($letter);
+    };
+    ($macro:ident, $letter:ident, $($tail:ident),*) => {
+        $macro!($letter, $($tail),*);
+        expand!($macro, $($tail),*);
+    };
+}
+
+macro_rules! impl_for_tuples {
+    ($macro:ident) => {
+        expand!($macro, P, O, N, M, L, K, J, I, H, G, F, E, D, C, B, A);
+    };
+}
+
+macro_rules! impl_resource_tuple {
+    ($($letter:ident),*) => {
+        impl<$($letter: Resource),*> ResourceTuple for ($($letter,)*) {
+            #[allow(non_snake_case)]
+            fn insert_into<Marker>(self, resources: &mut Resources<Marker>) {
+                let ($($letter,)*) = self;
+                $(resources.insert($letter);)*
+            }
+        }
+    }
+}
+
+impl_for_tuples!(impl_resource_tuple);
+
+/// Restores the overridden resource when dropped, see [`Resources::with_override()`].
+///
+/// [`Resources::with_override()`]: struct.Resources.html#method.with_override
+pub(crate) struct Override<'a, M> {
+    pub(crate) resources: &'a mut Resources<M>,
+    type_id: TypeId,
+    original: Option<Box<dyn Resource>>,
+}
+
+impl<'a, M> Override<'a, M> {
+    pub(crate) fn new<T: Resource>(resources: &'a mut Resources<M>, value: T) -> Self {
+        let original = unsealed(resources, |resources| {
+            let original = resources.take_boxed(TypeId::of::<T>());
+            resources.insert(value);
+            original
+        });
+        Self {
+            resources,
+            type_id: TypeId::of::<T>(),
+            original,
+        }
+    }
+}
+
+impl<'a, M> Drop for Override<'a, M> {
+    fn drop(&mut self) {
+        let (type_id, original) = (self.type_id, self.original.take());
+        unsealed(self.resources, |resources| {
+            resources.take_boxed(type_id);
+            resources.extend(original);
+        });
+    }
+}
+
+/// Runs the closure with the container unsealed, sealing it again afterwards if it was:
+/// an override is undone when dropped, so it doesn't change the structure of the container.
+fn unsealed<M, R>(resources: &mut Resources<M>, f: impl FnOnce(&mut Resources<M>) -> R) -> R {
+    let sealed = resources.is_sealed();
+    resources.unseal();
+    let result = f(resources);
+    if sealed {
+        resources.seal();
+    }
+    result
+}
+
+/// Asserts that a [`Resources`] container contains resources of all of the given types.
+///
+/// On failure, the panic message names the missing types and lists the present ones.
+///
+/// Available with the `test-util` feature.
+///
+/// ```rust
+/// # #[cfg(not(feature = "explicit"))] {
+/// # use resources::{assert_contains, Resources};
+/// let resources = Resources::test_with((1u32, 2.0f32));
+/// assert_contains!(resources, u32, f32);
+/// # }
+/// ```
+///
+/// [`Resources`]: struct.Resources.html
+#[macro_export]
+macro_rules! assert_contains {
+    ($resources:expr, $($resource:ty),+ $(,)?) => {{
+        let resources = &$resources;
+        let missing = [$((
+            resources.contains::<$resource>(),
+            ::std::any::type_name::<$resource>(),
+        )),+]
+        .iter()
+        .filter(|(contained, _)| !contained)
+        .map(|(_, type_name)| *type_name)
+        .collect::<::std::vec::Vec<_>>();
+        if !missing.is_empty() {
+            panic!(
+                "resources are missing: {}\npresent: {:?}",
+                missing.join(", "),
+                resources
+            );
+        }
+    }};
+}
+
+/// Asserts that a [`Resources`] container contains no resources of the given types.
+///
+/// On failure, the panic message names the present types.
+///
+/// Available with the `test-util` feature.
+///
+/// [`Resources`]: struct.Resources.html
+#[macro_export]
+macro_rules! assert_not_contains {
+    ($resources:expr, $($resource:ty),+ $(,)?) => {{
+        let resources = &$resources;
+        let present = [$((
+            resources.contains::<$resource>(),
+            ::std::any::type_name::<$resource>(),
+        )),+]
+        .iter()
+        .filter(|(contained, _)| *contained)
+        .map(|(_, type_name)| *type_name)
+        .collect::<::std::vec::Vec<_>>();
+        if !present.is_empty() {
+            panic!("resources are present: {}", present.join(", "));
+        }
+    }};
+}
@@ -0,0 +1,181 @@
+use std::any::{type_name, TypeId};
+
+use crate::{
+    error::Sealed,
+    lock::ResourceCell,
+    map::{Resource, Resources},
+    storage::TypeIdMap,
+};
+
+/// State of a resource before the transaction first changed it.
+enum Original {
+    /// No resource of the type was present.
+    Absent,
+    /// The resource's value, to be put back into its cell, which is still in the container.
+    Value {
+        value: Box<dyn Resource>,
+        restore: fn(&mut ResourceCell, Box<dyn Resource>),
+    },
+    /// The resource's cell, removed from the container.
+    Removed(Box<ResourceCell>),
+}
+
+fn restore_value<T: Resource>(cell: &mut ResourceCell, value: Box<dyn Resource>) {
+    if let Ok(value) = value.downcast::<T>() {
+        cell.replace(*value);
+    }
+}
+
+/// Handle for changing resources of a [`Resources`] container as part of a transaction,
+/// as passed to the closure given to [`transaction`].
+///
+/// The first change to a resource of each type records its original state: the resource
+/// itself when it's replaced or removed, or its clone when it's accessed mutably.
+/// If the transaction fails, the original states of all changed resources are restored
+/// in place, keeping the strategies of their locks, and resources of types it inserted
+/// are removed.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`transaction`]: struct.Resources.html#method.transaction
+pub struct Transaction<'a, M> {
+    resources: &'a mut Resources<M>,
+    originals: TypeIdMap<Original>,
+    committed: bool,
+}
+
+impl<'a, M> Transaction<'a, M> {
+    pub(crate) fn new(resources: &'a mut Resources<M>) -> Self {
+        Self {
+            resources,
+            originals: Default::default(),
+            committed: false,
+        }
+    }
+
+    /// Keeps the changes, dropping the original states of changed resources.
+    pub(crate) fn commit(mut self) {
+        self.committed = true;
+    }
+
+    /// Returns `true` if a resource of type `T` exists in the container.
+    pub fn contains<T: Resource>(&self) -> bool {
+        self.resources.contains::<T>()
+    }
+
+    /// Returns a reference to the stored resource of type `T`,
+    /// or `None` if it's not present in the container.
+    pub fn get<T: Resource>(&self) -> Option<&T> {
+        // The transaction borrows the container mutably, so no guards exist.
+        self.resources
+            .resource_ptr::<T>()
+            .map(|resource| unsafe { resource.as_ref() })
+    }
+
+    /// Returns a mutable reference to the stored resource of type `T`,
+    /// or `None` if it's not present in the container, or is [read-only].
+    ///
+    /// If the resource wasn't changed by the transaction yet, it's cloned first,
+    /// to be restored if the transaction fails.
+    ///
+    /// [read-only]: struct.Resources.html#method.insert_readonly
+    pub fn get_mut<T: Resource + Clone>(&mut self) -> Option<&mut T> {
+        if self.resources.is_readonly::<T>() {
+            return None;
+        }
+        let mut resource = self.resources.resource_ptr_mut::<T>()?;
+        self.originals
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Original::Value {
+                value: Box::new(unsafe { resource.as_ref() }.clone()),
+                restore: restore_value::<T>,
+            });
+        Some(unsafe { resource.as_mut() })
+    }
+
+    /// Inserts the given resource of type `T` into the container,
+    /// replacing the resource of this type if it was present.
+    ///
+    /// If the container is [sealed] and no such resource is present, returns an error instead.
+    ///
+    /// [sealed]: struct.Resources.html#method.seal
+    pub fn insert<T: Resource>(&mut self, resource: T) -> Result<(), Sealed<T>> {
+        if self.resources.is_sealed() && !self.resources.contains::<T>() {
+            return Err(Sealed {
+                resource,
+                type_name: type_name::<T>(),
+            });
+        }
+        let previous = self.resources.insert(resource);
+        if !self.originals.contains_key(&TypeId::of::<T>()) {
+            let original = match previous {
+                Some(value) => Original::Value {
+                    value: Box::new(value),
+                    restore: restore_value::<T>,
+                },
+                None => Original::Absent,
+            };
+            self.originals.insert(TypeId::of::<T>(), original);
+        }
+        Ok(())
+    }
+
+    /// Removes the resource of type `T` from the container,
+    /// returning `true` if it was present.
+    ///
+    /// The resource is dropped when the transaction succeeds, without running its finalizer.
+    /// If the container is [sealed] and such a resource is present, returns an error instead.
+    ///
+    /// [sealed]: struct.Resources.html#method.seal
+    pub fn remove<T: Resource>(&mut self) -> Result<bool, Sealed> {
+        let type_id = TypeId::of::<T>();
+        if self.resources.is_sealed() && self.resources.contains::<T>() {
+            return Err(Sealed {
+                resource: (),
+                type_name: type_name::<T>(),
+            });
+        }
+        let mut cell = match self.resources.take_cell(type_id) {
+            Some(cell) => cell,
+            None => return Ok(false),
+        };
+        let original = match self.originals.remove(&type_id) {
+            None => Original::Removed(Box::new(cell)),
+            Some(Original::Value { value, restore }) => {
+                restore(&mut cell, value);
+                Original::Removed(Box::new(cell))
+            }
+            // The removed cell was inserted by the transaction.
+            Some(original) => {
+                self.resources.drop_cell(cell);
+                original
+            }
+        };
+        self.originals.insert(type_id, original);
+        Ok(true)
+    }
+}
+
+impl<'a, M> Drop for Transaction<'a, M> {
+    /// Restores original states of changed resources, unless the transaction was committed.
+    fn drop(&mut self) {
+        let committed = self.committed;
+        for (type_id, original) in self.originals.drain() {
+            match original {
+                Original::Absent if !committed => {
+                    if let Some(cell) = self.resources.take_cell(type_id) {
+                        self.resources.drop_cell(cell);
+                    }
+                }
+                Original::Value { value, restore } if !committed => {
+                    self.resources
+                        .replace_in_cell(type_id, |cell| restore(cell, value));
+                }
+                Original::Removed(cell) if !committed => {
+                    self.resources.restore_cell(type_id, *cell)
+                }
+                Original::Removed(cell) => self.resources.drop_cell(*cell),
+                Original::Absent | Original::Value { .. } => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::any::TypeId;
+
+use crate::{map::Resource, refs::Ref};
+
+/// Hooks for traversing all resources of a [`Resources`] container via [`visit`].
+///
+/// Each resource is visited exactly once, in arbitrary order, with the `TypeId`
+/// and the compiler-provided name of its type. Resources that can be borrowed are passed
+/// to [`resource`]; those that are currently accessed mutably elsewhere are passed
+/// to [`borrowed`] instead.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(not(feature = "explicit"))] {
+/// # use resources::{Ref, Resource, Resources, ResourceVisitor};
+/// # use std::any::TypeId;
+/// struct Sum(u64);
+///
+/// impl ResourceVisitor for Sum {
+///     fn resource(&mut self, _: TypeId, _: &'static str, resource: Ref<'_, dyn Resource>) {
+///         if let Some(value) = resource.downcast_ref::<u32>() {
+///             self.0 += *value as u64;
+///         }
+///     }
+/// }
+///
+/// let mut resources = Resources::new();
+/// resources.insert(1u32);
+/// resources.insert(2f32);
+/// let mut sum = Sum(0);
+/// resources.visit(&mut sum);
+/// assert_eq!(sum.0, 1);
+/// # }
+/// ```
+///
+/// [`Resources`]: struct.Resources.html
+/// [`visit`]: struct.Resources.html#method.visit
+/// [`resource`]: #tymethod.resource
+/// [`borrowed`]: #method.borrowed
+pub trait ResourceVisitor {
+    /// Called with an immutable borrow of each resource that can be borrowed.
+    fn resource(
+        &mut self,
+        type_id: TypeId,
+        type_name: &'static str,
+        resource: Ref<'_, dyn Resource>,
+    );
+
+    /// Called for each resource that is currently accessed mutably elsewhere.
+    /// Does nothing by default.
+    fn borrowed(&mut self, type_id: TypeId, type_name: &'static str) {
+        let _ = (type_id, type_name);
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    collections::HashSet,
+    fmt::{Display, Formatter, Result as FmtResult},
+    panic::Location,
+    time::Duration,
+};
+
+use crate::map::Resources;
+
+/// Description of a guard that has been holding a resource for longer than a threshold,
+/// as returned by [`Resources::long_holds()`] and reported by [`Watchdog`].
+///
+/// [`Resources::long_holds()`]: struct.Resources.html#method.long_holds
+/// [`Watchdog`]: struct.Watchdog.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LongHold {
+    /// Unique identifier of the acquisition.
+    pub(crate) id: usize,
+    /// Compiler-provided name of the resource's type.
+    pub type_name: &'static str,
+    /// Location where the guard was acquired.
+    pub location: &'static Location<'static>,
+    /// Label given to the guard, if it was acquired via a labeled method, such as `get_labeled()`.
+    pub label: Option<&'static str>,
+    /// Whether the resource is borrowed mutably.
+    pub mutable: bool,
+    /// How long the guard has been holding the resource for.
+    pub held_for: Duration,
+}
+
+impl Display for LongHold {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "{} borrowed {} at {}",
+            self.type_name,
+            if self.mutable { "mutably" } else { "immutably" },
+            self.location,
+        )?;
+        if let Some(label) = self.label {
+            write!(f, " by `{}`", label)?;
+        }
+        write!(f, " for {:?}", self.held_for)
+    }
+}
+
+/// Reports guards holding resources of a [`Resources`] container for too long,
+/// each time [`check`] is called, such as periodically from a dedicated thread.
+///
+/// Each hold is reported once, via a callback that prints it to `stderr` by default.
+/// Only guards acquired via synchronous methods, such as `get()`, are tracked.
+///
+/// [`Resources`]: struct.Resources.html
+/// [`check`]: #method.check
+pub struct Watchdog {
+    threshold: Duration,
+    reported: HashSet<usize>,
+    report: Box<dyn FnMut(&LongHold) + Send>,
+}
+
+impl Watchdog {
+    /// Creates a watchdog that prints holds longer than the threshold to `stderr`.
+    pub fn new(threshold: Duration) -> Self {
+        Self::with_callback(threshold, |hold| {
+            eprintln!("resource held too long: {}", hold)
+        })
+    }
+
+    /// Creates a watchdog that passes holds longer than the threshold to the callback.
+    pub fn with_callback(
+        threshold: Duration,
+        report: impl FnMut(&LongHold) + Send + 'static,
+    ) -> Self {
+        Self {
+            threshold,
+            reported: HashSet::new(),
+            report: Box::new(report),
+        }
+    }
+
+    /// Returns how long a guard may hold a resource before it's reported.
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    /// Reports guards of the container that have held their resources for longer than
+    /// the threshold, and haven't been reported yet; returns how many were reported.
+    pub fn check<M>(&mut self, resources: &Resources<M>) -> usize {
+        let holds = resources.long_holds(self.threshold);
+        // Forgets released guards, keeping the set as small as the number of long holds.
+        self.reported
+            .retain(|id| holds.iter().any(|hold| hold.id == *id));
+        let mut reported = 0;
+        for hold in &holds {
+            if self.reported.insert(hold.id) {
+                (self.report)(hold);
+                reported += 1;
+            }
+        }
+        reported
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    any::type_name,
+    fmt::{Debug, Formatter, Result as FmtResult},
+    sync::{Arc, Weak},
+};
+
+#[cfg(feature = "explicit")]
+use crate::map::Resource;
+
+// Only `Arc`s of types that are resources themselves, so that the opt-in extends to them.
+#[cfg(feature = "explicit")]
+impl<T: Resource> Resource for Arc<T> {}
+
+/// Weak handle to a resource of type `Arc<T>`, obtained via [`Resources::downgrade()`].
+///
+/// The handle doesn't keep the resource alive: it can be upgraded as long as the resource
+/// is present in the container, or a strong reference obtained from it is held elsewhere.
+///
+/// [`Resources::downgrade()`]: struct.Resources.html#method.downgrade
+pub struct WeakResource<T> {
+    weak: Weak<T>,
+}
+
+impl<T> WeakResource<T> {
+    pub(crate) fn new(strong: &Arc<T>) -> Self {
+        Self {
+            weak: Arc::downgrade(strong),
+        }
+    }
+
+    /// Returns a strong reference to the value, or `None` if it was removed
+    /// from the container and all other strong references were dropped.
+    pub fn upgrade(&self) -> Option<Arc<T>> {
+        self.weak.upgrade()
+    }
+
+    /// Returns `true` if the value can still be upgraded.
+    pub fn is_alive(&self) -> bool {
+        self.weak.strong_count() > 0
+    }
+}
+
+impl<T> Clone for WeakResource<T> {
+    fn clone(&self) -> Self {
+        Self {
+            weak: self.weak.clone(),
+        }
+    }
+}
+
+impl<T> Debug for WeakResource<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("WeakResource")
+            .field("type", &type_name::<T>())
+            .field("alive", &self.is_alive())
+            .finish()
+    }
+}
@@ -0,0 +1,135 @@
+use hecs::{Component, ComponentError, ComponentRef, Entity, Query, QueryBorrow, World};
+use std::{
+    any::TypeId,
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+use crate::{
+    fetch::{CantFetch, Fetch},
+    map::Resources,
+};
+
+/// A `hecs::World` paired with a [`Resources`] container, for systems that access both,
+/// as returned by [`with_world`].
+///
+/// [`Resources`]: struct.Resources.html
+/// [`with_world`]: struct.Resources.html#method.with_world
+pub struct WorldResources<'a, M = ()> {
+    world: &'a World,
+    resources: &'a Resources<M>,
+}
+
+impl<'a, M> WorldResources<'a, M> {
+    pub(crate) fn new(world: &'a World, resources: &'a Resources<M>) -> Self {
+        Self { world, resources }
+    }
+
+    /// Returns the world.
+    pub fn world(&self) -> &'a World {
+        self.world
+    }
+
+    /// Returns the container of resources.
+    pub fn resources(&self) -> &'a Resources<M> {
+        self.resources
+    }
+
+    /// Retrieves up to 16 resources of any combination of mutability,
+    /// like [`Resources::fetch()`].
+    ///
+    /// [`Resources::fetch()`]: struct.Resources.html#method.fetch
+    pub fn fetch<R: Fetch<'a>>(&self) -> Result<R::Refs, WorldError> {
+        Ok(R::fetch(self.resources)?)
+    }
+
+    /// Prepares a query of entities of the world, like `hecs::World::query()`.
+    pub fn query<Q: Query>(&self) -> QueryBorrow<'a, Q> {
+        self.world.query::<Q>()
+    }
+
+    /// Retrieves resources and prepares a query of entities of the world, in one call.
+    pub fn fetch_query<R: Fetch<'a>, Q: Query>(
+        &self,
+    ) -> Result<(R::Refs, QueryBorrow<'a, Q>), WorldError> {
+        Ok((self.fetch::<R>()?, self.query::<Q>()))
+    }
+
+    /// Returns a reference to a component of the entity, like `hecs::World::get()`.
+    pub fn get<T: ComponentRef<'a>>(&self, entity: Entity) -> Result<T::Ref, WorldError> {
+        Ok(self.world.get::<T>(entity)?)
+    }
+}
+
+impl<'a, M> Clone for WorldResources<'a, M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, M> Copy for WorldResources<'a, M> {}
+
+/// Error that may occur when accessing a [`WorldResources`] pair.
+///
+/// [`WorldResources`]: struct.WorldResources.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum WorldError {
+    /// Resources couldn't be retrieved from the container.
+    Resource(CantFetch),
+    /// A component couldn't be retrieved from the world.
+    Component(ComponentError),
+}
+
+impl Display for WorldError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            WorldError::Resource(error) => error.fmt(f),
+            WorldError::Component(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for WorldError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WorldError::Resource(error) => Some(error),
+            WorldError::Component(error) => Some(error),
+        }
+    }
+}
+
+impl From<CantFetch> for WorldError {
+    fn from(error: CantFetch) -> Self {
+        WorldError::Resource(error)
+    }
+}
+
+impl From<ComponentError> for WorldError {
+    fn from(error: ComponentError) -> Self {
+        WorldError::Component(error)
+    }
+}
+
+/// Tuples of component types, such as `(A, B, C)`, declared as accessed by a [`System`];
+/// implemented for tuples of up to 16 `hecs::Component`s.
+///
+/// [`System`]: struct.System.html
+pub trait ComponentTypes {
+    /// Returns `TypeId`s of the types in the tuple, in order.
+    fn type_ids() -> Vec<TypeId>;
+}
+
+macro_rules! impl_component_types {
+    () => {};
+    ($letter:ident $(, $tail:ident)*) => {
+        impl<$letter: Component, $($tail: Component),*> ComponentTypes for ($letter, $($tail,)*) {
+            fn type_ids() -> Vec<TypeId> {
+                vec![TypeId::of::<$letter>(), $(TypeId::of::<$tail>()),*]
+            }
+        }
+
+        impl_component_types!($($tail),*);
+    };
+}
+
+impl_component_types!(P, O, N, M, L, K, J, I, H, G, F, E, D, C, B, A);
@@ -0,0 +1,109 @@
+//! Model-checking of the per-resource lock, run with:
+//! `RUSTFLAGS="--cfg loom" cargo test --test loom --release`
+#![cfg(loom)]
+
+use loom::{sync::Arc, thread};
+use resources::*;
+
+#[cfg_attr(feature = "derive", derive(Resource))]
+struct One(usize);
+
+#[test]
+fn readers_and_writer() {
+    loom::model(|| {
+        let mut resources = Resources::new();
+        resources.insert(One(0));
+        let resources = Arc::new(resources);
+
+        let writer = {
+            let resources = resources.clone();
+            thread::spawn(move || {
+                if let Ok(mut one) = resources.get_mut::<One>() {
+                    one.0 += 1;
+                    true
+                } else {
+                    false
+                }
+            })
+        };
+        let reader = {
+            let resources = resources.clone();
+            thread::spawn(move || resources.get::<One>().map(|one| one.0).ok())
+        };
+
+        let wrote = writer.join().unwrap();
+        let read = reader.join().unwrap();
+        if let Some(value) = read {
+            assert!(value == 0 || (wrote && value == 1));
+        }
+        let final_value = resources.get::<One>().unwrap().0;
+        assert_eq!(final_value, wrote as usize);
+    });
+}
+
+#[test]
+fn writers_exclude_each_other() {
+    loom::model(|| {
+        let mut resources = Resources::new();
+        resources.insert(One(0));
+        let resources = Arc::new(resources);
+
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let resources = resources.clone();
+                thread::spawn(move || {
+                    if let Ok(mut one) = resources.get_mut::<One>() {
+                        let value = one.0;
+                        thread::yield_now();
+                        one.0 = value + 1;
+                        1
+                    } else {
+                        0
+                    }
+                })
+            })
+            .collect();
+        let successes: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(resources.get_mut::<One>().unwrap().0, successes);
+    });
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking_writer_waits_for_reader() {
+    loom::model(|| {
+        let mut resources = Resources::new();
+        resources.insert(One(0));
+        let resources = Arc::new(resources);
+
+        let reader = {
+            let resources = resources.clone();
+            thread::spawn(move || resources.get_blocking::<One>().unwrap().0)
+        };
+        resources.get_mut_blocking::<One>().unwrap().0 = 1;
+
+        let read = reader.join().unwrap();
+        assert!(read == 0 || read == 1);
+        assert_eq!(resources.get::<One>().unwrap().0, 1);
+    });
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn priority_writer_and_reader() {
+    loom::model(|| {
+        let mut resources = Resources::new();
+        resources.insert(One(0));
+        let resources = Arc::new(resources);
+
+        let reader = {
+            let resources = resources.clone();
+            thread::spawn(move || resources.get_blocking::<One>().unwrap().0)
+        };
+        resources.get_mut_blocking_priority::<One>().unwrap().0 = 1;
+
+        let read = reader.join().unwrap();
+        assert!(read == 0 || read == 1);
+        assert_eq!(resources.get::<One>().unwrap().0, 1);
+    });
+}
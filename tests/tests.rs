@@ -1,9 +1,18 @@
 use resources::*;
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "derive", derive(Resource))]
 struct One(usize);
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for One {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "derive", derive(Resource))]
 struct Two(usize);
 
 impl Default for Two {
@@ -12,6 +21,22 @@ impl Default for Two {
     }
 }
 
+#[cfg_attr(feature = "derive", derive(Resource))]
+struct Maybe(Option<usize>);
+
+#[derive(PartialEq, PartialOrd, Hash)]
+#[cfg_attr(feature = "derive", derive(Resource))]
+struct Ordered(usize);
+
+#[cfg_attr(feature = "derive", derive(Resource))]
+struct Text(&'static str);
+
+impl std::fmt::Display for Text {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(self.0)
+    }
+}
+
 #[test]
 fn insert() {
     let mut resources = Resources::new();
@@ -32,6 +57,278 @@ fn contains() {
     assert!(resources.contains::<Two>());
 }
 
+#[test]
+fn type_ids() {
+    use std::any::TypeId;
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    let _borrowed = resources.get_mut::<One>().unwrap();
+
+    let mut type_ids = resources.type_ids().collect::<Vec<_>>();
+    type_ids.sort();
+    let mut expected = vec![TypeId::of::<One>(), TypeId::of::<Two>()];
+    expected.sort();
+    assert_eq!(type_ids, expected);
+}
+
+#[test]
+fn type_names() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.extend(vec![Box::new(Two(2)) as Box<dyn Resource>]);
+
+    let mut type_names = resources.type_names().collect::<Vec<_>>();
+    type_names.sort();
+    assert_eq!(type_names, ["tests::One", "tests::Two"]);
+    assert_eq!(
+        resources.type_name_of(std::any::TypeId::of::<Two>()),
+        Some("tests::Two")
+    );
+    assert_eq!(
+        format!("{:?}", resources),
+        r#"{"tests::One", "tests::Two"}"#
+    );
+
+    {
+        let _borrowed = resources.get_mut::<One>().unwrap();
+        let error = resources.get::<One>().err().unwrap();
+        assert_eq!(error.type_name(), "tests::One");
+        assert_eq!(
+            error.to_string(),
+            "cannot borrow immutably: tests::One: borrowed mutably via `get()` or similar"
+        );
+    }
+    resources.remove::<Two>();
+    let error = resources.get::<Two>().err().unwrap();
+    assert_eq!(error.to_string(), "no such resource: tests::Two");
+}
+
+#[test]
+fn extend_keeps_lock() {
+    let mut resources = Resources::new();
+    resources.insert_readonly(One(1));
+    resources.insert_with_lock(Two(2), LockKind::Mutex);
+    resources.extend(vec![
+        Box::new(One(3)) as Box<dyn Resource>,
+        Box::new(Two(4)) as Box<dyn Resource>,
+    ]);
+    assert!(resources.is_readonly::<One>());
+    assert!(resources.get_mut::<One>().unwrap_err().is_read_only());
+    assert_eq!(*resources.get::<One>().unwrap(), One(3));
+    assert_eq!(resources.lock_kind::<Two>(), Some(LockKind::Mutex));
+    assert_eq!(*resources.get::<Two>().unwrap(), Two(4));
+}
+
+#[test]
+fn guards_are_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Ref<'static, One>>();
+    assert_send_sync::<RefMut<'static, One>>();
+    assert_send_sync::<Ref<'static, dyn Resource>>();
+    assert_send_sync::<Resources>();
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    let one = resources.get::<One>().unwrap();
+    let mut two = resources.get_mut::<Two>().unwrap();
+    std::thread::scope(|scope| {
+        scope.spawn(move || assert_eq!(one.0, 1));
+        scope.spawn(|| two.0 = 3);
+    });
+    drop(two);
+    assert!(resources.get_mut::<One>().is_ok());
+    assert_eq!(*resources.get::<Two>().unwrap(), Two(3));
+}
+
+#[test]
+fn format_guards() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Text("text"));
+    assert_eq!(format!("{:?}", resources.get::<One>().unwrap()), "One(1)");
+    assert_eq!(
+        format!("{:?}", resources.get_mut::<One>().unwrap()),
+        "One(1)"
+    );
+    assert_eq!(format!("{:>6}", resources.get::<Text>().unwrap()), "  text");
+    assert_eq!(format!("{}", resources.get_mut::<Text>().unwrap()), "text");
+}
+
+#[test]
+fn compare_guards() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn hash(value: impl Hash) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let mut resources = Resources::new();
+    resources.insert(Ordered(1));
+    let first = resources.get::<Ordered>().unwrap();
+    let second = resources.get::<Ordered>().unwrap();
+    assert!(first == Ordered(1));
+    assert!(first == second);
+    assert!(first < Ordered(2));
+    assert!(first >= second);
+    assert_eq!(hash(&first), hash(Ordered(1)));
+}
+
+#[test]
+fn convert_guards() {
+    use std::borrow::{Borrow, BorrowMut};
+
+    fn read(one: impl AsRef<One>) -> usize {
+        one.as_ref().0
+    }
+
+    fn write(mut one: impl AsMut<One>) {
+        one.as_mut().0 += 1;
+    }
+
+    fn borrow(one: impl Borrow<One>) -> usize {
+        one.borrow().0
+    }
+
+    fn borrow_mut(mut one: impl BorrowMut<One>) {
+        one.borrow_mut().0 += 1;
+    }
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    write(resources.get_mut::<One>().unwrap());
+    borrow_mut(resources.get_mut::<One>().unwrap());
+    assert_eq!(read(resources.get::<One>().unwrap()), 3);
+    assert_eq!(borrow(resources.get::<One>().unwrap()), 3);
+    assert_eq!(read(resources.get_mut::<One>().unwrap()), 3);
+    assert_eq!(borrow(resources.get_mut::<One>().unwrap()), 3);
+}
+
+#[test]
+fn guard_set() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    let mut guards = GuardSet::new();
+    assert!(guards.is_empty());
+    guards.insert(resources.get::<One>().unwrap());
+    assert!(resources.get_mut::<One>().is_err());
+    guards.insert_mut(resources.get_mut::<Two>().unwrap());
+    assert_eq!(guards.len(), 2);
+    assert!(guards.contains::<One>());
+    assert!(!guards.contains::<Text>());
+    assert_eq!(guards.get::<One>(), Some(&One(1)));
+    assert_eq!(guards.get_mut::<One>(), None);
+    guards.get_mut::<Two>().unwrap().0 = 3;
+    assert_eq!(guards.get::<Two>(), Some(&Two(3)));
+    assert_eq!(format!("{:?}", guards), "[\"tests::One\", \"tests::Two\"]");
+    assert!(resources.get::<Two>().is_err());
+
+    assert!(guards.remove::<Two>());
+    assert!(!guards.remove::<Two>());
+    assert_eq!(*resources.get::<Two>().unwrap(), Two(3));
+    guards.insert(resources.get::<One>().unwrap());
+    assert_eq!(guards.len(), 1);
+    guards.clear();
+    assert!(guards.is_empty());
+    assert!(resources.get_mut::<One>().is_ok());
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serialize_guards() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    let json = serde_json::to_string(&resources.get::<One>().unwrap()).unwrap();
+    assert_eq!(json, "1");
+    let json = serde_json::to_string(&resources.get_mut::<One>().unwrap()).unwrap();
+    assert_eq!(json, "1");
+}
+
+#[test]
+fn map_guards() {
+    let mut resources = Resources::new();
+    resources.insert(Maybe(None));
+    {
+        let maybe = resources.get_mut::<Maybe>().unwrap();
+        let mut maybe = RefMut::try_map(maybe, |maybe| maybe.0.as_mut()).unwrap_err();
+        maybe.0 = Some(1);
+        let mut value = RefMut::try_map(maybe, |maybe| maybe.0.as_mut())
+            .ok()
+            .unwrap();
+        *value += 1;
+        assert!(resources.get::<Maybe>().is_err());
+    }
+    let value = Ref::map(resources.get::<Maybe>().unwrap(), |maybe| &maybe.0);
+    assert_eq!(*value, Some(2));
+    assert!(resources.get_mut::<Maybe>().is_err());
+    drop(value);
+    assert!(resources.get_mut::<Maybe>().is_ok());
+}
+
+#[test]
+fn set_replace_take() {
+    let mut resources = Resources::new();
+    resources.insert(Two(0));
+    let mut two = resources.get_mut::<Two>().unwrap();
+    RefMut::set(&mut two, Two(1));
+    assert_eq!(RefMut::replace(&mut two, Two(3)), Two(1));
+    assert_eq!(RefMut::take(&mut two), Two(3));
+    assert_eq!(*two, Two(2));
+}
+
+#[test]
+#[cfg(feature = "lock-order")]
+fn lock_order() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    {
+        // Non-blocking acquisitions can't deadlock, so they aren't checked.
+        let _two = resources.get_mut::<Two>().unwrap();
+        assert!(resources.get::<One>().is_ok());
+    }
+
+    #[cfg(feature = "blocking")]
+    {
+        {
+            let _one = resources.get_blocking::<One>().unwrap();
+            let _two = resources.get_mut_blocking::<Two>().unwrap();
+        }
+        let two = resources.get::<Two>().unwrap();
+        std::thread::scope(|scope| {
+            scope.spawn(move || drop(two));
+        });
+        drop(resources.get_mut_blocking::<One>().unwrap());
+
+        #[cfg(feature = "async")]
+        {
+            let _two = futures::executor::block_on(resources.get_async::<Two>()).unwrap();
+            let _one = resources.get_blocking::<One>().unwrap();
+        }
+
+        let _two = resources.get::<Two>().unwrap();
+        let violation = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = resources.get_blocking::<One>();
+        }));
+        let message = *violation.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("`tests::One`") && message.contains("`tests::Two`"));
+    }
+
+    #[cfg(feature = "async")]
+    {
+        let _two = resources.get::<Two>().unwrap();
+        let violation = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = futures::executor::block_on(resources.get_async::<One>());
+        }));
+        assert!(violation.is_err());
+    }
+}
+
 #[test]
 fn multiple_borrow() {
     let mut resources = Resources::new();
@@ -130,3 +427,3316 @@ fn entry() {
     assert_eq!(ref1.0, 1);
     assert_eq!(ref2.0, 2);
 }
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking_writer_is_not_starved() {
+    let mut resources = Resources::new();
+    resources.insert(One(0));
+    let resources = resources;
+
+    std::thread::scope(|scope| {
+        let reader = resources.get::<One>().unwrap();
+        let writer = scope.spawn(|| resources.get_mut_blocking::<One>().unwrap().0 += 1);
+
+        // Once the writer is waiting, new immutable borrows must not overtake it.
+        while resources.get::<One>().is_ok() {
+            std::thread::yield_now();
+        }
+        drop(reader);
+        writer.join().unwrap();
+    });
+
+    assert_eq!(resources.get_blocking::<One>().unwrap().0, 1);
+    assert!(resources.get_blocking::<Two>().is_err());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking_priority() {
+    let mut resources = Resources::new();
+    resources.insert(One(0));
+    let resources = resources;
+
+    std::thread::scope(|scope| {
+        let reader = resources.get::<One>().unwrap();
+        let writer = scope.spawn(|| resources.get_mut_blocking::<One>().unwrap().0 += 1);
+        while resources.get::<One>().is_ok() {
+            std::thread::yield_now();
+        }
+        // Jumps ahead of the waiting writer, which `get_blocking()` would deadlock behind.
+        let priority = resources.get_blocking_priority::<One>().unwrap();
+        assert_eq!(priority.0, 0);
+        drop((reader, priority));
+        writer.join().unwrap();
+    });
+
+    resources.get_mut_blocking_priority::<One>().unwrap().0 += 1;
+    assert_eq!(resources.get::<One>().unwrap().0, 2);
+    assert!(resources.get_blocking_priority::<Two>().is_err());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn backoff() {
+    use std::time::Duration;
+
+    let mut resources = Resources::new();
+    resources.insert(One(0));
+    assert_eq!(resources.backoff(), Backoff::default());
+
+    resources.set_backoff(Backoff::spin_then_fail(16));
+    {
+        let _one = resources.get_mut::<One>().unwrap();
+        assert_eq!(
+            resources.get_blocking::<One>().unwrap_err(),
+            CantGetResource::InvalidBorrow(InvalidBorrow::Immutable {
+                type_name: "tests::One",
+                conflict: Conflict::Writer {
+                    via: BorrowApi::Get,
+                    label: None,
+                },
+            })
+        );
+    }
+    {
+        let _one = resources.get::<One>().unwrap();
+        assert!(resources.get_blocking_recursive::<One>().is_ok());
+        assert!(resources.get_mut_blocking::<One>().is_err());
+    }
+    resources.get_mut_blocking::<One>().unwrap().0 += 1;
+
+    resources.set_backoff(Backoff::parking(0, 1, Duration::from_millis(1)));
+    let resources = resources;
+    std::thread::scope(|scope| {
+        let reader = resources.get::<One>().unwrap();
+        let writer = scope.spawn(|| resources.get_mut_blocking::<One>().unwrap().0 += 1);
+        while resources.get::<One>().is_ok() {
+            std::thread::yield_now();
+        }
+        drop(reader);
+        writer.join().unwrap();
+    });
+    assert_eq!(resources.get::<One>().unwrap().0, 2);
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn recursive_borrow() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    let resources = resources;
+
+    std::thread::scope(|scope| {
+        let outer = resources.get_recursive::<One>().unwrap();
+        let writer = scope.spawn(|| resources.get_mut_blocking::<One>().unwrap().0 += 1);
+
+        while resources.get::<One>().is_ok() {
+            std::thread::yield_now();
+        }
+        // Re-borrowing on the same thread works even though a mutable access is waiting.
+        let inner = resources.get_recursive::<One>().unwrap();
+        let inner_blocking = resources.get_blocking_recursive::<One>().unwrap();
+        assert_eq!(outer.0, inner.0);
+        assert_eq!(inner.0, inner_blocking.0);
+        drop((outer, inner, inner_blocking));
+        writer.join().unwrap();
+    });
+
+    assert_eq!(resources.get::<One>().unwrap().0, 2);
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn wait_changed() {
+    let mut resources = Resources::new();
+    resources.insert(One(0));
+    std::thread::scope(|scope| {
+        let waiter = scope.spawn(|| resources.wait_changed::<One>());
+        while !waiter.is_finished() {
+            resources.get_mut::<One>().unwrap().0 += 1;
+            std::thread::yield_now();
+        }
+        assert!(waiter.join().unwrap().is_ok());
+    });
+    assert!(resources.get::<One>().unwrap().0 > 0);
+    assert!(resources.wait_changed::<Two>().is_err());
+}
+
+#[cfg(feature = "stream")]
+#[test]
+fn change_streams() {
+    use futures::{executor::block_on, StreamExt};
+    use std::any::TypeId;
+
+    let mut resources = Resources::new();
+    let structure = resources.structure_changes();
+    resources.insert(One(1));
+    resources.insert(One(2));
+    resources.remove::<One>();
+    resources.insert(Two(2));
+
+    let mut changes = resources.changes::<Two>().unwrap();
+    std::thread::scope(|scope| {
+        scope.spawn(|| resources.get_mut::<Two>().unwrap().0 += 1);
+        assert_eq!(block_on(changes.next()), Some(()));
+    });
+    assert_eq!(*resources.get::<Two>().unwrap(), Two(3));
+
+    drop(resources);
+    let structure = block_on(structure.collect::<Vec<_>>());
+    let one = (TypeId::of::<One>(), "tests::One");
+    let two = (TypeId::of::<Two>(), "tests::Two");
+    assert_eq!(
+        structure,
+        [
+            StructureChange::Inserted {
+                type_id: one.0,
+                type_name: one.1
+            },
+            StructureChange::Removed {
+                type_id: one.0,
+                type_name: one.1
+            },
+            StructureChange::Inserted {
+                type_id: two.0,
+                type_name: two.1
+            },
+        ]
+    );
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_guards() {
+    use futures::executor::block_on;
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    std::thread::scope(|scope| {
+        let mut one = block_on(resources.write::<One>()).unwrap();
+        let reader = scope.spawn(|| block_on(async { resources.read::<One>().await.unwrap().0 }));
+        std::thread::yield_now();
+        one.0 = 2;
+        drop(one);
+        assert_eq!(reader.join().unwrap(), 2);
+    });
+    block_on(async {
+        let one = resources.read::<One>().await.unwrap();
+        let two = resources.read::<One>().await.unwrap();
+        assert_eq!(*one, *two);
+    });
+    assert!(block_on(resources.write::<Two>()).is_err());
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_guards_fifo() {
+    use futures::{executor::block_on, FutureExt};
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    let first = block_on(resources.read::<One>()).unwrap();
+    std::thread::scope(|scope| {
+        let writer = scope.spawn(|| block_on(resources.write::<One>()).unwrap().0 = 2);
+        // Once the writer is queued, readers can't overtake it.
+        while resources.read::<One>().now_or_never().is_some() {
+            std::thread::yield_now();
+        }
+        drop(first);
+        writer.join().unwrap();
+    });
+    assert_eq!(*block_on(resources.read::<One>()).unwrap(), One(2));
+}
+
+#[cfg(feature = "miette")]
+#[test]
+fn diagnostics() {
+    use miette::Diagnostic;
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    let _one = resources.get_mut::<One>().unwrap();
+    let error = resources.get::<One>().unwrap_err();
+    assert_eq!(
+        error.code().unwrap().to_string(),
+        "resources::invalid_borrow::immutable"
+    );
+    assert_eq!(
+        error.help().unwrap().to_string(),
+        "resource `tests::One` is exclusively borrowed via `get()` or similar; \
+         did you forget to drop a `RefMut`?"
+    );
+    let error = resources.get::<Two>().unwrap_err();
+    assert_eq!(
+        error.code().unwrap().to_string(),
+        "resources::no_such_resource"
+    );
+    assert!(error.help().unwrap().to_string().contains("`tests::Two`"));
+}
+
+#[test]
+fn borrow_state() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    resources.insert(Text("text"));
+    let resources = std::sync::Arc::new(resources);
+    assert_eq!(resources.borrow_state().to_string(), "");
+
+    let (one, one_line) = (resources.get_mut::<One>().unwrap(), line!());
+    let (two, two_line) = ([resources.get::<Two>(), resources.get::<Two>()], line!());
+    let state = resources.borrow_state().to_string();
+    let lines = state.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("tests::One: borrowed mutably"));
+    assert!(lines[1].starts_with("tests::Two: borrowed immutably 2 times"));
+    if cfg!(feature = "track-borrows") {
+        let site = |line| format!("{}:{}:", file!(), line);
+        assert!(lines[0].contains(&site(one_line)));
+        assert_eq!(lines[1].matches(&site(two_line)).count(), 2);
+    }
+
+    Resources::install_panic_hook(&resources);
+    assert!(std::panic::catch_unwind(|| panic!("expected")).is_err());
+    let _ = std::panic::take_hook();
+    drop((one, two));
+}
+
+#[test]
+fn report() {
+    use std::mem::size_of;
+
+    let mut resources = Resources::new();
+    resources.insert(Two(2));
+    resources.insert(One(1));
+    resources.insert(Text("text"));
+    resources.register_debug::<One>();
+    resources.register_debug::<Two>();
+
+    let two = resources.get_mut::<Two>().unwrap();
+    let report = resources.report();
+    let entries = report.entries();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].type_name, "tests::One");
+    assert_eq!(entries[0].size, size_of::<One>());
+    assert_eq!(entries[0].value.as_deref(), Some("One(1)"));
+    assert!(entries[1].value.is_none());
+    assert!(entries[2].borrowed_mutably);
+    assert!(entries[2].value.is_none());
+    drop(two);
+
+    let text = resources.get::<Text>().unwrap();
+    let report = resources.report().to_string();
+    assert_eq!(resources.report().to_string(), report);
+    drop(text);
+    let lines = report.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[0],
+        format!("3 resources, {} bytes", resources.memory_usage())
+    );
+    assert_eq!(
+        lines[1],
+        format!(
+            "tests::One ({} bytes, not borrowed) = One(1)",
+            size_of::<One>()
+        )
+    );
+    assert_eq!(
+        lines[2],
+        format!(
+            "tests::Text ({} bytes, borrowed immutably)",
+            size_of::<Text>()
+        )
+    );
+    assert_eq!(
+        lines[3],
+        format!(
+            "tests::Two ({} bytes, not borrowed) = Two(2)",
+            size_of::<Two>()
+        )
+    );
+}
+
+#[test]
+fn labeled() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    let _one = resources.get_mut_labeled::<One>("physics step").unwrap();
+    assert!(resources.get_labeled::<Text>("render").is_err());
+
+    let label = if cfg!(feature = "track-borrows") {
+        Some("physics step")
+    } else {
+        None
+    };
+    let error = resources.get::<One>().unwrap_err();
+    assert_eq!(
+        error,
+        CantGetResource::InvalidBorrow(InvalidBorrow::Immutable {
+            type_name: "tests::One",
+            conflict: Conflict::Writer {
+                via: BorrowApi::Get,
+                label,
+            },
+        })
+    );
+    let _two = resources.get_labeled::<Two>("render").unwrap();
+    if cfg!(feature = "track-borrows") {
+        assert!(error.to_string().ends_with(" by `physics step`"));
+        let state = resources.borrow_state().to_string();
+        assert!(state.contains("(`physics step`)"));
+        assert!(state.contains("(`render`)"));
+    }
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+// `metrics-util` storage is built on `crossbeam-epoch`, which Miri rejects.
+#[cfg_attr(miri, ignore)]
+fn metrics() {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    metrics::with_local_recorder(&recorder, || {
+        let mut resources = Resources::new();
+        resources.insert(One(1));
+        let _one = resources.get::<One>().unwrap();
+        assert!(resources.get_mut::<One>().is_err());
+    });
+    let values = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| {
+            let labels = key.key().labels().map(|label| label.value().to_owned());
+            let name = std::iter::once(key.key().name().to_owned()).chain(labels);
+            (name.collect::<Vec<_>>().join(" "), value)
+        })
+        .collect::<std::collections::HashMap<_, _>>();
+    // Inserting also acquires the resource mutably, to return a reference to it.
+    assert_eq!(
+        values["resources_acquisitions_total tests::One shared"],
+        DebugValue::Counter(1)
+    );
+    assert_eq!(
+        values["resources_acquisitions_total tests::One exclusive"],
+        DebugValue::Counter(1)
+    );
+    assert_eq!(
+        values["resources_conflicts_total tests::One exclusive"],
+        DebugValue::Counter(1)
+    );
+    assert!(matches!(
+        &values["resources_hold_seconds tests::One shared"],
+        DebugValue::Histogram(times) if times.len() == 1
+    ));
+    assert!(matches!(
+        &values["resources_stored"],
+        DebugValue::Gauge(stored) if **stored == 0.0
+    ));
+}
+
+#[cfg(feature = "chrome-tracing")]
+#[test]
+fn chrome_trace() {
+    use serde_json::Value;
+
+    // Recording is global, so a local type tells this test's events apart from others'.
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Traced;
+
+    let mut resources = Resources::new();
+    resources.insert(Traced);
+    let held = resources.get::<Traced>().unwrap();
+    ChromeTrace::start();
+    assert!(resources.get_mut::<Traced>().is_err());
+    drop(held);
+    drop(resources.get_mut::<Traced>().unwrap());
+    let trace = ChromeTrace::finish();
+    assert!(!trace.is_empty());
+    assert!(ChromeTrace::finish().is_empty());
+
+    let mut json = Vec::new();
+    trace.write_json(&mut json).unwrap();
+    let json: Value = serde_json::from_slice(&json).unwrap();
+    let events = json["traceEvents"].as_array().unwrap();
+    let track = events
+        .iter()
+        .find(|event| event["ph"] == "M" && event["args"]["name"] == "tests::chrome_trace::Traced")
+        .unwrap()["tid"]
+        .clone();
+    let names = events
+        .iter()
+        .filter(|event| event["ph"] != "M" && event["tid"] == track)
+        .map(|event| {
+            (
+                event["ph"].as_str().unwrap(),
+                event["name"].as_str().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            ("i", "conflict (exclusive)"),
+            ("X", "shared"),
+            ("X", "exclusive")
+        ]
+    );
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_getters() {
+    use futures::executor::block_on;
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    std::thread::scope(|scope| {
+        let one = block_on(resources.get_async::<One>()).unwrap();
+        let writer = scope
+            .spawn(|| block_on(async { resources.get_mut_async::<One>().await.unwrap().0 = 2 }));
+        std::thread::yield_now();
+        assert_eq!(one.0, 1);
+        drop(one);
+        writer.join().unwrap();
+    });
+    assert_eq!(block_on(resources.get_async::<One>()).unwrap().0, 2);
+    assert!(block_on(resources.get_mut_async::<Two>()).is_err());
+}
+
+#[test]
+fn get_opt() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    assert_eq!(*resources.get_opt::<One>().unwrap(), One(1));
+    resources.get_mut_opt::<One>().unwrap().0 = 2;
+    assert_eq!(*resources.get_opt::<One>().unwrap(), One(2));
+    assert!(resources.get_opt::<Two>().is_none());
+    assert!(resources.get_mut_opt::<Two>().is_none());
+}
+
+#[test]
+#[should_panic(expected = "cannot borrow immutably: tests::One")]
+fn get_opt_conflict() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    let _one = resources.get_mut::<One>().unwrap();
+    resources.get_opt::<One>();
+}
+
+#[test]
+fn lock_all() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    {
+        let mut all = resources.lock_all().unwrap();
+        all.get_mut::<One>().unwrap().0 = 3;
+        assert_eq!(*all.get::<One>().unwrap(), One(3));
+        assert!(all.contains::<Two>());
+        assert!(all.get::<Text>().is_err());
+        assert!(resources.get::<Two>().is_err());
+    }
+    {
+        let _two = resources.get::<Two>().unwrap();
+        assert_eq!(
+            resources.lock_all().err().unwrap(),
+            InvalidBorrow::Mutable {
+                type_name: "tests::Two",
+                conflict: Conflict::Readers {
+                    count: 1,
+                    via: BorrowApi::Get,
+                    label: None,
+                },
+            }
+        );
+    }
+    assert!(resources.get_mut::<One>().is_ok());
+    assert!(resources.get_mut::<Two>().is_ok());
+}
+
+#[test]
+fn read_all() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    {
+        let all = resources.read_all().unwrap();
+        let other = resources.read_all().unwrap();
+        assert_eq!(all.get::<One>().unwrap(), other.get::<One>().unwrap());
+        assert_eq!(*all.get::<Two>().unwrap(), Two(2));
+        assert!(all.get::<Text>().is_err());
+        assert!(resources.get::<Two>().is_ok());
+        assert!(resources.get_mut::<Two>().is_err());
+    }
+    {
+        let _one = resources.get_mut::<One>().unwrap();
+        assert_eq!(
+            resources.read_all().err().unwrap(),
+            InvalidBorrow::Immutable {
+                type_name: "tests::One",
+                conflict: Conflict::Writer {
+                    via: BorrowApi::Get,
+                    label: None,
+                },
+            }
+        );
+    }
+    assert!(resources.get_mut::<One>().is_ok());
+    assert!(resources.get_mut::<Two>().is_ok());
+}
+
+#[test]
+fn memory_budget() {
+    use std::mem::size_of;
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    assert_eq!(resources.memory_usage(), size_of::<One>());
+    assert_eq!(resources.memory_budget(), None);
+
+    resources.set_memory_budget(Some(size_of::<One>() + size_of::<Two>()));
+    assert_eq!(resources.try_insert(Two(2)).unwrap(), None);
+    assert_eq!(resources.try_insert(Two(3)).unwrap(), Some(Two(2)));
+    let error = resources.try_insert(Text("text")).err().unwrap();
+    assert_eq!(error.resource.0, "text");
+    assert_eq!(error.type_name, "tests::Text");
+    assert_eq!(error.memory_usage, resources.memory_usage());
+    assert!(!resources.contains::<Text>());
+
+    resources.remove::<One>();
+    assert_eq!(resources.memory_usage(), size_of::<Two>());
+    assert!(resources.try_insert(Maybe(None)).is_err());
+    resources.extend(vec![Box::new(Two(4)) as Box<dyn Resource>]);
+    assert_eq!(resources.memory_usage(), size_of::<Two>());
+    resources.set_memory_budget(None);
+    assert!(resources.try_insert(Text("text")).is_ok());
+}
+
+#[test]
+fn memory_budget_init() {
+    use std::mem::size_of;
+
+    let mut resources = Resources::new();
+    resources.set_memory_budget(Some(size_of::<One>()));
+    resources.add_initializer::<One, ()>(|_| One(1));
+    resources.add_initializer::<Two, (One,)>(|resources| Two(resources.get::<One>().unwrap().0));
+    match resources.init_all() {
+        Err(InitError::Budget(error)) => {
+            assert_eq!(error.type_name, "tests::Two");
+            assert_eq!(error.memory_usage, size_of::<One>());
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert!(resources.contains::<One>());
+    assert!(!resources.contains::<Two>());
+
+    // Infallible insertions aren't limited by the budget.
+    resources.insert(Two(2));
+    assert_eq!(
+        resources.memory_usage(),
+        size_of::<One>() + size_of::<Two>()
+    );
+}
+
+#[test]
+fn seal() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.seal();
+    assert!(resources.is_sealed());
+    assert_eq!(resources.insert(One(2)), Some(One(1)));
+    let error = resources.checked_insert(Two(2)).unwrap_err();
+    assert_eq!(error.resource, Two(2));
+    assert!(error.to_string().contains("container is sealed"));
+    assert!(resources.checked_remove::<One>().is_err());
+    assert_eq!(resources.checked_remove::<Two>().unwrap(), None);
+    assert_eq!(*resources.get::<One>().unwrap(), One(2));
+
+    resources.unseal();
+    assert!(!resources.is_sealed());
+    assert_eq!(resources.checked_insert(Two(2)).unwrap(), None);
+    assert_eq!(resources.remove::<One>(), Some(One(2)));
+}
+
+#[test]
+fn seal_clear() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert_transient(Two(2));
+    resources.seal();
+    assert_eq!(resources.insert(One(2)), Some(One(1)));
+    assert_eq!(resources.resource_scope(|_, one: &mut One| one.0), Ok(2));
+    resources.maintain();
+    assert!(!resources.contains::<Two>());
+    resources.clear();
+    assert!(!resources.contains::<One>());
+    assert!(resources.is_sealed());
+}
+
+#[test]
+#[should_panic(expected = "container is sealed: cannot insert or remove tests::Two")]
+fn seal_insert() {
+    let mut resources = Resources::new();
+    resources.seal();
+    resources.insert(Two(2));
+}
+
+#[test]
+#[should_panic(expected = "container is sealed: cannot insert or remove tests::One")]
+fn seal_remove() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.seal();
+    resources.remove::<One>();
+}
+
+#[test]
+#[should_panic(expected = "container is sealed: cannot insert or remove tests::Two")]
+fn seal_extend() {
+    let mut resources = Resources::new();
+    resources.seal();
+    resources.extend(vec![Box::new(Two(2)) as Box<dyn Resource>]);
+}
+
+#[test]
+fn seal_move_to() {
+    let mut source = Resources::new();
+    source.insert(One(1));
+    let mut destination = Resources::new();
+    destination.seal();
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        source.move_to(&mut destination, &[std::any::TypeId::of::<One>()]);
+    }));
+    assert!(panicked.is_err());
+    assert_eq!(*source.get::<One>().unwrap(), One(1));
+    assert!(!destination.contains::<One>());
+}
+
+#[test]
+fn drop_priorities() {
+    use std::sync::{Arc, Mutex};
+
+    type Log = Arc<Mutex<Vec<&'static str>>>;
+
+    macro_rules! logged {
+        ($($name:ident),*) => {$(
+            #[cfg_attr(feature = "derive", derive(Resource))]
+            struct $name(Log);
+
+            impl Drop for $name {
+                fn drop(&mut self) {
+                    self.0.lock().unwrap().push(stringify!($name));
+                }
+            }
+        )*};
+    }
+
+    logged!(Device, Buffer, Texture);
+
+    let log = Log::default();
+    let fill = |resources: &mut Resources| {
+        resources.insert(Texture(log.clone()));
+        resources.insert(Device(log.clone()));
+        resources.insert(Buffer(log.clone()));
+    };
+
+    let mut resources = Resources::new();
+    resources.set_drop_priority::<Device>(-1);
+    resources.set_drop_priority::<Texture>(1);
+    fill(&mut resources);
+    resources.clear();
+    assert!(resources.get::<Device>().is_err());
+    assert_eq!(*log.lock().unwrap(), ["Texture", "Buffer", "Device"]);
+
+    log.lock().unwrap().clear();
+    fill(&mut resources);
+    drop(resources);
+    assert_eq!(*log.lock().unwrap(), ["Texture", "Buffer", "Device"]);
+}
+
+#[test]
+fn finalizers() {
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Logger(Vec<&'static str>);
+
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Queue(Vec<&'static str>);
+
+    let mut resources = Resources::new();
+    resources.set_finalizer::<Queue>(|queue, resources| {
+        if let Ok(mut logger) = resources.get_mut::<Logger>() {
+            logger.0.append(&mut queue.0);
+        }
+    });
+    resources.insert(Logger(vec![]));
+    resources.insert(Queue(vec!["one", "two"]));
+    assert!(resources.remove::<Queue>().unwrap().0.is_empty());
+    assert_eq!(resources.get::<Logger>().unwrap().0, ["one", "two"]);
+
+    resources.insert(Queue(vec!["three"]));
+    resources.set_drop_priority::<Queue>(1);
+    resources.clear();
+    assert!(resources.get::<Logger>().is_err());
+
+    let log = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+    let shared = log.clone();
+    resources.set_finalizer::<Logger>(move |logger, _| {
+        shared.lock().unwrap().append(&mut logger.0);
+    });
+    resources.insert(Logger(vec![]));
+    resources.insert(Queue(vec!["four"]));
+    drop(resources);
+    assert_eq!(*log.lock().unwrap(), ["four"]);
+}
+
+#[test]
+fn prehashed() {
+    use std::{any::TypeId, hash::BuildHasher};
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    let one = PreHashed::<One>::new();
+    let two = PreHashed::<Two>::new();
+    assert_eq!(one.hash(), resources.hasher().hash_one(TypeId::of::<One>()));
+    assert!(resources.contains_prehashed(&one));
+    assert!(!resources.contains_prehashed(&two));
+    resources.get_mut_prehashed(&one).unwrap().0 = 2;
+    assert_eq!(resources.get_prehashed(&one).unwrap().0, 2);
+    assert!(resources.get_mut_prehashed(&two).is_err());
+}
+
+#[cfg(feature = "epoch")]
+#[test]
+// `crossbeam-epoch` violates Stacked Borrows, which Miri rejects.
+#[cfg_attr(miri, ignore)]
+fn rcu() {
+    let mut resources = Resources::new();
+    assert!(resources.insert_rcu(One(1)).is_none());
+    let old = resources.snapshot::<One>().unwrap();
+    {
+        // Rcu resources don't conflict with anything, including each other.
+        let _guard = resources.lock_all().unwrap();
+        resources.rcu::<One>().unwrap().publish(One(2));
+        resources.rcu::<One>().unwrap().update(|one| One(one.0 * 2));
+    }
+    assert_eq!(old.0, 1);
+    assert_eq!(resources.snapshot::<One>().unwrap().0, 4);
+    assert!(resources.snapshot::<Two>().is_err());
+    drop(old);
+    assert_eq!(resources.insert_rcu(One(5)).unwrap().0, 4);
+    assert!(!resources.contains::<One>());
+    assert_eq!(resources.remove_rcu::<One>().unwrap().0, 5);
+    assert!(resources.rcu::<One>().is_err());
+}
+
+#[test]
+fn branded() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    let sum = resources.branded(|view, mut token| {
+        assert!(view.contains::<One>());
+        assert!(!view.contains::<Text>());
+        assert!(view.get::<Text>(&token).is_err());
+        let one = view.get::<One>(&token).unwrap();
+        let two = view.get::<Two>(&token).unwrap();
+        let sum = one.0 + two.0;
+        view.get_mut::<Two>(&mut token).unwrap().0 = sum;
+        sum
+    });
+    assert_eq!(sum, 3);
+    assert_eq!(resources.get::<Two>().unwrap().0, 3);
+}
+
+#[test]
+fn state_hash() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    let empty = resources.state_hash().unwrap();
+    resources.set_state_hasher::<One>(|one| one.0 as u64);
+    resources.set_state_hasher::<Two>(|two| two.0 as u64);
+    let hash = resources.state_hash().unwrap();
+    assert_ne!(hash, empty);
+
+    let mut other = Resources::new();
+    other.set_state_hasher::<Two>(|two| two.0 as u64);
+    other.set_state_hasher::<One>(|one| one.0 as u64);
+    other.insert(Two(2));
+    other.insert(One(1));
+    assert_eq!(other.state_hash().unwrap(), hash);
+    other.get_mut::<Two>().unwrap().0 = 3;
+    assert_ne!(other.state_hash().unwrap(), hash);
+
+    let _one = resources.get_mut::<One>().unwrap();
+    assert!(resources.state_hash().is_err());
+}
+
+#[test]
+fn access_log() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+
+    let tick = |resources: &Resources, swapped: bool| {
+        resources.start_access_log();
+        if swapped {
+            resources.get_mut::<Two>().unwrap();
+            resources.get::<One>().unwrap();
+        } else {
+            resources.get::<One>().unwrap();
+            resources.get_mut::<Two>().unwrap();
+        }
+        assert!(resources.get::<Text>().is_err());
+        resources.finish_access_log()
+    };
+
+    let baseline = tick(&resources, false);
+    assert_eq!(baseline.accesses().len(), 2);
+    assert_eq!(baseline.accesses()[0].type_name(), "tests::One");
+    assert!(!baseline.accesses()[0].is_mutable());
+    assert!(baseline.accesses()[1].is_mutable());
+    assert_eq!(tick(&resources, false).first_divergence(&baseline), None);
+
+    let divergence = tick(&resources, true).first_divergence(&baseline).unwrap();
+    assert_eq!(divergence.index, 0);
+    assert_eq!(divergence.actual.unwrap().type_name(), "tests::Two");
+    assert!(divergence.to_string().starts_with(
+        "access #0 diverged: expected tests::One borrowed immutably at tests/tests.rs:"
+    ));
+
+    let divergence = AccessLog::default().first_divergence(&baseline).unwrap();
+    assert_eq!(divergence.actual, None);
+    assert!(resources.finish_access_log().accesses().is_empty());
+}
+
+#[test]
+fn clone_subset() {
+    #[derive(Clone)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Config(&'static str);
+
+    #[derive(Clone)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Camera(f32);
+
+    #[derive(Clone)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Missing;
+
+    let mut resources = Resources::new();
+    resources.insert(Config("config"));
+    resources.insert(Camera(1.0));
+    resources.insert(One(1));
+    let subset = resources.clone_subset::<(Config, Camera)>().unwrap();
+    assert_eq!(subset.get::<Config>().unwrap().0, "config");
+    assert_eq!(subset.get::<Camera>().unwrap().0, 1.0);
+    assert!(!subset.contains::<One>());
+    assert!(resources.contains::<Config>());
+
+    assert!(resources.clone_subset::<(Config, Missing)>().is_err());
+    let _camera = resources.get_mut::<Camera>().unwrap();
+    assert!(resources.clone_subset::<(Camera,)>().is_err());
+}
+
+#[test]
+fn move_to() {
+    use std::any::TypeId;
+
+    let mut persistent = Resources::new();
+    persistent.insert(One(1));
+    persistent.insert(Two(2));
+    let mut level = Resources::<()>::default();
+    level.insert(One(3));
+
+    persistent.move_to(&mut level, &[TypeId::of::<One>(), TypeId::of::<Text>()]);
+    assert!(!persistent.contains::<One>());
+    assert_eq!(level.get::<One>().unwrap().0, 1);
+
+    persistent.move_types_to::<(Two,), _>(&mut level);
+    assert_eq!(persistent.type_ids().count(), 0);
+    assert_eq!(level.get::<Two>().unwrap().0, 2);
+
+    level.move_types_to::<(One, Two), _>(&mut persistent);
+    assert_eq!(level.type_ids().count(), 0);
+    assert_eq!(persistent.type_ids().count(), 2);
+}
+
+#[test]
+fn split_filter() {
+    use std::any::TypeId;
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    let (saved, transient) = resources.split_filter(|type_id| type_id == TypeId::of::<One>());
+    assert_eq!(saved.get::<One>().unwrap().0, 1);
+    assert!(!saved.contains::<Two>());
+    assert_eq!(transient.get::<Two>().unwrap().0, 2);
+    assert!(!transient.contains::<One>());
+}
+
+#[test]
+fn transaction() {
+    #[derive(Clone)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Progress(Vec<usize>);
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Progress(vec![]));
+
+    let result: Result<(), &str> = resources.transaction(|tx| {
+        tx.get_mut::<Progress>().unwrap().0.push(1);
+        tx.insert(One(2)).unwrap();
+        tx.insert(Two(2)).unwrap();
+        assert_eq!(tx.get::<One>().unwrap().0, 2);
+        assert!(tx.remove::<One>().unwrap());
+        assert!(!tx.contains::<One>());
+        tx.get_mut::<Progress>().unwrap().0.push(2);
+        Err("corrupted save")
+    });
+    assert_eq!(result, Err("corrupted save"));
+    assert_eq!(resources.get::<One>().unwrap().0, 1);
+    assert!(!resources.contains::<Two>());
+    assert!(resources.get::<Progress>().unwrap().0.is_empty());
+
+    let result = resources.transaction(|tx| {
+        tx.get_mut::<Progress>().unwrap().0.push(1);
+        tx.insert(Two(2)).unwrap();
+        tx.remove::<One>().unwrap();
+        Ok::<_, ()>(3)
+    });
+    assert_eq!(result, Ok(3));
+    assert!(!resources.contains::<One>());
+    assert_eq!(resources.get::<Two>().unwrap().0, 2);
+    assert_eq!(resources.get::<Progress>().unwrap().0, [1]);
+
+    // Rolling back changes in place keeps strategies of locks, and works on a sealed container.
+    resources.insert_with_lock(One(1), LockKind::Mutex);
+    resources.drained_removals().for_each(drop);
+    resources.seal();
+    let result: Result<(), ()> = resources.transaction(|tx| {
+        tx.get_mut::<Progress>().unwrap().0.push(2);
+        tx.insert(One(2)).unwrap();
+        assert!(tx.insert(Maybe(None)).is_err());
+        assert!(tx.remove::<Two>().is_err());
+        Err(())
+    });
+    assert!(result.is_err());
+    assert_eq!(resources.get::<One>().unwrap().0, 1);
+    assert_eq!(resources.get::<Two>().unwrap().0, 2);
+    assert_eq!(resources.get::<Progress>().unwrap().0, [1]);
+    assert_eq!(resources.lock_kind::<One>(), Some(LockKind::Mutex));
+    assert!(!resources.removed::<Progress>());
+    assert!(!resources.removed::<One>());
+
+    resources.unseal();
+    let result: Result<(), ()> = resources.transaction(|tx| {
+        tx.remove::<One>().unwrap();
+        tx.insert(One(3)).unwrap();
+        tx.remove::<One>().unwrap();
+        Err(())
+    });
+    assert!(result.is_err());
+    assert_eq!(resources.get::<One>().unwrap().0, 1);
+    assert_eq!(resources.lock_kind::<One>(), Some(LockKind::Mutex));
+}
+
+#[test]
+fn par_exec() {
+    use std::sync::{Arc, Barrier, Mutex};
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    let order = Mutex::new(vec![]);
+    // Both readers have to run at the same time to get past the barrier.
+    let barrier = Arc::new(Barrier::new(2));
+    let reader = |name| {
+        let (barrier, order) = (barrier.clone(), &order);
+        System::new(move |resources: &Resources| {
+            let _one = resources.get::<One>().unwrap();
+            barrier.wait();
+            order.lock().unwrap().push(name);
+        })
+        .reads::<(One,)>()
+    };
+    resources.par_exec(vec![
+        System::new(|resources| {
+            resources.get_mut::<One>().unwrap().0 += 1;
+            order.lock().unwrap().push("writer");
+        })
+        .writes::<(One,)>()
+        .reads::<(Two,)>(),
+        reader("first"),
+        reader("second"),
+        System::new(|resources| resources.get_mut::<Two>().unwrap().0 = 3).writes::<(Two,)>(),
+    ]);
+    let order = order.into_inner().unwrap();
+    assert_eq!(order[0], "writer");
+    assert_eq!(order.len(), 3);
+    assert_eq!(resources.get::<One>().unwrap().0, 2);
+    assert_eq!(resources.get::<Two>().unwrap().0, 3);
+}
+
+#[test]
+fn par_exec_lens() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Settings {
+        gravity: Gravity,
+    }
+
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Gravity(f32);
+
+    let mut resources = Resources::new();
+    resources.insert(Settings {
+        gravity: Gravity(-9.8),
+    });
+    resources.register_lens(
+        |settings: &Settings| &settings.gravity,
+        |settings: &mut Settings| &mut settings.gravity,
+    );
+    let written = AtomicBool::new(false);
+    resources.par_exec(vec![
+        System::new(|resources| {
+            let mut settings = resources.get_mut::<Settings>().unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            settings.gravity.0 = -1.6;
+            written.store(true, Ordering::Relaxed);
+        })
+        .writes::<(Settings,)>(),
+        System::new(|resources| {
+            assert!(written.load(Ordering::Relaxed));
+            assert_eq!(resources.get::<Gravity>().unwrap().0, -1.6);
+        })
+        .reads::<(Gravity,)>(),
+    ]);
+}
+
+#[cfg(feature = "shred-compat")]
+#[test]
+fn shred_compat() {
+    use resources::shred::{self, Read, ReadExpect, RunNow, SystemData, World, Write};
+    use std::any::TypeId;
+
+    struct Count;
+
+    impl<'a> shred::System<'a> for Count {
+        type SystemData = (ReadExpect<'a, One>, Write<'a, Two>, Option<Read<'a, Maybe>>);
+
+        fn run(&mut self, (one, mut two, maybe): Self::SystemData) {
+            assert!(maybe.is_none());
+            two.0 += one.0;
+        }
+    }
+
+    let mut world = World::from(Resources::new());
+    world.insert(One(1));
+    let mut count = Count;
+    shred::System::setup(&mut count, &mut world);
+    assert_eq!(*world.fetch::<Two>(), Two(2));
+    count.run_now(&world);
+    assert_eq!(world.fetch::<Two>().0, 3);
+    assert!(world.try_fetch::<Maybe>().is_none());
+    assert!(!world.has_value::<Maybe>());
+
+    type Data<'a> = <Count as shred::System<'a>>::SystemData;
+    assert_eq!(
+        Data::reads(),
+        vec![TypeId::of::<One>(), TypeId::of::<Maybe>()]
+    );
+    assert_eq!(Data::writes(), vec![TypeId::of::<Two>()]);
+
+    let _one = world.fetch_mut::<One>();
+    let conflict = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        world.system_data::<Data>();
+    }));
+    assert!(conflict.is_err());
+    drop(_one);
+
+    let system = shred::into_system(Count);
+    assert!(system
+        .access()
+        .conflicts_with(&AccessSet::new().reads::<(Two,)>()));
+    assert!(!system
+        .access()
+        .conflicts_with(&AccessSet::new().reads::<(One,)>()));
+    world.par_exec(vec![system]);
+    assert_eq!(world.fetch::<Two>().0, 4);
+}
+
+#[cfg(feature = "hecs")]
+#[test]
+fn hecs_world() {
+    #[derive(Debug, PartialEq)]
+    struct Position(f32);
+
+    let mut world = hecs::World::new();
+    let entity = world.spawn((Position(1.0),));
+    let mut resources = Resources::new();
+    resources.insert(One(2));
+
+    resources.par_exec(vec![
+        System::new(|resources| {
+            let pair = resources.with_world(&world);
+            let (one, mut query) = pair.fetch_query::<&One, &mut Position>().unwrap();
+            for position in query.iter() {
+                position.0 *= one.0 as f32;
+            }
+        })
+        .reads::<(One,)>()
+        .writes_components::<(Position,)>(),
+        System::new(|resources| {
+            let pair = resources.with_world(&world);
+            assert_eq!(pair.get::<&Position>(entity).unwrap().0, 2.0);
+            assert!(matches!(pair.fetch::<&Two>(), Err(WorldError::Resource(_))));
+        })
+        .reads_components::<(Position,)>(),
+    ]);
+
+    world.despawn(entity).unwrap();
+    let pair = resources.with_world(&world);
+    assert_eq!(
+        pair.get::<&Position>(entity).err(),
+        Some(WorldError::Component(hecs::ComponentError::NoSuchEntity))
+    );
+}
+
+#[cfg(feature = "rhai")]
+#[test]
+fn script_access() {
+    use std::sync::Arc;
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Inventory {
+        gold: i64,
+        items: Vec<String>,
+    }
+
+    let mut resources = Resources::new();
+    resources.insert(Inventory {
+        gold: 5,
+        items: vec!["sword".to_string()],
+    });
+    resources.register_script_resource::<Inventory>("inventory");
+    assert_eq!(
+        resources
+            .script_get("inventory", "items.0")
+            .unwrap()
+            .into_string()
+            .unwrap(),
+        "sword"
+    );
+    assert_eq!(
+        resources.script_get("inventory", "items.1").unwrap_err(),
+        ScriptError::NoSuchField {
+            path: "items.1".to_string()
+        }
+    );
+    assert!(matches!(
+        resources.script_get("wallet", "gold"),
+        Err(ScriptError::NotRegistered { .. })
+    ));
+    assert!(matches!(
+        resources.script_set("inventory", "gold", "many".into()),
+        Err(ScriptError::Conversion(_))
+    ));
+
+    let resources = Arc::new(resources);
+    let mut engine = rhai::Engine::new();
+    Resources::register_script_functions(&resources, &mut engine);
+    engine
+        .run(
+            r#"
+            let gold = get_resource("inventory", "gold");
+            set_resource("inventory", "gold", gold * 2);
+            set_resource("inventory", "items.0", "shield");
+            "#,
+        )
+        .unwrap();
+    let inventory = resources.get::<Inventory>().unwrap();
+    assert_eq!(inventory.gold, 10);
+    assert_eq!(inventory.items, ["shield"]);
+    drop(inventory);
+    assert!(engine
+        .run(r#"get_resource("inventory", "silver")"#)
+        .is_err());
+}
+
+#[test]
+fn stable_ids() {
+    use std::any::TypeId;
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    resources.register_stable_id::<One>("tests::One");
+    resources.register_stable_id::<Two>(0x6ba7_b810_9dad_11d1_80b4_00c0_4fd4_30c8);
+    let one = StableId::from("tests::One");
+    let two = StableId::from(0x6ba7_b810_9dad_11d1_80b4_00c0_4fd4_30c8);
+    assert_eq!(two.to_string(), "6ba7b810-9dad-11d1-80b4-00c04fd430c8");
+    assert_eq!(resources.stable_id::<One>(), Some(&one));
+    assert_eq!(resources.type_id_of(&two), Some(TypeId::of::<Two>()));
+    assert_eq!(
+        resources
+            .get_stable(&one)
+            .unwrap()
+            .unwrap()
+            .downcast_ref::<One>(),
+        Some(&One(1))
+    );
+    {
+        let _one = resources.get_mut::<One>().unwrap();
+        assert_eq!(
+            resources.get_stable(&one).err().unwrap(),
+            InvalidBorrow::Immutable {
+                type_name: "tests::One",
+                conflict: Conflict::Writer {
+                    via: BorrowApi::Get,
+                    label: None,
+                },
+            }
+        );
+    }
+
+    // Registering the identifier for another type replaces its previous registration.
+    resources.register_stable_id::<Two>("tests::One");
+    assert_eq!(resources.stable_id::<One>(), None);
+    assert_eq!(resources.stable_id_of(TypeId::of::<Two>()), Some(&one));
+    assert!(resources.get_stable(&two).unwrap().is_none());
+    assert!(resources.remove_stable(&one).unwrap().is::<Two>());
+    assert!(!resources.contains_stable(&one));
+    assert!(resources.contains::<One>());
+}
+
+#[cfg(feature = "abi_stable")]
+#[test]
+fn ffi_access() {
+    use abi_stable::StableAbi;
+
+    #[repr(C)]
+    #[derive(StableAbi, Debug, PartialEq)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Score {
+        value: u32,
+    }
+
+    #[repr(C)]
+    #[derive(StableAbi)]
+    struct Wide {
+        value: u64,
+    }
+
+    let mut resources = Resources::new();
+    resources.insert(Score { value: 1 });
+    resources.register_ffi_resource::<Score>("score");
+    assert_eq!(
+        resources.stable_id::<Score>(),
+        Some(&StableId::from("score"))
+    );
+
+    // A module would receive the handle through an `extern "C"` function.
+    let ffi = resources.as_ffi();
+    ffi.get_mut::<Score>("score").unwrap().value += 1;
+    {
+        let score = ffi.get::<Score>("score").unwrap();
+        assert_eq!(*score, Score { value: 2 });
+        assert_eq!(
+            ffi.get_mut::<Score>("score").unwrap_err(),
+            FfiError::InvalidBorrow
+        );
+        assert_eq!(resources.get::<Score>().unwrap().value, 2);
+    }
+    assert!(matches!(
+        ffi.get::<Wide>("score"),
+        Err(FfiError::IncompatibleLayout(_))
+    ));
+    assert_eq!(
+        ffi.get::<Score>("points").unwrap_err(),
+        FfiError::NotRegistered
+    );
+    assert!(resources.get_mut::<Score>().is_ok());
+}
+
+#[test]
+fn plugins() {
+    use std::any::TypeId;
+
+    #[derive(Clone, Debug, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize))]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Weather {
+        rain: bool,
+    }
+
+    let mut resources = Resources::new();
+    resources.load_plugin("weather", |registry| {
+        let vtable = TypeVTable::of::<Weather>()
+            .with_stable_id("weather::Weather")
+            .with_clone::<Weather>()
+            .with_debug::<Weather>();
+        #[cfg(feature = "serde")]
+        let vtable = vtable.with_serialize::<Weather>();
+        registry.register(vtable);
+        registry.resources().insert(Weather { rain: true });
+    });
+    assert_eq!(
+        resources.plugin_types("weather"),
+        Some(&[TypeId::of::<Weather>()][..])
+    );
+
+    let vtable = resources.type_vtable(TypeId::of::<Weather>()).unwrap();
+    let weather = resources
+        .get_stable(&"weather::Weather".into())
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        format!("{:?}", vtable.debug(&*weather).unwrap()),
+        "Weather { rain: true }"
+    );
+    assert!(vtable.debug(&One(1)).is_none());
+    let clone = vtable.clone_resource(&*weather).unwrap();
+    assert_eq!(clone.downcast_ref(), Some(&Weather { rain: true }));
+    #[cfg(feature = "serde")]
+    assert_eq!(
+        serde_json::to_string(vtable.serialize(&*weather).unwrap()).unwrap(),
+        r#"{"rain":true}"#
+    );
+    drop(weather);
+
+    assert!(resources.unload_plugin("weather"));
+    assert!(!resources.contains::<Weather>());
+    assert!(resources.type_vtable(TypeId::of::<Weather>()).is_none());
+    assert!(resources.stable_id::<Weather>().is_none());
+    assert!(!resources.unload_plugin("weather"));
+}
+
+#[test]
+fn storage_alignment() {
+    #[derive(Debug, PartialEq)]
+    #[repr(align(64))]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Aligned(u8);
+
+    #[derive(Debug, PartialEq)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Unit;
+
+    let mut resources = Resources::new();
+    resources.insert(Aligned(1));
+    resources.insert(Unit);
+    resources.insert(One(1));
+    let aligned = resources.get::<Aligned>().unwrap();
+    assert_eq!(&*aligned as *const Aligned as usize % 64, 0);
+    drop(aligned);
+
+    // Boxed resources are moved into memory of the same layout.
+    let mut other = resources
+        .into_iter()
+        .map(|(_, resource)| resource)
+        .collect::<Resources>();
+    assert_eq!(*other.get::<Aligned>().unwrap(), Aligned(1));
+    assert_eq!(
+        other.type_name_of(std::any::TypeId::of::<Unit>()),
+        Some(std::any::type_name::<Unit>())
+    );
+    assert_eq!(other.remove::<Unit>(), Some(Unit));
+    other.insert(Unit);
+    assert_eq!(*other.get::<Unit>().unwrap(), Unit);
+    assert_eq!(other.remove::<Aligned>(), Some(Aligned(1)));
+    other.insert(Aligned(2));
+    let aligned = other.get::<Aligned>().unwrap();
+    assert_eq!(&*aligned as *const Aligned as usize % 64, 0);
+    drop(aligned);
+    assert_eq!(*other.get::<One>().unwrap(), One(1));
+}
+
+#[test]
+fn insert_unique_unchecked() {
+    let mut resources = Resources::new();
+    unsafe {
+        resources.insert_unique_unchecked(One(1));
+        resources.insert_unique_unchecked(Two(2));
+    }
+    assert_eq!(*resources.get::<One>().unwrap(), One(1));
+    assert_eq!(resources.insert(Two(3)), Some(Two(2)));
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "is already present")]
+fn insert_unique_unchecked_duplicate() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    unsafe { resources.insert_unique_unchecked(One(2)) };
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn prepared_fetch() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(0));
+    assert_eq!(
+        resources.prepare::<(&One, &Ordered)>().unwrap_err().cause,
+        CantGetResource::NoSuchResource(NoSuchResource {
+            type_name: std::any::type_name::<Ordered>()
+        })
+    );
+
+    let prepared = resources.prepare::<(&One, &mut Two)>().unwrap();
+    for _ in 0..3 {
+        let (one, mut two) = prepared.fetch().unwrap();
+        two.0 += one.0;
+    }
+    assert_eq!(*resources.get::<Two>().unwrap(), Two(3));
+    let _one = resources.get_mut::<One>().unwrap();
+    assert_eq!(
+        prepared.fetch().unwrap_err().cause,
+        CantGetResource::InvalidBorrow(InvalidBorrow::Immutable {
+            type_name: std::any::type_name::<One>(),
+            conflict: Conflict::Writer {
+                via: BorrowApi::Get,
+                label: None,
+            },
+        })
+    );
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn prepared_fetch_alias() {
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Renamed(One);
+
+    let mut resources = Resources::new();
+    resources.insert(Renamed(One(1)));
+    resources.alias::<One, Renamed>(|renamed| &renamed.0, |renamed| &mut renamed.0);
+    let prepared = resources.prepare::<(&mut One,)>().unwrap();
+    prepared.fetch().unwrap().0 .0 = 2;
+    {
+        let _renamed = resources.get::<Renamed>().unwrap();
+        assert!(prepared.fetch().unwrap_err().cause.is_borrow_conflict());
+    }
+    assert_eq!(*prepared.fetch().unwrap().0, One(2));
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn test_util() {
+    let mut resources = Resources::test_with((One(1), Two(2)));
+    assert_contains!(resources, One, Two);
+    assert_not_contains!(resources, Ordered);
+
+    let sum = resources.with_override(One(10), |resources| {
+        let one = resources.get::<One>().unwrap().0;
+        one + resources.get::<Two>().unwrap().0
+    });
+    assert_eq!(sum, 12);
+    assert_eq!(*resources.get::<One>().unwrap(), One(1));
+
+    resources.with_override(Ordered(0), |resources| assert_contains!(resources, Ordered));
+    assert_not_contains!(resources, Ordered);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        resources.with_override(Two(20), |_| panic!("system failed"));
+    }));
+    assert!(result.is_err());
+    assert_eq!(*resources.get::<Two>().unwrap(), Two(2));
+
+    resources.seal();
+    assert_eq!(
+        resources.with_override(One(10), |resources| resources.get::<One>().unwrap().0),
+        10
+    );
+    resources.with_override(Ordered(0), |resources| assert_contains!(resources, Ordered));
+    assert_not_contains!(resources, Ordered);
+    assert!(resources.is_sealed());
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn injected_failures() {
+    let resources = Resources::test_with((One(1), Two(2)));
+    resources.inject_failure::<One>(InjectedFailure::Conflict);
+    resources.inject_failure::<Two>(InjectedFailure::Missing);
+    assert_eq!(
+        resources.get_mut::<One>().unwrap_err(),
+        CantGetResource::InvalidBorrow(InvalidBorrow::Mutable {
+            type_name: "tests::One",
+            conflict: Conflict::Injected,
+        })
+    );
+    assert!(resources.get::<One>().unwrap_err().is_borrow_conflict());
+    assert!(resources.get::<Two>().unwrap_err().is_missing());
+    assert!(resources.get_opt::<Two>().is_none());
+    assert!(resources.contains::<Two>());
+
+    resources.clear_injected_failures();
+    assert_eq!(*resources.get::<One>().unwrap(), One(1));
+
+    let outcomes = |seed| {
+        resources.inject_random_failures(InjectedFailure::Missing, 0.5, seed);
+        let outcomes = (0..64)
+            .map(|_| resources.get::<One>().is_ok())
+            .collect::<Vec<_>>();
+        resources.clear_injected_failures();
+        outcomes
+    };
+    let first = outcomes(7);
+    assert_eq!(first, outcomes(7));
+    assert!(first.contains(&true) && first.contains(&false));
+    assert!(resources.get::<One>().is_ok());
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn injected_failures_other_accessors() {
+    use std::any::TypeId;
+    let mut resources = Resources::test_with((One(1), Two(2)));
+    resources.register_stable_id::<One>("tests::One");
+    resources.register_stable_id::<Two>("tests::Two");
+    resources.inject_failure::<One>(InjectedFailure::Conflict);
+    resources.inject_failure::<Two>(InjectedFailure::Missing);
+
+    assert!(resources
+        .get_labeled::<One>("system")
+        .unwrap_err()
+        .is_borrow_conflict());
+    assert!(resources
+        .get_recursive::<One>()
+        .unwrap_err()
+        .is_borrow_conflict());
+    assert!(resources
+        .get_prehashed(&PreHashed::<Two>::new())
+        .unwrap_err()
+        .is_missing());
+    assert!(matches!(
+        resources.get_many_dyn(&[TypeId::of::<One>()]),
+        Err(CantGetDyn::InvalidBorrow(_))
+    ));
+    assert!(matches!(
+        resources.get_many_dyn(&[TypeId::of::<Two>()]),
+        Err(CantGetDyn::NoSuchResource(_))
+    ));
+    assert!(resources
+        .get_stable(&StableId::from("tests::Two"))
+        .unwrap()
+        .is_none());
+    assert!(resources.get_stable(&StableId::from("tests::One")).is_err());
+    #[cfg(feature = "blocking")]
+    assert!(resources.get_blocking::<Two>().unwrap_err().is_missing());
+    #[cfg(feature = "async")]
+    assert!(futures::executor::block_on(resources.get_async::<Two>()).is_err());
+    #[cfg(feature = "fetch")]
+    assert!(resources
+        .prepare::<(&Two,)>()
+        .unwrap()
+        .fetch()
+        .unwrap_err()
+        .cause
+        .is_missing());
+
+    resources.clear_injected_failures();
+    assert!(resources.get_recursive::<One>().is_ok());
+    assert!(resources
+        .get_stable(&StableId::from("tests::Two"))
+        .unwrap()
+        .is_some());
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+#[should_panic(expected = "resources are missing: tests::Ordered")]
+fn assert_contains_missing() {
+    assert_contains!(Resources::test_with((One(1),)), One, Ordered);
+}
+
+#[cfg(feature = "inventory")]
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "derive", derive(Resource))]
+struct Registered(usize);
+
+#[cfg(feature = "inventory")]
+register_default!(Registered);
+
+#[cfg(feature = "inventory")]
+#[test]
+fn registered_defaults() {
+    let resources = Resources::with_registered();
+    assert_eq!(*resources.get::<Registered>().unwrap(), Registered(0));
+
+    let mut resources = Resources::new();
+    resources.insert(Registered(1));
+    resources.insert_registered();
+    assert_eq!(*resources.get::<Registered>().unwrap(), Registered(1));
+}
+
+#[test]
+fn events() {
+    let mut resources = Resources::new();
+    resources.add_events::<u32>();
+    let mut reader = EventReader::<u32>::new();
+    resources.event_writer::<u32>().unwrap().send(1);
+    resources.update_events();
+    resources
+        .event_writer::<u32>()
+        .unwrap()
+        .send_batch(vec![2, 3]);
+    {
+        let events = resources.get::<Events<u32>>().unwrap();
+        assert!(reader.has_unread(&events));
+        assert_eq!(reader.read(&events).copied().collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(!reader.has_unread(&events));
+    }
+    let mut late_reader = EventReader::<u32>::new();
+    resources.update_events();
+    resources.event_writer::<u32>().unwrap().send(4);
+    resources.update_events();
+    let events = resources.get::<Events<u32>>().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(reader.read(&events).copied().collect::<Vec<_>>(), [4]);
+    assert_eq!(late_reader.read(&events).copied().collect::<Vec<_>>(), [4]);
+    assert_eq!(reader.read(&events).count(), 0);
+}
+
+#[test]
+fn published() {
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    #[derive(Debug, Clone, PartialEq)]
+    struct Frame(usize);
+
+    let mut resources = Resources::new();
+    resources.insert(Published::new(Frame(0)));
+    let mut first = resources.subscribe::<Frame>().unwrap();
+    let mut second = resources.subscribe::<Frame>().unwrap();
+    assert!(!first.has_update());
+    assert_eq!(*first.latest(), Frame(0));
+
+    {
+        let mut published = resources.get_mut::<Published<Frame>>().unwrap();
+        published.publish(Frame(1));
+        published.publish(Frame(2));
+        assert_eq!(*published.get(), Frame(2));
+    }
+    assert!(first.has_update());
+    assert_eq!(*first.latest(), Frame(2));
+    assert!(!first.has_update());
+    assert_eq!(*first.latest(), Frame(2));
+
+    assert_eq!(*second.latest(), Frame(2));
+    drop(second);
+    let mut published = resources.get_mut::<Published<Frame>>().unwrap();
+    assert_eq!(published.subscribers(), 1);
+    let reader = std::thread::spawn(move || {
+        let mut last = 0;
+        while last < 100 {
+            let value = first.latest().0;
+            assert!(value >= last);
+            last = value;
+        }
+    });
+    for value in 3..=100 {
+        published.publish(Frame(value));
+    }
+    reader.join().unwrap();
+}
+
+#[test]
+fn removed() {
+    use std::any::TypeId;
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    assert!(!resources.removed::<One>());
+    resources.insert(One(3));
+    assert!(!resources.removed::<One>());
+    resources.remove::<One>();
+    resources.insert(One(4));
+    assert!(resources.removed::<One>());
+    assert!(!resources.removed::<Two>());
+    resources.maintain();
+    assert!(!resources.removed::<One>());
+    if let Entry::Occupied(entry) = resources.entry::<Two>() {
+        entry.remove();
+    }
+    resources.clear();
+    let mut removals = resources.drained_removals().collect::<Vec<_>>();
+    removals.sort();
+    let mut expected = vec![TypeId::of::<One>(), TypeId::of::<Two>()];
+    expected.sort();
+    assert_eq!(removals, expected);
+    assert!(!resources.removed::<Two>());
+}
+
+#[cfg(feature = "stream")]
+#[test]
+fn get_mut_untracked() {
+    use futures::{FutureExt, StreamExt};
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    let mut changes = resources.changes::<One>().unwrap();
+    {
+        let mut one = resources.get_mut_untracked::<One>().unwrap();
+        assert!(resources.get::<One>().is_err());
+        one.0 = 2;
+    }
+    assert_eq!(changes.next().now_or_never(), None);
+    resources.get_mut::<One>().unwrap().0 = 3;
+    assert_eq!(changes.next().now_or_never(), Some(Some(())));
+    assert_eq!(*resources.get::<One>().unwrap(), One(3));
+}
+
+#[test]
+fn scope_with() {
+    let mut resources = Resources::new();
+    let mut value = 1usize;
+    resources.scope_with(&mut value, |resources| {
+        let mut scoped = resources.get_scoped_mut::<usize>().unwrap();
+        assert!(resources.get_scoped::<usize>().is_err());
+        *scoped += 10;
+    });
+    assert_eq!(value, 11);
+    assert!(resources.get_scoped::<usize>().is_err());
+
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        resources.scope_with(&mut value, |_| panic!("scoped value is removed on panic"));
+    }));
+    assert!(panicked.is_err());
+    assert!(resources.get_scoped::<usize>().is_err());
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn scope_with_fetch() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    let mut external = 2usize;
+    resources.scope_with(&mut external, |resources| {
+        let (one, mut external) = resources.fetch::<(&One, Scoped<&mut usize>)>().unwrap();
+        *external += one.0;
+        drop(external);
+        let prepared = resources.prepare::<Scoped<&usize>>().unwrap();
+        assert_eq!(*prepared.fetch().unwrap(), 3);
+    });
+    assert_eq!(external, 3);
+}
+
+#[test]
+fn lenses() {
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Settings {
+        one: One,
+        two: Two,
+    }
+
+    let mut resources = Resources::new();
+    assert!(resources.get::<One>().is_err());
+    resources.insert(Settings {
+        one: One(1),
+        two: Two(2),
+    });
+    resources.register_lens(
+        |settings: &Settings| &settings.one,
+        |settings| &mut settings.one,
+    );
+    resources.register_lens(
+        |settings: &Settings| &settings.two,
+        |settings| &mut settings.two,
+    );
+    {
+        let one = resources.get::<One>().unwrap();
+        let two = resources.get_opt::<Two>().unwrap();
+        assert_eq!((one.0, two.0), (1, 2));
+        assert!(resources.get_mut::<Settings>().is_err());
+    }
+    {
+        let mut one = resources.get_mut::<One>().unwrap();
+        assert!(resources.get::<Two>().is_err());
+        one.0 = 3;
+    }
+    assert_eq!(resources.get::<Settings>().unwrap().one, One(3));
+
+    // Lenses are resolved by all accessors that lock resources.
+    assert_eq!(resources.get_recursive::<Two>().unwrap().0, 2);
+    let two = PreHashed::<Two>::new();
+    resources.get_mut_prehashed(&two).unwrap().0 = 5;
+    assert_eq!(resources.get_prehashed(&two).unwrap().0, 5);
+    #[cfg(feature = "blocking")]
+    {
+        resources.get_mut_blocking::<Two>().unwrap().0 = 6;
+        assert_eq!(resources.get_blocking_recursive::<Two>().unwrap().0, 6);
+    }
+    #[cfg(feature = "async")]
+    {
+        use futures::executor::block_on;
+
+        block_on(resources.get_mut_async::<Two>()).unwrap().0 = 7;
+        assert_eq!(block_on(resources.get_async::<Two>()).unwrap().0, 7);
+    }
+    {
+        let _two = resources.get_mut_labeled::<Two>("physics step").unwrap();
+        let error = resources.get_labeled::<One>("render").unwrap_err();
+        if cfg!(feature = "track-borrows") {
+            assert!(error.to_string().ends_with(" by `physics step`"));
+        }
+    }
+
+    resources.insert(One(4));
+    assert_eq!(*resources.get::<One>().unwrap(), One(4));
+    assert_eq!(resources.validate::<(One, Two)>(), Ok(()));
+    assert!(resources.unregister_lens::<Two>());
+    assert!(resources.get_mut_opt::<Two>().is_none());
+}
+
+#[test]
+fn alias() {
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Renamed(One);
+
+    let mut resources = Resources::new();
+    resources.insert(Renamed(One(1)));
+    resources.alias::<One, Renamed>(|renamed| &renamed.0, |renamed| &mut renamed.0);
+    assert_eq!(*resources.get::<One>().unwrap(), One(1));
+    assert_eq!(resources.validate::<(One,)>(), Ok(()));
+    {
+        let mut one = resources.get_mut::<One>().unwrap();
+        assert!(resources.get::<Renamed>().is_err());
+        one.0 = 2;
+    }
+    assert_eq!(resources.get::<Renamed>().unwrap().0, One(2));
+    assert!(resources.unalias::<One>());
+    assert!(!resources.unalias::<One>());
+    assert!(resources.get::<One>().is_err());
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn try_from_resources() {
+    use std::convert::TryFrom;
+
+    #[derive(Clone, Debug, PartialEq, Resource)]
+    struct Volume(u8);
+
+    #[derive(Clone, Debug, PartialEq, Resource)]
+    struct Name(String);
+
+    #[derive(TryFromResources)]
+    struct Settings {
+        volume: Volume,
+        name: Name,
+    }
+
+    #[derive(TryFromResources)]
+    struct Tuple(Name);
+
+    let mut resources = Resources::new();
+    resources.insert(Volume(7));
+    assert!(Settings::try_from(&resources).is_err());
+    resources.insert(Name("a".to_string()));
+    let settings = Settings::try_from(&resources).unwrap();
+    assert_eq!(settings.volume, Volume(7));
+    assert_eq!(settings.name, Name("a".to_string()));
+    {
+        let _name = resources.get_mut::<Name>().unwrap();
+        assert!(matches!(
+            Tuple::try_from(&resources),
+            Err(CantGetResource::InvalidBorrow(_))
+        ));
+    }
+    assert_eq!(Tuple::try_from(&resources).unwrap().0, settings.name);
+}
+
+#[test]
+fn validate() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    assert_eq!(resources.validate::<(One,)>(), Ok(()));
+    let missing = resources.validate::<(Two, One, Maybe)>().unwrap_err();
+    assert_eq!(missing.type_names, ["tests::Two", "tests::Maybe"]);
+    assert_eq!(
+        missing.to_string(),
+        "missing resources: tests::Two, tests::Maybe"
+    );
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn validate_derived() {
+    #[derive(RequiredResources)]
+    #[allow(dead_code)]
+    struct Physics {
+        one: One,
+        two: Two,
+    }
+
+    let mut resources = Resources::new();
+    resources.insert(Two(2));
+    assert_eq!(
+        resources.validate::<Physics>().unwrap_err().type_names,
+        ["tests::One"]
+    );
+    resources.insert(One(1));
+    assert_eq!(resources.validate::<Physics>(), Ok(()));
+}
+
+#[test]
+fn dependencies() {
+    let mut resources = Resources::new();
+    resources.add_dependency::<Two, One>();
+    resources.add_dependency::<Two, One>();
+    assert_eq!(
+        resources
+            .check_dependencies::<Two>()
+            .unwrap_err()
+            .type_names,
+        ["tests::One"]
+    );
+    assert_eq!(resources.check_dependencies::<One>(), Ok(()));
+    let error = resources.try_insert_dependent(Two(2)).unwrap_err();
+    assert_eq!(error.resource, Two(2));
+    assert_eq!(error.missing.type_names, ["tests::One"]);
+    assert_eq!(
+        error.to_string(),
+        "cannot insert `tests::Two`: missing resources: tests::One"
+    );
+    resources.insert(One(1));
+    assert_eq!(resources.try_insert_dependent(Two(2)), Ok(None));
+    assert_eq!(*resources.get::<Two>().unwrap(), Two(2));
+    // Replacing a resource doesn't check its dependencies.
+    resources.remove::<One>();
+    assert_eq!(resources.insert(Two(3)), Some(Two(2)));
+
+    let mut resources = Resources::new();
+    resources.add_dependency::<Two, One>();
+    resources.add_initializer::<Two, ()>(|resources| Two(resources.get::<One>().unwrap().0));
+    assert_eq!(
+        resources.init_all(),
+        Err(InitError::Missing(MissingResources {
+            type_names: vec!["tests::One"]
+        }))
+    );
+    resources.add_initializer::<One, ()>(|_| One(1));
+    assert_eq!(resources.init_all(), Ok(()));
+    assert_eq!(*resources.get::<Two>().unwrap(), Two(1));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "cannot insert `tests::Two`: missing resources: tests::One")]
+fn dependencies_insert() {
+    let mut resources = Resources::new();
+    resources.add_dependency::<Two, One>();
+    resources.insert(Two(2));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "cannot insert `tests::Two`: missing resources: tests::One")]
+fn dependencies_entry() {
+    let mut resources = Resources::new();
+    resources.add_dependency::<Two, One>();
+    if let Entry::Vacant(vacant) = resources.entry::<Two>() {
+        vacant.insert(Two(2));
+    }
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "cannot insert `tests::Two`: missing resources: tests::One")]
+fn dependencies_extend() {
+    let mut resources = Resources::new();
+    resources.add_dependency::<Two, One>();
+    resources.extend(Some(Box::new(Two(2)) as Box<dyn Resource>));
+}
+
+#[test]
+fn init_all() {
+    let mut resources = Resources::new();
+    resources.add_initializer::<Text, (Two,)>(|resources| {
+        assert_eq!(*resources.get::<Two>().unwrap(), Two(3));
+        Text("initialized")
+    });
+    resources
+        .add_initializer::<Two, (One,)>(|resources| Two(resources.get::<One>().unwrap().0 + 2));
+    resources.add_initializer::<One, ()>(|_| unreachable!());
+    resources.insert(One(1));
+    resources.init_all().unwrap();
+    assert_eq!(resources.get::<Text>().unwrap().0, "initialized");
+    assert_eq!(resources.init_all(), Ok(()));
+
+    let mut resources = Resources::new();
+    resources.add_initializer::<Two, (One,)>(|_| Two(2));
+    assert_eq!(
+        resources.init_all(),
+        Err(InitError::Missing(MissingResources {
+            type_names: vec!["tests::One"]
+        }))
+    );
+    resources.add_initializer::<One, (Text,)>(|_| One(1));
+    resources.add_initializer::<Text, (Two,)>(|_| Text("text"));
+    let error = resources.init_all().unwrap_err();
+    assert_eq!(
+        error,
+        InitError::Cycle {
+            type_names: vec!["tests::Two", "tests::One", "tests::Text"]
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "initializers depend on each other in a cycle: \
+         tests::Two -> tests::One -> tests::Text -> tests::Two"
+    );
+    resources.add_initializer::<Text, ()>(|_| Text("text"));
+    resources.seal();
+    assert_eq!(
+        resources.init_all(),
+        Err(InitError::Sealed(Sealed {
+            resource: (),
+            type_name: "tests::Text"
+        }))
+    );
+    resources.unseal();
+    resources.init_all().unwrap();
+    assert_eq!(*resources.get::<Two>().unwrap(), Two(2));
+}
+
+#[test]
+fn invalid_borrow_conflict() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    {
+        let _first = resources.get::<One>().unwrap();
+        let _second = resources.get_recursive::<One>().unwrap();
+        let error = match resources.get_mut::<One>().unwrap_err() {
+            CantGetResource::InvalidBorrow(error) => error,
+            error => panic!("unexpected error: {}", error),
+        };
+        assert_eq!(
+            error.conflict(),
+            Conflict::Readers {
+                count: 2,
+                via: BorrowApi::Recursive,
+                label: None,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "cannot borrow mutably: tests::One: borrowed immutably by 2 guard(s), \
+             the latest via a recursive method"
+        );
+    }
+    {
+        let _all = resources.lock_all().unwrap();
+        assert_eq!(
+            resources.get::<One>().unwrap_err(),
+            CantGetResource::InvalidBorrow(InvalidBorrow::Immutable {
+                type_name: "tests::One",
+                conflict: Conflict::Writer {
+                    via: BorrowApi::All,
+                    label: None,
+                },
+            })
+        );
+    }
+    assert!(resources.get_mut::<One>().is_ok());
+}
+
+#[test]
+fn insert_with_lock() {
+    let mut resources = Resources::new();
+    assert_eq!(resources.insert_with_lock(One(1), LockKind::Mutex), None);
+    resources.insert(Two(2));
+    #[cfg(feature = "blocking")]
+    resources.set_backoff(Backoff::spin_then_fail(16));
+    assert_eq!(resources.lock_kind::<One>(), Some(LockKind::Mutex));
+    assert_eq!(resources.lock_kind::<Two>(), Some(LockKind::RwLock));
+    assert_eq!(resources.lock_kind::<Text>(), None);
+    {
+        let _one = resources.get::<One>().unwrap();
+        assert_eq!(
+            resources.get::<One>().unwrap_err(),
+            CantGetResource::InvalidBorrow(InvalidBorrow::Immutable {
+                type_name: "tests::One",
+                conflict: Conflict::Readers {
+                    count: 1,
+                    via: BorrowApi::Get,
+                    label: None,
+                },
+            })
+        );
+        assert!(resources.get_mut::<One>().is_err());
+        // Recursive accesses are excluded too.
+        assert!(resources
+            .get_recursive::<One>()
+            .unwrap_err()
+            .is_borrow_conflict());
+        #[cfg(feature = "blocking")]
+        assert!(resources
+            .get_blocking_recursive::<One>()
+            .unwrap_err()
+            .is_borrow_conflict());
+    }
+    resources.get_mut::<One>().unwrap().0 += 1;
+
+    assert_eq!(resources.insert(One(3)), Some(One(2)));
+    assert_eq!(resources.lock_kind::<One>(), Some(LockKind::Mutex));
+    resources.remove::<One>();
+    resources.insert(One(4));
+    assert_eq!(resources.lock_kind::<One>(), Some(LockKind::RwLock));
+}
+
+#[test]
+fn insert_readonly() {
+    let mut resources = Resources::new();
+    assert_eq!(resources.insert_readonly(One(1)), None);
+    assert!(resources.is_readonly::<One>());
+    assert!(!resources.is_readonly::<Two>());
+    {
+        let one = resources.get::<One>().unwrap();
+        let again = resources.get::<One>().unwrap();
+        assert_eq!((one.0, again.0), (1, 1));
+        assert_eq!(resources.borrow_state().to_string(), "");
+    }
+    let error = resources.get_mut::<One>().unwrap_err();
+    assert!(error.is_read_only());
+    assert_eq!(
+        error,
+        CantGetResource::InvalidBorrow(InvalidBorrow::Mutable {
+            type_name: "tests::One",
+            conflict: Conflict::ReadOnly,
+        })
+    );
+    assert_eq!(
+        error.to_string(),
+        "cannot borrow mutably: tests::One: read-only"
+    );
+    assert!(resources.lock_all().is_err());
+    resources.branded(|view, mut token| {
+        assert!(view.get::<One>(&token).is_ok());
+        assert!(view.get_mut::<One>(&mut token).unwrap_err().is_read_only());
+    });
+
+    #[derive(Clone)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Limit(usize);
+
+    resources.insert_readonly(Limit(1));
+    let result: Result<(), ()> = resources.transaction(|tx| {
+        assert!(tx.get_mut::<Limit>().is_none());
+        assert_eq!(tx.get::<Limit>().map(|limit| limit.0), Some(1));
+        Ok(())
+    });
+    assert!(result.is_ok());
+
+    assert_eq!(resources.insert(One(2)), Some(One(1)));
+    assert!(resources.is_readonly::<One>());
+    resources.insert_with_lock(One(3), LockKind::RwLock);
+    resources.get_mut::<One>().unwrap().0 += 1;
+    assert_eq!(*resources.get::<One>().unwrap(), One(4));
+}
+
+#[test]
+fn resource_scope_readonly() {
+    let mut resources = Resources::new();
+    resources.insert_readonly(One(1));
+    let error = resources
+        .resource_scope(|_, one: &mut One| one.0 = 2)
+        .unwrap_err();
+    assert!(error.is_read_only());
+    assert!(resources.is_readonly::<One>());
+    assert_eq!(*resources.get::<One>().unwrap(), One(1));
+}
+
+#[test]
+#[should_panic(expected = "cannot borrow mutably: tests::One: read-only")]
+fn entry_readonly() {
+    let mut resources = Resources::new();
+    resources.insert_readonly(One(1));
+    assert_eq!(resources.entry::<One>().or_insert(One(2)).0, 1);
+}
+
+#[test]
+#[cfg(feature = "local-cache")]
+fn with_local() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug, PartialEq)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Config(u32);
+
+    impl Clone for Config {
+        fn clone(&self) -> Self {
+            CLONES.fetch_add(1, Ordering::Relaxed);
+            Config(self.0)
+        }
+    }
+
+    let mut resources = Resources::new();
+    resources.insert(Config(1));
+    assert_eq!(resources.with_local(|config: &Config| config.0), Ok(1));
+    assert_eq!(CLONES.load(Ordering::Relaxed), 0);
+
+    resources.cache_locally::<Config>();
+    assert!(resources.is_cached_locally::<Config>());
+    assert_eq!(resources.with_local(|config: &Config| config.0), Ok(1));
+    assert_eq!(resources.with_local(|config: &Config| config.0), Ok(1));
+    assert_eq!(CLONES.load(Ordering::Relaxed), 1);
+    std::thread::scope(|scope| {
+        scope.spawn(|| assert_eq!(resources.with_local(|config: &Config| config.0), Ok(1)));
+    });
+    assert_eq!(CLONES.load(Ordering::Relaxed), 1);
+    {
+        let mut config = resources.get_mut::<Config>().unwrap();
+        config.0 = 2;
+        assert_eq!(resources.with_local(|config: &Config| config.0), Ok(1));
+    }
+    assert_eq!(resources.with_local(|config: &Config| config.0), Ok(2));
+    assert_eq!(CLONES.load(Ordering::Relaxed), 2);
+
+    resources.insert(Config(3));
+    assert_eq!(resources.with_local(|config: &Config| config.0), Ok(3));
+    assert_eq!(CLONES.load(Ordering::Relaxed), 3);
+
+    // Writes that don't release the lock invalidate snapshots too.
+    let result: Result<(), ()> = resources.transaction(|tx| {
+        tx.get_mut::<Config>().unwrap().0 = 4;
+        Ok(())
+    });
+    assert!(result.is_ok());
+    assert_eq!(resources.with_local(|config: &Config| config.0), Ok(4));
+    resources.branded(|view, mut token| view.get_mut::<Config>(&mut token).unwrap().0 = 5);
+    assert_eq!(resources.with_local(|config: &Config| config.0), Ok(5));
+    assert_eq!(CLONES.load(Ordering::Relaxed), 6);
+
+    resources.uncache_locally::<Config>();
+    assert!(!resources.is_cached_locally::<Config>());
+    assert_eq!(resources.with_local(|config: &Config| config.0), Ok(5));
+    assert_eq!(CLONES.load(Ordering::Relaxed), 6);
+    resources.remove::<Config>();
+    assert!(resources
+        .with_local(|config: &Config| config.0)
+        .unwrap_err()
+        .is_missing());
+}
+
+#[test]
+fn schedule() {
+    let sets = [
+        AccessSet::new().reads::<(One,)>().writes::<(Two,)>(),
+        AccessSet::new().reads::<(One,)>(),
+        AccessSet::new().reads::<(Two,)>(),
+        AccessSet::new().writes::<(One,)>(),
+        AccessSet::new().writes::<(Text,)>(),
+        AccessSet::new().reads::<(One, Text)>(),
+    ];
+    assert!(sets[0].conflicts_with(&sets[2]));
+    assert!(!sets[0].conflicts_with(&sets[1]));
+    assert_eq!(
+        AccessSet::schedule(&sets),
+        vec![vec![0, 1, 4], vec![2, 3], vec![5]]
+    );
+    assert!(AccessSet::schedule(&[]).is_empty());
+
+    let system = System::<()>::with_access(sets[0].clone(), |_| ());
+    assert_eq!(system.access(), &sets[0]);
+}
+
+#[cfg(feature = "watchdog")]
+#[test]
+fn watchdog() {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let mut watchdog = Watchdog::with_callback(Duration::from_millis(10), {
+        let reported = reported.clone();
+        move |hold: &LongHold| reported.lock().unwrap().push(hold.to_string())
+    });
+    assert_eq!(watchdog.threshold(), Duration::from_millis(10));
+
+    let one = resources.get_mut_labeled::<One>("physics step").unwrap();
+    let _two = resources.get::<Two>().unwrap();
+    assert!(resources.long_holds(Duration::from_secs(60)).is_empty());
+    assert_eq!(watchdog.check(&resources), 0);
+    std::thread::sleep(Duration::from_millis(20));
+
+    let holds = resources.long_holds(Duration::from_millis(10));
+    assert_eq!(holds.len(), 2);
+    let hold = holds.iter().find(|hold| hold.mutable).unwrap();
+    assert_eq!(hold.type_name, "tests::One");
+    assert_eq!(hold.location.file(), file!());
+    assert_eq!(hold.label, Some("physics step"));
+    assert!(hold.held_for >= Duration::from_millis(20));
+
+    assert_eq!(watchdog.check(&resources), 2);
+    assert_eq!(watchdog.check(&resources), 0);
+    drop(one);
+    assert_eq!(watchdog.check(&resources), 0);
+    let reported = reported.lock().unwrap();
+    assert_eq!(reported.len(), 2);
+    assert!(reported
+        .iter()
+        .any(|hold| hold.starts_with("tests::One borrowed mutably at ")
+            && hold.contains(" by `physics step` for ")));
+}
+
+#[cfg(feature = "replication")]
+#[test]
+fn replication() {
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Score(u32);
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Level(String);
+
+    fn sync(server: &mut Resources, client: &mut Resources) -> String {
+        let mut delta = Vec::new();
+        server
+            .write_delta(&mut serde_json::Serializer::new(&mut delta))
+            .unwrap();
+        client
+            .apply_delta(&mut serde_json::Deserializer::from_slice(&delta))
+            .unwrap();
+        String::from_utf8(delta).unwrap()
+    }
+
+    let mut server = Resources::new();
+    let mut client = Resources::new();
+    for resources in [&mut server, &mut client] {
+        resources.register_replicated::<Score>("score");
+        resources.register_replicated::<Level>("level");
+    }
+    assert_eq!(client.stable_id::<Score>(), Some(&StableId::from("score")));
+    server.insert(Score(0));
+    server.insert(Level("intro".to_string()));
+    server.insert(One(1));
+    assert_eq!(
+        sync(&mut server, &mut client),
+        r#"{"level":"intro","score":0}"#
+    );
+    assert_eq!(*client.get::<Score>().unwrap(), Score(0));
+    assert_eq!(sync(&mut server, &mut client), "{}");
+
+    server.get_mut::<Score>().unwrap().0 += 10;
+    server.get_mut_untracked::<Level>().unwrap().0.push('!');
+    assert_eq!(sync(&mut server, &mut client), r#"{"score":10}"#);
+    assert_eq!(*client.get::<Level>().unwrap(), Level("intro".to_string()));
+
+    server.insert(Level("boss".to_string()));
+    server.remove::<Score>();
+    assert_eq!(
+        sync(&mut server, &mut client),
+        r#"{"level":"boss","score":null}"#
+    );
+    assert!(!client.contains::<Score>());
+    assert_eq!(*client.get::<Level>().unwrap(), Level("boss".to_string()));
+
+    // Applied changes aren't sent back.
+    let mut echo = Vec::new();
+    client
+        .write_delta(&mut serde_json::Serializer::new(&mut echo))
+        .unwrap();
+    assert_eq!(echo, b"{}");
+
+    let error = client
+        .apply_delta(&mut serde_json::Deserializer::from_str(r#"{"lives":3}"#))
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("unknown replicated resource `lives`"));
+
+    // A delta listing a resource more than once isn't applied at all.
+    let error = client
+        .apply_delta(&mut serde_json::Deserializer::from_str(
+            r#"{"level":null,"level":null,"level":null}"#,
+        ))
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("duplicate replicated resource `level`"));
+    assert_eq!(*client.get::<Level>().unwrap(), Level("boss".to_string()));
+
+    // Neither is one that would change the structure of a sealed container.
+    client.seal();
+    for delta in [r#"{"level":"sealed","score":1}"#, r#"{"level":null}"#] {
+        let error = client
+            .apply_delta(&mut serde_json::Deserializer::from_str(delta))
+            .unwrap_err();
+        assert!(error.to_string().contains("container is sealed"));
+    }
+    assert!(!client.contains::<Score>());
+    assert_eq!(*client.get::<Level>().unwrap(), Level("boss".to_string()));
+    client
+        .apply_delta(&mut serde_json::Deserializer::from_str(
+            r#"{"level":"sealed"}"#,
+        ))
+        .unwrap();
+    assert_eq!(*client.get::<Level>().unwrap(), Level("sealed".to_string()));
+    client.unseal();
+
+    // A delta that would exceed the memory budget isn't applied at all.
+    client.set_memory_budget(Some(client.memory_usage()));
+    let error = client
+        .apply_delta(&mut serde_json::Deserializer::from_str(
+            r#"{"level":"next","score":1}"#,
+        ))
+        .unwrap_err();
+    assert!(error.to_string().contains("memory budget exceeded"));
+    assert!(!client.contains::<Score>());
+    assert_eq!(*client.get::<Level>().unwrap(), Level("sealed".to_string()));
+}
+
+#[cfg(feature = "replication")]
+#[test]
+fn failed_lock_all_is_not_a_change() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct A(u32);
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct B(u32);
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct C(u32);
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct D(u32);
+
+    fn delta(resources: &mut Resources) -> String {
+        let mut delta = Vec::new();
+        resources
+            .write_delta(&mut serde_json::Serializer::new(&mut delta))
+            .unwrap();
+        String::from_utf8(delta).unwrap()
+    }
+
+    let mut resources = Resources::new();
+    resources.register_replicated::<A>("a");
+    resources.register_replicated::<B>("b");
+    resources.register_replicated::<C>("c");
+    resources.register_replicated::<D>("d");
+    resources.insert(A(1));
+    resources.insert(B(2));
+    resources.insert(C(3));
+    resources.insert(D(4));
+    assert_eq!(delta(&mut resources), r#"{"a":1,"b":2,"c":3,"d":4}"#);
+
+    // Whichever resource is held, the ones locked before it are released untouched.
+    {
+        let _a = resources.get::<A>().unwrap();
+        assert!(resources.lock_all().is_err());
+    }
+    {
+        let _b = resources.get::<B>().unwrap();
+        assert!(resources.lock_all().is_err());
+    }
+    {
+        let _c = resources.get::<C>().unwrap();
+        assert!(resources.lock_all().is_err());
+    }
+    {
+        let _d = resources.get::<D>().unwrap();
+        assert!(resources.lock_all().is_err());
+    }
+    assert_eq!(delta(&mut resources), "{}");
+}
+
+#[cfg(feature = "replication")]
+#[test]
+fn snapshot_migrations() {
+    #[derive(serde::Deserialize)]
+    struct ScoreV0(u32);
+
+    #[derive(serde::Deserialize)]
+    struct ScoreV1 {
+        points: u32,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Score {
+        points: u64,
+        bonus: u64,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Lives(u8);
+
+    let mut resources = Resources::new();
+    resources.register_replicated::<Score>("score");
+    resources.register_replicated::<Lives>("lives");
+    resources.set_snapshot_version(2);
+    resources.add_migration("score", 1, |old: ScoreV1| Score {
+        points: old.points.into(),
+        bonus: 0,
+    });
+    resources.add_migration("score", 0, |old: ScoreV0| ScoreV1 { points: old.0 });
+    assert_eq!(resources.snapshot_version(), 2);
+
+    let load = |resources: &mut Resources, json: &str| {
+        resources.load_snapshot(&mut serde_json::Deserializer::from_str(json))
+    };
+    load(
+        &mut resources,
+        r#"{"version":0,"resources":{"score":7,"lives":3}}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        *resources.get::<Score>().unwrap(),
+        Score {
+            points: 7,
+            bonus: 0
+        }
+    );
+    assert_eq!(*resources.get::<Lives>().unwrap(), Lives(3));
+    load(
+        &mut resources,
+        r#"{"version":1,"resources":{"score":{"points":8}}}"#,
+    )
+    .unwrap();
+    assert_eq!(resources.get::<Score>().unwrap().points, 8);
+
+    resources.get_mut::<Score>().unwrap().bonus = 2;
+    let mut saved = Vec::new();
+    resources
+        .write_snapshot(&mut serde_json::Serializer::new(&mut saved))
+        .unwrap();
+    let saved = String::from_utf8(saved).unwrap();
+    assert_eq!(
+        saved,
+        r#"{"version":2,"resources":{"lives":3,"score":{"points":8,"bonus":2}}}"#
+    );
+    resources.remove::<Lives>();
+    load(&mut resources, &saved).unwrap();
+    assert_eq!(*resources.get::<Lives>().unwrap(), Lives(3));
+    assert_eq!(resources.get::<Score>().unwrap().bonus, 2);
+
+    let error = load(&mut resources, r#"{"version":3,"resources":{}}"#).unwrap_err();
+    assert!(error.to_string().contains("snapshot version 3 is newer"));
+    let error = load(&mut resources, r#"{"resources":{},"version":2}"#).unwrap_err();
+    assert!(error.to_string().contains("missing field `version`"));
+}
+
+#[cfg(feature = "schemars")]
+#[test]
+fn json_schema() {
+    #[derive(serde::Serialize, schemars::JsonSchema)]
+    struct Point {
+        x: f32,
+        y: f32,
+    }
+
+    #[derive(serde::Serialize, schemars::JsonSchema)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Spawn {
+        position: Point,
+        count: u32,
+    }
+
+    #[derive(serde::Serialize, schemars::JsonSchema)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Camera {
+        target: Point,
+    }
+
+    let mut resources = Resources::new();
+    resources.register_schema::<Spawn>("spawn");
+    resources.register_schema::<Camera>("camera");
+    let schema = resources.json_schema();
+    assert_eq!(schema.get("type").unwrap(), "object");
+    assert_eq!(
+        schema.pointer("/properties/spawn/$ref").unwrap(),
+        "#/$defs/Spawn"
+    );
+    assert_eq!(
+        schema
+            .pointer("/$defs/Spawn/properties/position/$ref")
+            .unwrap(),
+        "#/$defs/Point"
+    );
+    assert!(schema.pointer("/$defs/Point/properties/x").is_some());
+    assert!(schema.pointer("/properties/camera").is_some());
+
+    let mut reordered = Resources::new();
+    reordered.register_schema::<Camera>("camera");
+    reordered.register_schema::<Spawn>("spawn");
+    assert_eq!(reordered.json_schema(), schema);
+}
+
+#[test]
+fn weak_resource() {
+    use std::sync::Arc;
+
+    let mut resources = Resources::new();
+    assert!(resources.downgrade::<One>().is_err());
+    assert!(resources.insert_arc(One(1)).is_none());
+    Arc::get_mut(&mut *resources.get_mut::<Arc<One>>().unwrap())
+        .unwrap()
+        .0 = 2;
+    let weak = resources.downgrade::<One>().unwrap();
+    assert!(weak.is_alive());
+    assert_eq!(*weak.upgrade().unwrap(), One(2));
+
+    let strong = weak.upgrade().unwrap();
+    drop(resources.remove::<Arc<One>>());
+    assert!(weak.is_alive());
+    drop(strong);
+    assert!(!weak.is_alive());
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn fixed_resources() {
+    use std::sync::Arc;
+
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    #[derive(Debug, PartialEq)]
+    struct Large([u64; 3]);
+
+    let mut resources = FixedResources::<2, 16>::new();
+    assert!(resources.is_empty());
+    assert_eq!(resources.insert(One(1)).unwrap(), None);
+    assert_eq!(resources.insert(One(2)).unwrap(), Some(One(1)));
+    assert_eq!(resources.insert(Large([0; 3])), Err(Large([0; 3])));
+    assert_eq!(resources.len(), 1);
+    {
+        let one = resources.get::<One>().unwrap();
+        assert!(resources.get_mut::<One>().is_err());
+        assert_eq!(*one, One(2));
+    }
+    assert!(matches!(
+        resources.get::<Two>(),
+        Err(CantGetResource::NoSuchResource(_))
+    ));
+
+    let dropped = Arc::new(One(0));
+    assert!(resources.insert(dropped.clone()).is_ok());
+    assert!(resources.insert(Two(0)).is_err());
+    assert_eq!(resources.remove::<One>(), Some(One(2)));
+    assert!(resources.insert(Two(3)).is_ok());
+    assert_eq!(Arc::strong_count(&dropped), 2);
+    drop(resources);
+    assert_eq!(Arc::strong_count(&dropped), 1);
+}
+
+#[test]
+fn get_unchecked() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    unsafe {
+        resources.get_mut_unchecked::<One>().unwrap().0 = 2;
+        assert_eq!(*resources.get_unchecked::<One>().unwrap(), One(2));
+        assert!(resources.get_unchecked::<Two>().is_err());
+    }
+    assert_eq!(*resources.get::<One>().unwrap(), One(2));
+}
+
+#[test]
+fn error_classification() {
+    use std::convert::TryFrom;
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    let missing = resources.get::<Two>().unwrap_err();
+    assert!(missing.is_missing());
+    assert!(!missing.is_borrow_conflict());
+    assert!(InvalidBorrow::try_from(missing.clone()).is_err());
+    assert_eq!(
+        NoSuchResource::try_from(missing.clone()).unwrap().type_name,
+        missing.type_name()
+    );
+    assert!(missing.clone().into_invalid_borrow().is_none());
+    assert!(missing.into_no_such_resource().is_some());
+
+    let _one = resources.get_mut::<One>().unwrap();
+    let conflict = resources.get::<One>().unwrap_err();
+    assert!(conflict.is_borrow_conflict());
+    assert!(!conflict.is_missing());
+    assert!(NoSuchResource::try_from(conflict.clone()).is_err());
+    assert!(InvalidBorrow::try_from(conflict.clone()).is_ok());
+    assert!(conflict.clone().into_no_such_resource().is_none());
+    assert!(conflict.into_invalid_borrow().is_some());
+}
+
+#[cfg(feature = "hot-reload")]
+#[test]
+// Miri isolates the file system.
+#[cfg_attr(miri, ignore)]
+fn hot_reload() {
+    use std::{
+        fs::{self, File},
+        time::{Duration, SystemTime},
+    };
+
+    fn load(bytes: &[u8]) -> Result<One, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(One(std::str::from_utf8(bytes)?.trim().parse()?))
+    }
+
+    let path = std::env::temp_dir().join(format!("resources-hot-reload-{}", std::process::id()));
+    // Modification times are set explicitly, as they may be too coarse to tell writes apart.
+    let write = |contents: &str, seconds: u64| {
+        fs::write(&path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+    };
+
+    let mut resources = Resources::new();
+    assert!(matches!(
+        resources.watch_file(&path, load),
+        Err(ReloadError::Io { .. })
+    ));
+    assert!(!resources.contains::<One>());
+
+    write("1", 1);
+    assert!(resources.watch_file(&path, load).unwrap().is_none());
+    assert_eq!(*resources.get::<One>().unwrap(), One(1));
+    assert!(resources.poll_reloads().is_empty());
+
+    write("2", 2);
+    assert!(resources.poll_reloads().is_empty());
+    assert_eq!(*resources.get::<One>().unwrap(), One(2));
+
+    write("two", 3);
+    let errors = resources.poll_reloads();
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(errors[0], ReloadError::Load { .. }));
+    assert_eq!(errors[0].type_name(), "tests::One");
+    assert_eq!(errors[0].path(), path);
+    assert!(resources.poll_reloads().is_empty());
+    assert_eq!(*resources.get::<One>().unwrap(), One(2));
+
+    resources.remove::<One>();
+    write("3", 4);
+    assert!(resources.poll_reloads().is_empty());
+    assert_eq!(*resources.get::<One>().unwrap(), One(3));
+
+    assert!(resources.unwatch_file::<One>());
+    assert!(!resources.unwatch_file::<One>());
+    write("4", 5);
+    assert!(resources.poll_reloads().is_empty());
+    assert_eq!(*resources.get::<One>().unwrap(), One(3));
+    fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "autosave")]
+#[test]
+fn autosave() {
+    use std::{
+        sync::{mpsc, Arc},
+        time::Duration,
+    };
+
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    #[derive(serde::Serialize)]
+    struct Missing;
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    let resources = Arc::new(resources);
+    let (sender, saves) = mpsc::channel();
+    let autosave = Autosave::every_ticks(2)
+        .with::<One>("one")
+        .with::<Missing>("missing")
+        .spawn(&resources, move |saved| {
+            assert!(saved.all().contains::<Two>());
+            sender.send(serde_json::to_string(saved).unwrap()).unwrap();
+        });
+
+    autosave.tick();
+    assert!(saves.recv_timeout(Duration::from_millis(50)).is_err());
+    autosave.tick();
+    assert_eq!(
+        saves.recv_timeout(Duration::from_secs(10)).unwrap(),
+        r#"{"one":1}"#
+    );
+
+    // The save may still be borrowing all resources.
+    let mut one = loop {
+        if let Ok(one) = resources.get_mut::<One>() {
+            break one;
+        }
+        std::thread::yield_now();
+    };
+    autosave.save_now();
+    assert!(saves.recv_timeout(Duration::from_millis(50)).is_err());
+    one.0 = 3;
+    drop(one);
+    assert_eq!(
+        saves.recv_timeout(Duration::from_secs(10)).unwrap(),
+        r#"{"one":3}"#
+    );
+    autosave.stop();
+    assert!(saves.recv().is_err());
+
+    let (sender, saves) = mpsc::channel();
+    let _autosave = Autosave::every(Duration::from_millis(1))
+        .with::<One>("one")
+        .spawn(&resources, move |saved| {
+            let _ = sender.send(serde_json::to_string(saved).unwrap());
+        });
+    assert_eq!(
+        saves.recv_timeout(Duration::from_secs(10)).unwrap(),
+        r#"{"one":3}"#
+    );
+}
+
+#[test]
+fn transient() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    assert!(resources.insert_transient(Two(2)).is_none());
+    assert!(resources.is_transient::<Two>());
+    assert!(!resources.is_transient::<One>());
+    assert_eq!(resources.insert(Two(3)), Some(Two(2)));
+    assert!(resources.is_transient::<Two>());
+
+    resources.maintain();
+    assert!(!resources.contains::<Two>());
+    assert!(!resources.is_transient::<Two>());
+    assert!(resources.removed::<Two>());
+    assert!(resources.contains::<One>());
+    resources.maintain();
+    assert!(!resources.removed::<Two>());
+
+    resources.insert_transient(Two(4));
+    assert_eq!(resources.remove::<Two>(), Some(Two(4)));
+    resources.insert(Two(5));
+    resources.maintain();
+    assert_eq!(*resources.get::<Two>().unwrap(), Two(5));
+}
+
+#[test]
+fn cache_eviction() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    resources.insert(Text("text"));
+    resources.set_cache_cost::<One>(10);
+    resources.set_cache_cost::<Two>(20);
+    resources.set_cache_cost::<Text>(5);
+    assert_eq!(resources.cache_cost::<Two>(), Some(20));
+    assert_eq!(resources.cache_usage(), 35);
+
+    resources.maintain();
+    assert_eq!(resources.cache_usage(), 35);
+
+    resources.set_cache_budget(Some(25));
+    assert_eq!(*resources.get::<One>().unwrap(), One(1));
+    resources.maintain();
+    assert!(resources.removed::<Two>());
+    assert!(resources.contains::<One>());
+    assert!(resources.contains::<Text>());
+    assert_eq!(resources.cache_usage(), 15);
+
+    resources.insert(Two(2));
+    resources.get_mut::<Text>().unwrap();
+    resources.maintain();
+    assert!(!resources.contains::<One>());
+    assert!(resources.contains::<Two>());
+    assert!(resources.contains::<Text>());
+
+    resources.unset_cache_cost::<Text>();
+    resources.set_cache_budget(Some(0));
+    resources.maintain();
+    assert!(resources.contains::<Text>());
+    assert_eq!(resources.cache_usage(), 0);
+}
+
+#[test]
+fn resource_scope() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+
+    let sum = resources.resource_scope(|resources, one: &mut One| {
+        assert!(!resources.contains::<One>());
+        let mut two = resources.get_mut::<Two>().unwrap();
+        one.0 += two.0;
+        two.0 = one.0;
+        one.0 + two.0
+    });
+    assert_eq!(sum, Ok(6));
+    assert_eq!(*resources.get::<One>().unwrap(), One(3));
+    assert_eq!(*resources.get::<Two>().unwrap(), Two(3));
+    assert!(!resources.removed::<One>());
+
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        resources.resource_scope(|_, one: &mut One| {
+            one.0 = 4;
+            panic!("scope panicked");
+        })
+    }));
+    assert!(panicked.is_err());
+    assert_eq!(*resources.get::<One>().unwrap(), One(4));
+
+    assert!(resources
+        .resource_scope(|_, _: &mut Maybe| ())
+        .unwrap_err()
+        .is_missing());
+}
+
+#[test]
+#[cfg(feature = "im")]
+fn im_resources() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    #[derive(Debug, PartialEq)]
+    struct Counted(usize);
+
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    #[derive(Debug, Clone)]
+    struct Label(&'static str);
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            CLONES.fetch_add(1, Ordering::Relaxed);
+            Counted(self.0)
+        }
+    }
+
+    let mut resources = ImResources::new();
+    assert!(resources.is_empty());
+    resources.insert(Counted(1));
+    resources.insert(Label("text"));
+    let snapshot = resources.clone();
+    assert_eq!(CLONES.load(Ordering::Relaxed), 0);
+
+    resources.get_mut::<Counted>().unwrap().0 += 1;
+    resources.get_mut::<Counted>().unwrap().0 += 1;
+    assert_eq!(CLONES.load(Ordering::Relaxed), 1);
+    assert_eq!(*resources.get::<Counted>().unwrap(), Counted(3));
+    assert_eq!(*snapshot.get::<Counted>().unwrap(), Counted(1));
+
+    assert_eq!(resources.remove::<Label>().unwrap().0, "text");
+    assert!(!resources.contains::<Label>());
+    assert_eq!(snapshot.get::<Label>().unwrap().0, "text");
+    assert_eq!(resources.len(), 1);
+    assert_eq!(snapshot.len(), 2);
+    assert_eq!(
+        resources.get::<Label>().unwrap_err().to_string(),
+        "no such resource: tests::im_resources::Label"
+    );
+    assert_eq!(resources.insert(Counted(4)), Some(Counted(3)));
+    assert_eq!(CLONES.load(Ordering::Relaxed), 1);
+    assert_eq!(
+        format!("{:?}", snapshot),
+        "{\"tests::im_resources::Counted\", \"tests::im_resources::Label\"}"
+    );
+}
+
+#[test]
+fn get_many_dyn() {
+    use std::any::TypeId;
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    {
+        let refs = resources
+            .get_many_dyn(&[
+                TypeId::of::<Two>(),
+                TypeId::of::<One>(),
+                TypeId::of::<Two>(),
+            ])
+            .unwrap();
+        assert_eq!(refs[0].downcast_ref::<Two>(), Some(&Two(2)));
+        assert_eq!(refs[1].downcast_ref::<One>(), Some(&One(1)));
+        assert!(resources.get_mut::<Two>().is_err());
+    }
+    {
+        let mut refs = resources
+            .get_many_dyn_mut(&[TypeId::of::<One>(), TypeId::of::<Two>()])
+            .unwrap();
+        refs[0].downcast_mut::<One>().unwrap().0 = 10;
+        assert!(resources.get::<Two>().is_err());
+    }
+    assert_eq!(*resources.get::<One>().unwrap(), One(10));
+
+    resources.start_access_log();
+    assert_eq!(
+        resources
+            .get_many_dyn_mut(&[TypeId::of::<One>(), TypeId::of::<One>()])
+            .err(),
+        Some(CantGetDyn::Duplicate {
+            type_name: "tests::One"
+        })
+    );
+    assert!(resources.finish_access_log().accesses().is_empty());
+    assert_eq!(
+        resources
+            .get_many_dyn(&[TypeId::of::<One>(), TypeId::of::<Text>()])
+            .err(),
+        Some(CantGetDyn::NoSuchResource(TypeId::of::<Text>()))
+    );
+    let _one = resources.get_mut::<One>().unwrap();
+    assert!(matches!(
+        resources.get_many_dyn(&[TypeId::of::<Two>(), TypeId::of::<One>()]),
+        Err(CantGetDyn::InvalidBorrow(_))
+    ));
+    assert!(resources.get_mut::<Two>().is_ok());
+}
+
+#[test]
+#[cfg(feature = "rayon")]
+fn par_values_mut() {
+    use rayon::iter::ParallelIterator;
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    assert_eq!(resources.par_values_mut().count(), 2);
+    resources.par_values_mut().for_each(|resource| {
+        if let Some(one) = resource.downcast_mut::<One>() {
+            one.0 += 10;
+        } else if let Some(two) = resource.downcast_mut::<Two>() {
+            two.0 += 20;
+        }
+    });
+    assert_eq!(*resources.get::<One>().unwrap(), One(11));
+    assert_eq!(*resources.get::<Two>().unwrap(), Two(22));
+}
+
+#[test]
+fn get_copy() {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Frame(u32);
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Tick(u64);
+
+    let mut resources = Resources::new();
+    resources.insert(Frame(5));
+    let resources = resources;
+
+    assert_eq!(resources.get_copy::<Frame>(), Ok(Frame(5)));
+    assert!(resources.get_copy::<Tick>().is_err());
+    let _guard = resources.get_mut::<Frame>().unwrap();
+    assert!(resources.get_copy::<Frame>().is_err());
+}
+
+#[test]
+fn drop_resources() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[cfg_attr(feature = "derive", derive(Resource))]
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let mut resources = Resources::new();
+    resources.insert(Counted(drops.clone()));
+    resources.insert(Counted(drops.clone()));
+    assert_eq!(drops.load(Ordering::Relaxed), 1);
+    resources.insert(One(1));
+    assert_eq!(resources.remove::<One>(), Some(One(1)));
+    resources.insert(One(2));
+    drop(resources);
+    assert_eq!(drops.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn reuse_memory() {
+    fn address_of_one(resources: &Resources) -> *const One {
+        let one = resources.get::<One>().unwrap();
+        &*one as *const One
+    }
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    let address = address_of_one(&resources);
+
+    assert_eq!(resources.remove::<One>(), Some(One(1)));
+    resources.insert(One(2));
+    assert_eq!(address_of_one(&resources), address);
+
+    assert_eq!(resources.insert(One(3)), Some(One(2)));
+    assert_eq!(address_of_one(&resources), address);
+
+    resources.remove::<One>();
+    resources.shrink_to_fit();
+    resources.insert(One(4));
+    assert_eq!(resources.get::<One>().unwrap().0, 4);
+}
+
+#[cfg(feature = "allocator-api")]
+#[test]
+fn custom_allocator() {
+    use allocator_api2::alloc::{AllocError, Allocator, Global};
+    use std::{
+        alloc::Layout,
+        ptr::NonNull,
+        sync::{
+            atomic::{AtomicIsize, Ordering},
+            Arc,
+        },
+    };
+
+    struct Tracked(Arc<AtomicIsize>);
+
+    unsafe impl Allocator for Tracked {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.0.fetch_sub(1, Ordering::Relaxed);
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    let live = Arc::new(AtomicIsize::new(0));
+    let mut resources = Resources::new_in(Tracked(live.clone()));
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    assert!(live.load(Ordering::Relaxed) > 0);
+    assert_eq!(resources.remove::<One>(), Some(One(1)));
+    drop(resources);
+    assert_eq!(live.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn static_resources() {
+    let resources = StaticResources::new((One(0), Two(0)));
+    {
+        let mut ref1 = resources.get_mut::<One, _>().unwrap();
+        let ref2 = resources.get::<Two, _>().unwrap();
+        assert!(resources.get::<One, _>().is_err());
+        assert!(resources.get_mut::<Two, _>().is_err());
+        ref1.0 = ref2.0 + 1;
+    }
+    assert_eq!(resources.into_inner(), (One(1), Two(0)));
+}
+
+#[test]
+fn boxed_resources() {
+    let boxed: Vec<Box<dyn Resource>> = vec![Box::new(One(1)), Box::new(Two(2))];
+    let mut resources: Resources = boxed.into_iter().collect();
+    assert_eq!(*resources.get::<One>().unwrap(), One(1));
+    assert_eq!(*resources.get::<Two>().unwrap(), Two(2));
+
+    resources.extend(vec![Box::new(One(3)) as Box<dyn Resource>]);
+    assert_eq!(resources.remove::<One>(), Some(One(3)));
+    assert_eq!(*resources.get::<Two>().unwrap(), Two(2));
+}
+
+#[test]
+fn into_iter() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    let mut rebuilt: Resources = resources
+        .into_iter()
+        .filter(|(type_id, _)| *type_id != std::any::TypeId::of::<Two>())
+        .map(|(_, resource)| resource)
+        .collect();
+    assert_eq!(rebuilt.remove::<One>(), Some(One(1)));
+    assert!(!rebuilt.contains::<Two>());
+}
+
+#[test]
+fn into_any_map() {
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+    let mut map = resources.into_any_map();
+    assert_eq!(map.len(), 2);
+    let one = map.remove(&std::any::TypeId::of::<One>()).unwrap();
+    assert_eq!(one.downcast::<One>().ok().map(|one| *one), Some(One(1)));
+
+    map.insert(std::any::TypeId::of::<One>(), Box::new(One(3)));
+    let mut converter = AnyMapConverter::new();
+    converter.register::<One>();
+    let mut resources = Resources::new();
+    let leftovers = resources.insert_any_map(map, &converter);
+    assert_eq!(*resources.get::<One>().unwrap(), One(3));
+    assert_eq!(leftovers.len(), 1);
+    assert!(leftovers[&std::any::TypeId::of::<Two>()].is::<Two>());
+}
+
+#[test]
+fn visit() {
+    use std::any::TypeId;
+
+    #[derive(Default)]
+    struct Visitor {
+        visited: Vec<(TypeId, &'static str, usize)>,
+        borrowed: Vec<&'static str>,
+    }
+
+    impl ResourceVisitor for Visitor {
+        fn resource(
+            &mut self,
+            type_id: TypeId,
+            type_name: &'static str,
+            resource: Ref<'_, dyn Resource>,
+        ) {
+            let value = match resource.downcast_ref::<One>() {
+                Some(one) => one.0,
+                None => resource.downcast_ref::<Two>().unwrap().0,
+            };
+            self.visited.push((type_id, type_name, value));
+        }
+
+        fn borrowed(&mut self, _: TypeId, type_name: &'static str) {
+            self.borrowed.push(type_name);
+        }
+    }
+
+    let mut resources = Resources::new();
+    resources.insert(One(1));
+    resources.insert(Two(2));
+
+    let mut visitor = Visitor::default();
+    {
+        let _borrowed = resources.get_mut::<Two>().unwrap();
+        resources.visit(&mut visitor);
+    }
+    assert_eq!(visitor.visited, [(TypeId::of::<One>(), "tests::One", 1)]);
+    assert_eq!(visitor.borrowed, ["tests::Two"]);
+    assert!(resources.get_mut::<One>().is_ok());
+}